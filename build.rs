@@ -0,0 +1,5 @@
+fn main() {
+    // Sets up the linker flags needed to build the Node.js addon.
+    #[cfg(feature = "node")]
+    napi_build::setup();
+}
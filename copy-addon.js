@@ -0,0 +1,14 @@
+// Copies the shared library built by `cargo rustc` to `mantra-miner.node`, the file loaded by
+// `index.js`.
+const fs = require('fs')
+const path = require('path')
+
+const libraries = {
+  darwin: 'libmantra_miner.dylib',
+  win32: 'mantra_miner.dll',
+}
+const library = libraries[process.platform] || 'libmantra_miner.so'
+fs.copyFileSync(
+  path.join(__dirname, 'target', 'release', library),
+  path.join(__dirname, 'mantra-miner.node'),
+)
@@ -0,0 +1,46 @@
+/*
+ * C bindings for mantra-miner, available when the crate is built with the `ffi` feature.
+ * See src/ffi.rs for the documentation of each function.
+ */
+
+#ifndef MANTRA_MINER_H
+#define MANTRA_MINER_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef enum MantraMinerStatus {
+    MANTRA_MINER_OK = 0,
+    MANTRA_MINER_NULL_POINTER = 1,
+    MANTRA_MINER_INVALID_OPTIONS = 2,
+    MANTRA_MINER_FAILED = 3,
+} MantraMinerStatus;
+
+typedef struct MantraMinerOptions {
+    const char *preparation;
+    size_t preparation_repeats;
+    const char *const *mantras;
+    size_t mantras_len;
+    const char *conclusion;
+    size_t conclusion_repeats;
+    size_t repeats;
+    uint64_t rate_ns;
+} MantraMinerOptions;
+
+typedef struct MantraMiner MantraMiner;
+
+MantraMiner *mantra_miner_new(const MantraMinerOptions *options);
+void mantra_miner_free(MantraMiner *miner);
+MantraMinerStatus mantra_miner_start(MantraMiner *miner);
+MantraMinerStatus mantra_miner_stop(MantraMiner *miner);
+uint64_t mantra_miner_count(const MantraMiner *miner);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* MANTRA_MINER_H */
@@ -0,0 +1,28 @@
+/** The options used to configure the mantra miner from JavaScript. */
+export interface NodeOptions {
+  /** The mantras to recite, each given as text split into syllables on whitespace and hyphens. */
+  mantras: Array<string>
+  /** The preparation recited before the mantras, if any. */
+  preparation?: string
+  /** The conclusion recited after the mantras, if any. */
+  conclusion?: string
+  /**
+   * The number of times to repeat the entire sadhana. If missing, the sadhana is repeated until
+   * the miner is stopped.
+   */
+  repeats?: number
+  /** The number of nanoseconds to wait between each syllable or character. */
+  rateNs: number
+}
+
+/** A mantra miner that recites the sadhana in a background thread. */
+export class MantraMiner {
+  /** Returns a new mantra miner with the given options. */
+  constructor(options: NodeOptions)
+  /** Starts running the mantra miner in a background thread. */
+  start(): void
+  /** Stops the mantra miner and waits for its thread to exit. */
+  stop(): void
+  /** Returns the number of completed recitations of the entire sadhana. */
+  count(): number
+}
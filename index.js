@@ -0,0 +1,2 @@
+// Loads the Node.js addon built by `npm run build`.
+module.exports = require('./mantra-miner.node')
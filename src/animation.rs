@@ -0,0 +1,182 @@
+//! Contains a voice that animates a prayer wheel or a mandala in a terminal, advancing one frame
+//! for each recited syllable. Each frame is drawn over the previous one with ANSI escape codes, so
+//! the output should be a terminal rather than a file.
+
+use std::io::{self, Write};
+
+use crate::Voice;
+
+/// The text carved around the prayer wheel, which scrolls past as the wheel turns.
+const WHEEL_TEXT: &str = "om mani padme hum ";
+
+/// The number of characters of the text visible on the front of the prayer wheel.
+const WHEEL_WIDTH: usize = 9;
+
+/// The frames of the mandala, which turns by an eighth of a circle on each frame.
+const MANDALA: [[&str; 5]; 2] = [
+    [
+        "      o      ",
+        "      |      ",
+        " o----@----o ",
+        "      |      ",
+        "      o      ",
+    ],
+    [
+        "  o       o  ",
+        "    \\   /    ",
+        "      @      ",
+        "    /   \\    ",
+        "  o       o  ",
+    ],
+];
+
+/// An animation drawn by `AnimatedVoice`.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Animation {
+    /// A prayer wheel on its handle, whose mantra scrolls past as it turns.
+    #[default]
+    PrayerWheel,
+
+    /// A mandala whose petals turn around its center.
+    Mandala,
+}
+
+impl Animation {
+    /// Returns the number of frames after which the animation repeats.
+    pub fn frames(&self) -> usize {
+        match self {
+            Animation::PrayerWheel => WHEEL_TEXT.len(),
+            Animation::Mandala => MANDALA.len(),
+        }
+    }
+
+    /// Returns the lines of the given frame of the animation. Frames past the last one wrap around
+    /// to the first.
+    pub fn frame(&self, index: usize) -> Vec<String> {
+        let index = index % self.frames();
+        match self {
+            Animation::PrayerWheel => {
+                let text: String = WHEEL_TEXT
+                    .chars()
+                    .cycle()
+                    .skip(index)
+                    .take(WHEEL_WIDTH)
+                    .collect();
+                let rim = "-".repeat(WHEEL_WIDTH);
+                vec![
+                    format!(" .{rim}."),
+                    format!(" |{text}|"),
+                    format!(" '{rim}'"),
+                    format!("{:>w$}", "||", w = WHEEL_WIDTH / 2 + 4),
+                    format!("{:>w$}", "||", w = WHEEL_WIDTH / 2 + 4),
+                ]
+            }
+            Animation::Mandala => MANDALA[index].iter().map(|line| line.to_string()).collect(),
+        }
+    }
+}
+
+/// A voice that draws an animation to a terminal, advancing one frame for each syllable of the
+/// mantras and showing the syllable under it. The preparation and conclusion leave the animation
+/// as it is.
+pub struct AnimatedVoice<W: Write + Send> {
+    /// The terminal to which the animation is drawn.
+    output: W,
+
+    /// The animation to draw.
+    animation: Animation,
+
+    /// The index of the next frame.
+    frame: usize,
+
+    /// The number of lines drawn for the previous frame, which the next one is drawn over.
+    drawn: usize,
+}
+
+impl<W: Write + Send> AnimatedVoice<W> {
+    /// Returns a voice that draws the given animation to the output.
+    pub fn new(output: W, animation: Animation) -> AnimatedVoice<W> {
+        AnimatedVoice {
+            output,
+            animation,
+            frame: 0,
+            drawn: 0,
+        }
+    }
+}
+
+impl<W: Write + Send> Voice for AnimatedVoice<W> {
+    fn recite_syllable(&mut self, syllable: &str) -> io::Result<()> {
+        let mut lines = self.animation.frame(self.frame);
+        lines.push(syllable.to_string());
+
+        let mut buf = String::new();
+        if self.drawn > 0 {
+            buf.push_str(&format!("\x1b[{}A", self.drawn));
+        }
+        for line in &lines {
+            buf.push_str("\r\x1b[2K");
+            buf.push_str(line);
+            buf.push('\n');
+        }
+        self.output.write_all(buf.as_bytes())?;
+        self.output.flush()?;
+
+        self.frame = (self.frame + 1) % self.animation.frames();
+        self.drawn = lines.len();
+        Ok(())
+    }
+
+    fn recite_character(&mut self, _: char) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.output.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use crate::{AnimatedVoice, Animation, Voice};
+
+    #[test]
+    fn frames() {
+        assert_eq!(
+            Animation::PrayerWheel.frame(0),
+            [
+                " .---------.",
+                " |om mani p|",
+                " '---------'",
+                "      ||",
+                "      ||",
+            ]
+        );
+        assert_eq!(Animation::PrayerWheel.frame(14)[1], " |hum om ma|");
+        assert_eq!(
+            Animation::PrayerWheel.frame(18),
+            Animation::PrayerWheel.frame(0)
+        );
+        assert_eq!(Animation::Mandala.frame(3), Animation::Mandala.frame(1));
+    }
+
+    #[test]
+    fn draw() -> Result<()> {
+        let mut voice = AnimatedVoice::new(Vec::new(), Animation::Mandala);
+        voice.recite_syllable("om")?;
+        voice.recite_character('x')?;
+        voice.recite_syllable("ah")?;
+
+        // The second frame is drawn over the first, including the line with the syllable.
+        let output = String::from_utf8(voice.output)?;
+        let (first, second) = output.split_once("\x1b[6A").unwrap();
+        assert_eq!(first.matches('\n').count(), 6);
+        assert!(first.contains(" o----@----o \n"));
+        assert!(first.ends_with("\r\x1b[2Kom\n"));
+        assert!(second.contains("    \\   /    \n"));
+        assert!(second.ends_with("\r\x1b[2Kah\n"));
+        Ok(())
+    }
+}
@@ -0,0 +1,441 @@
+//! Contains an asynchronous version of the mantra miner that runs as a tokio task instead of a
+//! dedicated thread, as well as a stream of the recited syllables. Available with the `tokio`
+//! feature.
+
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use parking_lot::Mutex;
+use tokio::{
+    sync::{mpsc, watch},
+    task::JoinHandle,
+};
+use tokio_stream::wrappers::ReceiverStream;
+
+#[cfg(feature = "power")]
+use crate::PowerMonitor;
+use crate::{
+    playlist::PlaylistState,
+    queue::RunOutput,
+    recitation::{Recitation, Recited},
+    Error, LoadMonitor, MinerEvent, MinerStats, Options, Output, Playlist, Progress, Result,
+    SharedOutput, SharedState, Voice,
+};
+
+/// The number of syllables buffered by a syllable stream before the miner waits for the consumer to
+/// catch up.
+const SYLLABLE_STREAM_CAPACITY: usize = 32;
+
+/// A syllable recited by the asynchronous mantra miner.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RecitedSyllable {
+    /// The index of the mantra in the options.
+    pub mantra_index: usize,
+
+    /// The zero-based number of the repetition of the mantra.
+    pub repeat: usize,
+
+    /// The recited syllable.
+    pub syllable: String,
+}
+
+/// The list of channels backing the syllable streams of the asynchronous mantra miner.
+type SyllableSenders = Arc<Mutex<Vec<mpsc::Sender<RecitedSyllable>>>>;
+
+/// The state used by the task running the asynchronous mantra miner to recite the sadhana.
+struct AsyncRecitation {
+    /// The recitation of the sadhana.
+    recitation: Recitation<RunOutput>,
+
+    /// The channels backing the syllable streams.
+    syllable_senders: SyllableSenders,
+
+    /// The channel used to receive the signal to stop.
+    stop_channel: watch::Receiver<bool>,
+}
+
+impl AsyncRecitation {
+    /// Returns whether the signal to stop has been received or the miner that sends it was
+    /// dropped.
+    fn should_stop(&self) -> bool {
+        *self.stop_channel.borrow() || self.stop_channel.has_changed().is_err()
+    }
+
+    /// Waits for the delay after the last syllable or character recited and returns whether the
+    /// recitation should stop. The wait is interrupted as soon as the signal to stop is received.
+    async fn sleep(&mut self) -> bool {
+        let delay = self.recitation.next_delay();
+        self.wait(delay).await
+    }
+
+    /// Waits for the given delay and returns whether the recitation should stop. The wait is
+    /// interrupted as soon as the signal to stop is received.
+    async fn wait(&mut self, delay: Duration) -> bool {
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => self.should_stop(),
+            _ = self.stop_channel.changed() => true,
+        }
+    }
+
+    /// Sends the syllable to every syllable stream, waiting for consumers whose buffers are full.
+    /// Streams that have been dropped are removed. Returns whether the recitation should stop,
+    /// since the signal to stop interrupts the wait.
+    async fn send_syllable(&mut self, syllable: RecitedSyllable) -> bool {
+        let senders = self.syllable_senders.lock().clone();
+        if senders.is_empty() {
+            return false;
+        }
+        for sender in senders {
+            tokio::select! {
+                _ = sender.send(syllable.clone()) => {},
+                _ = self.stop_channel.changed() => return true,
+            }
+        }
+        self.syllable_senders
+            .lock()
+            .retain(|sender| !sender.is_closed());
+        false
+    }
+
+    /// Runs the asynchronous mantra miner. A sadhana interrupted by the signal to stop is not
+    /// counted.
+    async fn run(mut self) -> Result<()> {
+        while !self.should_stop() {
+            if let Some(delay) = self.recitation.power_pause() {
+                if self.wait(delay).await {
+                    break;
+                }
+                continue;
+            }
+            let recited = match self.recitation.step()? {
+                None => break,
+                Some(recited) => recited,
+            };
+
+            if let Recited::Syllable {
+                mantra_index,
+                repeat,
+                syllable_index,
+            } = recited
+            {
+                let syllable = RecitedSyllable {
+                    mantra_index,
+                    repeat,
+                    syllable: self.recitation.reciter.options().mantras[mantra_index].syllables
+                        [syllable_index]
+                        .to_string(),
+                };
+                if self.send_syllable(syllable).await {
+                    break;
+                }
+            }
+            if self.sleep().await {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A mantra miner that "recites" mantras in a tokio task, using `tokio::time::sleep` to wait
+/// between syllables instead of blocking a dedicated thread. The output is written to directly
+/// from the task, so it should not block for long periods of time. Unlike [`crate::MantraMiner`],
+/// the asynchronous miner cannot be paused.
+pub struct AsyncMantraMiner {
+    /// The options used to configure the mantra miner.
+    options: Options,
+
+    /// The state shared with the task running the mantra miner.
+    state: SharedState,
+
+    /// The channel used to signal the task to stop.
+    stop_channel: Option<watch::Sender<bool>>,
+
+    /// The handle of the task running the mantra miner.
+    task_handle: Option<JoinHandle<Result<()>>>,
+
+    /// The output to which the mantras are recited.
+    output: Arc<Mutex<Output>>,
+
+    /// The channels backing the syllable streams.
+    syllable_senders: SyllableSenders,
+}
+
+impl AsyncMantraMiner {
+    /// Returns a new instance of `AsyncMantraMiner` with the given options. The mantras are
+    /// recited to `std::io::sink()`.
+    pub fn new(options: Options) -> AsyncMantraMiner {
+        Self::with_output(options, Box::new(std::io::sink()))
+    }
+
+    /// Returns a new instance of `AsyncMantraMiner` with the given options that recites the
+    /// mantras to the given output. The output is flushed after every recitation of the sadhana.
+    pub fn with_output(options: Options, output: Output) -> AsyncMantraMiner {
+        AsyncMantraMiner {
+            state: SharedState::new(&options),
+            options,
+            stop_channel: None,
+            task_handle: None,
+            output: Arc::new(Mutex::new(output)),
+            syllable_senders: SyllableSenders::default(),
+        }
+    }
+
+    /// Returns the mantra miner with the given playlist, which it cycles through instead of
+    /// reciting a single sadhana. Same as `MantraMiner::with_playlist`.
+    pub fn with_playlist(mut self, playlist: Playlist) -> AsyncMantraMiner {
+        if let Some(sadhana) = playlist.sadhanas.first() {
+            self.options = sadhana.playlist_options();
+            self.state.update_mantra_stats(&self.options);
+        }
+        *self.state.playlist.lock() = Some(PlaylistState::new(playlist));
+        self
+    }
+
+    /// Spawns a new tokio task to run the mantra miner. Must be called from within a tokio
+    /// runtime. Any task started previously is signaled to stop but not awaited. If the previous
+    /// task stopped in the middle of a sadhana, the new one resumes from there. Returns
+    /// `Error::InvalidOptions` if the options or the playlist fail validation.
+    pub fn start(&mut self) -> Result<()> {
+        self.options.validate()?;
+        self.state.validate_playlist()?;
+        if let Some(tx) = self.stop_channel.take() {
+            let _ = tx.send(true);
+        }
+
+        self.state.timing.lock().start();
+        let (tx, rx) = watch::channel(false);
+        let output = RunOutput::new(
+            SharedOutput(self.output.clone()),
+            &self.options,
+            &self.state,
+        );
+        let mut recitation = Recitation::new(self.options.clone(), output, self.state.clone());
+        let checkpoint = *self.state.checkpoint.lock();
+        if let Some(checkpoint) = checkpoint {
+            recitation.resume(checkpoint);
+        }
+        let recitation = AsyncRecitation {
+            recitation,
+            syllable_senders: self.syllable_senders.clone(),
+            stop_channel: rx,
+        };
+        self.task_handle = Some(tokio::spawn(recitation.run()));
+        self.stop_channel = Some(tx);
+        Ok(())
+    }
+
+    /// Stops the task running the mantra miner and waits for it to finish. Returns the error that
+    /// made the task exit early, if any.
+    pub async fn stop(&mut self) -> Result<()> {
+        if let Some(tx) = self.stop_channel.take() {
+            let _ = tx.send(true);
+        }
+        match self.task_handle.take() {
+            None => Ok(()),
+            Some(handle) => handle.await.map_err(|_| Error::ThreadPanicked)?,
+        }
+    }
+
+    /// Sets the monitor sampled in the load-aware mode, which replaces the system load average.
+    /// Takes effect immediately, even if the miner is running.
+    pub fn set_load_monitor(&mut self, monitor: Box<dyn LoadMonitor>) {
+        *self.state.load_monitor.lock() = monitor;
+    }
+
+    /// Sets the voice that recites the sadhana instead of writing it to the output. Passing `None`
+    /// restores writing to the output. Takes effect immediately, even if the miner is running.
+    pub fn set_voice(&mut self, voice: Option<Box<dyn Voice>>) {
+        *self.state.voice.lock() = voice;
+    }
+
+    /// Sets the target of the records logged with the `log` feature, which defaults to
+    /// `mantra_miner::recitation`. Takes effect immediately, even if the miner is running.
+    #[cfg(feature = "log")]
+    pub fn set_log_target(&mut self, target: impl Into<String>) {
+        *self.state.log_target.lock() = target.into();
+    }
+
+    /// Sets the monitor used to check whether the machine runs on battery, which replaces the one
+    /// that queries the operating system. Takes effect the next time the power supply is checked.
+    #[cfg(feature = "power")]
+    pub fn set_power_monitor(&mut self, monitor: Box<dyn PowerMonitor>) {
+        *self.state.power_monitor.lock() = monitor;
+    }
+
+    /// Returns the position within the sadhana of the last syllable or character recited, or
+    /// `None` if nothing has been recited yet.
+    pub fn progress(&self) -> Option<Progress> {
+        *self.state.progress.lock()
+    }
+
+    /// Returns the time, as shown by the system clock, at which the miner was last started, or
+    /// `None` if it was never started.
+    pub fn started_at(&self) -> Option<SystemTime> {
+        self.state.timing.lock().started_at
+    }
+
+    /// Returns how long the miner has been running since it was last started. Once the miner is
+    /// stopped or finishes, this is the duration of the run.
+    pub fn elapsed(&self) -> Duration {
+        self.state.timing.lock().elapsed()
+    }
+
+    /// Returns the average time taken by a recitation of the entire sadhana since the miner was
+    /// last started, or `None` if none has been completed yet.
+    pub fn average_sadhana_duration(&self) -> Option<Duration> {
+        self.state.timing.lock().average_sadhana_duration()
+    }
+
+    /// Returns the options used to configure this mantra miner.
+    pub fn options(&self) -> Options {
+        self.options.clone()
+    }
+
+    /// Returns a channel that receives the events emitted by the mantra miner from this point on.
+    pub fn subscribe(&self) -> std::sync::mpsc::Receiver<MinerEvent> {
+        self.state.subscribers.subscribe()
+    }
+
+    /// Returns a stream of the syllables recited by the mantra miner from this point on. The stream
+    /// buffers a small number of syllables, after which the miner waits for them to be consumed
+    /// before reciting more, so a slow consumer slows down the recitation. Dropping the stream
+    /// unsubscribes it.
+    pub fn syllable_stream(&self) -> ReceiverStream<RecitedSyllable> {
+        let (tx, rx) = mpsc::channel(SYLLABLE_STREAM_CAPACITY);
+        self.syllable_senders.lock().push(tx);
+        ReceiverStream::new(rx)
+    }
+
+    /// Returns the count of the mantra miner.
+    pub fn count(&self) -> u64 {
+        self.state.count()
+    }
+
+    /// Returns the total number of mantra syllables and characters of the preparation and
+    /// conclusion that have been recited.
+    pub fn syllable_count(&self) -> u64 {
+        self.state.syllables()
+    }
+
+    /// Returns the number of interrupted recitations of the sadhana. Same as
+    /// `MantraMiner::interrupted_sadhanas`.
+    pub fn interrupted_sadhanas(&self) -> u64 {
+        self.state.interrupted_sadhanas()
+    }
+
+    /// Returns the progress of the sadhana in progress. Same as `MantraMiner::sadhana_progress`.
+    pub fn sadhana_progress(&self) -> Option<f64> {
+        *self.state.sadhana_progress.lock()
+    }
+
+    /// Returns the name of the sadhana of the playlist being recited. Same as
+    /// `MantraMiner::current_sadhana`.
+    pub fn current_sadhana(&self) -> Option<String> {
+        self.state.current_sadhana()
+    }
+
+    /// Returns the statistics of the mantra miner.
+    pub fn stats(&self) -> MinerStats {
+        self.state.stats()
+    }
+}
+
+impl Drop for AsyncMantraMiner {
+    /// Signals the task running the mantra miner to stop. The task cannot be awaited here, but it
+    /// exits the next time it checks the signal.
+    fn drop(&mut self) {
+        if let Some(tx) = self.stop_channel.take() {
+            let _ = tx.send(true);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use std::time::Duration;
+    use tokio_stream::StreamExt;
+
+    use crate::{
+        async_miner::{AsyncMantraMiner, RecitedSyllable},
+        Error, Mantra, Options,
+    };
+
+    fn options(rate_ns: u64, repeats: Option<usize>) -> Options {
+        Options {
+            preparation: Some("ready".to_string()),
+            mantras: vec![Mantra::from_text("om ma ni pad me hum")],
+            conclusion: Some("done".to_string()),
+            rate: Duration::from_nanos(rate_ns),
+            repeats,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn finite_run() -> Result<()> {
+        let mut miner = AsyncMantraMiner::new(options(1000, Some(3)));
+        miner.start()?;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        miner.stop().await?;
+        assert_eq!(miner.count(), 3);
+        assert_eq!(miner.syllable_count(), 3 * (5 + 6 + 4));
+        assert_eq!(miner.stats().mantras[0].completed, 3);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn stop_interrupts_sleep() -> Result<()> {
+        let mut miner = AsyncMantraMiner::new(options(60_000_000_000, None));
+        miner.start()?;
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        tokio::time::timeout(Duration::from_millis(100), miner.stop()).await??;
+        assert_eq!(miner.count(), 0);
+        assert_eq!(miner.syllable_count(), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn start_invalid_options() {
+        let mut miner = AsyncMantraMiner::new(Options::default());
+        assert!(matches!(miner.start(), Err(Error::InvalidOptions(_))));
+    }
+
+    #[tokio::test]
+    async fn syllable_stream() -> Result<()> {
+        let mut miner = AsyncMantraMiner::new(options(1000, Some(2)));
+        let stream = miner.syllable_stream();
+        let dropped_stream = miner.syllable_stream();
+        drop(dropped_stream);
+        miner.start()?;
+
+        let syllables: Vec<RecitedSyllable> = stream.take(7).collect().await;
+        assert_eq!(syllables[0].syllable, "om");
+        assert_eq!(syllables[5].syllable, "hum");
+        assert_eq!(
+            syllables[6],
+            RecitedSyllable {
+                mantra_index: 0,
+                repeat: 0,
+                syllable: "om".to_string(),
+            }
+        );
+        miner.stop().await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn stop_with_full_stream() -> Result<()> {
+        let mut miner = AsyncMantraMiner::new(options(1000, None));
+        let _stream = miner.syllable_stream();
+        miner.start()?;
+
+        // The stream is never consumed, so the miner waits until it is stopped.
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        tokio::time::timeout(Duration::from_millis(100), miner.stop()).await??;
+        Ok(())
+    }
+}
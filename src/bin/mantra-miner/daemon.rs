@@ -0,0 +1,353 @@
+//! Implements the control socket used to manage the miner while it runs in daemon mode. Clients
+//! send one command per line and receive one line in reply for each of them. The socket is a Unix
+//! domain socket on Unix and a named pipe on Windows.
+
+use anyhow::{bail, Context, Result};
+use clap::ValueEnum;
+use std::{
+    fmt,
+    io::{self, BufRead, BufReader, Write},
+    path::Path,
+    sync::mpsc::{self, Sender},
+    thread,
+};
+
+#[cfg(windows)]
+use std::{
+    ffi::OsString,
+    fs::{File, OpenOptions},
+    os::windows::{
+        ffi::OsStrExt,
+        io::{AsRawHandle, FromRawHandle},
+    },
+    ptr,
+};
+#[cfg(unix)]
+use std::{
+    fs,
+    net::Shutdown,
+    os::unix::net::{UnixListener, UnixStream},
+    path::PathBuf,
+};
+
+use crate::Message;
+
+/// A command sent to the miner through the control socket.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, ValueEnum)]
+pub enum Command {
+    /// Replies with the state and the counts of the miner.
+    Status,
+
+    /// Pauses the miner.
+    Pause,
+
+    /// Resumes the miner after it was paused.
+    Resume,
+
+    /// Stops the miner and exits the daemon.
+    Stop,
+
+    /// Reads the sadhana file again and uses the new options from the next sadhana on.
+    Reload,
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.to_possible_value() {
+            Some(value) => write!(f, "{}", value.get_name()),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A command received through the control socket, along with the channel used to send the reply
+/// back to the client.
+#[derive(Debug)]
+pub struct Request {
+    /// The command to run.
+    pub command: Command,
+
+    /// The channel to which the reply is sent.
+    pub reply: Sender<String>,
+}
+
+/// Keeps the control socket open while it's alive. On Unix, the socket file is removed when it's
+/// dropped, while a named pipe goes away on its own once the daemon exits.
+#[derive(Debug)]
+pub struct Listener {
+    /// The path of the socket.
+    #[cfg(unix)]
+    path: PathBuf,
+}
+
+#[cfg(unix)]
+impl Drop for Listener {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Reads the commands sent by a single client and writes the replies to them.
+fn serve(reader: impl BufRead, mut writer: impl Write, tx: &Sender<Message>) -> io::Result<()> {
+    for line in reader.lines() {
+        let line = line?;
+        let reply = match Command::from_str(line.trim(), true) {
+            Ok(command) => {
+                let (reply_tx, reply_rx) = mpsc::channel();
+                let request = Request {
+                    command,
+                    reply: reply_tx,
+                };
+                if tx.send(Message::Request(request)).is_err() {
+                    break;
+                }
+                reply_rx
+                    .recv()
+                    .unwrap_or_else(|_| "error: the miner exited".to_string())
+            }
+            Err(_) => format!("error: unknown command {:?}", line.trim()),
+        };
+        writeln!(writer, "{}", reply)?;
+    }
+    Ok(())
+}
+
+/// Starts listening on the control socket at the given path in a background thread. Each command
+/// is sent as a request to the given channel. A stale socket left behind by a previous daemon is
+/// replaced, but the call fails if another daemon is still listening on it.
+#[cfg(unix)]
+pub fn listen(path: &Path, tx: Sender<Message>) -> Result<Listener> {
+    if path.exists() {
+        if UnixStream::connect(path).is_ok() {
+            bail!("another daemon is listening on {}", path.display());
+        }
+        fs::remove_file(path)
+            .with_context(|| format!("failed to remove stale socket {}", path.display()))?;
+    }
+
+    let listener = UnixListener::bind(path)
+        .with_context(|| format!("failed to listen on {}", path.display()))?;
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let tx = tx.clone();
+            thread::spawn(move || {
+                let reader = BufReader::new(stream.try_clone()?);
+                serve(reader, stream, &tx)
+            });
+        }
+    });
+    Ok(Listener {
+        path: path.to_path_buf(),
+    })
+}
+
+/// Sends a command to the daemon listening on the given path and returns its reply.
+#[cfg(unix)]
+pub fn send(path: &Path, command: Command) -> Result<String> {
+    let mut stream = UnixStream::connect(path)
+        .with_context(|| format!("failed to connect to {}", path.display()))?;
+    writeln!(stream, "{}", command)?;
+    stream.shutdown(Shutdown::Write)?;
+
+    let mut reply = String::new();
+    BufReader::new(stream).read_line(&mut reply)?;
+    Ok(reply.trim_end().to_string())
+}
+
+/// Returns the name of the named pipe for the given path. A path that already names a pipe, such as
+/// `\\.\pipe\mantra-miner`, is used as is, and any other path is turned into the name of a pipe.
+#[cfg(windows)]
+fn pipe_name(path: &Path) -> OsString {
+    const PREFIX: &str = r"\\.\pipe\";
+    let path = path.to_string_lossy();
+    if path.to_ascii_lowercase().starts_with(PREFIX) {
+        path.into_owned().into()
+    } else {
+        format!("{}{}", PREFIX, path.replace(['\\', '/', ':'], "-")).into()
+    }
+}
+
+/// Creates a new instance of the named pipe with the given null-terminated name. The creation of
+/// the first instance fails if another process already owns a pipe with that name.
+#[cfg(windows)]
+fn create_pipe(name: &[u16], first: bool) -> io::Result<File> {
+    use windows_sys::Win32::{
+        Foundation::INVALID_HANDLE_VALUE,
+        Storage::FileSystem::{FILE_FLAG_FIRST_PIPE_INSTANCE, PIPE_ACCESS_DUPLEX},
+        System::Pipes::{
+            CreateNamedPipeW, PIPE_READMODE_BYTE, PIPE_REJECT_REMOTE_CLIENTS, PIPE_TYPE_BYTE,
+            PIPE_UNLIMITED_INSTANCES, PIPE_WAIT,
+        },
+    };
+
+    let mut open_mode = PIPE_ACCESS_DUPLEX;
+    if first {
+        open_mode |= FILE_FLAG_FIRST_PIPE_INSTANCE;
+    }
+    let pipe_mode = PIPE_TYPE_BYTE | PIPE_READMODE_BYTE | PIPE_WAIT | PIPE_REJECT_REMOTE_CLIENTS;
+    // SAFETY: The name is a null-terminated wide string that outlives the call, and the null
+    // security attributes select the default ones.
+    let handle = unsafe {
+        CreateNamedPipeW(
+            name.as_ptr(),
+            open_mode,
+            pipe_mode,
+            PIPE_UNLIMITED_INSTANCES,
+            4096,
+            4096,
+            0,
+            ptr::null(),
+        )
+    };
+    if handle == INVALID_HANDLE_VALUE {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: The handle was just created, and the file becomes its only owner.
+    Ok(unsafe { File::from_raw_handle(handle) })
+}
+
+/// Waits until a client connects to the given instance of the named pipe.
+#[cfg(windows)]
+fn connect_pipe(pipe: &File) -> io::Result<()> {
+    use windows_sys::Win32::{Foundation::ERROR_PIPE_CONNECTED, System::Pipes::ConnectNamedPipe};
+
+    // SAFETY: The handle stays valid while the file is borrowed, and the pipe was not opened for
+    // overlapped I/O, so the call blocks without an `OVERLAPPED` structure.
+    if unsafe { ConnectNamedPipe(pipe.as_raw_handle(), ptr::null_mut()) } == 0 {
+        // A client that connected before the call is already connected.
+        let error = io::Error::last_os_error();
+        if error.raw_os_error() != Some(ERROR_PIPE_CONNECTED as i32) {
+            return Err(error);
+        }
+    }
+    Ok(())
+}
+
+/// Starts listening on the named pipe for the given path in a background thread. Each command is
+/// sent as a request to the given channel. The call fails if another daemon is already listening
+/// on the pipe.
+#[cfg(windows)]
+pub fn listen(path: &Path, tx: Sender<Message>) -> Result<Listener> {
+    use windows_sys::Win32::Foundation::ERROR_ACCESS_DENIED;
+
+    let name: Vec<u16> = pipe_name(path).encode_wide().chain(Some(0)).collect();
+    let mut pipe = match create_pipe(&name, true) {
+        Ok(pipe) => pipe,
+        Err(error) if error.raw_os_error() == Some(ERROR_ACCESS_DENIED as i32) => {
+            bail!("another daemon is listening on {}", path.display());
+        }
+        Err(error) => {
+            return Err(error).with_context(|| format!("failed to listen on {}", path.display()));
+        }
+    };
+    thread::spawn(move || loop {
+        // Each instance of the pipe serves a single client, so a new one is created to wait for
+        // the next client.
+        if connect_pipe(&pipe).is_ok() {
+            let tx = tx.clone();
+            thread::spawn(move || {
+                let reader = BufReader::new(pipe.try_clone()?);
+                serve(reader, pipe, &tx)
+            });
+        }
+        pipe = match create_pipe(&name, false) {
+            Ok(pipe) => pipe,
+            Err(_) => break,
+        };
+    });
+    Ok(Listener {})
+}
+
+/// Sends a command to the daemon listening on the named pipe for the given path and returns its
+/// reply.
+#[cfg(windows)]
+pub fn send(path: &Path, command: Command) -> Result<String> {
+    let mut pipe = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(pipe_name(path))
+        .with_context(|| format!("failed to connect to {}", path.display()))?;
+    writeln!(pipe, "{}", command)?;
+
+    let mut reply = String::new();
+    BufReader::new(pipe).read_line(&mut reply)?;
+    Ok(reply.trim_end().to_string())
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn listen(_path: &Path, _tx: Sender<Message>) -> Result<Listener> {
+    bail!("daemon mode is only supported on Unix and Windows")
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn send(_path: &Path, _command: Command) -> Result<String> {
+    bail!("daemon mode is only supported on Unix and Windows")
+}
+
+#[cfg(all(test, any(unix, windows)))]
+mod tests {
+    use anyhow::Result;
+    use std::{sync::mpsc, thread};
+    use tempfile::tempdir;
+
+    use crate::{
+        daemon::{self, Command},
+        Message,
+    };
+
+    #[test]
+    fn send_commands() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().join("miner.sock");
+        let (tx, rx) = mpsc::channel();
+        let _listener = daemon::listen(&path, tx)?;
+
+        let handle = thread::spawn(move || {
+            for _ in 0..2 {
+                if let Ok(Message::Request(request)) = rx.recv() {
+                    let _ = request.reply.send(format!("got {}", request.command));
+                }
+            }
+        });
+        assert_eq!(daemon::send(&path, Command::Status)?, "got status");
+        assert_eq!(daemon::send(&path, Command::Reload)?, "got reload");
+        handle.join().unwrap();
+
+        // A second daemon cannot listen on the same socket.
+        assert!(daemon::listen(&path, mpsc::channel().0).is_err());
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn remove_socket() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().join("miner.sock");
+        let listener = daemon::listen(&path, mpsc::channel().0)?;
+        assert!(path.exists());
+
+        drop(listener);
+        assert!(!path.exists());
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unknown_command() -> Result<()> {
+        use std::{
+            io::{BufRead, BufReader, Write},
+            os::unix::net::UnixStream,
+        };
+
+        let dir = tempdir()?;
+        let path = dir.path().join("miner.sock");
+        let _listener = daemon::listen(&path, mpsc::channel().0)?;
+
+        let mut stream = UnixStream::connect(&path)?;
+        writeln!(stream, "meditate")?;
+        let mut reply = String::new();
+        BufReader::new(stream).read_line(&mut reply)?;
+        assert_eq!(reply.trim_end(), "error: unknown command \"meditate\"");
+        Ok(())
+    }
+}
@@ -0,0 +1,559 @@
+//! A command-line interface to recite a sadhana with the mantra miner, either from a sadhana file
+//! in TOML or JSON format or from mantras given inline. With `--socket`, the miner runs as a daemon
+//! that can be controlled through a Unix domain socket, or a named pipe on Windows, and `--send`
+//! sends a command to it.
+
+mod daemon;
+
+use anyhow::{bail, Context, Result};
+use clap::Parser;
+use mantra_miner::{
+    CpuPercent, FinishReason, Mantra, MantraMiner, MinerState, Options, PowerPolicy, Rotation,
+    ThreadPriority, Timestamp, TranscriptWriter,
+};
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::PathBuf,
+    sync::mpsc::{self, RecvTimeoutError},
+    time::Duration,
+};
+
+use crate::daemon::{Command, Request};
+
+/// Recites mantras while it runs.
+#[derive(Debug, Parser)]
+#[command(version, about)]
+struct Args {
+    /// The sadhana file to recite, in TOML or JSON format. The format is detected from the
+    /// extension, and files without a `.json` extension are read as TOML.
+    file: Option<PathBuf>,
+
+    /// A mantra to recite, split into syllables on whitespace and hyphens. Can be given multiple
+    /// times, and replaces the mantras in the sadhana file.
+    #[arg(long = "mantra", value_name = "TEXT")]
+    mantras: Vec<String>,
+
+    /// The preparation recited before the mantras.
+    #[arg(long)]
+    preparation: Option<String>,
+
+    /// The conclusion recited after the mantras.
+    #[arg(long)]
+    conclusion: Option<String>,
+
+    /// The number of times to recite the sadhana. If missing, the sadhana is recited until the
+    /// program is interrupted.
+    #[arg(long)]
+    repeats: Option<usize>,
+
+    /// The longest time to recite for, such as `20m`, after which the program exits.
+    #[arg(long, value_parser = humantime::parse_duration)]
+    run_for: Option<Duration>,
+
+    /// The total number of mantra repetitions after which the program exits, such as `100000`.
+    #[arg(long, value_name = "COUNT")]
+    target_mantras: Option<u64>,
+
+    /// The time to wait between each syllable or character, such as `10ms` or `1s`.
+    #[arg(long, value_parser = humantime::parse_duration)]
+    rate: Option<Duration>,
+
+    /// The time to wait between each character of the preparation. Defaults to the rate.
+    #[arg(long, value_parser = humantime::parse_duration)]
+    preparation_rate: Option<Duration>,
+
+    /// The time to wait between each character of the conclusion. Defaults to the rate.
+    #[arg(long, value_parser = humantime::parse_duration)]
+    conclusion_rate: Option<Duration>,
+
+    /// The maximum amount by which each wait is randomly lengthened or shortened, such as `3ms`.
+    #[arg(long, value_parser = humantime::parse_duration)]
+    jitter: Option<Duration>,
+
+    /// The number of mantra repetitions to recite per day. Overrides the rates.
+    #[arg(long, value_name = "COUNT")]
+    mantras_per_day: Option<u64>,
+
+    /// The time to rest after each repetition of a mantra.
+    #[arg(long, value_parser = humantime::parse_duration)]
+    pause_between_mantras: Option<Duration>,
+
+    /// The time to rest after each recitation of the sadhana.
+    #[arg(long, value_parser = humantime::parse_duration)]
+    pause_between_sadhanas: Option<Duration>,
+
+    /// The maximum share of one core, in percent, that the miner may use, such as `0.1`.
+    #[arg(long, value_name = "PERCENT", value_parser = parse_cpu_percent)]
+    max_cpu_percent: Option<CpuPercent>,
+
+    /// The priority of the thread that recites the sadhana: `normal`, `low`, or `idle`.
+    #[arg(long, value_name = "PRIORITY", value_parser = parse_priority)]
+    thread_priority: Option<ThreadPriority>,
+
+    /// Recites more slowly while the system load is high.
+    #[arg(long)]
+    load_aware: bool,
+
+    /// Keeps the pacing and the counts without writing the recitation.
+    #[arg(long)]
+    skip_output: bool,
+
+    /// Recites as fast as possible without waiting between syllables.
+    #[arg(long)]
+    turbo: bool,
+
+    /// Pauses while the machine runs on battery.
+    #[arg(long)]
+    pause_on_battery: bool,
+
+    /// Pauses while the machine runs on battery with a charge below the given percent.
+    #[arg(long, value_name = "PERCENT", conflicts_with = "pause_on_battery")]
+    pause_below_charge: Option<u8>,
+
+    /// The text written after each syllable, such as a space to recite on a single line. Escapes
+    /// such as `\t` are not interpreted.
+    #[arg(long, value_name = "TEXT")]
+    separator: Option<String>,
+
+    /// Writes a timestamp before each syllable: `wall` for the time of the system clock, or
+    /// `monotonic` for the time since the miner started.
+    #[arg(long, value_name = "KIND", value_parser = parse_timestamp)]
+    timestamp: Option<Timestamp>,
+
+    /// The file to which the sadhana is recited. If missing, the recitation is discarded.
+    #[arg(long)]
+    output: Option<PathBuf>,
+
+    /// Rotates the output file once it reaches the given size in bytes, keeping five old files.
+    #[arg(long, value_name = "BYTES", requires = "output")]
+    rotate_size: Option<u64>,
+
+    /// Rotates the output file once it's older than the given duration, such as `1day`.
+    #[arg(long, value_parser = humantime::parse_duration, requires = "output")]
+    rotate_every: Option<Duration>,
+
+    /// How often to print the counts of the miner.
+    #[arg(long, default_value = "1s", value_parser = humantime::parse_duration)]
+    interval: Duration,
+
+    /// Runs the miner as a daemon controlled through a Unix domain socket at the given path. On
+    /// Windows, a named pipe is used instead, such as `\\.\pipe\mantra-miner`.
+    #[arg(long, value_name = "PATH")]
+    socket: Option<PathBuf>,
+
+    /// Sends a command to the daemon listening on `--socket`, prints its reply, and exits.
+    #[arg(long, value_name = "COMMAND", requires = "socket")]
+    send: Option<Command>,
+}
+
+/// Parses the maximum share of one core that the miner may use.
+fn parse_cpu_percent(text: &str) -> Result<CpuPercent, String> {
+    text.parse()
+        .ok()
+        .and_then(CpuPercent::new)
+        .ok_or_else(|| "expected a percentage greater than zero and at most 100".to_string())
+}
+
+/// Parses the priority of the thread that recites the sadhana.
+fn parse_priority(text: &str) -> Result<ThreadPriority, String> {
+    match text {
+        "normal" => Ok(ThreadPriority::Normal),
+        "low" => Ok(ThreadPriority::Low),
+        "idle" => Ok(ThreadPriority::Idle),
+        _ => Err("expected normal, low, or idle".to_string()),
+    }
+}
+
+/// Parses the kind of timestamp written before each syllable.
+fn parse_timestamp(text: &str) -> Result<Timestamp, String> {
+    match text {
+        "wall" => Ok(Timestamp::WallClock),
+        "monotonic" => Ok(Timestamp::Monotonic),
+        _ => Err("expected wall or monotonic".to_string()),
+    }
+}
+
+impl Args {
+    /// Reads the sadhana file, if any, and applies the options given in the command line.
+    fn options(&self) -> Result<Options> {
+        let mut options = match &self.file {
+            Some(path) => {
+                let is_json = path.extension().is_some_and(|ext| ext == "json");
+                if is_json {
+                    let contents = fs::read_to_string(path)
+                        .with_context(|| format!("failed to read {}", path.display()))?;
+                    serde_json::from_str(&contents)
+                        .with_context(|| format!("failed to parse {}", path.display()))?
+                } else {
+                    Options::from_toml_file(path)?
+                }
+            }
+            None => Options::default(),
+        };
+
+        if !self.mantras.is_empty() {
+            options.mantras = self
+                .mantras
+                .iter()
+                .map(|text| Mantra::from_text(text))
+                .collect();
+        }
+        if self.preparation.is_some() {
+            options.preparation.clone_from(&self.preparation);
+        }
+        if self.conclusion.is_some() {
+            options.conclusion.clone_from(&self.conclusion);
+        }
+        if self.repeats.is_some() {
+            options.repeats = self.repeats;
+        }
+        if self.run_for.is_some() {
+            options.run_for = self.run_for;
+        }
+        if self.target_mantras.is_some() {
+            options.target_mantras = self.target_mantras;
+        }
+        if let Some(rate) = self.rate {
+            options.rate = rate;
+        }
+        if self.preparation_rate.is_some() {
+            options.preparation_rate = self.preparation_rate;
+        }
+        if self.conclusion_rate.is_some() {
+            options.conclusion_rate = self.conclusion_rate;
+        }
+        if self.jitter.is_some() {
+            options.jitter = self.jitter;
+        }
+        if self.mantras_per_day.is_some() {
+            options.mantras_per_day = self.mantras_per_day;
+        }
+        if self.pause_between_mantras.is_some() {
+            options.pause_between_mantras = self.pause_between_mantras;
+        }
+        if self.pause_between_sadhanas.is_some() {
+            options.pause_between_sadhanas = self.pause_between_sadhanas;
+        }
+        if self.max_cpu_percent.is_some() {
+            options.max_cpu_percent = self.max_cpu_percent;
+        }
+        if let Some(priority) = self.thread_priority {
+            options.thread_priority = priority;
+        }
+        if self.load_aware {
+            options.load_aware = true;
+        }
+        if self.skip_output {
+            options.skip_output = true;
+        }
+        if self.turbo {
+            options.turbo = true;
+        }
+        if self.pause_on_battery {
+            options.power_policy = PowerPolicy::PauseOnBattery;
+        }
+        if let Some(threshold) = self.pause_below_charge {
+            options.power_policy = PowerPolicy::PauseBelowCharge(threshold);
+        }
+        if self.separator.is_some() {
+            options.separator.clone_from(&self.separator);
+        }
+        if self.timestamp.is_some() {
+            options.format.timestamp = self.timestamp;
+        }
+
+        if options.mantras.is_empty() {
+            bail!("no mantras to recite: pass a sadhana file or use --mantra");
+        }
+        options.validate()?;
+        Ok(options)
+    }
+
+    /// Returns a new miner with the options given in the command line.
+    fn miner(&self) -> Result<MantraMiner> {
+        let options = self.options()?;
+        match &self.output {
+            Some(path) if self.rotate_size.is_some() || self.rotate_every.is_some() => {
+                let rotation = Rotation {
+                    max_bytes: self.rotate_size,
+                    max_age: self.rotate_every,
+                    ..Default::default()
+                };
+                let transcript = TranscriptWriter::create(path, rotation)
+                    .with_context(|| format!("failed to open {}", path.display()))?;
+                Ok(MantraMiner::with_output(options, Box::new(transcript)))
+            }
+            Some(path) => {
+                let file = File::create(path)
+                    .with_context(|| format!("failed to create {}", path.display()))?;
+                Ok(MantraMiner::with_output(options, Box::new(file)))
+            }
+            None => Ok(MantraMiner::new(options)),
+        }
+    }
+}
+
+/// A message received by the main loop.
+#[derive(Debug)]
+pub enum Message {
+    /// The program was interrupted with Ctrl-C.
+    Interrupt,
+
+    /// A command was received through the control socket.
+    Request(Request),
+}
+
+/// Runs a command received through the control socket and returns the reply to it.
+fn handle(args: &Args, miner: &MantraMiner, command: Command) -> Result<String> {
+    match command {
+        Command::Status => Ok(format!(
+            "{} sadhanas={} syllables={}",
+            if miner.is_paused() {
+                "paused"
+            } else {
+                "running"
+            },
+            miner.count(),
+            miner.syllable_count(),
+        )),
+        Command::Pause => {
+            miner.pause()?;
+            Ok("paused".to_string())
+        }
+        Command::Resume => {
+            miner.resume()?;
+            Ok("resumed".to_string())
+        }
+        Command::Stop => Ok("stopping".to_string()),
+        Command::Reload => {
+            // The counts are kept, and the new options are used from the next sadhana on.
+            miner.update_options(args.options()?)?;
+            Ok("reloaded".to_string())
+        }
+    }
+}
+
+/// Prints the counts of the miner on a single line that is overwritten on every call.
+fn print_counts(miner: &MantraMiner) -> io::Result<()> {
+    let mut stdout = io::stdout().lock();
+    write!(
+        stdout,
+        "\rsadhanas: {}  syllables: {}",
+        miner.count(),
+        miner.syllable_count(),
+    )?;
+    stdout.flush()
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+    if let (Some(command), Some(socket)) = (args.send, &args.socket) {
+        println!("{}", daemon::send(socket, command)?);
+        return Ok(());
+    }
+    let miner = args.miner()?;
+
+    let (tx, rx) = mpsc::channel();
+    let _listener = match &args.socket {
+        Some(socket) => Some(daemon::listen(socket, tx.clone())?),
+        None => None,
+    };
+    ctrlc::set_handler(move || {
+        let _ = tx.send(Message::Interrupt);
+    })
+    .context("failed to set the Ctrl-C handler")?;
+
+    miner.start()?;
+    loop {
+        match rx.recv_timeout(args.interval) {
+            Ok(Message::Interrupt) | Err(RecvTimeoutError::Disconnected) => break,
+            Ok(Message::Request(request)) => {
+                let reply = handle(&args, &miner, request.command)
+                    .unwrap_or_else(|error| format!("error: {:#}", error));
+                let _ = request.reply.send(reply);
+                if request.command == Command::Stop {
+                    break;
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+        }
+
+        // The daemon does not print the counts, which can be queried with the status command.
+        if args.socket.is_none() {
+            print_counts(&miner)?;
+        }
+        if miner.state() == MinerState::Finished {
+            break;
+        }
+    }
+
+    let finish_reason = miner.finish_reason();
+    miner.stop_and_join()?;
+    print_counts(&miner)?;
+    println!();
+    if let (Some(FinishReason::TargetReached), Some(target)) =
+        (finish_reason, miner.options().target_mantras)
+    {
+        println!("Reached the target of {} mantra repetitions", target);
+    }
+    for (index, stats) in miner.stats().mantras.iter().enumerate() {
+        println!("{}: {} repetitions", stats.label(index), stats.completed);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use clap::Parser;
+    use mantra_miner::{CpuPercent, Mantra, PowerPolicy, ThreadPriority, Timestamp};
+    use std::{io::Write, time::Duration};
+    use tempfile::Builder;
+
+    use crate::{daemon::Command, handle, Args};
+
+    #[test]
+    fn inline_options() -> Result<()> {
+        let args = Args::try_parse_from([
+            "mantra-miner",
+            "--mantra",
+            "om ma-ni pad-me hum",
+            "--repeats",
+            "108",
+            "--run-for",
+            "20m",
+            "--target-mantras",
+            "100000",
+            "--rate",
+            "10ms",
+            "--preparation-rate",
+            "50ms",
+            "--jitter",
+            "3ms",
+            "--max-cpu-percent",
+            "0.1",
+            "--thread-priority",
+            "idle",
+            "--load-aware",
+            "--turbo",
+            "--pause-below-charge",
+            "20",
+            "--separator",
+            " ",
+            "--timestamp",
+            "monotonic",
+        ])?;
+        let options = args.options()?;
+        assert_eq!(options.mantras[0].syllables.len(), 6);
+        assert_eq!(options.repeats, Some(108));
+        assert_eq!(options.run_for, Some(Duration::from_secs(20 * 60)));
+        assert_eq!(options.target_mantras, Some(100_000));
+        assert_eq!(options.rate, Duration::from_millis(10));
+        assert_eq!(options.preparation_rate, Some(Duration::from_millis(50)));
+        assert_eq!(options.conclusion_rate, None);
+        assert_eq!(options.jitter, Some(Duration::from_millis(3)));
+        assert_eq!(options.max_cpu_percent, CpuPercent::new(0.1));
+        assert_eq!(options.thread_priority, ThreadPriority::Idle);
+        assert!(options.load_aware);
+        assert!(options.turbo);
+        assert_eq!(options.power_policy, PowerPolicy::PauseBelowCharge(20));
+        assert_eq!(options.separator(), " ");
+        assert_eq!(options.format.timestamp, Some(Timestamp::Monotonic));
+        assert_eq!(args.interval, Duration::from_secs(1));
+        Ok(())
+    }
+
+    #[test]
+    fn json_file() -> Result<()> {
+        let mut file = Builder::new().suffix(".json").tempfile()?;
+        write!(
+            file,
+            r#"{{"mantras": [{{"syllables": ["om", "ah", "hum"]}}], "rate_ns": 1000}}"#
+        )?;
+        let path = file.path().to_str().unwrap();
+        let args = Args::try_parse_from(["mantra-miner", path, "--repeats", "3"])?;
+        let options = args.options()?;
+        assert_eq!(
+            options.mantras[0].syllables,
+            Mantra::from_text("om ah hum").syllables
+        );
+        assert_eq!(options.repeats, Some(3));
+        assert_eq!(options.rate, Duration::from_micros(1));
+        Ok(())
+    }
+
+    #[test]
+    fn toml_file() -> Result<()> {
+        let mut file = Builder::new().suffix(".toml").tempfile()?;
+        write!(file, "rate_ns = 1000\n[[mantras]]\nsyllables = [\"hri\"]\n")?;
+        let path = file.path().to_str().unwrap();
+        let args = Args::try_parse_from(["mantra-miner", path, "--mantra", "om ah hum"])?;
+        let options = args.options()?;
+        assert_eq!(
+            options.mantras[0].syllables,
+            Mantra::from_text("om ah hum").syllables
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn missing_mantras() -> Result<()> {
+        let args = Args::try_parse_from(["mantra-miner", "--rate", "1s"])?;
+        assert!(args.options().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn rotated_output() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("transcript.txt");
+        let args = Args::try_parse_from([
+            "mantra-miner",
+            "--mantra",
+            "om",
+            "--repeats",
+            "2",
+            "--rate",
+            "1us",
+            "--output",
+            path.to_str().unwrap(),
+            "--rotate-size",
+            "1",
+        ])?;
+        let miner = args.miner()?;
+        miner.start()?;
+        miner.wait()?;
+        assert_eq!(std::fs::read_to_string(&path)?, "om\n");
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("transcript.txt.1"))?,
+            "om\n"
+        );
+
+        // Rotation only applies to an output file.
+        assert!(Args::try_parse_from(["mantra-miner", "--rotate-size", "1"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn handle_commands() -> Result<()> {
+        let args =
+            Args::try_parse_from(["mantra-miner", "--mantra", "om ah hum", "--rate", "1us"])?;
+        let miner = args.miner()?;
+        miner.start()?;
+
+        assert_eq!(handle(&args, &miner, Command::Pause)?, "paused");
+        assert!(handle(&args, &miner, Command::Status)?.starts_with("paused sadhanas="));
+        assert_eq!(handle(&args, &miner, Command::Resume)?, "resumed");
+        assert!(handle(&args, &miner, Command::Status)?.starts_with("running sadhanas="));
+        let count = miner.count();
+        assert_eq!(handle(&args, &miner, Command::Reload)?, "reloaded");
+        assert!(miner.count() >= count);
+        assert_eq!(handle(&args, &miner, Command::Stop)?, "stopping");
+        miner.stop_and_join()?;
+
+        // Pausing fails once the miner is stopped.
+        assert!(handle(&args, &miner, Command::Pause).is_err());
+        Ok(())
+    }
+}
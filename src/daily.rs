@@ -0,0 +1,409 @@
+//! Contains the daily statistics of the mantra miner, which bucket the completed recitations by
+//! calendar day in the local time zone to compute daily totals, streaks, and rolling averages.
+
+use serde::{
+    de::{self, Visitor},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+use std::{
+    collections::BTreeMap,
+    fmt,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// The number of seconds in a day.
+const SECONDS_PER_DAY: i64 = 86_400;
+
+/// A calendar day. It's serialized as an ISO 8601 date, such as `"2024-10-04"`.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Date {
+    /// The year.
+    pub year: i32,
+
+    /// The month, from 1 to 12.
+    pub month: u8,
+
+    /// The day of the month, from 1 to 31.
+    pub day: u8,
+}
+
+impl Date {
+    /// Returns the date of the given number of days since the Unix epoch.
+    fn from_days(days: i64) -> Date {
+        // The algorithm is described in http://howardhinnant.github.io/date_algorithms.html.
+        let days = days + 719_468;
+        let era = days.div_euclid(146_097);
+        let day_of_era = days.rem_euclid(146_097);
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let shifted_month = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+        let month = if shifted_month < 10 {
+            shifted_month + 3
+        } else {
+            shifted_month - 9
+        };
+        let year = year_of_era + era * 400 + i64::from(month <= 2);
+        Date {
+            year: year as i32,
+            month: month as u8,
+            day: day as u8,
+        }
+    }
+
+    /// Returns the number of days since the Unix epoch of the date.
+    fn to_days(self) -> i64 {
+        // The inverse of `from_days`, from the same source.
+        let year = i64::from(self.year) - i64::from(self.month <= 2);
+        let era = year.div_euclid(400);
+        let year_of_era = year.rem_euclid(400);
+        let shifted_month = (i64::from(self.month) + 9) % 12;
+        let day_of_year = (153 * shifted_month + 2) / 5 + i64::from(self.day) - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        era * 146_097 + day_of_era - 719_468
+    }
+
+    /// Returns the number of days in the given month of the given year.
+    fn days_in_month(year: i32, month: u8) -> u8 {
+        match month {
+            2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+            2 => 28,
+            4 | 6 | 9 | 11 => 30,
+            _ => 31,
+        }
+    }
+
+    /// Returns the date written in the ISO 8601 format, such as `2024-10-04`, or `None` if it's
+    /// not a valid date.
+    pub(crate) fn parse(text: &str) -> Option<Date> {
+        let mut parts = text.splitn(3, '-');
+        let year = parts.next()?.parse().ok()?;
+        let month = parts.next()?.parse().ok()?;
+        let day = parts.next()?.parse().ok()?;
+        if !(1..=12).contains(&month) || !(1..=Self::days_in_month(year, month)).contains(&day) {
+            return None;
+        }
+        Some(Date { year, month, day })
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+impl Serialize for Date {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Date {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct DateVisitor;
+
+        impl Visitor<'_> for DateVisitor {
+            type Value = Date;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a date such as \"2024-10-04\"")
+            }
+
+            fn visit_str<E: de::Error>(self, text: &str) -> Result<Date, E> {
+                Date::parse(text).ok_or_else(|| E::custom(format!("invalid date {:?}", text)))
+            }
+        }
+
+        deserializer.deserialize_str(DateVisitor)
+    }
+}
+
+/// The recitations completed during a calendar day.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct DayStats {
+    /// The day.
+    pub date: Date,
+
+    /// The number of recitations of the sadhana completed during the day.
+    pub sadhanas: u64,
+
+    /// The number of mantra repetitions completed during the day.
+    pub mantras: u64,
+}
+
+/// The daily statistics of the mantra miner, as returned by `MantraMiner::daily_stats`.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct DailyStats {
+    /// The days on which the miner recited, from the oldest to the most recent.
+    pub days: Vec<DayStats>,
+
+    /// The number of consecutive days, up to today, on which at least one recitation of the
+    /// sadhana was completed. A streak that reached yesterday is not broken until today ends.
+    pub current_streak: u32,
+
+    /// The largest number of consecutive days on which at least one recitation of the sadhana was
+    /// completed.
+    pub longest_streak: u32,
+
+    /// The average number of recitations of the sadhana completed per day over the last seven
+    /// days, including today.
+    pub weekly_average: f64,
+
+    /// The average number of recitations of the sadhana completed per day over the last thirty
+    /// days, including today.
+    pub monthly_average: f64,
+}
+
+/// Returns the offset from UTC in seconds of the local time zone at the given time since the Unix
+/// epoch, which accounts for daylight saving time. The offset is only available on Linux, macOS,
+/// FreeBSD, and Windows, and other platforms use UTC.
+#[cfg(any(target_os = "linux", target_os = "macos", target_os = "freebsd"))]
+fn utc_offset(seconds: i64) -> i64 {
+    let time = seconds as libc::time_t;
+    // SAFETY: `tm` is plain data for which all zeroes is a valid value.
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    // SAFETY: Both pointers are valid for the duration of the call.
+    if unsafe { libc::localtime_r(&time, &mut tm) }.is_null() {
+        return 0;
+    }
+    tm.tm_gmtoff as i64
+}
+
+#[cfg(windows)]
+fn utc_offset(seconds: i64) -> i64 {
+    use windows_sys::Win32::{
+        Foundation::SYSTEMTIME, System::Time::SystemTimeToTzSpecificLocalTime,
+    };
+
+    let date = Date::from_days(seconds.div_euclid(SECONDS_PER_DAY));
+    let time = seconds.rem_euclid(SECONDS_PER_DAY);
+    let utc = SYSTEMTIME {
+        wYear: date.year as u16,
+        wMonth: date.month as u16,
+        wDay: date.day as u16,
+        wHour: (time / 3600) as u16,
+        wMinute: (time / 60 % 60) as u16,
+        wSecond: (time % 60) as u16,
+        ..Default::default()
+    };
+    let mut local = SYSTEMTIME::default();
+    // SAFETY: Both pointers are valid for the duration of the call, and the null time zone stands
+    // for the current one.
+    if unsafe { SystemTimeToTzSpecificLocalTime(std::ptr::null(), &utc, &mut local) } == 0 {
+        return 0;
+    }
+    let local_date = Date {
+        year: i32::from(local.wYear),
+        month: local.wMonth as u8,
+        day: local.wDay as u8,
+    };
+    let local_time =
+        i64::from(local.wHour) * 3600 + i64::from(local.wMinute) * 60 + i64::from(local.wSecond);
+    local_date.to_days() * SECONDS_PER_DAY + local_time - seconds
+}
+
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "macos",
+    target_os = "freebsd",
+    windows
+)))]
+fn utc_offset(_seconds: i64) -> i64 {
+    0
+}
+
+/// Returns the number of seconds since the Unix epoch of the given time in the local time zone.
+fn local_seconds(time: SystemTime) -> i64 {
+    let seconds = match time.duration_since(UNIX_EPOCH) {
+        Ok(elapsed) => elapsed.as_secs() as i64,
+        Err(error) => -(error.duration().as_secs() as i64),
+    };
+    seconds + utc_offset(seconds)
+}
+
+/// Returns the number of days since the Unix epoch of the given time in the local time zone.
+pub(crate) fn local_day(time: SystemTime) -> i64 {
+    local_seconds(time).div_euclid(SECONDS_PER_DAY)
+}
+
+/// Returns the number of minutes since midnight of the given time in the local time zone.
+pub(crate) fn local_minute(time: SystemTime) -> u32 {
+    (local_seconds(time).rem_euclid(SECONDS_PER_DAY) / 60) as u32
+}
+
+/// The totals of the recitations completed on each day, identified by its number of days since the
+/// Unix epoch in the local time zone.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct DailyTotals(BTreeMap<i64, (u64, u64)>);
+
+impl DailyTotals {
+    /// Returns the totals of the given days, such as those saved by a count store.
+    pub(crate) fn from_days(days: &[DayStats]) -> DailyTotals {
+        DailyTotals(
+            days.iter()
+                .map(|day| (day.date.to_days(), (day.sadhanas, day.mantras)))
+                .collect(),
+        )
+    }
+
+    /// Returns the totals of each recorded day, from the oldest to the most recent.
+    pub(crate) fn days(&self) -> Vec<DayStats> {
+        self.0
+            .iter()
+            .map(|(&day, &(sadhanas, mantras))| DayStats {
+                date: Date::from_days(day),
+                sadhanas,
+                mantras,
+            })
+            .collect()
+    }
+
+    /// Records that a recitation of the sadhana was completed on the given day.
+    pub(crate) fn record_sadhana(&mut self, day: i64) {
+        self.0.entry(day).or_default().0 += 1;
+    }
+
+    /// Records that the given number of mantra repetitions were completed on the given day.
+    pub(crate) fn record_mantra(&mut self, day: i64, repetitions: u64) {
+        let totals = self.0.entry(day).or_default();
+        totals.1 = totals.1.saturating_add(repetitions);
+    }
+
+    /// Returns the number of recitations of the sadhana completed on the given day.
+    fn sadhanas(&self, day: i64) -> u64 {
+        self.0.get(&day).map_or(0, |totals| totals.0)
+    }
+
+    /// Returns the average number of recitations of the sadhana completed per day over the given
+    /// number of days, ending with the given day.
+    fn average(&self, today: i64, days: i64) -> f64 {
+        let total: u64 = self
+            .0
+            .range(today - days + 1..=today)
+            .map(|(_, totals)| totals.0)
+            .sum();
+        total as f64 / days as f64
+    }
+
+    /// Returns the statistics of the recorded days, given the current day.
+    pub(crate) fn stats(&self, today: i64) -> DailyStats {
+        let mut longest_streak = 0;
+        let mut streak = 0;
+        let mut previous = None;
+        for (&day, _) in self.0.iter().filter(|(_, totals)| totals.0 > 0) {
+            streak = if previous == Some(day - 1) {
+                streak + 1
+            } else {
+                1
+            };
+            longest_streak = longest_streak.max(streak);
+            previous = Some(day);
+        }
+
+        let mut day = if self.sadhanas(today) > 0 {
+            today
+        } else {
+            today - 1
+        };
+        let mut current_streak = 0;
+        while self.sadhanas(day) > 0 {
+            current_streak += 1;
+            day -= 1;
+        }
+
+        DailyStats {
+            days: self.days(),
+            current_streak,
+            longest_streak,
+            weekly_average: self.average(today, 7),
+            monthly_average: self.average(today, 30),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{
+        de::{self, value::StrDeserializer, IntoDeserializer},
+        Deserialize,
+    };
+
+    use crate::daily::{DailyTotals, Date};
+
+    #[test]
+    fn date() {
+        let date = |year, month, day| Date { year, month, day };
+        assert_eq!(Date::from_days(0), date(1970, 1, 1));
+        assert_eq!(Date::from_days(-1), date(1969, 12, 31));
+        assert_eq!(Date::from_days(11_016), date(2000, 2, 29));
+        assert_eq!(Date::from_days(20_000), date(2024, 10, 4));
+        assert_eq!(Date::from_days(20_000).to_string(), "2024-10-04");
+        for days in [-1, 0, 11_016, 20_000, -719_468] {
+            assert_eq!(Date::from_days(days).to_days(), days);
+        }
+        assert_eq!(Date::parse("2024-10-04"), Some(date(2024, 10, 4)));
+        assert_eq!(Date::parse("2024-13-04"), None);
+        assert_eq!(Date::parse("2024-10"), None);
+    }
+
+    #[test]
+    fn invalid_dates() {
+        let date = |year, month, day| Date { year, month, day };
+        assert_eq!(Date::parse("2024-02-29"), Some(date(2024, 2, 29)));
+        assert_eq!(Date::parse("2000-02-29"), Some(date(2000, 2, 29)));
+        assert_eq!(Date::parse("2024-12-31"), Some(date(2024, 12, 31)));
+        assert_eq!(Date::parse("2024-02-30"), None);
+        assert_eq!(Date::parse("2024-02-31"), None);
+        assert_eq!(Date::parse("2023-02-29"), None);
+        assert_eq!(Date::parse("1900-02-29"), None);
+        assert_eq!(Date::parse("2024-04-31"), None);
+        assert_eq!(Date::parse("2024-10-00"), None);
+
+        // The serialized dates are checked in the same way.
+        let deserializer: StrDeserializer<'_, de::value::Error> = "2024-02-31".into_deserializer();
+        assert!(Date::deserialize(deserializer).is_err());
+    }
+
+    #[test]
+    fn streaks() {
+        let mut totals = DailyTotals::default();
+        for day in [1, 2, 3, 10, 11] {
+            totals.record_sadhana(day);
+        }
+        totals.record_mantra(12, 1);
+
+        // The streak that reached yesterday is still going.
+        let stats = totals.stats(12);
+        assert_eq!(stats.current_streak, 2);
+        assert_eq!(stats.longest_streak, 3);
+
+        let stats = totals.stats(13);
+        assert_eq!(stats.current_streak, 0);
+    }
+
+    #[test]
+    fn totals_and_averages() {
+        let mut totals = DailyTotals::default();
+        for _ in 0..3 {
+            totals.record_sadhana(100);
+            totals.record_mantra(100, 1);
+        }
+        for _ in 0..4 {
+            totals.record_sadhana(80);
+        }
+
+        let stats = totals.stats(100);
+        assert_eq!(stats.days.len(), 2);
+        assert_eq!(stats.days[1].date, Date::from_days(100));
+        assert_eq!(stats.days[1].sadhanas, 3);
+        assert_eq!(stats.days[1].mantras, 3);
+        assert_eq!(stats.weekly_average, 3.0 / 7.0);
+        assert_eq!(stats.monthly_average, 7.0 / 30.0);
+
+        // The totals can be restored from the days they return.
+        let restored = DailyTotals::from_days(&totals.days());
+        assert_eq!(restored.stats(100), stats);
+    }
+}
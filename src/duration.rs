@@ -0,0 +1,192 @@
+//! Contains the human-friendly format of the durations in the sadhana files, such as `"10ms"`,
+//! `"1.5s"`, or `"500us"`. Integers are read as a number of nanoseconds, so that files written for
+//! the deprecated `rate_ns` option keep working.
+
+use alloc::{format, string::String};
+use core::{fmt, time::Duration};
+use serde::{
+    de::{self, Visitor},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+
+/// The units accepted after the number, along with the number of nanoseconds in each.
+const UNITS: [(&str, u128); 8] = [
+    ("ns", 1),
+    ("us", 1_000),
+    ("µs", 1_000),
+    ("ms", 1_000_000),
+    ("s", 1_000_000_000),
+    ("min", 60_000_000_000),
+    ("m", 60_000_000_000),
+    ("h", 3_600_000_000_000),
+];
+
+/// Parses a duration made of a number, which can have a fractional part, followed by a unit.
+/// Returns `None` if the text is not a valid duration or the duration is too long.
+pub(crate) fn parse(text: &str) -> Option<Duration> {
+    let text = text.trim();
+    let split = text
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let (_, unit_ns) = UNITS.iter().find(|(name, _)| *name == unit.trim_start())?;
+
+    let (integer, fraction) = number.split_once('.').unwrap_or((number, ""));
+    if integer.is_empty() && fraction.is_empty() {
+        return None;
+    }
+    let integer: u128 = if integer.is_empty() {
+        0
+    } else {
+        integer.parse().ok()?
+    };
+    let mut nanos = integer.checked_mul(*unit_ns)?;
+    if !fraction.is_empty() {
+        let digits = fraction.get(..fraction.len().min(18))?;
+        let scale = 10u128.pow(digits.len() as u32);
+        nanos = nanos.checked_add(digits.parse::<u128>().ok()? * unit_ns / scale)?;
+    }
+    Some(Duration::new(
+        u64::try_from(nanos / 1_000_000_000).ok()?,
+        (nanos % 1_000_000_000) as u32,
+    ))
+}
+
+/// Formats a duration in the largest unit that represents it exactly.
+pub(crate) fn format(duration: Duration) -> String {
+    let nanos = duration.as_nanos();
+    match nanos {
+        0 => "0s".into(),
+        n if n % 1_000_000_000 == 0 => format!("{}s", n / 1_000_000_000),
+        n if n % 1_000_000 == 0 => format!("{}ms", n / 1_000_000),
+        n if n % 1_000 == 0 => format!("{}us", n / 1_000),
+        n => format!("{}ns", n),
+    }
+}
+
+/// A duration read from and written to the sadhana files in the human-friendly format.
+struct HumanDuration(Duration);
+
+impl Serialize for HumanDuration {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format(self.0))
+    }
+}
+
+impl<'de> Deserialize<'de> for HumanDuration {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct HumanDurationVisitor;
+
+        impl Visitor<'_> for HumanDurationVisitor {
+            type Value = HumanDuration;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a duration such as \"10ms\" or a number of nanoseconds")
+            }
+
+            fn visit_u64<E: de::Error>(self, nanos: u64) -> Result<HumanDuration, E> {
+                Ok(HumanDuration(Duration::from_nanos(nanos)))
+            }
+
+            fn visit_i64<E: de::Error>(self, nanos: i64) -> Result<HumanDuration, E> {
+                u64::try_from(nanos)
+                    .map(|nanos| HumanDuration(Duration::from_nanos(nanos)))
+                    .map_err(|_| E::custom("the duration must not be negative"))
+            }
+
+            fn visit_str<E: de::Error>(self, text: &str) -> Result<HumanDuration, E> {
+                parse(text)
+                    .map(HumanDuration)
+                    .ok_or_else(|| E::custom(format!("invalid duration {:?}", text)))
+            }
+        }
+
+        deserializer.deserialize_any(HumanDurationVisitor)
+    }
+}
+
+/// Reads and writes a `Duration` field in the human-friendly format.
+pub(crate) mod human {
+    use core::time::Duration;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::HumanDuration;
+
+    pub(crate) fn serialize<S: Serializer>(
+        duration: &Duration,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        HumanDuration(*duration).serialize(serializer)
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Duration, D::Error> {
+        HumanDuration::deserialize(deserializer).map(|duration| duration.0)
+    }
+}
+
+/// Reads and writes an `Option<Duration>` field in the human-friendly format.
+pub(crate) mod human_option {
+    use core::time::Duration;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::HumanDuration;
+
+    pub(crate) fn serialize<S: Serializer>(
+        duration: &Option<Duration>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        duration.map(HumanDuration).serialize(serializer)
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Duration>, D::Error> {
+        Option::<HumanDuration>::deserialize(deserializer)
+            .map(|duration| duration.map(|duration| duration.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use crate::duration::{format, parse};
+
+    #[test]
+    fn parse_durations() {
+        assert_eq!(parse("10ms"), Some(Duration::from_millis(10)));
+        assert_eq!(parse("1s"), Some(Duration::from_secs(1)));
+        assert_eq!(parse("500us"), Some(Duration::from_micros(500)));
+        assert_eq!(parse("500µs"), Some(Duration::from_micros(500)));
+        assert_eq!(parse("1.5s"), Some(Duration::from_millis(1500)));
+        assert_eq!(parse(".25 ms"), Some(Duration::from_micros(250)));
+        assert_eq!(parse("2m"), Some(Duration::from_secs(120)));
+        assert_eq!(parse("2min"), Some(Duration::from_secs(120)));
+        assert_eq!(parse("1h"), Some(Duration::from_secs(3600)));
+        assert_eq!(parse(" 7ns "), Some(Duration::from_nanos(7)));
+
+        assert_eq!(parse("10"), None);
+        assert_eq!(parse("ms"), None);
+        assert_eq!(parse(".ms"), None);
+        assert_eq!(parse("10 days"), None);
+        assert_eq!(parse("-1s"), None);
+        assert_eq!(parse("1.2.3s"), None);
+    }
+
+    #[test]
+    fn format_durations() {
+        for duration in [
+            Duration::ZERO,
+            Duration::from_nanos(7),
+            Duration::from_micros(500),
+            Duration::from_millis(10),
+            Duration::from_millis(1500),
+            Duration::from_secs(120),
+        ] {
+            assert_eq!(parse(&format(duration)), Some(duration));
+        }
+        assert_eq!(format(Duration::from_millis(1500)), "1500ms");
+    }
+}
@@ -0,0 +1,229 @@
+//! Defines a mantra miner for microcontrollers, which recites the sadhana to a serial writer and
+//! waits between syllables using an `embedded-hal` delay.
+
+use embedded_hal::delay::DelayNs;
+use embedded_io::Write;
+
+use crate::{Options, Reciter, ReciterEvent};
+
+/// The number of nanoseconds in a millisecond.
+const NANOS_PER_MILLI: u64 = 1_000_000;
+
+/// A mantra miner that recites the sadhana to a serial writer, such as a UART, and waits for the
+/// rate set in the options between syllables using an `embedded-hal` delay or timer. Unlike
+/// `MantraMiner`, it does not spawn anything in the background, so the firmware calls
+/// [`EmbeddedMiner::step`] from its main loop or runs the whole sadhana with
+/// [`EmbeddedMiner::run`].
+pub struct EmbeddedMiner<W: Write, D: DelayNs> {
+    /// The reciter that walks through the sadhana.
+    reciter: Reciter,
+
+    /// The serial writer to which the sadhana is recited.
+    serial: W,
+
+    /// The delay used to wait between syllables.
+    delay: D,
+}
+
+impl<W: Write, D: DelayNs> EmbeddedMiner<W, D> {
+    /// Returns a new miner that recites the sadhana described by the options to the given serial
+    /// writer, waiting between syllables with the given delay.
+    pub fn new(options: Options, serial: W, delay: D) -> EmbeddedMiner<W, D> {
+        EmbeddedMiner {
+            reciter: Reciter::new(options),
+            serial,
+            delay,
+        }
+    }
+
+    /// Returns the reciter, which keeps track of the position within the sadhana and the counts of
+    /// what has been recited.
+    pub fn reciter(&self) -> &Reciter {
+        &self.reciter
+    }
+
+    /// Returns the number of completed recitations of the entire sadhana.
+    pub fn count(&self) -> usize {
+        self.reciter.count()
+    }
+
+    /// Consumes the miner and returns the serial writer and the delay, so that the peripherals can
+    /// be reused.
+    pub fn release(self) -> (W, D) {
+        (self.serial, self.delay)
+    }
+
+    /// Waits for the delay after the last syllable or character recited. Delays that do not fit in
+    /// the nanosecond delay of `embedded-hal` are split into a delay in milliseconds and the
+    /// remaining nanoseconds.
+    fn wait(&mut self) {
+        let rate_ns = self
+            .reciter
+            .next_delay()
+            .as_nanos()
+            .try_into()
+            .unwrap_or(u64::MAX);
+        let millis = rate_ns / NANOS_PER_MILLI;
+        let nanos = (rate_ns % NANOS_PER_MILLI) as u32;
+        let mut remaining = millis;
+        while remaining > 0 {
+            let chunk = remaining.min(u32::MAX as u64);
+            self.delay.delay_ms(chunk as u32);
+            remaining -= chunk;
+        }
+        if nanos > 0 {
+            self.delay.delay_ns(nanos);
+        }
+    }
+
+    /// Recites the next syllable or character of the sadhana to the serial writer and waits for
+    /// the rate set in the options. Returns `false` once the sadhana has been recited the number of
+    /// times set in the options. The writer is flushed after every recitation of the sadhana.
+    pub fn step(&mut self) -> Result<bool, W::Error> {
+        while let Some(event) = self.reciter.next_event() {
+            match event {
+                ReciterEvent::Character(c) => {
+                    let mut b = [0; 4];
+                    self.serial.write_all(c.encode_utf8(&mut b).as_bytes())?;
+                }
+                ReciterEvent::Grapheme(grapheme) => self.serial.write_all(grapheme.as_bytes())?,
+                ReciterEvent::Syllable {
+                    mantra_index,
+                    syllable_index,
+                    ..
+                } => {
+                    let options = self.reciter.options();
+                    let syllable = &options.mantras[mantra_index].syllables[syllable_index];
+                    for text in [
+                        options.format.syllable_prefix.as_str(),
+                        syllable,
+                        &options.format.syllable_suffix,
+                        options.separator(),
+                    ] {
+                        self.serial.write_all(text.as_bytes())?;
+                    }
+                }
+                ReciterEvent::TextCompleted { .. } => {
+                    if self.reciter.options().format.section_newline {
+                        self.serial.write_all(b"\n")?;
+                    }
+                    continue;
+                }
+                ReciterEvent::SadhanaCompleted { .. } => {
+                    self.serial.flush()?;
+                    continue;
+                }
+                _ => continue,
+            }
+            self.wait();
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    /// Recites the sadhana until it has been recited the number of times set in the options. If
+    /// the sadhana is repeated indefinitely, this method only returns if writing to the serial
+    /// writer fails.
+    pub fn run(&mut self) -> Result<(), W::Error> {
+        while self.step()? {}
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::ToString, vec, vec::Vec};
+    use core::{convert::Infallible, time::Duration};
+    use embedded_hal::delay::DelayNs;
+    use embedded_io::{ErrorType, Write};
+
+    use crate::{embedded::EmbeddedMiner, Mantra, Options, OutputFormat};
+
+    /// A serial writer that stores everything written to it.
+    #[derive(Default)]
+    struct Serial {
+        written: Vec<u8>,
+        flushes: usize,
+    }
+
+    impl ErrorType for Serial {
+        type Error = Infallible;
+    }
+
+    impl Write for Serial {
+        fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            self.written.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> Result<(), Self::Error> {
+            self.flushes += 1;
+            Ok(())
+        }
+    }
+
+    /// A delay that adds up the time it was asked to wait instead of waiting.
+    #[derive(Default)]
+    struct Delay {
+        waited_ns: u64,
+    }
+
+    impl DelayNs for Delay {
+        fn delay_ns(&mut self, ns: u32) {
+            self.waited_ns += ns as u64;
+        }
+    }
+
+    #[test]
+    fn run_sadhana() {
+        let options = Options {
+            preparation: Some("ab".to_string()),
+            mantras: vec![Mantra::from_text("om ah hum")],
+            rate: Duration::from_nanos(1500),
+            repeats: Some(2),
+            ..Default::default()
+        };
+        let mut miner = EmbeddedMiner::new(options, Serial::default(), Delay::default());
+        miner.run().unwrap();
+        assert_eq!(miner.count(), 2);
+
+        let (serial, delay) = miner.release();
+        assert_eq!(serial.written, "abom\nah\nhum\n".repeat(2).as_bytes());
+        assert_eq!(serial.flushes, 2);
+        assert_eq!(delay.waited_ns, 2 * 5 * 1500);
+    }
+
+    #[test]
+    fn formatted_output() {
+        let options = Options {
+            preparation: Some("ab".to_string()),
+            mantras: vec![Mantra::from_text("om ah")],
+            separator: Some(" ".to_string()),
+            format: OutputFormat {
+                syllable_prefix: "<".to_string(),
+                syllable_suffix: ">".to_string(),
+                section_newline: true,
+                timestamp: None,
+            },
+            repeats: Some(1),
+            ..Default::default()
+        };
+        let mut miner = EmbeddedMiner::new(options, Serial::default(), Delay::default());
+        miner.run().unwrap();
+        assert_eq!(miner.release().0.written, b"ab\n<om> <ah> ");
+    }
+
+    #[test]
+    fn long_rate() {
+        let options = Options {
+            mantras: vec![Mantra::from_text("hri")],
+            rate: Duration::from_nanos(5_000_000_123),
+            repeats: Some(1),
+            ..Default::default()
+        };
+        let mut miner = EmbeddedMiner::new(options, Serial::default(), Delay::default());
+        assert!(miner.step().unwrap());
+        assert!(!miner.step().unwrap());
+        assert_eq!(miner.release().1.waited_ns, 5_000_000_123);
+    }
+}
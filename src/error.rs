@@ -0,0 +1,140 @@
+//! Defines the errors returned by the mantra miner.
+
+use alloc::string::String;
+#[cfg(feature = "std")]
+use core::fmt;
+use core::time::Duration;
+#[cfg(feature = "std")]
+use std::{io, path::PathBuf};
+use thiserror::Error;
+
+#[cfg(feature = "std")]
+use crate::{Progress, Section};
+
+/// An error returned by the mantra miner.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    /// The operation requires the mantra miner to be stopped, but it is running.
+    #[error("the mantra miner is already running")]
+    AlreadyRunning,
+
+    /// The operation requires the mantra miner to be running, but it is stopped.
+    #[error("the mantra miner is not running")]
+    NotRunning,
+
+    /// The options used to configure the mantra miner are invalid.
+    #[error("invalid options: {0}")]
+    InvalidOptions(String),
+
+    /// Writing the recitation to the output failed.
+    #[cfg(feature = "std")]
+    #[error("failed to write to the output: {0}")]
+    WriteFailed(#[from] io::Error),
+
+    /// The recitation stopped because writing a syllable or character to the output failed.
+    #[cfg(feature = "std")]
+    #[error("the recitation failed: {0}")]
+    RecitationFailed(RecitationError),
+
+    /// The sadhana file at the given path could not be read.
+    #[cfg(feature = "std")]
+    #[error("failed to read sadhana file {}: {source}", path.display())]
+    ReadFailed {
+        /// The path to the sadhana file.
+        path: PathBuf,
+
+        /// The underlying error.
+        source: io::Error,
+    },
+
+    /// The sadhana could not be parsed.
+    #[cfg(feature = "std")]
+    #[error("failed to parse sadhana: {0}")]
+    ParseFailed(#[from] toml::de::Error),
+
+    /// The counts could not be loaded from the count store.
+    #[cfg(feature = "std")]
+    #[error("failed to load the counts from the store: {0}")]
+    StoreFailed(io::Error),
+
+    /// A notification could not be delivered to the webhook.
+    #[cfg(feature = "webhook")]
+    #[error("failed to notify the webhook: {0}")]
+    WebhookFailed(reqwest::Error),
+
+    /// The thread or task running the mantra miner panicked.
+    #[error("the mantra miner thread panicked")]
+    ThreadPanicked,
+
+    /// The thread running the mantra miner did not exit within the given timeout.
+    #[error("the mantra miner thread did not exit within {0:?}")]
+    Timeout(Duration),
+
+    /// The operation would block the current thread, which the spawner of the mantra miner does
+    /// not allow.
+    #[error("the spawner of the mantra miner does not allow blocking")]
+    BlockingUnsupported,
+}
+
+/// The failure that stopped a recitation, with enough context to tell which syllable or character
+/// the output could not accept.
+#[cfg(feature = "std")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RecitationError {
+    /// The position within the sadhana of the syllable or character being written, or of the last
+    /// one written if the output failed while being flushed.
+    pub progress: Option<Progress>,
+
+    /// The syllable or character being written, or `None` if the output failed while being
+    /// flushed.
+    pub text: Option<String>,
+
+    /// The kind of the error returned by the output.
+    pub kind: io::ErrorKind,
+
+    /// The message of the error returned by the output.
+    pub message: String,
+}
+
+#[cfg(feature = "std")]
+impl RecitationError {
+    /// Returns the error returned by the output while writing the given text at the given position.
+    pub(crate) fn new(progress: Option<Progress>, text: Option<&str>, error: &io::Error) -> Self {
+        RecitationError {
+            progress,
+            text: text.map(String::from),
+            kind: error.kind(),
+            message: error.to_string(),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl fmt::Display for RecitationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.text {
+            Some(text) => write!(f, "failed to write {:?}", text)?,
+            None => write!(f, "failed to flush the output")?,
+        }
+        if let Some(progress) = &self.progress {
+            match (
+                progress.section,
+                progress.mantra_index,
+                progress.syllable_index,
+            ) {
+                (Section::Mantras, Some(mantra), Some(syllable)) => write!(
+                    f,
+                    " at syllable {} of repetition {} of mantra {}",
+                    syllable, progress.repeat, mantra
+                )?,
+                _ => write!(f, " in section {}", progress.section_index)?,
+            }
+            write!(f, " of sadhana {}", progress.run)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// A specialized `Result` type for the operations of the mantra miner.
+pub type Result<T> = core::result::Result<T, Error>;
@@ -0,0 +1,154 @@
+//! Contains the export of the statistics of the mantra miner to CSV and JSON files, available with
+//! the `export` feature, so that the recitations can be analyzed or visualized in external tools.
+
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use crate::MinerStats;
+
+/// Returns the given field quoted as required by CSV if it contains a comma, a quote, or a line
+/// break.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+impl MinerStats {
+    /// Writes the statistics to a CSV file at the given path, replacing it if it exists. The file
+    /// has a row for each day and a row for each mantra, told apart by the `kind` column:
+    ///
+    /// ```csv
+    /// kind,date,mantra,sadhanas,repetitions,syllables
+    /// day,2024-10-04,,3,324,
+    /// mantra,,Mani,,324,1944
+    /// ```
+    ///
+    /// The `repetitions` of a day are the mantra repetitions completed during it. Mantras without a
+    /// name are labeled by their position in the options, as in `#1`.
+    pub fn export_csv<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_csv(&mut writer)?;
+        writer.flush()
+    }
+
+    /// Writes the statistics in the CSV format described in `export_csv` to the given writer.
+    fn write_csv(&self, writer: &mut impl Write) -> io::Result<()> {
+        writeln!(writer, "kind,date,mantra,sadhanas,repetitions,syllables")?;
+        for day in &self.days {
+            writeln!(
+                writer,
+                "day,{},,{},{},",
+                day.date, day.sadhanas, day.mantras
+            )?;
+        }
+        for (index, mantra) in self.mantras.iter().enumerate() {
+            writeln!(
+                writer,
+                "mantra,,{},,{},{}",
+                csv_field(&mantra.label(index)),
+                mantra.completed,
+                mantra.syllables
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Writes the statistics to a JSON file at the given path, replacing it if it exists. The file
+    /// contains an object with the total numbers of completed `sadhanas` and
+    /// `interrupted_sadhanas`, the `mantras` with their `name`, `completed` repetitions, and
+    /// recited `syllables`, the `multiplier` of the repetitions, the `days` with their `date`,
+    /// written as in the CSV file, `sadhanas`, and mantra repetitions (`mantras`), and the
+    /// `playlist` with the `name` and `completed` recitations of each of its sadhanas.
+    pub fn export_json<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(&mut writer, self)?;
+        writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use std::fs;
+
+    use crate::{export::csv_field, Date, DayStats, MantraStats, MinerStats, SadhanaStats};
+
+    /// Returns statistics with a day and two mantras.
+    fn stats() -> MinerStats {
+        MinerStats {
+            sadhanas: 3,
+            interrupted_sadhanas: 1,
+            mantras: vec![
+                MantraStats {
+                    name: Some("Mani, short".to_string()),
+                    completed: 3,
+                    syllables: 18,
+                },
+                MantraStats {
+                    name: None,
+                    completed: 6,
+                    syllables: 6,
+                },
+            ],
+            multiplier: 1,
+            days: vec![DayStats {
+                date: Date {
+                    year: 2024,
+                    month: 10,
+                    day: 4,
+                },
+                sadhanas: 3,
+                mantras: 9,
+            }],
+            playlist: vec![SadhanaStats {
+                name: "Morning".to_string(),
+                completed: 2,
+            }],
+        }
+    }
+
+    #[test]
+    fn quote_csv_field() {
+        assert_eq!(csv_field("Mani"), "Mani");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("a\"b"), "\"a\"\"b\"");
+    }
+
+    #[test]
+    fn export_csv() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("stats.csv");
+        stats().export_csv(&path)?;
+        assert_eq!(
+            fs::read_to_string(&path)?,
+            "kind,date,mantra,sadhanas,repetitions,syllables\n\
+             day,2024-10-04,,3,9,\n\
+             mantra,,\"Mani, short\",,3,18\n\
+             mantra,,#1,,6,6\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn export_json() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("stats.json");
+        stats().export_json(&path)?;
+        let json: serde_json::Value = serde_json::from_str(&fs::read_to_string(&path)?)?;
+        assert_eq!(json["sadhanas"], 3);
+        assert_eq!(json["interrupted_sadhanas"], 1);
+        assert_eq!(json["mantras"][0]["name"], "Mani, short");
+        assert_eq!(json["mantras"][1]["completed"], 6);
+        assert_eq!(json["days"][0]["date"], "2024-10-04");
+        assert_eq!(json["days"][0]["mantras"], 9);
+        assert_eq!(json["playlist"][0]["name"], "Morning");
+        assert_eq!(json["playlist"][0]["completed"], 2);
+        Ok(())
+    }
+}
@@ -0,0 +1,271 @@
+//! Defines the C ABI used to embed the mantra miner in applications not written in Rust. The
+//! declarations matching these functions are in `include/mantra_miner.h`. The shared library is
+//! built with `cargo rustc --lib --release --features ffi --crate-type cdylib`.
+
+use std::{
+    ffi::{c_char, CStr},
+    ptr, slice,
+    time::Duration,
+};
+
+use crate::{Error, Mantra, MantraMiner, Options};
+
+/// The status returned by the functions of the C ABI.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MantraMinerStatus {
+    /// The operation succeeded.
+    Ok = 0,
+
+    /// A required pointer was null.
+    NullPointer = 1,
+
+    /// The options are invalid.
+    InvalidOptions = 2,
+
+    /// The operation failed for any other reason, such as the thread running the miner panicking.
+    Failed = 3,
+}
+
+impl From<Error> for MantraMinerStatus {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::InvalidOptions(_) => MantraMinerStatus::InvalidOptions,
+            _ => MantraMinerStatus::Failed,
+        }
+    }
+}
+
+/// The options used to create a mantra miner through the C ABI. Strings are null-terminated and
+/// encoded in UTF-8, and a null string means the section is omitted. A count of zero means the
+/// default value of the corresponding field of `Options`, so that only the mantras and the rate
+/// need to be set in a zeroed struct.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct MantraMinerOptions {
+    /// The text of the preparation, or null to omit it.
+    pub preparation: *const c_char,
+
+    /// The number of times to repeat the preparation. Zero means once.
+    pub preparation_repeats: usize,
+
+    /// An array of `mantras_len` mantras, each given as text split into syllables on whitespace
+    /// and hyphens.
+    pub mantras: *const *const c_char,
+
+    /// The number of mantras in the `mantras` array.
+    pub mantras_len: usize,
+
+    /// The text of the conclusion, or null to omit it.
+    pub conclusion: *const c_char,
+
+    /// The number of times to repeat the conclusion. Zero means once.
+    pub conclusion_repeats: usize,
+
+    /// The number of times to repeat the entire sadhana. Zero means indefinitely.
+    pub repeats: usize,
+
+    /// The number of nanoseconds to wait between each syllable or character.
+    pub rate_ns: u64,
+}
+
+/// Converts a count where zero means the default into an optional value.
+fn nonzero(value: usize) -> Option<usize> {
+    (value != 0).then_some(value)
+}
+
+/// Converts a nullable C string into an owned string. Returns `Err` if the string is not valid
+/// UTF-8.
+///
+/// # Safety
+///
+/// `s` must be null or point to a valid null-terminated string.
+unsafe fn from_c_str(s: *const c_char) -> Result<Option<String>, MantraMinerStatus> {
+    if s.is_null() {
+        return Ok(None);
+    }
+    CStr::from_ptr(s)
+        .to_str()
+        .map(|s| Some(s.to_string()))
+        .map_err(|_| MantraMinerStatus::InvalidOptions)
+}
+
+impl MantraMinerOptions {
+    /// Converts the C options into the options of the mantra miner.
+    ///
+    /// # Safety
+    ///
+    /// All the pointers must be null or valid as described in the documentation of the fields.
+    unsafe fn into_options(self) -> Result<Options, MantraMinerStatus> {
+        let texts = if self.mantras.is_null() {
+            &[]
+        } else {
+            slice::from_raw_parts(self.mantras, self.mantras_len)
+        };
+        let mut mantras = Vec::with_capacity(texts.len());
+        for text in texts {
+            let text = from_c_str(*text)?.ok_or(MantraMinerStatus::NullPointer)?;
+            mantras.push(Mantra::from_text(&text));
+        }
+
+        Ok(Options {
+            preparation: from_c_str(self.preparation)?,
+            preparation_repeats: nonzero(self.preparation_repeats),
+            mantras,
+            conclusion: from_c_str(self.conclusion)?,
+            conclusion_repeats: nonzero(self.conclusion_repeats),
+            repeats: nonzero(self.repeats),
+            rate: Duration::from_nanos(self.rate_ns),
+            ..Default::default()
+        })
+    }
+}
+
+/// Creates a new mantra miner with the given options. Returns null if `options` is null or the
+/// strings in it are not valid UTF-8. The miner must be freed with `mantra_miner_free`.
+///
+/// # Safety
+///
+/// `options` must be null or point to a valid `MantraMinerOptions`.
+#[no_mangle]
+pub unsafe extern "C" fn mantra_miner_new(options: *const MantraMinerOptions) -> *mut MantraMiner {
+    let Some(&options) = options.as_ref() else {
+        return ptr::null_mut();
+    };
+    match options.into_options() {
+        Ok(options) => Box::into_raw(Box::new(MantraMiner::new(options))),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Stops and frees a mantra miner created with `mantra_miner_new`. Does nothing if `miner` is
+/// null.
+///
+/// # Safety
+///
+/// `miner` must be null or a pointer returned by `mantra_miner_new` that has not been freed.
+#[no_mangle]
+pub unsafe extern "C" fn mantra_miner_free(miner: *mut MantraMiner) {
+    if !miner.is_null() {
+        drop(Box::from_raw(miner));
+    }
+}
+
+/// Starts running the mantra miner in a background thread.
+///
+/// # Safety
+///
+/// `miner` must be null or a pointer returned by `mantra_miner_new` that has not been freed.
+#[no_mangle]
+pub unsafe extern "C" fn mantra_miner_start(miner: *mut MantraMiner) -> MantraMinerStatus {
+    let Some(miner) = miner.as_mut() else {
+        return MantraMinerStatus::NullPointer;
+    };
+    match miner.start() {
+        Ok(()) => MantraMinerStatus::Ok,
+        Err(error) => error.into(),
+    }
+}
+
+/// Stops the mantra miner and waits for its thread to exit.
+///
+/// # Safety
+///
+/// `miner` must be null or a pointer returned by `mantra_miner_new` that has not been freed.
+#[no_mangle]
+pub unsafe extern "C" fn mantra_miner_stop(miner: *mut MantraMiner) -> MantraMinerStatus {
+    let Some(miner) = miner.as_mut() else {
+        return MantraMinerStatus::NullPointer;
+    };
+    match miner.stop_and_join() {
+        Ok(()) => MantraMinerStatus::Ok,
+        Err(error) => error.into(),
+    }
+}
+
+/// Returns the number of completed recitations of the entire sadhana, or zero if `miner` is null.
+///
+/// # Safety
+///
+/// `miner` must be null or a pointer returned by `mantra_miner_new` that has not been freed.
+#[no_mangle]
+pub unsafe extern "C" fn mantra_miner_count(miner: *const MantraMiner) -> u64 {
+    miner.as_ref().map_or(0, MantraMiner::count)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ffi::CString, ptr, thread, time::Duration};
+
+    use crate::ffi::*;
+
+    fn options(mantras: &[*const c_char]) -> MantraMinerOptions {
+        MantraMinerOptions {
+            preparation: ptr::null(),
+            preparation_repeats: 0,
+            mantras: mantras.as_ptr(),
+            mantras_len: mantras.len(),
+            conclusion: ptr::null(),
+            conclusion_repeats: 0,
+            repeats: 0,
+            rate_ns: 1000,
+        }
+    }
+
+    #[test]
+    fn run_miner() {
+        let mantra = CString::new("om ma-ni pad-me hum").unwrap();
+        let mantras = [mantra.as_ptr()];
+        let options = options(&mantras);
+        unsafe {
+            let miner = mantra_miner_new(&options);
+            assert!(!miner.is_null());
+            assert_eq!(mantra_miner_start(miner), MantraMinerStatus::Ok);
+            thread::sleep(Duration::from_millis(100));
+            assert_eq!(mantra_miner_stop(miner), MantraMinerStatus::Ok);
+            assert!(mantra_miner_count(miner) > 0);
+            mantra_miner_free(miner);
+        }
+    }
+
+    #[test]
+    fn invalid_options() {
+        let options = options(&[]);
+        unsafe {
+            let miner = mantra_miner_new(&options);
+            assert!(!miner.is_null());
+            assert_eq!(mantra_miner_start(miner), MantraMinerStatus::InvalidOptions);
+            mantra_miner_free(miner);
+        }
+    }
+
+    #[test]
+    fn null_pointers() {
+        unsafe {
+            assert!(mantra_miner_new(ptr::null()).is_null());
+            assert_eq!(
+                mantra_miner_start(ptr::null_mut()),
+                MantraMinerStatus::NullPointer
+            );
+            assert_eq!(
+                mantra_miner_stop(ptr::null_mut()),
+                MantraMinerStatus::NullPointer
+            );
+            assert_eq!(mantra_miner_count(ptr::null()), 0);
+            mantra_miner_free(ptr::null_mut());
+        }
+
+        // A null mantra is rejected.
+        let mantras = [ptr::null()];
+        let options = options(&mantras);
+        unsafe { assert!(mantra_miner_new(&options).is_null()) };
+    }
+
+    #[test]
+    fn invalid_utf8() {
+        let mantra = CString::new(vec![0xff, 0xfe]).unwrap();
+        let mantras = [mantra.as_ptr()];
+        let options = options(&mantras);
+        unsafe { assert!(mantra_miner_new(&options).is_null()) };
+    }
+}
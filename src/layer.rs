@@ -0,0 +1,546 @@
+//! Contains the layers used to transform the output of the mantra miner before it reaches its final
+//! destination. Layers are chained in a [`Pipeline`], which is itself an output, so that
+//! transformations such as transliteration or counting can be combined with any writer. A [`Tee`]
+//! writes the same output to several pipelines, so that each destination can apply layers of its
+//! own, such as writing a romanized transcript to a file and Tibetan script to a display.
+
+use std::{
+    io::{self, Write},
+    mem, str,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use crate::Output;
+
+/// A transformation applied to the output of the mantra miner. Each layer receives the bytes
+/// written to it and writes the result to the next layer, or to the final output if it's the last
+/// one.
+pub trait Layer: Send {
+    /// Transforms the given bytes and writes the result to `next`.
+    fn write(&mut self, buf: &[u8], next: &mut dyn Write) -> io::Result<()>;
+
+    /// Writes any output held back by the layer to `next` and flushes it.
+    fn flush(&mut self, next: &mut dyn Write) -> io::Result<()> {
+        next.flush()
+    }
+}
+
+impl<F: FnMut(&[u8], &mut dyn Write) -> io::Result<()> + Send> Layer for F {
+    fn write(&mut self, buf: &[u8], next: &mut dyn Write) -> io::Result<()> {
+        self(buf, next)
+    }
+}
+
+/// An output that passes everything written to it through a chain of layers before writing it to
+/// the final output. The layers are applied in the order they were added.
+pub struct Pipeline {
+    /// The layers, in the order they are applied.
+    layers: Vec<Box<dyn Layer>>,
+
+    /// The final output.
+    output: Output,
+}
+
+impl Pipeline {
+    /// Returns a pipeline without layers that writes to the given output.
+    pub fn new(output: Output) -> Pipeline {
+        Pipeline {
+            layers: Vec::new(),
+            output,
+        }
+    }
+
+    /// Adds a layer at the end of the pipeline, right before the final output.
+    pub fn layer(mut self, layer: impl Layer + 'static) -> Pipeline {
+        self.layers.push(Box::new(layer));
+        self
+    }
+
+    /// Adds a layer at the end of the pipeline that converts the text to another script, so that
+    /// each output of a [`Tee`] can be written in the script of its readers.
+    #[cfg(feature = "script")]
+    pub fn transliterate(self, transliteration: crate::script::Transliteration) -> Pipeline {
+        self.layer(transliteration.layer())
+    }
+
+    /// Returns the part of the pipeline that starts at the first layer.
+    fn chain(&mut self) -> Chain<'_> {
+        Chain {
+            layers: &mut self.layers,
+            output: &mut self.output,
+        }
+    }
+}
+
+impl Write for Pipeline {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.chain().write_all(buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.chain().flush()
+    }
+}
+
+/// The layers of a pipeline from a given one onwards, seen as the output of the layer before.
+struct Chain<'a> {
+    /// The remaining layers.
+    layers: &'a mut [Box<dyn Layer>],
+
+    /// The final output.
+    output: &'a mut dyn Write,
+}
+
+impl Write for Chain<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.layers.split_first_mut() {
+            Some((layer, layers)) => {
+                layer.write(
+                    buf,
+                    &mut Chain {
+                        layers,
+                        output: &mut *self.output,
+                    },
+                )?;
+                Ok(buf.len())
+            }
+            None => self.output.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.layers.split_first_mut() {
+            Some((layer, layers)) => layer.flush(&mut Chain {
+                layers,
+                output: &mut *self.output,
+            }),
+            None => self.output.flush(),
+        }
+    }
+}
+
+/// A layer that maps the text written to it with the given function, such as `str::to_uppercase`
+/// or a transliteration. A character split across two writes is held back until it's complete,
+/// and invalid UTF-8 is passed through unchanged. By default the text of each write is mapped as
+/// it arrives, which suits functions that map each character on its own. Functions that look at
+/// the characters around each one, such as a transliteration, should set a boundary so that they
+/// only see whole words.
+pub struct MapText<F> {
+    /// The function applied to the text.
+    map: F,
+
+    /// The characters at which the text can be split, or `None` if it can be split anywhere.
+    boundary: Option<fn(char) -> bool>,
+
+    /// The text after the last boundary and the start of a character whose remaining bytes have
+    /// not been written yet.
+    pending: Vec<u8>,
+}
+
+impl<F: FnMut(&str) -> String + Send> MapText<F> {
+    /// Returns a layer that maps the text with the given function.
+    pub fn new(map: F) -> MapText<F> {
+        MapText {
+            map,
+            boundary: None,
+            pending: Vec::new(),
+        }
+    }
+
+    /// Sets the characters at which the text can be split, such as `char::is_whitespace`. The text
+    /// after the last of them is held back until the next one is written or the layer is flushed,
+    /// and the function is only given text that ends with one of them.
+    pub fn boundary(mut self, boundary: fn(char) -> bool) -> MapText<F> {
+        self.boundary = Some(boundary);
+        self
+    }
+
+    /// Maps the given text and writes it to `next`, except for the text after the last boundary,
+    /// which is held back if `hold` is true.
+    fn map_text(&mut self, text: &str, hold: bool, next: &mut dyn Write) -> io::Result<()> {
+        let end = match self.boundary {
+            Some(boundary) if hold => text
+                .char_indices()
+                .rfind(|(_, c)| boundary(*c))
+                .map_or(0, |(index, c)| index + c.len_utf8()),
+            _ => text.len(),
+        };
+        let (text, rest) = text.split_at(end);
+        if !text.is_empty() {
+            next.write_all((self.map)(text).as_bytes())?;
+        }
+        self.pending.extend_from_slice(rest.as_bytes());
+        Ok(())
+    }
+}
+
+impl<F: FnMut(&str) -> String + Send> Layer for MapText<F> {
+    fn write(&mut self, buf: &[u8], next: &mut dyn Write) -> io::Result<()> {
+        self.pending.extend_from_slice(buf);
+        let bytes = mem::take(&mut self.pending);
+        let mut rest = bytes.as_slice();
+        while !rest.is_empty() {
+            match str::from_utf8(rest) {
+                Ok(text) => {
+                    self.map_text(text, true, next)?;
+                    break;
+                }
+                Err(error) => {
+                    let (valid, invalid) = rest.split_at(error.valid_up_to());
+                    let text = str::from_utf8(valid).unwrap_or_default();
+                    match error.error_len() {
+                        Some(len) => {
+                            // Invalid bytes end the text before them, which is mapped whole.
+                            self.map_text(text, false, next)?;
+                            next.write_all(&invalid[..len])?;
+                            rest = &invalid[len..];
+                        }
+                        None => {
+                            self.map_text(text, true, next)?;
+                            self.pending.extend_from_slice(invalid);
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self, next: &mut dyn Write) -> io::Result<()> {
+        let bytes = mem::take(&mut self.pending);
+        let valid = match str::from_utf8(&bytes) {
+            Ok(_) => bytes.len(),
+            Err(error) => error.valid_up_to(),
+        };
+        let text = str::from_utf8(&bytes[..valid]).unwrap_or_default();
+        self.map_text(text, false, next)?;
+        next.write_all(&bytes[valid..])?;
+        next.flush()
+    }
+}
+
+/// An output that writes everything written to it to each of several outputs, such as pipelines
+/// with different layers. Every output receives each write, even if one of them fails, and the
+/// first error returned by them is returned. A write that failed is expected to be written again,
+/// as with `ErrorPolicy::Retry`, and the outputs that already received it are then skipped, so
+/// that only the failed outputs receive it twice.
+#[derive(Default)]
+pub struct Tee {
+    /// The outputs, in the order they are written to.
+    outputs: Vec<Output>,
+
+    /// The number of bytes of the failed write that each output has already received.
+    written: Vec<usize>,
+}
+
+impl Tee {
+    /// Returns a tee without outputs.
+    pub fn new() -> Tee {
+        Tee::default()
+    }
+
+    /// Adds an output to the tee.
+    pub fn output(mut self, output: Output) -> Tee {
+        self.outputs.push(output);
+        self.written.push(0);
+        self
+    }
+
+    /// Calls the given function with each output, and returns the first error it returned, if any.
+    fn each(&mut self, mut f: impl FnMut(&mut Output) -> io::Result<()>) -> io::Result<()> {
+        let mut result = Ok(());
+        for output in &mut self.outputs {
+            let next = f(output);
+            if result.is_ok() {
+                result = next;
+            }
+        }
+        result
+    }
+}
+
+impl Write for Tee {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut result = Ok(buf.len());
+        for (output, written) in self.outputs.iter_mut().zip(&mut self.written) {
+            while *written < buf.len() {
+                match output.write(&buf[*written..]) {
+                    Ok(0) => {
+                        if result.is_ok() {
+                            result = Err(io::ErrorKind::WriteZero.into());
+                        }
+                        break;
+                    }
+                    Ok(len) => *written += len,
+                    Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+                    Err(error) => {
+                        if result.is_ok() {
+                            result = Err(error);
+                        }
+                        break;
+                    }
+                }
+            }
+        }
+        if result.is_ok() {
+            for written in &mut self.written {
+                *written -= buf.len();
+            }
+        }
+        result
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.each(|output| output.flush())
+    }
+}
+
+/// A layer that counts the bytes that reach it and passes them through unchanged.
+#[derive(Clone, Debug, Default)]
+pub struct CountBytes(Arc<AtomicU64>);
+
+impl CountBytes {
+    /// Returns the number of bytes counted so far, including those counted by its clones.
+    pub fn count(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+impl Layer for CountBytes {
+    fn write(&mut self, buf: &[u8], next: &mut dyn Write) -> io::Result<()> {
+        next.write_all(buf)?;
+        self.0.fetch_add(buf.len() as u64, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use parking_lot::Mutex;
+    use std::{
+        io::{self, Write},
+        mem,
+        sync::Arc,
+        time::Duration,
+    };
+
+    use crate::{
+        layer::{CountBytes, MapText, Pipeline, Tee},
+        ErrorPolicy, Mantra, MantraMiner, Options,
+    };
+
+    /// An output that stores everything written to it.
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn layers_in_order() -> Result<()> {
+        let buffer = SharedBuffer::default();
+        let counter = CountBytes::default();
+        let mut pipeline = Pipeline::new(Box::new(buffer.clone()))
+            .layer(MapText::new(|text: &str| text.to_uppercase()))
+            .layer(|buf: &[u8], next: &mut dyn Write| {
+                next.write_all(b"<")?;
+                next.write_all(buf)?;
+                next.write_all(b">")
+            })
+            .layer(counter.clone());
+
+        // The character split across the two writes is uppercased once complete.
+        let text = "oṃ ā".as_bytes();
+        pipeline.write_all(&text[..text.len() - 1])?;
+        pipeline.write_all(&text[text.len() - 1..])?;
+        pipeline.flush()?;
+        assert_eq!(String::from_utf8(buffer.0.lock().clone())?, "<OṂ ><Ā>");
+        assert_eq!(counter.count(), "<OṂ ><Ā>".len() as u64);
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_text() -> Result<()> {
+        let buffer = SharedBuffer::default();
+        let mut pipeline = Pipeline::new(Box::new(buffer.clone()))
+            .layer(MapText::new(|text: &str| text.to_uppercase()));
+        pipeline.write_all(b"om\xffah\xe1")?;
+        pipeline.flush()?;
+        assert_eq!(*buffer.0.lock(), b"OM\xffAH\xe1");
+        Ok(())
+    }
+
+    #[test]
+    fn boundary() -> Result<()> {
+        let buffer = SharedBuffer::default();
+        let mut pipeline = Pipeline::new(Box::new(buffer.clone()))
+            .layer(MapText::new(|text: &str| format!("[{text}]")).boundary(char::is_whitespace));
+        for buf in [&b"o"[..], b"m a", b"h\n", b"hu\xffm", b"\xe0\xa4"] {
+            pipeline.write_all(buf)?;
+        }
+        assert_eq!(*buffer.0.lock(), b"[om ][ah\n][hu]\xff");
+        pipeline.flush()?;
+        assert_eq!(*buffer.0.lock(), b"[om ][ah\n][hu]\xff[m]\xe0\xa4");
+        Ok(())
+    }
+
+    #[test]
+    fn miner_output() -> Result<()> {
+        let buffer = SharedBuffer::default();
+        let pipeline = Pipeline::new(Box::new(buffer.clone()))
+            .layer(MapText::new(|text: &str| text.replace('\n', " ")));
+        let options = Options {
+            mantras: vec![Mantra::from_text("om ah hum")],
+            rate: Duration::from_micros(1),
+            repeats: Some(1),
+            ..Default::default()
+        };
+        let miner = MantraMiner::with_output(options, Box::new(pipeline));
+        miner.start()?;
+        miner.wait()?;
+        assert_eq!(String::from_utf8(buffer.0.lock().clone())?, "om ah hum ");
+        Ok(())
+    }
+
+    #[test]
+    fn tee() -> Result<()> {
+        /// An output that fails every write.
+        struct Broken;
+
+        impl Write for Broken {
+            fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+                Err(io::Error::other("broken"))
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        // Each output applies its own layers, and receives the writes even after another fails.
+        let plain = SharedBuffer::default();
+        let upper = SharedBuffer::default();
+        let mut tee = Tee::new()
+            .output(Box::new(plain.clone()))
+            .output(Box::new(Broken))
+            .output(Box::new(
+                Pipeline::new(Box::new(upper.clone()))
+                    .layer(MapText::new(|text: &str| text.to_uppercase())),
+            ));
+        assert!(tee.write_all(b"om ah").is_err());
+        tee.flush()?;
+        assert_eq!(String::from_utf8(plain.0.lock().clone())?, "om ah");
+        assert_eq!(String::from_utf8(upper.0.lock().clone())?, "OM AH");
+        Ok(())
+    }
+
+    #[test]
+    fn tee_retry() -> Result<()> {
+        /// An output that fails its first write.
+        struct FailOnce(bool, SharedBuffer);
+
+        impl Write for FailOnce {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                if !mem::replace(&mut self.0, true) {
+                    return Err(io::Error::other("failed once"));
+                }
+                self.1.write(buf)
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        // The retried write only reaches the output that failed.
+        let healthy = SharedBuffer::default();
+        let flaky = SharedBuffer::default();
+        let tee = Tee::new()
+            .output(Box::new(healthy.clone()))
+            .output(Box::new(FailOnce(false, flaky.clone())));
+        let options = Options {
+            mantras: vec![Mantra::from_text("om ah hum")],
+            rate: Duration::from_micros(1),
+            repeats: Some(1),
+            error_policy: ErrorPolicy::Retry(3),
+            ..Default::default()
+        };
+        let miner = MantraMiner::with_output(options, Box::new(tee));
+        miner.start()?;
+        miner.wait()?;
+        assert_eq!(
+            String::from_utf8(healthy.0.lock().clone())?,
+            "om\nah\nhum\n"
+        );
+        assert_eq!(String::from_utf8(flaky.0.lock().clone())?, "om\nah\nhum\n");
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "script")]
+    fn scripts() -> Result<()> {
+        use crate::script::Transliteration;
+
+        let transcript = SharedBuffer::default();
+        let display = SharedBuffer::default();
+        let tee = Tee::new()
+            .output(Box::new(transcript.clone()))
+            .output(Box::new(
+                Pipeline::new(Box::new(display.clone()))
+                    .transliterate(Transliteration::IastToDevanagari),
+            ));
+        let options = Options {
+            mantras: vec![Mantra::from_sanskrit("oṃ āḥ hūṃ")],
+            rate: Duration::from_micros(1),
+            repeats: Some(1),
+            ..Default::default()
+        };
+        let miner = MantraMiner::with_output(options, Box::new(tee));
+        miner.start()?;
+        miner.wait()?;
+        assert_eq!(
+            String::from_utf8(transcript.0.lock().clone())?,
+            "oṃ\nāḥ\nhūṃ\n"
+        );
+        assert_eq!(String::from_utf8(display.0.lock().clone())?, "ओं\nआः\nहूं\n");
+
+        // The output of the miner can reach the layer a character at a time, such as when its
+        // buffer fills up, but the words are converted whole.
+        let display = SharedBuffer::default();
+        let mut pipeline = Pipeline::new(Box::new(display.clone()))
+            .transliterate(Transliteration::DevanagariToIast);
+        for c in "कि नमः".chars() {
+            pipeline.write_all(c.to_string().as_bytes())?;
+        }
+        assert_eq!(String::from_utf8(display.0.lock().clone())?, "ki ");
+        pipeline.flush()?;
+        assert_eq!(String::from_utf8(display.0.lock().clone())?, "ki namaḥ");
+
+        // Wylie is converted a line at a time, keeping the spaces between syllables as tshegs
+        // except before a shad.
+        let display = SharedBuffer::default();
+        let mut pipeline =
+            Pipeline::new(Box::new(display.clone())).transliterate(Transliteration::WylieToTibetan);
+        for c in "bla ma la /\noM ma ".chars() {
+            pipeline.write_all(c.to_string().as_bytes())?;
+        }
+        assert_eq!(String::from_utf8(display.0.lock().clone())?, "བླ་མ་ལ།\n");
+        pipeline.flush()?;
+        assert_eq!(String::from_utf8(display.0.lock().clone())?, "བླ་མ་ལ།\nཨོཾ་མ་");
+        Ok(())
+    }
+}
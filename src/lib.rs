@@ -16,51 +16,566 @@
 //! prayer wheel can contain millions or more mantras. Another example consists of carving mantras
 //! in rock, which is common in the Himalayas and Tibet.
 //!
+//! The threads and IO used by the mantra miner are behind the default `std` feature. Without it,
+//! the crate is `no_std` and only requires `alloc`, and [`Reciter`] can be driven by the timers of
+//! the target to walk through the sadhana. The `embedded` feature adds `EmbeddedMiner`, which
+//! recites to a serial writer and waits between syllables with an `embedded-hal` delay. The
+//! `power` feature lets the miner pause while the machine runs on battery, and the `precise`
+//! feature keeps sub-millisecond rates by spinning at the end of each wait. The `graphemes` feature
+//! recites the preparation and conclusion one grapheme cluster at a time instead of one character
+//! at a time, so that scripts such as Tibetan and Devanagari are never written apart from their
+//! combining marks, and the `normalization` feature converts the texts to the Unicode normalization
+//! form set in the options. The `tracing` feature emits a span for each recitation of the sadhana
+//! and an event for each completed mantra, and the `log` feature logs the completed sadhanas,
+//! malas, and mantras for applications that use `log`. The `otel` feature exports the same
+//! counters and spans through OpenTelemetry, and the `sqlite` feature adds a count store that keeps
+//! the history of the recitations in a SQLite database.
+//!
+//! The [`presets`] module contains well-known mantras, so that most users do not need to type the
+//! syllables themselves, and the [`prayers`] module contains traditional prayers to use as the
+//! preparation and conclusion. With the `script` feature, the `script` module converts mantras
+//! written in Wylie to Tibetan script and between IAST and Devanagari, either in the options or as
+//! a layer of the output. A [`Playlist`] lets the miner cycle through several named sadhanas,
+//! one after the other or following a daily schedule, and a [`PrayerWheel`] credits the copies of
+//! a mantra it contains each time the application turns it, rather than reciting over time. An
+//! [`AnimatedVoice`] draws a turning prayer wheel or mandala to the terminal as the syllables are
+//! recited, and with the `speech` feature, a `SpeechVoice` speaks them through the speech
+//! synthesizer of the operating system or another `SpeechEngine`. The `midi` feature adds a
+//! `MidiVoice` that plays each syllable as a note on a MIDI device or into a MIDI file. The `tui`
+//! feature adds a `LiveView` widget for `ratatui` applications that shows the syllable being
+//! recited, a turning prayer wheel, and the running counts, and the `progress` feature adds a
+//! `ProgressReporter` that drives an `indicatif` progress bar through a finite number of sadhanas.
+//! With the `webhook` feature, a `WebhookNotifier` posts milestones and completed sessions to a
+//! URL as JSON, and with the `mqtt` feature, an `MqttPublisher` publishes the events and counters
+//! to an MQTT broker.
+//!
 //! For more information, check the project's README.
 
-use anyhow::Result;
-use parking_lot::Mutex;
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "std")]
+mod animation;
+#[cfg(feature = "tokio")]
+mod async_miner;
+#[cfg(feature = "std")]
+mod daily;
+mod duration;
+#[cfg(feature = "embedded")]
+mod embedded;
+mod error;
+#[cfg(feature = "export")]
+mod export;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "std")]
+mod layer;
+#[cfg(feature = "std")]
+mod load;
+#[cfg(feature = "midi")]
+mod midi;
+#[cfg(feature = "uniffi")]
+mod mobile;
+#[cfg(feature = "mqtt")]
+mod mqtt;
+#[cfg(feature = "node")]
+mod node;
+#[cfg(feature = "normalization")]
+mod normalize;
+#[cfg(feature = "otel")]
+mod otel;
+#[cfg(feature = "std")]
+mod playlist;
+#[cfg(feature = "power")]
+mod power;
+pub mod prayers;
+#[cfg(feature = "precise")]
+mod precise;
+pub mod presets;
+#[cfg(feature = "std")]
+mod priority;
+#[cfg(feature = "progress")]
+mod progress_bar;
+#[cfg(feature = "std")]
+mod prometheus;
+#[cfg(feature = "python")]
+mod python;
+#[cfg(feature = "std")]
+mod queue;
+#[cfg(feature = "std")]
+mod recitation;
+mod reciter;
+pub mod sadhana;
+#[cfg(feature = "script")]
+pub mod script;
+#[cfg(feature = "std")]
+mod spawner;
+#[cfg(feature = "speech")]
+mod speech;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+#[cfg(feature = "std")]
+mod store;
+#[cfg(feature = "std")]
+mod timing;
+#[cfg(feature = "std")]
+mod transcript;
+#[cfg(feature = "tui")]
+mod tui;
+#[cfg(feature = "std")]
+mod voice;
+#[cfg(feature = "wasm")]
+mod wasm;
+#[cfg(feature = "webhook")]
+mod webhook;
+#[cfg(feature = "std")]
+mod wheel;
+
+use alloc::{
+    format,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use core::time::Duration;
+#[cfg(feature = "std")]
+use parking_lot::{Condvar, Mutex};
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "std")]
 use std::{
-    io::{sink, BufWriter, Write},
+    collections::VecDeque,
+    fs,
+    io::{sink, Write},
+    ops::Deref,
+    path::Path,
     sync::{
-        mpsc::{self, Receiver, Sender, TryRecvError},
-        Arc,
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        mpsc::{self, Receiver, Sender},
     },
     thread,
-    time::Duration,
+    time::{Instant, SystemTime},
 };
 
+#[cfg(feature = "std")]
+pub use animation::{AnimatedVoice, Animation};
+#[cfg(feature = "tokio")]
+pub use async_miner::{AsyncMantraMiner, RecitedSyllable};
+#[cfg(feature = "std")]
+pub use daily::{DailyStats, Date, DayStats};
+#[cfg(feature = "embedded")]
+pub use embedded::EmbeddedMiner;
+#[cfg(feature = "std")]
+pub use error::RecitationError;
+pub use error::{Error, Result};
+#[cfg(feature = "std")]
+pub use layer::{CountBytes, Layer, MapText, Pipeline, Tee};
+#[cfg(feature = "std")]
+pub use load::{LoadMonitor, SystemLoad};
+#[cfg(feature = "midi")]
+pub use midi::{MidiFile, MidiSink, MidiVoice, RawMidi};
+#[cfg(feature = "mqtt")]
+pub use mqtt::{MqttPublisher, MqttSink, MqttTopics};
+#[cfg(feature = "std")]
+pub use playlist::{Playlist, PlaylistOrder, PlaylistSadhana, SadhanaStats, TimeOfDay};
+#[cfg(feature = "power")]
+pub use power::{PowerMonitor, PowerStatus, SystemPower};
+#[cfg(feature = "progress")]
+pub use progress_bar::ProgressReporter;
+pub use reciter::{Checkpoint, Progress, Reciter, ReciterEvent, Section};
+#[cfg(feature = "std")]
+pub use spawner::{RecitationTask, Spawner, ThreadSpawner};
+#[cfg(feature = "speech")]
+pub use speech::{SpeechEngine, SpeechUnit, SpeechVoice, SystemSpeech};
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteStore;
+#[cfg(feature = "std")]
+pub use store::{CountStore, FileStore, SadhanaRecord, StoredCounts, WalStore};
+#[cfg(feature = "std")]
+pub use transcript::{Rotation, TranscriptWriter};
+#[cfg(feature = "tui")]
+pub use tui::LiveView;
+#[cfg(feature = "std")]
+pub use voice::Voice;
+#[cfg(feature = "wasm")]
+pub use wasm::WasmSpawner;
+#[cfg(feature = "webhook")]
+pub use webhook::{Notification, NotificationKind, WebhookNotifier};
+#[cfg(feature = "std")]
+pub use wheel::PrayerWheel;
+
+#[cfg(feature = "std")]
+use daily::DailyTotals;
+#[cfg(feature = "std")]
+use playlist::PlaylistState;
+#[cfg(feature = "std")]
+use queue::RunOutput;
+#[cfg(feature = "std")]
+use recitation::Recitation;
+#[cfg(feature = "std")]
+use spawner::Completion;
+#[cfg(feature = "std")]
+use timing::Timing;
+
+#[cfg(feature = "uniffi")]
+uniffi::setup_scaffolding!();
+
 /// A mantra to be "recited" by the miner. Since a computer can't actually recite a mantra, the term
 /// refers to the process of writing the mantra syllable by syllable to an output buffer.
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(default)]
 pub struct Mantra {
-    /// The syllables of the mantra. The mantra will be recited syllable by syllable.
-    pub syllables: Vec<String>,
+    /// An optional name used to identify the mantra in statistics, events, and user interfaces.
+    pub name: Option<String>,
+
+    /// The deity associated with the mantra, if any.
+    pub deity: Option<String>,
+
+    /// The language or script in which the syllables are written, such as `Sanskrit`.
+    pub language: Option<String>,
+
+    /// The text in which the mantra is found, if known.
+    pub source: Option<String>,
+
+    /// The syllables of the mantra. The mantra will be recited syllable by syllable. They are
+    /// shared rather than copied when the options are cloned, so that starting a miner or running
+    /// several miners with the same mantras does not copy every syllable.
+    pub syllables: Arc<[Arc<str>]>,
 
     /// The number of times to repeat the mantra. If it's `None`, the mantra will be repeated once.
     pub repeats: Option<usize>,
+
+    /// The weight of the mantra when the options select one mantra per sadhana by weight, so that
+    /// a mantra can be emphasized without listing it more than once. If it's `None`, the weight is
+    /// one.
+    pub weight: Option<u32>,
+
+    /// Recites the mantra only in every `frequency`th recitation of the sadhana, starting with the
+    /// first, such as a long dharani recited once every ten sadhanas. If it's `None`, the mantra is
+    /// recited in every sadhana.
+    pub frequency: Option<usize>,
 }
 
 impl Mantra {
-    /// Writes the mantra syllable by syllable to the given buffer.
-    fn recite<T>(&self, output: &mut BufWriter<T>, rate: Duration) -> Result<()>
-    where
-        T: Write,
-    {
-        let repeats = self.repeats.unwrap_or(1);
-        for _ in 0..repeats {
-            for syllable in &self.syllables {
-                output.write_all(syllable.as_bytes())?;
-                output.write_all("\n".as_bytes())?;
-                thread::sleep(rate);
-            }
+    /// Returns a new mantra whose syllables are obtained by splitting the given text with the
+    /// default syllable splitter. For example, `"om ma-ni pad-me hum"` results in the syllables
+    /// `om`, `ma`, `ni`, `pad`, `me`, and `hum`.
+    pub fn from_text(text: &str) -> Mantra {
+        Self::from_text_with(text, &SyllableSplitter::default())
+    }
+
+    /// Returns a new mantra whose syllables are obtained by splitting the given text with the given
+    /// syllable splitter.
+    pub fn from_text_with(text: &str, splitter: &SyllableSplitter) -> Mantra {
+        Mantra {
+            syllables: splitter.split(text).into_iter().map(Arc::from).collect(),
+            ..Default::default()
         }
-        Ok(())
+    }
+}
+
+/// Configures how the text of a mantra is split into syllables. The text is always split on
+/// whitespace.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SyllableSplitter {
+    /// Whether to also split the text on hyphens. Defaults to `true`, so that texts such as
+    /// `"pad-me"` are split into the syllables `pad` and `me`.
+    pub split_on_hyphens: bool,
+}
+
+impl Default for SyllableSplitter {
+    fn default() -> Self {
+        SyllableSplitter {
+            split_on_hyphens: true,
+        }
+    }
+}
+
+impl SyllableSplitter {
+    /// Splits the given text into syllables. Empty syllables are discarded.
+    pub fn split(&self, text: &str) -> Vec<String> {
+        text.split(|c: char| c.is_whitespace() || (self.split_on_hyphens && c == '-'))
+            .filter(|syllable| !syllable.is_empty())
+            .map(|syllable| syllable.to_string())
+            .collect()
+    }
+}
+
+/// Configures how completed mantra repetitions are counted in the traditional units of a mala, a
+/// string of prayer beads, and a bum, a large number of malas usually set as the target of a
+/// retreat.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(default)]
+pub struct MalaConfig {
+    /// The number of mantra repetitions in a mala. Defaults to 108.
+    pub beads_per_mala: usize,
+
+    /// The number of malas in a bum. Defaults to 100.
+    pub malas_per_bum: usize,
+}
+
+impl Default for MalaConfig {
+    fn default() -> Self {
+        MalaConfig {
+            beads_per_mala: 108,
+            malas_per_bum: 100,
+        }
+    }
+}
+
+impl MalaConfig {
+    /// Converts the given number of mantra repetitions into bums, malas, and beads.
+    pub fn count(&self, repetitions: usize) -> MalaCount {
+        let total_malas = repetitions / self.beads_per_mala;
+        MalaCount {
+            bums: total_malas / self.malas_per_bum,
+            malas: total_malas % self.malas_per_bum,
+            beads: repetitions % self.beads_per_mala,
+        }
+    }
+}
+
+/// Configures how the recited text is formatted in the output, for example to make each syllable
+/// a tagged line in a log.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(default)]
+pub struct OutputFormat {
+    /// The text written before each syllable of a mantra.
+    pub syllable_prefix: String,
+
+    /// The text written after each syllable of a mantra, before the separator.
+    pub syllable_suffix: String,
+
+    /// Whether to write a newline after each recitation of the preparation and the conclusion, so
+    /// that they don't run into the following text.
+    pub section_newline: bool,
+
+    /// The timestamp written before each syllable of a mantra, followed by a space, so that the
+    /// pace of the recitation can be checked later. If it's `None`, no timestamp is written. It's
+    /// ignored by the embedded miner.
+    pub timestamp: Option<Timestamp>,
+}
+
+/// The kind of timestamp written before each syllable of a mantra.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Timestamp {
+    /// The time of the system clock in UTC, in the RFC 3339 format with milliseconds, such as
+    /// `2024-05-01T12:00:00.250Z`.
+    WallClock,
+
+    /// The time since the run started in seconds with milliseconds, such as `+12.250s`.
+    Monotonic,
+}
+
+/// A number of mantra repetitions expressed in traditional units.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct MalaCount {
+    /// The number of completed bums.
+    pub bums: usize,
+
+    /// The number of completed malas that do not add up to a full bum.
+    pub malas: usize,
+
+    /// The number of repetitions that do not add up to a full mala.
+    pub beads: usize,
+}
+
+/// The operating system priority of the thread that recites the sadhana.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ThreadPriority {
+    /// The priority the thread is created with.
+    #[default]
+    Normal,
+
+    /// A lower priority than the other threads of the program. On Linux and Android, the thread is
+    /// given the highest nice value, and on Windows, the lowest thread priority.
+    Low,
+
+    /// The thread only runs when the system is otherwise idle. On Linux and Android, the thread
+    /// uses the `SCHED_IDLE` policy, and on Windows, it enters background processing mode, which
+    /// also lowers its I/O priority.
+    Idle,
+}
+
+/// When the mantra miner pauses because of the power supply of the machine.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PowerPolicy {
+    /// The miner runs regardless of the power supply.
+    #[default]
+    Ignore,
+
+    /// The miner pauses while the machine runs on battery and resumes on AC power.
+    PauseOnBattery,
+
+    /// The miner pauses while the machine runs on battery with a charge below the given percent.
+    PauseBelowCharge(u8),
+}
+
+/// Which mantras are recited in each recitation of the sadhana.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MantraSelection {
+    /// Every mantra is recited, in order.
+    #[default]
+    All,
+
+    /// A single mantra, drawn at random with the same chance for each, is recited in each
+    /// recitation of the sadhana. The sections of other mantras are skipped.
+    Random,
+
+    /// A single mantra, drawn at random with a chance proportional to its weight, is recited in
+    /// each recitation of the sadhana. The sections of other mantras are skipped.
+    Weighted,
+}
+
+/// How the mantras of consecutive mantra sections are recited.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Interleave {
+    /// Each mantra is repeated the number of times set for it before moving on to the next.
+    #[default]
+    Sequential,
+
+    /// The mantras take turns reciting one repetition each, like several prayer wheels turning at
+    /// once. A mantra with fewer repeats than the others drops out once it's done.
+    Repetitions,
+
+    /// The mantras take turns reciting one syllable each, until each has recited its syllables
+    /// the number of times set for it.
+    Syllables,
+}
+
+/// The Unicode normalization form to which the texts in the options are converted before they are
+/// recited, so that the counts and byte lengths of the recitation do not depend on how the texts
+/// were typed.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Normalization {
+    /// The texts are composed, so that a letter and its accents are a single character when
+    /// possible.
+    #[default]
+    Nfc,
+
+    /// The texts are decomposed, so that accents are always combining marks separate from the
+    /// letters they modify.
+    Nfd,
+
+    /// The texts are recited as they were typed.
+    Unchanged,
+}
+
+/// Whether the mantra miner restarts the recitation after it panics, for example because the
+/// output panicked while being written to.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RestartPolicy {
+    /// The miner stops when the recitation panics.
+    #[default]
+    Never,
+
+    /// The recitation is restarted up to the given number of times during each run.
+    UpTo(u32),
+
+    /// The recitation is always restarted.
+    Always,
+}
+
+impl RestartPolicy {
+    /// Returns whether the recitation can be restarted after it already was the given number of
+    /// times during the run.
+    pub fn allows(&self, restarts: u32) -> bool {
+        match self {
+            RestartPolicy::Never => false,
+            RestartPolicy::UpTo(max_restarts) => restarts < *max_restarts,
+            RestartPolicy::Always => true,
+        }
+    }
+}
+
+/// What the mantra miner does when writing to the output fails.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorPolicy {
+    /// The miner stops and reports the error.
+    #[default]
+    Abort,
+
+    /// The syllable or character that could not be written is skipped and not counted, and the
+    /// miner moves on. A sadhana whose output could not be flushed is still counted.
+    Skip,
+
+    /// The write is retried up to the given number of times, waiting 10 milliseconds before the
+    /// first retry and doubling the wait after each one. The miner stops if the last retry fails.
+    Retry(u32),
+}
+
+/// What the mantra miner does with a write that does not fit in the output queue.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OverflowPolicy {
+    /// The recitation waits until the queue has room for the write.
+    #[default]
+    Block,
+
+    /// The write is dropped.
+    DropNewest,
+
+    /// The oldest writes in the queue are dropped to make room for the new one.
+    DropOldest,
+}
+
+/// A share of one core, in percent, used as the CPU budget of the mantra miner. It's kept in
+/// thousandths of a percent so that options can be compared exactly, and it's written as a number,
+/// such as `0.1`.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct CpuPercent(u32);
+
+impl CpuPercent {
+    /// Returns the given share of one core, rounded to a thousandth of a percent, or `None` if it's
+    /// not greater than zero and at most 100 percent once rounded.
+    pub fn new(percent: f64) -> Option<CpuPercent> {
+        let thousandths = percent * 1000.0 + 0.5;
+        if (1.0..100_001.0).contains(&thousandths) {
+            Some(CpuPercent(thousandths as u32))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the share of one core in percent.
+    pub fn percent(self) -> f64 {
+        f64::from(self.0) / 1000.0
+    }
+}
+
+impl Serialize for CpuPercent {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> core::result::Result<S::Ok, S::Error> {
+        serializer.serialize_f64(self.percent())
+    }
+}
+
+impl<'de> Deserialize<'de> for CpuPercent {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> core::result::Result<Self, D::Error> {
+        let percent = f64::deserialize(deserializer)?;
+        CpuPercent::new(percent).ok_or_else(|| {
+            serde::de::Error::custom(
+                "the CPU budget must be greater than zero and at most 100 percent",
+            )
+        })
     }
 }
 
 /// The options used to configure the mantra miner.
-#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(default)]
 pub struct Options {
     /// Traditional Buddhist sadhanas, or ritual practices, consists of three parts. The first part,
     /// preparation, consists of taking refuge in the Three Jewels and arising bodhicitta, the
@@ -83,16 +598,450 @@ pub struct Options {
     /// once.
     pub conclusion_repeats: Option<usize>,
 
+    /// The sections of the sadhana, in the order in which they are recited, for practices that do
+    /// not fit the preparation, mantras, and conclusion above. The sections refer to the mantras
+    /// above by their index, and the preparation and conclusion must not be set along with them.
+    /// If the list is empty, the sadhana is made of the preparation, the mantras, and the
+    /// conclusion. See [`Options::sadhana_sections`].
+    pub sections: Vec<sadhana::Section>,
+
+    /// Which mantras are recited in each recitation of the sadhana. The preparation, conclusion,
+    /// and the sections that are not mantras are always recited.
+    pub selection: MantraSelection,
+
+    /// How the mantras of consecutive mantra sections are recited. Each mantra keeps its own
+    /// repeats and counts when they are interleaved.
+    pub interleave: Interleave,
+
+    /// The number of mantra repetitions credited for each repetition actually recited, like the
+    /// microfilm inside a prayer wheel on which a single turn carries a hundred thousand mantras.
+    /// The statistics, events, and daily totals count the credited repetitions, while the output
+    /// only contains the recited ones. If it's `None`, each repetition is credited once.
+    pub multiplier: Option<usize>,
+
     /// The number of times to repeat the entire sadhana. If it's `None`, the sadhana will be
     /// repeated indefinitely until the miner is stopped or the program is terminated.
     pub repeats: Option<usize>,
 
-    /// The number of nanoseconds to wait between each syllable of a mantra or character of the
-    /// preparation or conclusion.
-    pub rate_ns: u64,
+    /// The longest the miner runs after it's started, such as the length of a practice session.
+    /// Once it has elapsed, the miner stops on its own, even if the sadhana repeats indefinitely.
+    /// The sadhana being recited at that time is not counted. If it's `None`, there is no limit.
+    #[serde(with = "duration::human_option")]
+    pub run_for: Option<Duration>,
+
+    /// The total number of mantra repetitions, across all mantras, after which the miner stops on
+    /// its own, such as 100,000. The repetitions completed in earlier runs of the same miner count
+    /// towards the target. If it's `None`, there is no target.
+    pub target_mantras: Option<u64>,
+
+    /// The time to wait between each syllable of a mantra or character of the preparation or
+    /// conclusion. Sadhana files can also set it with the deprecated `rate_ns` key.
+    #[serde(alias = "rate_ns", with = "duration::human")]
+    pub rate: Duration,
+
+    /// The time to wait between each character of the preparation. The preparation is prose, so it
+    /// can be paced differently from the syllables of the mantras. If it's `None`, `rate` is used
+    /// instead.
+    #[serde(with = "duration::human_option")]
+    pub preparation_rate: Option<Duration>,
+
+    /// The time to wait between each character of the conclusion. If it's `None`, `rate` is used
+    /// instead.
+    #[serde(with = "duration::human_option")]
+    pub conclusion_rate: Option<Duration>,
+
+    /// The maximum amount by which each wait is randomly lengthened or shortened, so that the
+    /// writes do not happen at fixed intervals. For example, a rate of 10ms with a jitter of 3ms
+    /// waits between 7ms and 13ms. If it's `None`, the rate is used as is.
+    #[serde(with = "duration::human_option")]
+    pub jitter: Option<Duration>,
+
+    /// The number of mantra repetitions to recite per day. If set, the rates above are ignored and
+    /// the same delay is used for every syllable and character, chosen so that the target is met
+    /// including the time spent on the preparation and conclusion. The miner schedules the delays
+    /// against the clock, so that the time spent writing the output does not slow it down.
+    pub mantras_per_day: Option<u64>,
+
+    /// The time to rest after each repetition of a mantra, on top of the rate. If it's `None`, the
+    /// next repetition starts right away.
+    #[serde(with = "duration::human_option")]
+    pub pause_between_mantras: Option<Duration>,
+
+    /// The time to rest after each recitation of the sadhana, on top of the rate. If it's `None`,
+    /// the next recitation starts right away.
+    #[serde(with = "duration::human_option")]
+    pub pause_between_sadhanas: Option<Duration>,
+
+    /// The maximum share of one core, in percent, that the miner may use. For example, `0.1` keeps
+    /// the miner under a thousandth of a core. The miner measures the CPU time used by its thread
+    /// to recite each syllable or character and waits longer when needed to stay under the budget.
+    /// On platforms where the CPU time of a thread is not available, the time spent reciting is
+    /// used instead, which includes any time the thread was not scheduled. It's ignored by the
+    /// embedded miner, which cannot measure time. If it's `None`, there is no limit.
+    pub max_cpu_percent: Option<CpuPercent>,
+
+    /// The priority given to the thread that recites the sadhana, so that the miner does not
+    /// compete with the real work of the program. It's applied by `RecitationTask::run_blocking`
+    /// when the recitation starts, and has no effect on miners driven by an asynchronous runtime
+    /// or on other platforms. Lowering the priority is best effort, and failures are ignored.
+    pub thread_priority: ThreadPriority,
+
+    /// Whether to recite more slowly while the machine is busy. The load is sampled every second
+    /// from the system load average, or from the monitor set with `MantraMiner::set_load_monitor`,
+    /// and the delays are lengthened while it's high. The configured rate is used again once the
+    /// load drops. It's ignored by the embedded miner.
+    pub load_aware: bool,
+
+    /// Whether to skip writing the recitation to the output or voice, while keeping the pacing and
+    /// the counts, as in a virtual recitation. The recent output and the events are still updated.
+    pub skip_output: bool,
+
+    /// Whether to recite as fast as possible without waiting between syllables. The delays are
+    /// still computed, so that the scheduling of the recitation runs as usual but finishes right
+    /// away, which suits benchmarks and tests. It requires a limit on the recitation, such as
+    /// `repeats`, `run_for`, or `target_mantras`.
+    pub turbo: bool,
+
+    /// Whether to end the waits between syllables precisely by spinning for the last two
+    /// milliseconds of each wait instead of sleeping, so that sub-millisecond rates are kept. It
+    /// costs some CPU, and requires the `precise` feature. It's ignored without it, as well as by
+    /// the asynchronous and embedded miners.
+    pub precise_pacing: bool,
+
+    /// The Unicode normalization form to which the syllables of the mantras, the preparation, the
+    /// conclusion, and the texts of the sections are converted before they are recited. It requires
+    /// the `normalization` feature, and the texts are recited as they were typed without it.
+    pub normalization: Normalization,
+
+    /// Whether to pause while the machine runs on battery. The power supply is checked every ten
+    /// seconds from the operating system, or from the monitor set with
+    /// `MantraMiner::set_power_monitor`, and the miner resumes on its own once the machine is
+    /// plugged in. It requires the `power` feature, and is ignored without it.
+    pub power_policy: PowerPolicy,
+
+    /// Whether to restart the recitation when it panics. The recitation restarts from the
+    /// beginning of the sadhana after a delay that starts at 100 milliseconds and doubles after
+    /// each restart, up to a minute. The counts are kept, and the panic is reported by
+    /// `MantraMiner::last_panic`. It only applies to miners driven by a `RecitationTask`.
+    pub restart_policy: RestartPolicy,
+
+    /// What to do when writing to the output fails. By default, the miner stops and the error is
+    /// returned by `MantraMiner::last_error`. Every error, including those skipped or retried, is
+    /// passed to the callback set with `MantraMiner::on_error`.
+    pub error_policy: ErrorPolicy,
+
+    /// The number of bytes of output that can wait to be written. If it's set, the recitation
+    /// queues its writes and a dedicated thread writes them to the output, so that slow outputs do
+    /// not delay the recitation. Errors returned by the output are then reported by the next write
+    /// to the queue. If it's `None`, the recitation writes to the output itself. Changes take
+    /// effect the next time the miner is started.
+    pub output_queue: Option<usize>,
+
+    /// What to do with a write that does not fit in the output queue. The number of bytes dropped
+    /// is returned by `MantraMiner::dropped_bytes`.
+    pub overflow_policy: OverflowPolicy,
+
+    /// The text written after each syllable of a mantra. If it's `None`, each syllable is followed
+    /// by a newline.
+    pub separator: Option<String>,
+
+    /// The formatting applied to the recited text.
+    pub format: OutputFormat,
+
+    /// The configuration used to report completed mantra repetitions in malas.
+    pub mala: MalaConfig,
+
+    /// The number of recently recited syllables to keep in memory, so that they can be retrieved
+    /// with `MantraMiner::recent_output`. The preparation and conclusion are stored as a single
+    /// entry each time they are recited. If the value is zero, nothing is stored.
+    pub recent_output_capacity: usize,
 }
 
 impl Options {
+    /// Returns the text written after each syllable of a mantra, which is a newline unless another
+    /// separator is set.
+    pub fn separator(&self) -> &str {
+        self.separator.as_deref().unwrap_or("\n")
+    }
+
+    /// Returns the number of mantra repetitions credited for each repetition recited, which is one
+    /// unless a multiplier is set.
+    pub fn multiplier(&self) -> usize {
+        self.multiplier.unwrap_or(1)
+    }
+
+    /// Returns the delay between each syllable or character needed to recite `mantras_per_day`
+    /// mantra repetitions per day, or `None` if no target is set or it is zero. The pauses between
+    /// mantras and sadhanas are subtracted from the time available to recite. When a single mantra
+    /// is drawn for each sadhana or some sections are only recited every few sadhanas, the rate is
+    /// that of an average sadhana.
+    pub fn target_rate(&self) -> Option<Duration> {
+        const NANOS_PER_DAY: u128 = 86_400_000_000_000;
+
+        let mantras_per_day = u128::from(self.mantras_per_day.filter(|n| *n > 0)?);
+        let sections = self.sadhana_sections();
+
+        // The totals are those of an average sadhana, multiplied by the total weight so that they
+        // remain integers.
+        let (weights, total_weight) = self.average_weights(&sections);
+        let mut units = 0;
+        let mut repetitions = 0;
+        let mut silence_nanos = 0;
+        for (section, &weight) in sections.iter().zip(&weights) {
+            let repeats = section.repeats(&self.mantras) as u128 * weight;
+            units += section.units(&self.mantras) as u128 * repeats;
+            match section {
+                sadhana::Section::Mantra { .. } => repetitions += repeats,
+                sadhana::Section::Silence { duration, .. } => {
+                    silence_nanos += duration.as_nanos() * weight
+                }
+                sadhana::Section::Text { .. } | sadhana::Section::Group { .. } => {}
+            }
+        }
+        if units == 0 {
+            return None;
+        }
+
+        // Each sadhana takes the time needed to recite its mantra repetitions at the target rate.
+        let sadhana_nanos = NANOS_PER_DAY * repetitions / mantras_per_day;
+        let pause_nanos = self.pause_between_mantras.unwrap_or_default().as_nanos() * repetitions
+            + self.pause_between_sadhanas.unwrap_or_default().as_nanos() * total_weight
+            + silence_nanos;
+        let nanos = sadhana_nanos.saturating_sub(pause_nanos) / units;
+        Some(Duration::from_nanos(nanos.try_into().unwrap_or(u64::MAX)))
+    }
+
+    /// Returns the time needed to recite the sadhana once with the rates and pauses set in the
+    /// options, or with the target rate if `mantras_per_day` is set. The jitter is ignored, since
+    /// it averages out over many syllables, and so is the time spent writing the output. When a
+    /// single mantra is drawn for each sadhana or some sections are only recited every few
+    /// sadhanas, the duration is that of an average sadhana.
+    pub fn sadhana_duration(&self) -> Duration {
+        let target_rate = self.target_rate();
+        let rate = |rate: Option<Duration>| target_rate.or(rate).unwrap_or(self.rate).as_nanos();
+        let sections = self.sadhana_sections();
+        let (weights, total_weight) = self.average_weights(&sections);
+
+        let mut nanos = self.pause_between_sadhanas.unwrap_or_default().as_nanos() * total_weight;
+        for (section, &weight) in sections.iter().zip(&weights) {
+            let repeats = section.repeats(&self.mantras) as u128;
+            let units = section.units(&self.mantras) as u128;
+            let section_nanos = match section {
+                sadhana::Section::Text {
+                    rate: text_rate, ..
+                } => repeats * units * rate(*text_rate),
+                sadhana::Section::Mantra { .. } => {
+                    repeats
+                        * (units * rate(None)
+                            + self.pause_between_mantras.unwrap_or_default().as_nanos())
+                }
+                sadhana::Section::Silence { duration, .. } => duration.as_nanos(),
+                // The sections of the sadhana have their groups expanded.
+                sadhana::Section::Group { .. } => 0,
+            };
+            nanos += weight * section_nanos;
+        }
+        let nanos = nanos / total_weight.max(1);
+        Duration::from_nanos(nanos.try_into().unwrap_or(u64::MAX))
+    }
+
+    /// Returns the time needed to complete one credited mantra repetition in an average sadhana,
+    /// which is the time needed to recite the sadhana once divided by the repetitions it credits, or
+    /// `None` if the sadhana credits no repetitions.
+    #[cfg(feature = "std")]
+    fn mantra_duration(&self) -> Option<Duration> {
+        let sections = self.sadhana_sections();
+        let (weights, total_weight) = self.average_weights(&sections);
+        let repetitions: u128 = sections
+            .iter()
+            .zip(&weights)
+            .filter(|(section, _)| matches!(section, sadhana::Section::Mantra { .. }))
+            .map(|(section, &weight)| section.repeats(&self.mantras) as u128 * weight)
+            .sum::<u128>()
+            * self.multiplier() as u128;
+        if repetitions == 0 {
+            return None;
+        }
+        let nanos = self.sadhana_duration().as_nanos() * total_weight / repetitions;
+        Some(Duration::from_nanos(nanos.try_into().unwrap_or(u64::MAX)))
+    }
+
+    /// Returns the number of nanoseconds to wait between each syllable or character.
+    #[deprecated(note = "use the `rate` field instead")]
+    pub fn rate_ns(&self) -> u64 {
+        self.rate.as_nanos().try_into().unwrap_or(u64::MAX)
+    }
+
+    /// Sets the time to wait between each syllable or character to the given number of
+    /// nanoseconds.
+    #[deprecated(note = "use the `rate` field instead")]
+    pub fn set_rate_ns(&mut self, rate_ns: u64) {
+        self.rate = Duration::from_nanos(rate_ns);
+    }
+
+    /// Parses the options from a string containing a sadhana in TOML format. All fields are
+    /// optional and take their default values if missing. Durations are written as a number
+    /// followed by a unit, such as `"10ms"`, `"1.5s"`, or `"500us"`, and plain integers are read as
+    /// nanoseconds. For example:
+    ///
+    /// ```toml
+    /// preparation = "I take refuge in the Three Jewels and arise bodhicitta."
+    /// conclusion = "I dedicate the merit of this practice to all sentient beings."
+    /// rate = "1ms"
+    ///
+    /// [[mantras]]
+    /// name = "Mani"
+    /// syllables = ["om", "ma", "ni", "pad", "me", "hum"]
+    /// repeats = 108
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn from_toml_str(contents: &str) -> Result<Options> {
+        Ok(toml::from_str(contents)?)
+    }
+
+    /// Reads and parses the options from the sadhana file in TOML format at the given path. See
+    /// [`Options::from_toml_str`] for a description of the format.
+    #[cfg(feature = "std")]
+    pub fn from_toml_file<P: AsRef<Path>>(path: P) -> Result<Options> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path).map_err(|source| Error::ReadFailed {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        Self::from_toml_str(&contents)
+    }
+
+    /// Checks that the options describe a sadhana that can be recited. The mantra list must not be
+    /// empty, every mantra must have at least one syllable, the sections must refer to existing
+    /// mantras, expand to at most a million sections once their groups are repeated, and recite
+    /// at least one character or syllable, the frequencies of the sections and mantras must be
+    /// greater than zero, the mantras drawn by weight must not all weigh zero, and the rate must
+    /// be greater than zero when the sadhana is repeated indefinitely, since the miner would
+    /// otherwise spin at full speed forever. Returns `Error::InvalidOptions` describing the first
+    /// problem found.
+    pub fn validate(&self) -> Result<()> {
+        if self.mantras.is_empty() {
+            return Err(Error::InvalidOptions(
+                "the list of mantras is empty".to_string(),
+            ));
+        }
+
+        for (index, mantra) in self.mantras.iter().enumerate() {
+            if mantra.syllables.is_empty() {
+                return Err(Error::InvalidOptions(format!(
+                    "the mantra at index {} has no syllables",
+                    index
+                )));
+            }
+        }
+
+        if !self.sections.is_empty() && (self.preparation.is_some() || self.conclusion.is_some()) {
+            return Err(Error::InvalidOptions(
+                "the preparation and conclusion cannot be set along with the sections".to_string(),
+            ));
+        }
+
+        for (index, section) in self.sections.iter().enumerate() {
+            if let Some(mantra) = section.max_mantra_index() {
+                if mantra >= self.mantras.len() {
+                    return Err(Error::InvalidOptions(format!(
+                        "the section at index {} refers to the missing mantra at index {}",
+                        index, mantra
+                    )));
+                }
+            }
+        }
+
+        let expanded_len = self.sections.iter().fold(0, |len: usize, section| {
+            len.saturating_add(section.expanded_len())
+        });
+        if expanded_len > sadhana::MAX_EXPANDED_SECTIONS {
+            return Err(Error::InvalidOptions(format!(
+                "the groups of the sections expand to more than {} sections",
+                sadhana::MAX_EXPANDED_SECTIONS
+            )));
+        }
+
+        let sections = self.sadhana_sections();
+        if sections
+            .iter()
+            .any(|section| section.frequency(&self.mantras) == 0)
+        {
+            return Err(Error::InvalidOptions(
+                "the frequencies of the sections and mantras must be greater than zero".to_string(),
+            ));
+        }
+
+        if self
+            .selection(&sections)
+            .is_some_and(|selection| selection.total() == 0)
+        {
+            return Err(Error::InvalidOptions(
+                "the weights of the mantras recited by the sadhana are all zero".to_string(),
+            ));
+        }
+
+        let units: usize = sections
+            .iter()
+            .map(|section| section.units(&self.mantras) * section.repeats(&self.mantras))
+            .sum();
+        if units == 0 {
+            return Err(Error::InvalidOptions(
+                "the sadhana does not recite any character or syllable".to_string(),
+            ));
+        }
+
+        if self.mala.beads_per_mala == 0 || self.mala.malas_per_bum == 0 {
+            return Err(Error::InvalidOptions(
+                "the number of beads per mala and malas per bum must be greater than zero"
+                    .to_string(),
+            ));
+        }
+
+        if self.multiplier == Some(0) {
+            return Err(Error::InvalidOptions(
+                "the multiplier of the mantra repetitions must be greater than zero".to_string(),
+            ));
+        }
+
+        if self.mantras_per_day == Some(0) {
+            return Err(Error::InvalidOptions(
+                "the number of mantras per day must be greater than zero".to_string(),
+            ));
+        }
+
+        if self.output_queue == Some(0) {
+            return Err(Error::InvalidOptions(
+                "the size of the output queue must be greater than zero".to_string(),
+            ));
+        }
+
+        if let PowerPolicy::PauseBelowCharge(threshold) = self.power_policy {
+            if threshold > 100 {
+                return Err(Error::InvalidOptions(
+                    "the battery charge threshold must be at most 100 percent".to_string(),
+                ));
+            }
+        }
+
+        if self.turbo
+            && self.repeats.is_none()
+            && self.run_for.is_none()
+            && self.target_mantras.is_none()
+        {
+            return Err(Error::InvalidOptions(
+                "the turbo mode requires a limit on the recitation".to_string(),
+            ));
+        }
+
+        if self.rate.is_zero() && self.mantras_per_day.is_none() && self.repeats.is_none() {
+            return Err(Error::InvalidOptions(
+                "the rate must be greater than zero when repeating indefinitely".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
     /// Returns whether the mantra miner should perform another iteration.
     fn should_repeat(&self, count: usize) -> bool {
         match self.repeats {
@@ -102,283 +1051,4197 @@ impl Options {
     }
 }
 
-/// A mantra miner that spawns a thread and "recites" mantras by writing them to an output buffer.
-pub struct MantraMiner {
-    /// The options used to configure the mantra miner.
-    options: Options,
+/// A bounded, thread-safe buffer that stores the most recently recited syllables. Once the buffer
+/// is full, the oldest syllables are discarded to make room for new ones.
+#[cfg(feature = "std")]
+#[derive(Clone, Debug, Default)]
+struct RecentOutput {
+    /// The maximum number of syllables stored in the buffer.
+    capacity: usize,
+
+    /// The syllables in the buffer, ordered from oldest to newest.
+    syllables: Arc<Mutex<VecDeque<String>>>,
+}
+
+#[cfg(feature = "std")]
+impl RecentOutput {
+    /// Returns a new, empty buffer that can store up to `capacity` syllables.
+    fn new(capacity: usize) -> RecentOutput {
+        RecentOutput {
+            capacity,
+            syllables: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))),
+        }
+    }
+
+    /// Adds a syllable to the buffer, discarding the oldest one if the buffer is full.
+    fn push(&self, syllable: &str) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let mut syllables = self.syllables.lock();
+        if syllables.len() == self.capacity {
+            syllables.pop_front();
+        }
+        syllables.push_back(syllable.to_string());
+    }
+
+    /// Returns up to the last `n` syllables in the buffer, ordered from oldest to newest.
+    fn recent(&self, n: usize) -> Vec<String> {
+        let syllables = self.syllables.lock();
+        let skip = syllables.len().saturating_sub(n);
+        syllables.iter().skip(skip).cloned().collect()
+    }
+}
+
+/// The type of the writers to which the mantra miner can recite.
+#[cfg(feature = "std")]
+pub type Output = Box<dyn Write + Send>;
+
+/// A writer that shares the output of the mantra miner across the threads it spawns. Each write
+/// locks the underlying output, so a thread that is still winding down after being stopped cannot
+/// interleave partial writes with the thread that replaced it.
+#[cfg(feature = "std")]
+struct SharedOutput(Arc<Mutex<Output>>);
+
+#[cfg(feature = "std")]
+impl Write for SharedOutput {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().write(buf)
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> std::io::Result<()> {
+        self.0.lock().write_all(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.0.lock().flush()
+    }
+}
+
+/// A writer that counts the bytes written to the output of the mantra miner, used to summarize the
+/// recitations run on the calling thread.
+#[cfg(feature = "std")]
+struct CountingOutput {
+    /// The output of the mantra miner.
+    output: RunOutput,
+
+    /// The number of bytes written so far.
+    bytes: u64,
+}
+
+#[cfg(feature = "std")]
+impl Write for CountingOutput {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.output.write(buf)?;
+        self.bytes += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.output.flush()
+    }
+}
+
+/// An event emitted by the mantra miner while it recites the sadhana.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum MinerEvent {
+    /// A new recitation of the sadhana has started.
+    SadhanaStarted {
+        /// The number of recitations completed since the miner was last started.
+        run: usize,
+    },
+
+    /// A repetition of a mantra has been completed.
+    MantraCompleted {
+        /// The position of the mantra in the options.
+        index: usize,
+
+        /// The name of the mantra, if any.
+        name: Option<String>,
+
+        /// The zero-based number of the repetition.
+        repeat: usize,
+
+        /// The number of repetitions counted for it, which is the multiplier set in the options.
+        credited: usize,
+    },
+
+    /// A syllable of a mantra has been recited.
+    SyllableRecited {
+        /// The syllable.
+        syllable: String,
+    },
+
+    /// A recitation of the sadhana has been completed.
+    SadhanaCompleted {
+        /// The number of recitations completed since the miner was last started, not counting
+        /// this one.
+        run: usize,
+
+        /// The updated count of the mantra miner.
+        count: u64,
+    },
+}
+
+/// The list of channels subscribed to the events of the mantra miner.
+#[cfg(feature = "std")]
+#[derive(Clone, Default)]
+struct Subscribers(Arc<Mutex<Vec<Sender<MinerEvent>>>>);
+
+#[cfg(feature = "std")]
+impl Subscribers {
+    /// Adds a new subscriber and returns the channel on which it receives the events.
+    fn subscribe(&self) -> Receiver<MinerEvent> {
+        let (tx, rx) = mpsc::channel();
+        self.0.lock().push(tx);
+        rx
+    }
+
+    /// Returns whether there are no subscribers.
+    fn is_empty(&self) -> bool {
+        self.0.lock().is_empty()
+    }
+
+    /// Sends the event to all subscribers, removing those whose receivers have been dropped.
+    fn emit(&self, event: MinerEvent) {
+        self.0.lock().retain(|tx| tx.send(event.clone()).is_ok());
+    }
+}
+
+/// The statistics of the recitations of a single mantra.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct MantraStats {
+    /// The name of the mantra, if any.
+    pub name: Option<String>,
+
+    /// The number of completed repetitions of the mantra.
+    pub completed: usize,
+
+    /// The number of syllables of the mantra that have been recited.
+    pub syllables: usize,
+}
+
+impl MantraStats {
+    /// Returns the label used to refer to the mantra at the given index in logs and user
+    /// interfaces, which is its name if it has one, and its index otherwise.
+    pub fn label(&self, index: usize) -> String {
+        match &self.name {
+            Some(name) => name.clone(),
+            None => format!("#{}", index),
+        }
+    }
+}
+
+/// The statistics of the mantra miner.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct MinerStats {
+    /// The number of completed recitations of the entire sadhana. Same as `MantraMiner::count`.
+    pub sadhanas: u64,
+
+    /// The number of recitations of the sadhana that were interrupted before their completion.
+    /// Same as `MantraMiner::interrupted_sadhanas`.
+    pub interrupted_sadhanas: u64,
+
+    /// The statistics of each mantra, in the same order as in the options, with the repetitions
+    /// counted the number of times set by the multiplier of the options.
+    pub mantras: Vec<MantraStats>,
+
+    /// The number of repetitions credited for each mantra repetition recited by the current
+    /// options. Same as `Options::multiplier`.
+    pub multiplier: usize,
+
+    /// The recitations completed on each calendar day in the local time zone, from the oldest to
+    /// the most recent. Same as the days returned by `MantraMiner::daily_stats`.
+    #[cfg(feature = "std")]
+    pub days: Vec<DayStats>,
+
+    /// The statistics of each sadhana of the playlist set with `MantraMiner::with_playlist`, in
+    /// the same order as in the playlist, or an empty list if there is no playlist.
+    #[cfg(feature = "std")]
+    pub playlist: Vec<SadhanaStats>,
+}
+
+/// A summary of the recitations run on the calling thread with `MantraMiner::recite_once` or
+/// `MantraMiner::recite_n`.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct RecitationSummary {
+    /// The number of completed recitations of the entire sadhana.
+    pub sadhanas: u64,
+
+    /// The number of completed repetitions of the mantras, across all mantras.
+    pub mantras: u64,
+
+    /// The number of syllables and characters recited.
+    pub syllables: u64,
+
+    /// The time taken by the recitation.
+    pub duration: Duration,
+
+    /// The number of bytes written to the output.
+    pub bytes_written: u64,
+}
+
+/// A flag used to pause and resume the thread running the mantra miner.
+#[cfg(feature = "std")]
+#[derive(Clone, Default)]
+struct PauseFlag(Arc<(Mutex<bool>, Condvar)>);
+
+#[cfg(feature = "std")]
+impl PauseFlag {
+    /// Sets whether the miner is paused and wakes up the thread if it was waiting.
+    fn set(&self, paused: bool) {
+        let (lock, condvar) = &*self.0;
+        *lock.lock() = paused;
+        condvar.notify_all();
+    }
+
+    /// Returns whether the miner is paused.
+    fn is_paused(&self) -> bool {
+        *self.0 .0.lock()
+    }
+
+    /// Blocks the current thread for as long as the miner is paused.
+    fn wait(&self) {
+        let (lock, condvar) = &*self.0;
+        let mut paused = lock.lock();
+        while *paused {
+            condvar.wait(&mut paused);
+        }
+    }
+}
+
+/// The signal used to wake up the count watches when the count of the mantra miner changes or the
+/// miner is dropped. The flag records whether the miner was dropped.
+#[cfg(feature = "std")]
+#[derive(Clone, Default)]
+struct CountSignal(Arc<(Mutex<bool>, Condvar)>);
+
+#[cfg(feature = "std")]
+impl CountSignal {
+    /// Wakes up the watches so that they read the new count.
+    fn notify(&self) {
+        let (lock, condvar) = &*self.0;
+        let _closed = lock.lock();
+        condvar.notify_all();
+    }
+
+    /// Records that the miner was dropped and wakes up the watches so that they stop waiting.
+    fn close(&self) {
+        let (lock, condvar) = &*self.0;
+        *lock.lock() = true;
+        condvar.notify_all();
+    }
+}
+
+/// A receiver that is notified whenever the count of the mantra miner changes, returned by
+/// `MantraMiner::count_watch`. Only the latest count is kept, so a slow receiver skips the values
+/// it missed instead of falling behind, which suits UIs that only display the current count.
+#[cfg(feature = "std")]
+#[derive(Clone)]
+pub struct CountWatch {
+    /// The count of the mantra miner.
+    count: Arc<AtomicU64>,
+
+    /// The signal sent when the count changes.
+    signal: CountSignal,
+
+    /// The last count returned by this watch.
+    seen: u64,
+}
+
+#[cfg(feature = "std")]
+impl CountWatch {
+    /// Returns the current count without marking it as seen.
+    pub fn count(&self) -> u64 {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    /// Returns whether the count has changed since it was last returned by `changed`.
+    pub fn has_changed(&self) -> bool {
+        self.count() != self.seen
+    }
+
+    /// Blocks until the count differs from the one last returned and returns the new count, or
+    /// `None` once the miner has been dropped.
+    pub fn changed(&mut self) -> Option<u64> {
+        self.wait_until(None)
+    }
+
+    /// Same as `changed`, but returns `None` if the count has not changed within the timeout.
+    pub fn changed_timeout(&mut self, timeout: Duration) -> Option<u64> {
+        self.wait_until(Some(Instant::now() + timeout))
+    }
+
+    /// Blocks until the count changes, the miner is dropped, or the deadline passes.
+    fn wait_until(&mut self, deadline: Option<Instant>) -> Option<u64> {
+        let (lock, condvar) = &*self.signal.0;
+        let mut closed = lock.lock();
+        loop {
+            let count = self.count();
+            if count != self.seen {
+                self.seen = count;
+                return Some(count);
+            }
+            if *closed {
+                return None;
+            }
+            match deadline {
+                Some(deadline) => {
+                    if condvar.wait_until(&mut closed, deadline).timed_out() {
+                        return None;
+                    }
+                }
+                None => condvar.wait(&mut closed),
+            }
+        }
+    }
+}
+
+/// The callback called with the errors returned by the output of the mantra miner.
+#[cfg(feature = "std")]
+type ErrorCallback = Box<dyn FnMut(&RecitationError) + Send>;
+
+/// The target of the records logged with the `log` feature, unless another is set with
+/// `MantraMiner::set_log_target`.
+#[cfg(feature = "log")]
+const DEFAULT_LOG_TARGET: &str = "mantra_miner::recitation";
+
+/// The state shared between the mantra miner and the thread running it.
+#[cfg(feature = "std")]
+#[derive(Clone)]
+struct SharedState {
+    /// The number of times the mantra miner has completed a recitation of the entire sadhana. It's
+    /// an atomic so that it can be read often without contention.
+    count: Arc<AtomicU64>,
+
+    /// The total number of syllables and characters that have been recited. It's an atomic for the
+    /// same reason as `count`.
+    syllable_count: Arc<AtomicU64>,
+
+    /// The number of recitations of the sadhana that were interrupted before their completion.
+    interrupted_sadhanas: Arc<AtomicU64>,
+
+    /// The statistics of each mantra, in the same order as in the options.
+    mantra_stats: Arc<Mutex<Vec<MantraStats>>>,
+
+    /// The number of repetitions credited for each mantra repetition by the current options.
+    multiplier: Arc<AtomicUsize>,
+
+    /// The statistics of each mantra during the recitation of the sadhana in progress, passed to
+    /// the count store once it completes.
+    sadhana_stats: Arc<Mutex<Vec<MantraStats>>>,
+
+    /// The totals of the recitations completed on each day.
+    daily: Arc<Mutex<DailyTotals>>,
+
+    /// The buffer storing the most recently recited syllables.
+    recent_output: RecentOutput,
+
+    /// The subscribers to the events of the mantra miner.
+    subscribers: Subscribers,
+
+    /// The flag used to pause the thread running the mantra miner.
+    pause_flag: PauseFlag,
+
+    /// The signal sent to the count watches when the count changes.
+    count_signal: CountSignal,
+
+    /// The rate set with `MantraMiner::set_rate` that the recitation has not picked up yet.
+    rate: Arc<Mutex<Option<Duration>>>,
+
+    /// The options set with `MantraMiner::update_options` that the recitation has not picked up
+    /// yet.
+    options: Arc<Mutex<Option<Options>>>,
+
+    /// The position of the last syllable or character recited.
+    progress: Arc<Mutex<Option<Progress>>>,
+
+    /// The position right after the last syllable or character recited, from which the next run
+    /// resumes, or `None` if the last sadhana was completed.
+    checkpoint: Arc<Mutex<Option<Checkpoint>>>,
+
+    /// The fraction of the sadhana recited up to the checkpoint, or `None` if there is none.
+    sadhana_progress: Arc<Mutex<Option<f64>>>,
+
+    /// The playlist set with `MantraMiner::with_playlist` and the position within it, if any.
+    playlist: Arc<Mutex<Option<PlaylistState>>>,
+
+    /// The timing of the current or last run.
+    timing: Arc<Mutex<Timing>>,
+
+    /// The reason the last run finished on its own, if it did.
+    finish_reason: Arc<Mutex<Option<FinishReason>>>,
+
+    /// The error that stopped the last run, if any.
+    last_error: Arc<Mutex<Option<RecitationError>>>,
+
+    /// The message of the last panic of the recitation, if any.
+    last_panic: Arc<Mutex<Option<String>>>,
+
+    /// The number of times the recitation was restarted after a panic during the current or last
+    /// run.
+    restarts: Arc<Mutex<u32>>,
+
+    /// The callback set with `MantraMiner::on_error`, if any.
+    error_callback: Arc<Mutex<Option<ErrorCallback>>>,
+
+    /// The number of bytes of output dropped because the output queue was full.
+    dropped_bytes: Arc<AtomicU64>,
+
+    /// The voice set with `MantraMiner::set_voice`, if any. The recitation writes to the output
+    /// when it's `None`.
+    voice: Arc<Mutex<Option<Box<dyn Voice>>>>,
+
+    /// The store set with `MantraMiner::with_store`, if any, to which the counts are saved after
+    /// every completed recitation of the sadhana.
+    store: Arc<Mutex<Option<Box<dyn CountStore>>>>,
+
+    /// The monitor sampled by the recitation when the options enable the load-aware mode.
+    load_monitor: Arc<Mutex<Box<dyn LoadMonitor>>>,
+
+    /// The target of the log records, set with `MantraMiner::set_log_target`.
+    #[cfg(feature = "log")]
+    log_target: Arc<Mutex<String>>,
+
+    /// The OpenTelemetry counters updated by the recitation.
+    #[cfg(feature = "otel")]
+    otel: otel::Instruments,
+
+    /// The monitor sampled by the recitation when the options pause the miner on battery.
+    #[cfg(feature = "power")]
+    power_monitor: Arc<Mutex<Box<dyn PowerMonitor>>>,
+}
+
+#[cfg(feature = "std")]
+impl SharedState {
+    /// Returns the initial state for a mantra miner with the given options.
+    fn new(options: &Options) -> SharedState {
+        let mantra_stats = options
+            .mantras
+            .iter()
+            .map(|mantra| MantraStats {
+                name: mantra.name.clone(),
+                ..Default::default()
+            })
+            .collect();
+        SharedState {
+            count: Arc::new(AtomicU64::new(0)),
+            syllable_count: Arc::new(AtomicU64::new(0)),
+            interrupted_sadhanas: Arc::new(AtomicU64::new(0)),
+            mantra_stats: Arc::new(Mutex::new(mantra_stats)),
+            multiplier: Arc::new(AtomicUsize::new(options.multiplier())),
+            sadhana_stats: Arc::new(Mutex::new(Vec::new())),
+            daily: Arc::new(Mutex::new(DailyTotals::default())),
+            recent_output: RecentOutput::new(options.recent_output_capacity),
+            subscribers: Subscribers::default(),
+            pause_flag: PauseFlag::default(),
+            count_signal: CountSignal::default(),
+            rate: Arc::new(Mutex::new(None)),
+            options: Arc::new(Mutex::new(None)),
+            progress: Arc::new(Mutex::new(None)),
+            checkpoint: Arc::new(Mutex::new(None)),
+            sadhana_progress: Arc::new(Mutex::new(None)),
+            playlist: Arc::new(Mutex::new(None)),
+            timing: Arc::new(Mutex::new(Timing::default())),
+            finish_reason: Arc::new(Mutex::new(None)),
+            last_error: Arc::new(Mutex::new(None)),
+            last_panic: Arc::new(Mutex::new(None)),
+            restarts: Arc::new(Mutex::new(0)),
+            error_callback: Arc::new(Mutex::new(None)),
+            dropped_bytes: Arc::new(AtomicU64::new(0)),
+            voice: Arc::new(Mutex::new(None)),
+            store: Arc::new(Mutex::new(None)),
+            load_monitor: Arc::new(Mutex::new(Box::new(SystemLoad))),
+            #[cfg(feature = "log")]
+            log_target: Arc::new(Mutex::new(DEFAULT_LOG_TARGET.to_string())),
+            #[cfg(feature = "otel")]
+            otel: otel::Instruments::default(),
+            #[cfg(feature = "power")]
+            power_monitor: Arc::new(Mutex::new(Box::new(SystemPower))),
+        }
+    }
+
+    /// Updates the statistics to match the mantras in the given options. The statistics of a
+    /// mantra are kept if the mantra at the same position has the same name, and reset otherwise.
+    fn update_mantra_stats(&self, options: &Options) {
+        self.multiplier
+            .store(options.multiplier(), Ordering::Relaxed);
+        let mut mantra_stats = self.mantra_stats.lock();
+        *mantra_stats = options
+            .mantras
+            .iter()
+            .enumerate()
+            .map(|(index, mantra)| match mantra_stats.get(index) {
+                Some(stats) if stats.name == mantra.name => stats.clone(),
+                _ => MantraStats {
+                    name: mantra.name.clone(),
+                    ..Default::default()
+                },
+            })
+            .collect();
+    }
+
+    /// Records that a recitation of the sadhana has started.
+    fn start_sadhana(&self) {
+        self.timing.lock().start_sadhana();
+        *self.sadhana_stats.lock() = self
+            .mantra_stats
+            .lock()
+            .iter()
+            .map(|stats| MantraStats {
+                name: stats.name.clone(),
+                ..Default::default()
+            })
+            .collect();
+    }
+
+    /// Records that a character of the preparation or conclusion has been recited.
+    fn record_character(&self) {
+        self.syllable_count.fetch_add(1, Ordering::Relaxed);
+        #[cfg(feature = "otel")]
+        self.otel.record_syllable();
+    }
+
+    /// Records that the preparation or conclusion has been completely recited.
+    fn record_text(&self, text: &str) {
+        self.recent_output.push(text);
+    }
+
+    /// Records that a syllable of the mantra at the given index has been recited.
+    fn record_syllable(&self, index: usize, syllable: &str) {
+        self.recent_output.push(syllable);
+        self.syllable_count.fetch_add(1, Ordering::Relaxed);
+        if let Some(stats) = self.mantra_stats.lock().get_mut(index) {
+            stats.syllables += 1;
+        }
+        if let Some(stats) = self.sadhana_stats.lock().get_mut(index) {
+            stats.syllables += 1;
+        }
+        #[cfg(feature = "otel")]
+        self.otel.record_syllable();
+        if !self.subscribers.is_empty() {
+            self.subscribers.emit(MinerEvent::SyllableRecited {
+                syllable: syllable.to_string(),
+            });
+        }
+    }
+
+    /// Records that a repetition of the mantra at the given index has been completed, crediting the
+    /// given number of repetitions.
+    fn record_mantra(&self, index: usize, repeat: usize, credited: usize) {
+        let name = match self.mantra_stats.lock().get_mut(index) {
+            Some(stats) => {
+                stats.completed = stats.completed.saturating_add(credited);
+                #[cfg(feature = "tracing")]
+                tracing::info!(
+                    mantra = stats.name.as_deref(),
+                    index,
+                    repeat,
+                    count = stats.completed,
+                    "mantra completed"
+                );
+                #[cfg(feature = "log")]
+                log::trace!(
+                    target: &self.log_target.lock(),
+                    "completed repetition {} of mantra {} ({} in total)",
+                    repeat,
+                    stats.name.as_deref().unwrap_or(&index.to_string()),
+                    stats.completed
+                );
+                #[cfg(feature = "otel")]
+                self.otel.record_mantra(stats.label(index), credited as u64);
+                stats.name.clone()
+            }
+            None => None,
+        };
+        if let Some(stats) = self.sadhana_stats.lock().get_mut(index) {
+            stats.completed = stats.completed.saturating_add(credited);
+        }
+        self.daily
+            .lock()
+            .record_mantra(daily::local_day(SystemTime::now()), credited as u64);
+        self.subscribers.emit(MinerEvent::MantraCompleted {
+            index,
+            name,
+            repeat,
+            credited,
+        });
+    }
+
+    /// Returns the number of times the mantra miner has completed a recitation of the entire
+    /// sadhana.
+    fn count(&self) -> u64 {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    /// Returns the total number of syllables and characters that have been recited.
+    fn syllables(&self) -> u64 {
+        self.syllable_count.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of recitations of the sadhana that were interrupted before their
+    /// completion.
+    fn interrupted_sadhanas(&self) -> u64 {
+        self.interrupted_sadhanas.load(Ordering::Relaxed)
+    }
+
+    /// Returns the statistics of the mantra miner.
+    fn stats(&self) -> MinerStats {
+        MinerStats {
+            sadhanas: self.count(),
+            interrupted_sadhanas: self.interrupted_sadhanas(),
+            mantras: self.mantra_stats.lock().clone(),
+            multiplier: self.multiplier.load(Ordering::Relaxed),
+            days: self.daily_stats().days,
+            playlist: self
+                .playlist
+                .lock()
+                .as_ref()
+                .map_or_else(Vec::new, |playlist| playlist.stats().to_vec()),
+        }
+    }
+
+    /// Returns the name of the sadhana of the playlist being recited, or `None` if there is no
+    /// playlist.
+    fn current_sadhana(&self) -> Option<String> {
+        self.playlist
+            .lock()
+            .as_ref()
+            .and_then(|playlist| playlist.current_name().map(str::to_string))
+    }
+
+    /// Moves the playlist, if any, to the sadhana to recite next at the current time, and returns
+    /// its options if it's not the one being recited.
+    fn next_playlist_options(&self) -> Option<Options> {
+        self.playlist
+            .lock()
+            .as_mut()?
+            .advance(daily::local_minute(SystemTime::now()))
+    }
+
+    /// Checks that the playlist, if any, can be recited.
+    fn validate_playlist(&self) -> Result<()> {
+        match &*self.playlist.lock() {
+            Some(playlist) => playlist.playlist().validate(),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the statistics of the recitations completed on each day, in the local time zone.
+    fn daily_stats(&self) -> DailyStats {
+        self.daily.lock().stats(daily::local_day(SystemTime::now()))
+    }
+
+    /// Returns the total number of mantra repetitions completed across all mantras.
+    fn completed_mantras(&self) -> u64 {
+        self.mantra_stats
+            .lock()
+            .iter()
+            .map(|stats| stats.completed as u64)
+            .sum()
+    }
+
+    /// Passes the recitation of the sadhana that just completed with the given count and duration
+    /// to the count store, if any, and saves the updated counts to it. A failed save is ignored,
+    /// since the next one replaces all the counts.
+    fn save_counts(&self, count: u64, duration: Option<Duration>) {
+        let mut store = self.store.lock();
+        let Some(store) = &mut *store else {
+            return;
+        };
+        if let Some(duration) = duration {
+            let record = SadhanaRecord {
+                completed_at: SystemTime::now(),
+                duration,
+                count,
+                mantras: self.sadhana_stats.lock().clone(),
+            };
+            let _ = store.record_sadhana(&record);
+        }
+        let counts = StoredCounts {
+            sadhanas: count,
+            syllables: self.syllables(),
+            interrupted_sadhanas: self.interrupted_sadhanas(),
+            checkpoint: None,
+            days: self.daily.lock().days(),
+        };
+        let _ = store.save(&counts);
+    }
+
+    /// Saves the counts with the checkpoint of the recitation to the count store, if any, once a
+    /// run ends, so that the next run resumes from it even after the program restarts. A failed
+    /// save is ignored, as in `save_counts`.
+    fn save_checkpoint(&self) {
+        if let Some(store) = &mut *self.store.lock() {
+            let counts = StoredCounts {
+                sadhanas: self.count(),
+                syllables: self.syllables(),
+                interrupted_sadhanas: self.interrupted_sadhanas(),
+                checkpoint: *self.checkpoint.lock(),
+                days: self.daily.lock().days(),
+            };
+            let _ = store.save(&counts);
+        }
+    }
+
+    /// Records that a recitation of the sadhana was interrupted before its completion.
+    fn record_interruption(&self) {
+        self.interrupted_sadhanas.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Loads the counts saved in the count store, if any, and replaces the counts and daily totals
+    /// of the miner with them.
+    fn load_counts(&self) -> Result<()> {
+        if let Some(store) = &mut *self.store.lock() {
+            let counts = store.load().map_err(Error::StoreFailed)?;
+            self.count.store(counts.sadhanas, Ordering::Relaxed);
+            self.syllable_count
+                .store(counts.syllables, Ordering::Relaxed);
+            self.interrupted_sadhanas
+                .store(counts.interrupted_sadhanas, Ordering::Relaxed);
+            *self.checkpoint.lock() = counts.checkpoint;
+            *self.daily.lock() = DailyTotals::from_days(&counts.days);
+        }
+        Ok(())
+    }
+
+    /// Records that a recitation of the sadhana has been completed. `run` is the number of
+    /// recitations completed since the miner was last started, not counting this one.
+    fn record_sadhana(&self, run: usize) {
+        let duration = self.timing.lock().complete_sadhana();
+        self.checkpoint.lock().take();
+        self.sadhana_progress.lock().take();
+        let count = self.count.fetch_add(1, Ordering::Relaxed) + 1;
+        if let Some(playlist) = &mut *self.playlist.lock() {
+            playlist.record_sadhana();
+        }
+        self.count_signal.notify();
+        self.daily
+            .lock()
+            .record_sadhana(daily::local_day(SystemTime::now()));
+        #[cfg(feature = "log")]
+        log::debug!(target: &self.log_target.lock(), "completed sadhana {count}");
+        #[cfg(feature = "otel")]
+        self.otel.record_sadhana();
+        self.save_counts(count, duration);
+        self.subscribers
+            .emit(MinerEvent::SadhanaCompleted { run, count });
+    }
+}
+
+/// The state of a mantra miner, as returned by `MantraMiner::state`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MinerState {
+    /// The miner has not been started, or it was stopped and its thread has exited.
+    Idle,
+
+    /// The miner is reciting the sadhana.
+    Running,
+
+    /// The miner was paused with `MantraMiner::pause`.
+    Paused,
+
+    /// The miner was stopped, but its thread has not exited yet.
+    Stopping,
+
+    /// The thread exited on its own, either for one of the reasons returned by
+    /// `MantraMiner::finish_reason` or because writing to the output failed.
+    Finished,
+}
+
+/// The reason a run of the mantra miner finished on its own, as returned by
+/// `MantraMiner::finish_reason`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum FinishReason {
+    /// The sadhana was recited the number of times set in the options.
+    Repeats,
+
+    /// The time limit set with `run_for` was reached.
+    TimeLimit,
+
+    /// The total number of mantra repetitions set with `target_mantras` was reached.
+    TargetReached,
+}
+
+/// The controls of the current run of the mantra miner, shared between the miner and its handles.
+#[cfg(feature = "std")]
+#[derive(Default)]
+struct RunControl {
+    /// The channel used to signal the thread to stop.
+    stop_channel: Option<Sender<()>>,
+
+    /// The latch set once the task running the mantra miner has finished, used to wait for it.
+    completion: Option<Completion>,
+
+    /// Whether the last run finished on its own and was joined by `MantraMiner::wait`.
+    finished: bool,
+}
+
+#[cfg(feature = "std")]
+impl RunControl {
+    /// Signals the thread running the mantra miner to stop without waiting for it, and resumes it
+    /// if it was paused so that it can exit.
+    fn stop(&mut self, state: &SharedState) {
+        if let Some(tx) = self.stop_channel.take() {
+            let _ = tx.send(());
+            let mut timing = state.timing.lock();
+            let started = timing.started;
+            timing.end(started);
+        }
+        self.finished = false;
+        state.pause_flag.set(false);
+    }
+
+    /// Returns whether the thread running the mantra miner has been started and has not exited.
+    fn thread_running(&self) -> bool {
+        self.completion
+            .as_ref()
+            .is_some_and(|completion| !completion.is_finished())
+    }
+}
+
+/// A mantra miner that spawns a thread and "recites" mantras by writing them to an output buffer.
+/// All its methods take `&self`, so the miner can be shared across threads, for example in an
+/// `Arc`, without another lock.
+#[cfg(feature = "std")]
+pub struct MantraMiner {
+    /// The options used to configure the mantra miner.
+    options: Mutex<Options>,
+
+    /// The state shared with the thread running the mantra miner.
+    state: SharedState,
+
+    /// The controls of the current run, shared with the handles of the miner.
+    run: Arc<Mutex<RunControl>>,
+
+    /// The output to which the mantras are recited.
+    output: Arc<Mutex<Output>>,
+
+    /// The spawner used to run the mantra miner in the background.
+    spawner: Box<dyn Spawner>,
+}
+
+#[cfg(feature = "std")]
+impl MantraMiner {
+    /// Returns a new instance of `MantraMiner` with the given options. The mantras are recited to
+    /// `std::io::sink()`.
+    pub fn new(options: Options) -> MantraMiner {
+        Self::with_output(options, Box::new(sink()))
+    }
+
+    /// Returns a new instance of `MantraMiner` with the given options that recites the mantras to
+    /// the given output. The output is flushed after every recitation of the sadhana.
+    pub fn with_output(options: Options, output: Output) -> MantraMiner {
+        Self::with_spawner(options, output, Box::new(ThreadSpawner))
+    }
+
+    /// Returns a new instance of `MantraMiner` with the given options that recites the mantras to
+    /// the given output, using the given spawner to run the recitation in the background.
+    pub fn with_spawner(
+        options: Options,
+        output: Output,
+        spawner: Box<dyn Spawner>,
+    ) -> MantraMiner {
+        MantraMiner {
+            state: SharedState::new(&options),
+            options: Mutex::new(options),
+            run: Arc::default(),
+            output: Arc::new(Mutex::new(output)),
+            spawner,
+        }
+    }
+
+    /// Returns the mantra miner with the given count store, which saves the lifetime counts of the
+    /// miner after every completed recitation of the sadhana. The counts saved before are loaded
+    /// every time the miner is started, so they survive restarts of the program. For example:
+    ///
+    /// ```no_run
+    /// # use mantra_miner::{FileStore, MantraMiner, Options};
+    /// let store = FileStore::new("counts.toml");
+    /// let miner = MantraMiner::new(Options::default()).with_store(Box::new(store));
+    /// ```
+    pub fn with_store(self, store: Box<dyn CountStore>) -> MantraMiner {
+        *self.state.store.lock() = Some(store);
+        self
+    }
+
+    /// Returns the mantra miner with the given playlist, which it cycles through instead of
+    /// reciting a single sadhana. The options of the miner are replaced by those of the first
+    /// sadhana of the playlist, repeated indefinitely, and the playlist moves on to the next
+    /// sadhana before each recitation as set by its order. The miner keeps count of the
+    /// recitations of each sadhana, which are part of `MantraMiner::stats`. Options set with
+    /// `update_options` apply to the sadhana being recited until the playlist moves on, and a
+    /// checkpoint is only resumed if the playlist stays on the same sadhana. The playlist is
+    /// validated when the miner is started.
+    pub fn with_playlist(self, playlist: Playlist) -> MantraMiner {
+        if let Some(sadhana) = playlist.sadhanas.first() {
+            let options = sadhana.playlist_options();
+            self.state.update_mantra_stats(&options);
+            *self.options.lock() = options;
+        }
+        *self.state.playlist.lock() = Some(PlaylistState::new(playlist));
+        self
+    }
+
+    /// Returns a guard that owns a new mantra miner with the given options, started in the
+    /// background, and stops it and waits for its thread to exit once dropped. This allows a block
+    /// of work to run while the miner recites without starting and stopping it manually. Returns
+    /// `Error::InvalidOptions` if the options fail validation.
+    pub fn scoped(options: Options) -> Result<ScopedMiner> {
+        let miner = MantraMiner::new(options);
+        miner.start()?;
+        Ok(ScopedMiner(miner))
+    }
+
+    /// Starts running the mantra miner in the background using its spawner, which by default
+    /// spawns a new thread. A running thread is stopped first, and the counts are loaded from the
+    /// count store, if any, once it has exited and saved its own. Returns
+    /// `Error::InvalidOptions` if the options or the playlist fail validation, and
+    /// `Error::StoreFailed` if the counts cannot be loaded.
+    pub fn start(&self) -> Result<()> {
+        let options = self.options.lock();
+        options.validate()?;
+        self.state.validate_playlist()?;
+
+        // Stop any existing thread. It saves its counts to the store as it exits, so they're only
+        // loaded once it has exited. If the spawner cannot block and the task is still running, the
+        // counts in memory are kept, since they're at least as recent as the saved ones.
+        let previous = {
+            let mut run = self.run.lock();
+            run.stop(&self.state);
+            run.completion.take()
+        };
+        let previous_running = match previous {
+            Some(completion) if self.spawner.can_block() => {
+                completion.wait();
+                false
+            }
+            Some(completion) => !completion.is_finished(),
+            None => false,
+        };
+        if !previous_running {
+            self.state.load_counts()?;
+        }
+        let mut run = self.run.lock();
+
+        // The new thread starts with the current options, so any update not picked up by the
+        // previous thread is discarded.
+        self.state.rate.lock().take();
+        self.state.options.lock().take();
+        self.state.progress.lock().take();
+        self.state.update_mantra_stats(&options);
+        self.state.timing.lock().start();
+        self.state.finish_reason.lock().take();
+        self.state.last_error.lock().take();
+        self.state.last_panic.lock().take();
+        *self.state.restarts.lock() = 0;
+
+        let (tx, rx) = mpsc::channel();
+        let completion = Completion::default();
+        let output = RunOutput::new(SharedOutput(self.output.clone()), &options, &self.state);
+        let mut recitation = Recitation::new(options.clone(), output, self.state.clone());
+        let checkpoint = *self.state.checkpoint.lock();
+        if let Some(checkpoint) = checkpoint {
+            recitation.resume(checkpoint);
+        }
+        self.spawner
+            .spawn(RecitationTask::new(recitation, rx, completion.clone()));
+        run.stop_channel = Some(tx);
+        run.completion = Some(completion);
+        Ok(())
+    }
+
+    /// Stops the thread running the mantra miner. A paused miner is resumed so that its thread can
+    /// exit.
+    pub fn stop(&self) -> Result<()> {
+        self.run.lock().stop(&self.state);
+        Ok(())
+    }
+
+    /// Stops the thread running the mantra miner and blocks until it has exited. The thread checks
+    /// whether it should stop before each syllable or character and while waiting between them, so
+    /// this only blocks while the thread is writing to the output. If the spawner does not allow
+    /// blocking, the miner is stopped without waiting and `Error::BlockingUnsupported` is returned.
+    /// Returns `Error::RecitationFailed` if the thread had stopped because writing to the output
+    /// failed.
+    pub fn stop_and_join(&self) -> Result<()> {
+        self.stop()?;
+        if let Err(err) = self.check_can_block() {
+            // The stopped task exits on its own the next time it is stepped.
+            self.run.lock().completion = None;
+            return Err(err);
+        }
+        let completion = self.run.lock().completion.take();
+        match completion {
+            Some(completion) => self.joined(completion.wait()),
+            None => Ok(()),
+        }
+    }
+
+    /// Stops the thread running the mantra miner and blocks until it has exited or the timeout has
+    /// elapsed, whichever happens first. Returns an error if the thread is still running after the
+    /// timeout. In that case, the thread will still exit on its own, and it can be waited on again
+    /// by calling this method or `stop_and_join`. If the spawner does not allow blocking, the miner
+    /// is stopped without waiting and `Error::BlockingUnsupported` is returned.
+    pub fn stop_with_timeout(&self, timeout: Duration) -> Result<()> {
+        self.stop()?;
+        if let Err(err) = self.check_can_block() {
+            // The stopped task exits on its own the next time it is stepped.
+            self.run.lock().completion = None;
+            return Err(err);
+        }
+        let completion = self.run.lock().completion.take();
+        if let Some(completion) = completion {
+            match completion.wait_timeout(timeout) {
+                None => {
+                    self.run.lock().completion = Some(completion);
+                    return Err(Error::Timeout(timeout));
+                }
+                Some(panicked) => return self.joined(panicked),
+            }
+        }
+        Ok(())
+    }
+
+    /// Blocks until a finite run of the mantra miner has recited the sadhana the number of times
+    /// set in the options, has run for the time set with `run_for`, has reached the number of
+    /// mantra repetitions set with `target_mantras`, or has been stopped, and returns a summary of
+    /// the recitation. Returns `Error::NotRunning` if the miner was never started or its thread has
+    /// already been joined, and `Error::InvalidOptions` if the sadhana is repeated indefinitely
+    /// without a time limit or target, since the run would never complete. Returns
+    /// `Error::BlockingUnsupported` if the spawner does not allow blocking, and
+    /// `Error::RecitationFailed` if the run stopped because writing to the output failed.
+    pub fn wait(&self) -> Result<MinerStats> {
+        let options = self.options();
+        if options.repeats.is_none()
+            && options.run_for.is_none()
+            && options.target_mantras.is_none()
+        {
+            return Err(Error::InvalidOptions(
+                "cannot wait for a sadhana that repeats indefinitely".to_string(),
+            ));
+        }
+        self.check_can_block()?;
+
+        let completion = self.run.lock().completion.take();
+        let panicked = completion.ok_or(Error::NotRunning)?.wait();
+        if !panicked {
+            let mut run = self.run.lock();
+            run.finished = run.stop_channel.take().is_some();
+        }
+        self.joined(panicked)?;
+        Ok(self.stats())
+    }
+
+    /// Returns the error that stopped the task that was just joined, if it panicked or writing to
+    /// the output failed.
+    fn joined(&self, panicked: bool) -> Result<()> {
+        if panicked {
+            return Err(Error::ThreadPanicked);
+        }
+        match self.last_error() {
+            Some(error) => Err(Error::RecitationFailed(error)),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the error that stopped the last run of the mantra miner because writing to the
+    /// output failed, with the position of the syllable or character being written. It's cleared
+    /// when the miner is started again.
+    pub fn last_error(&self) -> Option<RecitationError> {
+        self.state.last_error.lock().clone()
+    }
+
+    /// Returns the message of the last panic of the recitation during the current or last run,
+    /// whether or not the recitation was restarted afterwards. It's cleared when the miner is
+    /// started again.
+    pub fn last_panic(&self) -> Option<String> {
+        self.state.last_panic.lock().clone()
+    }
+
+    /// Returns the number of times the recitation was restarted after a panic during the current
+    /// or last run, as allowed by the restart policy in the options.
+    pub fn restarts(&self) -> u32 {
+        *self.state.restarts.lock()
+    }
+
+    /// Returns the total number of bytes of output dropped because the output queue was full, as
+    /// allowed by the overflow policy in the options.
+    pub fn dropped_bytes(&self) -> u64 {
+        self.state.dropped_bytes.load(Ordering::Relaxed)
+    }
+
+    /// Returns the counts of the mantra miner in the Prometheus text exposition format, so that
+    /// they can be served to a Prometheus server, for example by a daemon running the miner. The
+    /// counters cover the completed sadhanas, the recited syllables, the dropped output, and the
+    /// completed repetitions and recited syllables of each mantra.
+    pub fn render_prometheus(&self) -> String {
+        prometheus::render(&self.state)
+    }
+
+    /// Recites the sadhana once on the calling thread and returns a summary of the recitation. Same
+    /// as `recite_n(1)`.
+    pub fn recite_once(&self) -> Result<RecitationSummary> {
+        self.recite_n(1)
+    }
+
+    /// Recites the sadhana `n` times on the calling thread, waiting between syllables as set in
+    /// the options, and returns a summary of the recitation once it's done. No thread is spawned,
+    /// and the counts and statistics of the miner are updated as if it had been started. The
+    /// recitation ends early if the time limit or target set in the options is reached. The
+    /// priority of the calling thread is left unchanged. Returns `Error::AlreadyRunning` if the
+    /// miner is running, `Error::BlockingUnsupported` if the spawner does not allow blocking, and
+    /// `Error::RecitationFailed` if writing to the output fails.
+    pub fn recite_n(&self, n: usize) -> Result<RecitationSummary> {
+        if self.thread_running() {
+            return Err(Error::AlreadyRunning);
+        }
+        self.check_can_block()?;
+        let options = Options {
+            repeats: Some(n),
+            ..self.options()
+        };
+        options.validate()?;
+        self.state.validate_playlist()?;
+
+        self.stop()?;
+        self.run.lock().completion = None;
+        self.state.rate.lock().take();
+        self.state.options.lock().take();
+        self.state.progress.lock().take();
+        self.state.update_mantra_stats(&options);
+        self.state.timing.lock().start();
+        self.state.finish_reason.lock().take();
+        self.state.last_error.lock().take();
+        self.state.last_panic.lock().take();
+        *self.state.restarts.lock() = 0;
+
+        let sadhanas = self.state.count();
+        let syllables = self.state.syllables();
+        let mantras = self.state.completed_mantras();
+        let started = Instant::now();
+        let output = CountingOutput {
+            output: RunOutput::new(SharedOutput(self.output.clone()), &options, &self.state),
+            bytes: 0,
+        };
+        let mut recitation = Recitation::new(options, output, self.state.clone());
+        loop {
+            if let Some(delay) = recitation.power_pause() {
+                thread::sleep(delay);
+                continue;
+            }
+            if recitation.step()?.is_none() {
+                break;
+            }
+            let delay = recitation.next_delay();
+            #[cfg(feature = "precise")]
+            if recitation.reciter.options().precise_pacing {
+                precise::wait(delay, |delay| {
+                    thread::sleep(delay);
+                    false
+                });
+                continue;
+            }
+            thread::sleep(delay);
+        }
+
+        Ok(RecitationSummary {
+            sadhanas: self.state.count() - sadhanas,
+            mantras: self.state.completed_mantras() - mantras,
+            syllables: self.state.syllables() - syllables,
+            duration: started.elapsed(),
+            bytes_written: recitation.output.get_ref().bytes,
+        })
+    }
+
+    /// Returns `Error::BlockingUnsupported` if the spawner does not allow waiting for its tasks.
+    fn check_can_block(&self) -> Result<()> {
+        if self.spawner.can_block() {
+            Ok(())
+        } else {
+            Err(Error::BlockingUnsupported)
+        }
+    }
+
+    /// Returns whether the thread running the mantra miner has been started and has not exited.
+    fn thread_running(&self) -> bool {
+        self.run.lock().thread_running()
+    }
+
+    /// Returns the state of the mantra miner. A finite run is `Finished` once its thread has exited
+    /// on its own, until the miner is started or stopped again.
+    pub fn state(&self) -> MinerState {
+        let run = self.run.lock();
+        match &run.completion {
+            Some(completion) if !completion.is_finished() => {
+                if run.stop_channel.is_none() {
+                    MinerState::Stopping
+                } else if self.is_paused() {
+                    MinerState::Paused
+                } else {
+                    MinerState::Running
+                }
+            }
+            // The channel to stop the thread is only dropped when the miner is stopped.
+            Some(_) if run.stop_channel.is_some() => MinerState::Finished,
+            None if run.finished => MinerState::Finished,
+            _ => MinerState::Idle,
+        }
+    }
+
+    /// Returns why the last run of the mantra miner finished on its own, or `None` if the miner is
+    /// not in the `Finished` state or its thread exited because writing to the output failed.
+    pub fn finish_reason(&self) -> Option<FinishReason> {
+        if self.state() != MinerState::Finished {
+            return None;
+        }
+        *self.state.finish_reason.lock()
+    }
+
+    /// Returns whether the mantra miner has been started and is reciting or paused, that is, its
+    /// thread has neither finished nor been stopped.
+    pub fn is_running(&self) -> bool {
+        matches!(self.state(), MinerState::Running | MinerState::Paused)
+    }
+
+    /// Pauses the thread running the mantra miner before it recites the next syllable or
+    /// character. The position within the current sadhana is preserved, and the recitation
+    /// continues from there once the miner is resumed. Starting or stopping the miner resumes it.
+    /// Returns `Error::NotRunning` if the miner is not running.
+    pub fn pause(&self) -> Result<()> {
+        if !self.thread_running() {
+            return Err(Error::NotRunning);
+        }
+        self.state.pause_flag.set(true);
+        Ok(())
+    }
+
+    /// Resumes the thread running the mantra miner after a call to `pause`. Returns
+    /// `Error::NotRunning` if the miner is not running.
+    pub fn resume(&self) -> Result<()> {
+        if !self.thread_running() {
+            return Err(Error::NotRunning);
+        }
+        self.state.pause_flag.set(false);
+        Ok(())
+    }
+
+    /// Returns whether the mantra miner is paused.
+    pub fn is_paused(&self) -> bool {
+        self.state.pause_flag.is_paused()
+    }
+
+    /// Changes the rate of the mantra miner without restarting it. A running miner uses the new
+    /// rate from the next syllable or character on, and the preparation and conclusion only use it
+    /// if they have no rate of their own. The rate is ignored while a target throughput is set.
+    /// Returns `Error::InvalidOptions` if the options would fail validation with the new rate.
+    pub fn set_rate(&self, rate: Duration) -> Result<()> {
+        let mut options = self.options.lock();
+        let updated = Options {
+            rate,
+            ..options.clone()
+        };
+        updated.validate()?;
+        *options = updated;
+        *self.state.rate.lock() = Some(rate);
+        Ok(())
+    }
+
+    /// Replaces the options of the mantra miner without restarting it or resetting its counts. A
+    /// running miner finishes the current sadhana with the old options and uses the new ones from
+    /// the next sadhana on. The statistics of a mantra are kept if the mantra at the same position
+    /// in the new options has the same name. The capacity of the recent output cannot be changed
+    /// this way. Returns `Error::InvalidOptions` if the new options fail validation.
+    pub fn update_options(&self, options: Options) -> Result<()> {
+        options.validate()?;
+        let mut current = self.options.lock();
+        if self.thread_running() {
+            self.state.rate.lock().take();
+            *self.state.options.lock() = Some(options.clone());
+        } else {
+            self.state.update_mantra_stats(&options);
+        }
+        *current = options;
+        Ok(())
+    }
+
+    /// Sets the monitor sampled in the load-aware mode, which replaces the system load average.
+    /// The host program can use it to report its own activity, so that the miner backs off while
+    /// the program is busy. Takes effect immediately, even if the miner is running.
+    pub fn set_load_monitor(&self, monitor: Box<dyn LoadMonitor>) {
+        *self.state.load_monitor.lock() = monitor;
+    }
+
+    /// Sets the voice that recites the sadhana instead of writing it to the output, for example to
+    /// speak the syllables or to drive an external device. The errors returned by the voice are
+    /// handled according to the error policy, as errors returned by the output are. Passing `None`
+    /// restores writing to the output. Takes effect immediately, even if the miner is running.
+    pub fn set_voice(&self, voice: Option<Box<dyn Voice>>) {
+        *self.state.voice.lock() = voice;
+    }
+
+    /// Sets the target of the records logged with the `log` feature, which defaults to
+    /// `mantra_miner::recitation`. Takes effect immediately, even if the miner is running.
+    #[cfg(feature = "log")]
+    pub fn set_log_target(&self, target: impl Into<String>) {
+        *self.state.log_target.lock() = target.into();
+    }
+
+    /// Sets the callback called with every error returned by the output, whether the error
+    /// policy in the options skips the write, retries it, or stops the miner. The callback is
+    /// called from the thread reciting the sadhana, and must not call this method. Takes effect
+    /// immediately, even if the miner is running.
+    pub fn on_error(&self, callback: impl FnMut(&RecitationError) + Send + 'static) {
+        *self.state.error_callback.lock() = Some(Box::new(callback));
+    }
+
+    /// Sets the monitor used to check whether the machine runs on battery, which replaces the one
+    /// that queries the operating system. Takes effect the next time the power supply is checked.
+    #[cfg(feature = "power")]
+    pub fn set_power_monitor(&self, monitor: Box<dyn PowerMonitor>) {
+        *self.state.power_monitor.lock() = monitor;
+    }
+
+    /// Returns the position within the sadhana of the last syllable or character recited since the
+    /// miner was last started, or `None` if nothing has been recited yet. For example, a UI can
+    /// show the name of the mantra at `mantra_index` in the options and the number of its
+    /// repetition.
+    pub fn progress(&self) -> Option<Progress> {
+        *self.state.progress.lock()
+    }
+
+    /// Returns the position right after the last syllable or character recited, or `None` if the
+    /// last sadhana was completed or nothing has been recited yet. When the miner is stopped in
+    /// the middle of a sadhana, the next call to `start` resumes from this position instead of
+    /// restarting the sadhana. With a count store, the checkpoint is saved when a run ends and
+    /// loaded when the miner is started, so the recitation resumes even after the program
+    /// restarts.
+    pub fn checkpoint(&self) -> Option<Checkpoint> {
+        *self.state.checkpoint.lock()
+    }
+
+    /// Returns the time, as shown by the system clock, at which the miner was last started, or
+    /// `None` if it was never started.
+    pub fn started_at(&self) -> Option<SystemTime> {
+        self.state.timing.lock().started_at
+    }
+
+    /// Returns how long the miner has been running since it was last started, including the time
+    /// spent paused. Once the miner is stopped or finishes, this is the duration of the run.
+    /// Returns zero if the miner was never started.
+    pub fn elapsed(&self) -> Duration {
+        self.state.timing.lock().elapsed()
+    }
+
+    /// Returns the average time taken by a recitation of the entire sadhana since the miner was
+    /// last started, or `None` if none has been completed yet.
+    pub fn average_sadhana_duration(&self) -> Option<Duration> {
+        self.state.timing.lock().average_sadhana_duration()
+    }
+
+    /// Returns the estimated time until a finite run of the miner stops on its own, either because
+    /// it has recited the sadhana the number of times set in the options, because the time set
+    /// with `run_for` has elapsed, or because the number of mantra repetitions set with
+    /// `target_mantras` has been completed, whichever comes first. Until a recitation of the
+    /// sadhana or a mantra repetition has been completed, the time it takes is computed from the
+    /// options with `Options::sadhana_duration`, and the average observed since the miner was
+    /// started is used afterwards. Returns zero if the run has finished, and `None` if the sadhana
+    /// repeats indefinitely or the miner is not running.
+    pub fn estimated_remaining(&self) -> Option<Duration> {
+        let options = self.options();
+        if options.repeats.is_none()
+            && options.run_for.is_none()
+            && options.target_mantras.is_none()
+        {
+            return None;
+        }
+        match self.state() {
+            MinerState::Finished => Some(Duration::ZERO),
+            MinerState::Idle => None,
+            _ => {
+                let completed_mantras = self.state.completed_mantras();
+                let timing = self.state.timing.lock();
+                let by_repeats = options
+                    .repeats
+                    .map(|repeats| timing.estimated_remaining(repeats, options.sadhana_duration()));
+                let by_time = options
+                    .run_for
+                    .map(|run_for| run_for.saturating_sub(timing.elapsed()));
+                let by_mantras = options.target_mantras.and_then(|target| {
+                    timing.estimated_remaining_mantras(
+                        target,
+                        completed_mantras,
+                        options.mantra_duration(),
+                    )
+                });
+                by_repeats
+                    .into_iter()
+                    .chain(by_time)
+                    .chain(by_mantras)
+                    .min()
+            }
+        }
+    }
+
+    /// Returns the options used to configure this mantra miner.
+    pub fn options(&self) -> Options {
+        self.options.lock().clone()
+    }
+
+    /// Returns up to the last `n` recited syllables, ordered from oldest to newest. Only the last
+    /// `recent_output_capacity` syllables are kept, as configured in the options.
+    pub fn recent_output(&self, n: usize) -> Vec<String> {
+        self.state.recent_output.recent(n)
+    }
+
+    /// Returns a watch that is notified whenever the count of the mantra miner increments, so that
+    /// a UI can update as soon as a sadhana is completed instead of polling `count`. The watch
+    /// starts at the current count.
+    pub fn count_watch(&self) -> CountWatch {
+        CountWatch {
+            count: self.state.count.clone(),
+            signal: self.state.count_signal.clone(),
+            seen: self.count(),
+        }
+    }
+
+    /// Returns a channel that receives the events emitted by the mantra miner from this point on.
+    /// Dropping the receiver unsubscribes it.
+    pub fn subscribe(&self) -> Receiver<MinerEvent> {
+        self.state.subscribers.subscribe()
+    }
+
+    /// Returns the count of the mantra miner, that is, the number of completed recitations of the
+    /// entire sadhana. Reading it is cheap, so UIs can poll it often.
+    pub fn count(&self) -> u64 {
+        self.state.count()
+    }
+
+    /// Returns the total number of mantra syllables and characters of the preparation and
+    /// conclusion that have been recited, including those of incomplete sadhanas.
+    pub fn syllable_count(&self) -> u64 {
+        self.state.syllables()
+    }
+
+    /// Returns the number of recitations of the sadhana that were interrupted before their
+    /// completion, because the miner was stopped, reached the limits set in the options, or
+    /// restarted after a panic. These recitations are not included in `count`. A recitation
+    /// resumed from its checkpoint and interrupted again is counted again.
+    pub fn interrupted_sadhanas(&self) -> u64 {
+        self.state.interrupted_sadhanas()
+    }
+
+    /// Returns the fraction of the sadhana in progress recited so far, between zero and one, or
+    /// `None` if no sadhana is in progress. Each character of the preparation and conclusion and
+    /// each syllable of the mantras counts the same. Once the miner stops, this is the progress of
+    /// the interrupted sadhana, from which the next run resumes.
+    pub fn sadhana_progress(&self) -> Option<f64> {
+        *self.state.sadhana_progress.lock()
+    }
+
+    /// Returns the name of the sadhana of the playlist being recited, or of the last one recited
+    /// if the miner is stopped, or `None` if no playlist is set. The playlist starts with its
+    /// first sadhana.
+    pub fn current_sadhana(&self) -> Option<String> {
+        self.state.current_sadhana()
+    }
+
+    /// Returns the number of completed repetitions of all mantras, expressed in bums, malas, and
+    /// beads as configured in the options.
+    pub fn malas(&self) -> MalaCount {
+        let repetitions = self
+            .state
+            .mantra_stats
+            .lock()
+            .iter()
+            .map(|stats| stats.completed)
+            .sum();
+        self.options.lock().mala.count(repetitions)
+    }
+
+    /// Returns the statistics of the mantra miner, including how many times each mantra was
+    /// completed and how many of its syllables were recited, and how many recitations were
+    /// completed on each day. With the `export` feature, the statistics can be exported to CSV and
+    /// JSON files with `MinerStats::export_csv` and `MinerStats::export_json`.
+    pub fn stats(&self) -> MinerStats {
+        self.state.stats()
+    }
+
+    /// Returns the recitations completed on each calendar day in the local time zone, with the
+    /// current and longest streaks of days with at least one completed sadhana, and the average
+    /// number of sadhanas per day over the last week and month. The days are saved by the count
+    /// store, if any, so they include those of earlier runs of the program. The local time zone is
+    /// only known on Linux, macOS, FreeBSD, and Windows, and the days are in UTC elsewhere. The
+    /// statistics can be serialized to be displayed by a user interface.
+    pub fn daily_stats(&self) -> DailyStats {
+        self.state.daily_stats()
+    }
+
+    /// Returns a handle that controls the mantra miner and reads its counts from any thread.
+    pub fn handle(&self) -> MinerHandle {
+        MinerHandle {
+            state: self.state.clone(),
+            run: self.run.clone(),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Drop for MantraMiner {
+    /// Stops the thread running the mantra miner and waits for it to exit, so that dropping the
+    /// miner does not leave a detached thread reciting in the background.
+    fn drop(&mut self) {
+        let _ = self.stop_and_join();
+        self.state.count_signal.close();
+    }
+}
+
+/// A mantra miner that recites while the guard is alive, returned by `MantraMiner::scoped`. The
+/// miner can be used through the guard, and is stopped and joined when the guard is dropped.
+#[cfg(feature = "std")]
+pub struct ScopedMiner(MantraMiner);
+
+#[cfg(feature = "std")]
+impl ScopedMiner {
+    /// Stops the miner, waits for its thread to exit, and returns its statistics. Unlike dropping
+    /// the guard, this reports whether the thread panicked.
+    pub fn finish(self) -> Result<MinerStats> {
+        self.0.stop_and_join()?;
+        Ok(self.0.stats())
+    }
+}
+
+#[cfg(feature = "std")]
+impl Deref for ScopedMiner {
+    type Target = MantraMiner;
+
+    fn deref(&self) -> &MantraMiner {
+        &self.0
+    }
+}
+
+#[cfg(feature = "std")]
+impl Drop for ScopedMiner {
+    /// Stops the miner and waits for its thread to exit.
+    fn drop(&mut self) {
+        let _ = self.0.stop_and_join();
+    }
+}
+
+/// A cheap, cloneable handle to a mantra miner, returned by `MantraMiner::handle`, that can pause,
+/// resume, and stop it and read its counts from any thread. Starting the miner and waiting for it
+/// still require the miner itself. The handle does not keep the miner alive, and dropping the
+/// miner stops it as usual.
+#[cfg(feature = "std")]
+#[derive(Clone)]
+pub struct MinerHandle {
+    /// The state shared with the miner.
+    state: SharedState,
+
+    /// The controls of the current run of the miner.
+    run: Arc<Mutex<RunControl>>,
+}
+
+#[cfg(feature = "std")]
+impl MinerHandle {
+    /// Pauses the miner before it recites the next syllable or character. Same as
+    /// `MantraMiner::pause`.
+    pub fn pause(&self) -> Result<()> {
+        if !self.run.lock().thread_running() {
+            return Err(Error::NotRunning);
+        }
+        self.state.pause_flag.set(true);
+        Ok(())
+    }
+
+    /// Resumes the miner after a call to `pause`. Same as `MantraMiner::resume`.
+    pub fn resume(&self) -> Result<()> {
+        if !self.run.lock().thread_running() {
+            return Err(Error::NotRunning);
+        }
+        self.state.pause_flag.set(false);
+        Ok(())
+    }
+
+    /// Returns whether the miner is paused.
+    pub fn is_paused(&self) -> bool {
+        self.state.pause_flag.is_paused()
+    }
+
+    /// Signals the miner to stop without waiting for its thread to exit. Same as
+    /// `MantraMiner::stop`.
+    pub fn stop(&self) {
+        self.run.lock().stop(&self.state);
+    }
+
+    /// Returns whether the miner has been started and is reciting or paused. Same as
+    /// `MantraMiner::is_running`.
+    pub fn is_running(&self) -> bool {
+        let run = self.run.lock();
+        run.stop_channel.is_some() && run.thread_running()
+    }
+
+    /// Returns the count of the miner.
+    pub fn count(&self) -> u64 {
+        self.state.count()
+    }
+
+    /// Returns a watch that is notified whenever the count of the miner increments. Same as
+    /// `MantraMiner::count_watch`.
+    pub fn count_watch(&self) -> CountWatch {
+        CountWatch {
+            count: self.state.count.clone(),
+            signal: self.state.count_signal.clone(),
+            seen: self.count(),
+        }
+    }
+
+    /// Returns the total number of syllables and characters recited by the miner.
+    pub fn syllable_count(&self) -> u64 {
+        self.state.syllables()
+    }
+
+    /// Returns the number of interrupted recitations of the sadhana. Same as
+    /// `MantraMiner::interrupted_sadhanas`.
+    pub fn interrupted_sadhanas(&self) -> u64 {
+        self.state.interrupted_sadhanas()
+    }
+
+    /// Returns the progress of the sadhana in progress. Same as `MantraMiner::sadhana_progress`.
+    pub fn sadhana_progress(&self) -> Option<f64> {
+        *self.state.sadhana_progress.lock()
+    }
+
+    /// Returns the name of the sadhana of the playlist being recited. Same as
+    /// `MantraMiner::current_sadhana`.
+    pub fn current_sadhana(&self) -> Option<String> {
+        self.state.current_sadhana()
+    }
+
+    /// Returns the statistics of the miner.
+    pub fn stats(&self) -> MinerStats {
+        self.state.stats()
+    }
+
+    /// Returns the daily statistics of the miner. Same as `MantraMiner::daily_stats`.
+    pub fn daily_stats(&self) -> DailyStats {
+        self.state.daily_stats()
+    }
+
+    /// Returns the counts of the mantra miner in the Prometheus text exposition format. Same as
+    /// `MantraMiner::render_prometheus`.
+    pub fn render_prometheus(&self) -> String {
+        prometheus::render(&self.state)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use anyhow::Result;
+    use parking_lot::Mutex;
+    use std::{
+        io::Write,
+        sync::{mpsc::TryRecvError, Arc},
+        thread,
+        time::Duration,
+    };
+    use tempfile::NamedTempFile;
+    #[cfg(feature = "tracing")]
+    use tracing_subscriber::fmt::format::FmtSpan;
+
+    use crate::{
+        sadhana, CountStore, CpuPercent, Error, ErrorPolicy, FileStore, FinishReason, Interleave,
+        MalaConfig, MalaCount, Mantra, MantraMiner, MantraSelection, MantraStats, MinerEvent,
+        MinerState, MinerStats, Normalization, Options, OutputFormat, OverflowPolicy, Playlist,
+        PlaylistOrder, PlaylistSadhana, PowerPolicy, Progress, RecentOutput, RestartPolicy,
+        SadhanaRecord, SadhanaStats, Section, StoredCounts, SyllableSplitter, ThreadPriority,
+        Voice,
+    };
+
+    const PREPARATION: &str = "I take refuge in the Three Jewels and arise bodhicitta.";
+    const DEDICATION: &str = "I dedicate the merit of this practice to all sentient beings.";
+
+    /// A writer that stores the output in a buffer that can be inspected by the tests.
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// A logger that keeps the target and message of every record. The logger is global, so each
+    /// test sets its own log target and only looks at the records logged with it.
+    #[cfg(feature = "log")]
+    struct TestLogger(Mutex<Vec<(String, String)>>);
+
+    #[cfg(feature = "log")]
+    impl log::Log for TestLogger {
+        fn enabled(&self, _metadata: &log::Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &log::Record) {
+            self.0
+                .lock()
+                .push((record.target().to_string(), record.args().to_string()));
+        }
+
+        fn flush(&self) {}
+    }
+
+    #[cfg(feature = "log")]
+    static LOGGER: TestLogger = TestLogger(Mutex::new(Vec::new()));
+
+    /// Installs the test logger, if it's not installed yet.
+    #[cfg(feature = "log")]
+    fn install_logger() {
+        static INSTALL: std::sync::Once = std::sync::Once::new();
+        INSTALL.call_once(|| {
+            log::set_logger(&LOGGER).unwrap();
+            log::set_max_level(log::LevelFilter::Trace);
+        });
+    }
+
+    /// Returns the messages logged so far with the given target.
+    #[cfg(feature = "log")]
+    fn logged(target: &str) -> Vec<String> {
+        LOGGER
+            .0
+            .lock()
+            .iter()
+            .filter(|(logged_target, _)| logged_target == target)
+            .map(|(_, message)| message.clone())
+            .collect()
+    }
+
+    /// A writer that blocks for 200ms on every write.
+    struct SlowWriter;
+
+    impl Write for SlowWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            thread::sleep(Duration::from_millis(200));
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// A writer that rejects every write.
+    struct BrokenWriter;
+
+    impl Write for BrokenWriter {
+        fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+            Err(std::io::ErrorKind::BrokenPipe.into())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn simple_mantra() -> Mantra {
+        Mantra {
+            name: None,
+            syllables: ["om", "ma", "ni", "pad", "me", "hum"].map(Arc::from).into(),
+            repeats: None,
+            ..Default::default()
+        }
+    }
+
+    fn repeated_mantra() -> Mantra {
+        Mantra {
+            name: None,
+            syllables: ["hri"].map(Arc::from).into(),
+            repeats: Some(108),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    #[allow(clippy::bool_assert_comparison, clippy::field_reassign_with_default)]
+    fn should_repeat() {
+        let mut options = Options::default();
+
+        options.repeats = Some(10);
+        assert_eq!(options.should_repeat(5), true);
+        assert_eq!(options.should_repeat(10), false);
+        assert_eq!(options.should_repeat(50), false);
+
+        options.repeats = None;
+        assert_eq!(options.should_repeat(5), true);
+        assert_eq!(options.should_repeat(10), true);
+        assert_eq!(options.should_repeat(50), true);
+    }
+
+    #[test]
+    fn validate() {
+        let valid = Options {
+            mantras: vec![simple_mantra()],
+            rate: Duration::from_micros(1),
+            ..Default::default()
+        };
+        assert!(valid.validate().is_ok());
+
+        let no_mantras = Options {
+            mantras: vec![],
+            ..valid.clone()
+        };
+        assert!(matches!(
+            no_mantras.validate(),
+            Err(Error::InvalidOptions(_))
+        ));
+
+        let no_syllables = Options {
+            mantras: vec![simple_mantra(), Mantra::default()],
+            ..valid.clone()
+        };
+        assert!(matches!(
+            no_syllables.validate(),
+            Err(Error::InvalidOptions(_))
+        ));
+
+        let zero_rate = Options {
+            rate: Duration::ZERO,
+            ..valid.clone()
+        };
+        assert!(matches!(
+            zero_rate.validate(),
+            Err(Error::InvalidOptions(_))
+        ));
+
+        let bad_threshold = Options {
+            power_policy: PowerPolicy::PauseBelowCharge(101),
+            ..valid.clone()
+        };
+        assert!(matches!(
+            bad_threshold.validate(),
+            Err(Error::InvalidOptions(_))
+        ));
+
+        let empty_queue = Options {
+            output_queue: Some(0),
+            ..valid.clone()
+        };
+        assert!(matches!(
+            empty_queue.validate(),
+            Err(Error::InvalidOptions(_))
+        ));
+
+        let missing_mantra = Options {
+            sections: vec![sadhana::Section::Mantra {
+                index: 1,
+                repeats: None,
+                frequency: None,
+            }],
+            ..valid.clone()
+        };
+        assert!(matches!(
+            missing_mantra.validate(),
+            Err(Error::InvalidOptions(_))
+        ));
+
+        let sections_and_preparation = Options {
+            preparation: Some("ok".to_string()),
+            sections: vec![sadhana::Section::Mantra {
+                index: 0,
+                repeats: None,
+                frequency: None,
+            }],
+            ..valid.clone()
+        };
+        assert!(matches!(
+            sections_and_preparation.validate(),
+            Err(Error::InvalidOptions(_))
+        ));
+
+        let only_silence = Options {
+            sections: vec![sadhana::Section::Silence {
+                duration: Duration::from_secs(1),
+                frequency: None,
+            }],
+            ..valid.clone()
+        };
+        assert!(matches!(
+            only_silence.validate(),
+            Err(Error::InvalidOptions(_))
+        ));
+
+        let missing_mantra_in_group = Options {
+            sections: vec![sadhana::Section::Group {
+                sections: vec![sadhana::Section::Mantra {
+                    index: 3,
+                    repeats: None,
+                    frequency: None,
+                }],
+                repeats: Some(0),
+                frequency: None,
+            }],
+            ..valid.clone()
+        };
+        assert!(matches!(
+            missing_mantra_in_group.validate(),
+            Err(Error::InvalidOptions(_))
+        ));
+
+        let zero_weights = Options {
+            mantras: vec![Mantra {
+                weight: Some(0),
+                ..simple_mantra()
+            }],
+            selection: MantraSelection::Weighted,
+            ..valid.clone()
+        };
+        assert!(matches!(
+            zero_weights.validate(),
+            Err(Error::InvalidOptions(_))
+        ));
+
+        let endless_turbo = Options {
+            turbo: true,
+            repeats: None,
+            ..valid.clone()
+        };
+        assert!(matches!(
+            endless_turbo.validate(),
+            Err(Error::InvalidOptions(_))
+        ));
+
+        let zero_frequency = Options {
+            mantras: vec![Mantra {
+                frequency: Some(0),
+                ..simple_mantra()
+            }],
+            ..valid.clone()
+        };
+        assert!(matches!(
+            zero_frequency.validate(),
+            Err(Error::InvalidOptions(_))
+        ));
+
+        let huge_group = Options {
+            sections: vec![sadhana::Section::Group {
+                sections: vec![sadhana::Section::Group {
+                    sections: vec![sadhana::Section::Mantra {
+                        index: 0,
+                        repeats: None,
+                        frequency: None,
+                    }],
+                    repeats: Some(usize::MAX),
+                    frequency: None,
+                }],
+                repeats: Some(2),
+                frequency: None,
+            }],
+            ..valid.clone()
+        };
+        assert!(matches!(
+            huge_group.validate(),
+            Err(Error::InvalidOptions(_))
+        ));
+
+        let finite_zero_rate = Options {
+            rate: Duration::ZERO,
+            repeats: Some(1),
+            ..valid
+        };
+        assert!(finite_zero_rate.validate().is_ok());
+    }
+
+    #[test]
+    fn start_invalid_options() {
+        let miner = MantraMiner::new(Options::default());
+        assert!(matches!(miner.start(), Err(Error::InvalidOptions(_))));
+    }
+
+    #[test]
+    fn mantra_from_text() {
+        let mantra = Mantra::from_text("om ma-ni  pad-me\thum");
+        assert_eq!(mantra, simple_mantra());
+
+        let splitter = SyllableSplitter {
+            split_on_hyphens: false,
+        };
+        let mantra = Mantra::from_text_with("om ma-ni pad-me hum", &splitter);
+        assert_eq!(
+            *mantra.syllables,
+            ["om", "ma-ni", "pad-me", "hum"].map(Arc::<str>::from)
+        );
+        assert_eq!(mantra.repeats, None);
+    }
+
+    #[test]
+    fn shared_syllables() {
+        // Cloning the options shares the syllables instead of copying them.
+        let options = Options {
+            mantras: vec![simple_mantra()],
+            ..Default::default()
+        };
+        let cloned = options.clone();
+        assert!(Arc::ptr_eq(
+            &options.mantras[0].syllables,
+            &cloned.mantras[0].syllables
+        ));
+    }
+
+    #[test]
+    fn options_from_toml_file() -> Result<()> {
+        let mut file = NamedTempFile::new()?;
+        write!(
+            file,
+            r#"
+preparation = "{}"
+conclusion = "{}"
+conclusion_repeats = 3
+rate = "1us"
+preparation_rate = "1.5ms"
+repeats = 10
+thread_priority = "idle"
+
+[[mantras]]
+name = "Mani"
+deity = "Avalokiteshvara"
+language = "Sanskrit"
+syllables = ["om", "ma", "ni", "pad", "me", "hum"]
+
+[[mantras]]
+syllables = ["hri"]
+repeats = 108
+"#,
+            PREPARATION, DEDICATION
+        )?;
+
+        let options = Options::from_toml_file(file.path())?;
+        let mut mani = simple_mantra();
+        mani.name = Some("Mani".to_string());
+        mani.deity = Some("Avalokiteshvara".to_string());
+        mani.language = Some("Sanskrit".to_string());
+        assert_eq!(
+            options,
+            Options {
+                preparation: Some(PREPARATION.to_string()),
+                preparation_repeats: None,
+                mantras: vec![mani, repeated_mantra()],
+                conclusion: Some(DEDICATION.to_string()),
+                conclusion_repeats: Some(3),
+                sections: Vec::new(),
+                selection: MantraSelection::All,
+                interleave: Interleave::Sequential,
+                multiplier: None,
+                rate: Duration::from_micros(1),
+                preparation_rate: Some(Duration::from_micros(1500)),
+                conclusion_rate: None,
+                jitter: None,
+                mantras_per_day: None,
+                pause_between_mantras: None,
+                pause_between_sadhanas: None,
+                max_cpu_percent: None,
+                thread_priority: ThreadPriority::Idle,
+                load_aware: false,
+                skip_output: false,
+                turbo: false,
+                precise_pacing: false,
+                normalization: Normalization::Nfc,
+                power_policy: PowerPolicy::Ignore,
+                restart_policy: RestartPolicy::Never,
+                error_policy: ErrorPolicy::Abort,
+                output_queue: None,
+                overflow_policy: OverflowPolicy::Block,
+                run_for: None,
+                target_mantras: None,
+                separator: None,
+                format: OutputFormat::default(),
+                mala: MalaConfig::default(),
+                recent_output_capacity: 0,
+                repeats: Some(10),
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn target_rate() {
+        let mut options = Options {
+            preparation: Some("ab".to_string()),
+            mantras: vec![Mantra {
+                repeats: Some(3),
+                ..Mantra::from_text("om ah")
+            }],
+            conclusion: Some("c".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(options.target_rate(), None);
+
+        // A sadhana of 9 units with 3 repetitions must take 3 seconds to recite one mantra per
+        // second.
+        options.mantras_per_day = Some(86_400);
+        assert_eq!(
+            options.target_rate(),
+            Some(Duration::from_nanos(333_333_333))
+        );
+        assert!(options.validate().is_ok());
+
+        // The pauses take time away from the recitation.
+        options.pause_between_mantras = Some(Duration::from_millis(100));
+        options.pause_between_sadhanas = Some(Duration::from_millis(300));
+        assert_eq!(
+            options.target_rate(),
+            Some(Duration::from_nanos(266_666_666))
+        );
+
+        options.mantras_per_day = Some(0);
+        assert_eq!(options.target_rate(), None);
+        assert!(options.validate().is_err());
+    }
+
+    #[test]
+    fn sadhana_duration() {
+        let mut options = Options {
+            preparation: Some("ab".to_string()),
+            mantras: vec![Mantra {
+                repeats: Some(3),
+                ..Mantra::from_text("om ah")
+            }],
+            conclusion: Some("c".to_string()),
+            rate: Duration::from_millis(10),
+            preparation_rate: Some(Duration::from_millis(20)),
+            pause_between_mantras: Some(Duration::from_millis(100)),
+            pause_between_sadhanas: Some(Duration::from_millis(300)),
+            ..Default::default()
+        };
+        // 2 * 20ms + 6 * 10ms + 3 * 100ms + 1 * 10ms + 300ms.
+        assert_eq!(options.sadhana_duration(), Duration::from_millis(710));
+
+        // With a target of one mantra per second, the 3 repetitions take about 3 seconds.
+        options.mantras_per_day = Some(86_400);
+        let duration = options.sadhana_duration();
+        assert!(duration <= Duration::from_secs(3));
+        assert!(duration > Duration::from_millis(2999));
+
+        // The silences are part of the sadhana.
+        let options = Options {
+            mantras: vec![Mantra::from_text("om ah")],
+            sections: vec![
+                sadhana::Section::Text {
+                    text: "ab".to_string(),
+                    repeats: Some(2),
+                    rate: Some(Duration::from_millis(20)),
+                    frequency: None,
+                },
+                sadhana::Section::Silence {
+                    duration: Duration::from_secs(1),
+                    frequency: None,
+                },
+                sadhana::Section::Mantra {
+                    index: 0,
+                    repeats: Some(3),
+                    frequency: None,
+                },
+            ],
+            rate: Duration::from_millis(10),
+            ..Default::default()
+        };
+        // 4 * 20ms + 1s + 6 * 10ms.
+        assert_eq!(options.sadhana_duration(), Duration::from_millis(1140));
+
+        // A mantra drawn for each sadhana takes the average of the mantras.
+        let options = Options {
+            mantras: vec![Mantra::from_text("om"), Mantra::from_text("om ah hum")],
+            selection: MantraSelection::Random,
+            rate: Duration::from_millis(10),
+            ..Default::default()
+        };
+        assert_eq!(options.sadhana_duration(), Duration::from_millis(20));
+
+        // A dharani recited every tenth sadhana adds a tenth of its length to the average.
+        let options = Options {
+            mantras: vec![
+                Mantra::from_text("om"),
+                Mantra {
+                    frequency: Some(10),
+                    ..Mantra::from_text("a b c d e f g h i j")
+                },
+            ],
+            rate: Duration::from_millis(10),
+            ..Default::default()
+        };
+        assert_eq!(options.sadhana_duration(), Duration::from_millis(20));
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn deprecated_rate_ns() -> Result<()> {
+        let mut options = Options::from_toml_str("rate_ns = 2000")?;
+        assert_eq!(options.rate, Duration::from_micros(2));
+        assert_eq!(options.rate_ns(), 2000);
+        options.set_rate_ns(3000);
+        assert_eq!(options.rate, Duration::from_micros(3));
+
+        // The rate is written back in the human-friendly format.
+        let contents = toml::to_string(&options).unwrap();
+        assert!(contents.contains("rate = \"3us\""));
+        assert_eq!(Options::from_toml_str(&contents)?, options);
+        Ok(())
+    }
+
+    #[test]
+    fn options_from_invalid_toml() {
+        assert!(matches!(
+            Options::from_toml_str("mantras = 3"),
+            Err(Error::ParseFailed(_))
+        ));
+        assert!(matches!(
+            Options::from_toml_str("rate = \"10 days\""),
+            Err(Error::ParseFailed(_))
+        ));
+        assert!(matches!(
+            Options::from_toml_file("/does/not/exist.toml"),
+            Err(Error::ReadFailed { .. })
+        ));
+    }
+
+    #[test]
+    fn stop_within_sadhana() -> Result<()> {
+        let options = Options {
+            preparation: None,
+            preparation_repeats: None,
+            mantras: vec![repeated_mantra()],
+            conclusion: None,
+            conclusion_repeats: None,
+            sections: Vec::new(),
+            selection: MantraSelection::All,
+            interleave: Interleave::Sequential,
+            multiplier: None,
+            rate: Duration::from_millis(1),
+            preparation_rate: None,
+            conclusion_rate: None,
+            jitter: None,
+            mantras_per_day: None,
+            pause_between_mantras: None,
+            pause_between_sadhanas: None,
+            max_cpu_percent: None,
+            thread_priority: ThreadPriority::Normal,
+            load_aware: false,
+            skip_output: false,
+            turbo: false,
+            precise_pacing: false,
+            normalization: Normalization::Nfc,
+            power_policy: PowerPolicy::Ignore,
+            restart_policy: RestartPolicy::Never,
+            error_policy: ErrorPolicy::Abort,
+            output_queue: None,
+            overflow_policy: OverflowPolicy::Block,
+            run_for: None,
+            target_mantras: None,
+            separator: None,
+            format: OutputFormat::default(),
+            mala: MalaConfig::default(),
+            recent_output_capacity: 0,
+            repeats: None,
+        };
+        let miner = MantraMiner::new(options);
+        miner.start()?;
+        thread::sleep(Duration::from_millis(10));
+
+        // The sadhana takes over 100ms, but the thread stops after the current syllable.
+        miner.stop_with_timeout(Duration::from_millis(50))?;
+        assert_eq!(miner.count(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn set_repeats() -> Result<()> {
+        let options = Options {
+            preparation: None,
+            preparation_repeats: None,
+            mantras: vec![simple_mantra()],
+            conclusion: None,
+            conclusion_repeats: None,
+            sections: Vec::new(),
+            selection: MantraSelection::All,
+            interleave: Interleave::Sequential,
+            multiplier: None,
+            rate: Duration::from_micros(1),
+            preparation_rate: None,
+            conclusion_rate: None,
+            jitter: None,
+            mantras_per_day: None,
+            pause_between_mantras: None,
+            pause_between_sadhanas: None,
+            max_cpu_percent: None,
+            thread_priority: ThreadPriority::Normal,
+            load_aware: false,
+            skip_output: false,
+            turbo: false,
+            precise_pacing: false,
+            normalization: Normalization::Nfc,
+            power_policy: PowerPolicy::Ignore,
+            restart_policy: RestartPolicy::Never,
+            error_policy: ErrorPolicy::Abort,
+            output_queue: None,
+            overflow_policy: OverflowPolicy::Block,
+            run_for: None,
+            target_mantras: None,
+            separator: None,
+            format: OutputFormat::default(),
+            mala: MalaConfig::default(),
+            recent_output_capacity: 0,
+            repeats: Some(10),
+        };
+        let miner = MantraMiner::new(options);
+        miner.start()?;
+        miner.wait()?;
+        assert_eq!(miner.count(), 10);
+        Ok(())
+    }
+
+    #[test]
+    fn indefinite_repeats() -> Result<()> {
+        let options = Options {
+            preparation: None,
+            preparation_repeats: None,
+            mantras: vec![simple_mantra()],
+            conclusion: None,
+            conclusion_repeats: None,
+            sections: Vec::new(),
+            selection: MantraSelection::All,
+            interleave: Interleave::Sequential,
+            multiplier: None,
+            rate: Duration::from_micros(1),
+            preparation_rate: None,
+            conclusion_rate: None,
+            jitter: None,
+            mantras_per_day: None,
+            pause_between_mantras: None,
+            pause_between_sadhanas: None,
+            max_cpu_percent: None,
+            thread_priority: ThreadPriority::Normal,
+            load_aware: false,
+            skip_output: false,
+            turbo: false,
+            precise_pacing: false,
+            normalization: Normalization::Nfc,
+            power_policy: PowerPolicy::Ignore,
+            restart_policy: RestartPolicy::Never,
+            error_policy: ErrorPolicy::Abort,
+            output_queue: None,
+            overflow_policy: OverflowPolicy::Block,
+            run_for: None,
+            target_mantras: None,
+            separator: None,
+            format: OutputFormat::default(),
+            mala: MalaConfig::default(),
+            recent_output_capacity: 0,
+            repeats: None,
+        };
+        let miner = MantraMiner::new(options);
+        miner.start()?;
+        thread::sleep(Duration::from_millis(10));
+        miner.stop()?;
+        assert!(miner.count() > 10);
+        Ok(())
+    }
+
+    #[test]
+    fn with_preparation_and_conclusion() -> Result<()> {
+        let options = Options {
+            preparation: Some(PREPARATION.to_string()),
+            preparation_repeats: None,
+            mantras: vec![simple_mantra()],
+            conclusion: Some(DEDICATION.to_string()),
+            conclusion_repeats: None,
+            sections: Vec::new(),
+            selection: MantraSelection::All,
+            interleave: Interleave::Sequential,
+            multiplier: None,
+            rate: Duration::from_micros(1),
+            preparation_rate: None,
+            conclusion_rate: None,
+            jitter: None,
+            mantras_per_day: None,
+            pause_between_mantras: None,
+            pause_between_sadhanas: None,
+            max_cpu_percent: None,
+            thread_priority: ThreadPriority::Normal,
+            load_aware: false,
+            skip_output: false,
+            turbo: false,
+            precise_pacing: false,
+            normalization: Normalization::Nfc,
+            power_policy: PowerPolicy::Ignore,
+            restart_policy: RestartPolicy::Never,
+            error_policy: ErrorPolicy::Abort,
+            output_queue: None,
+            overflow_policy: OverflowPolicy::Block,
+            run_for: None,
+            target_mantras: None,
+            separator: None,
+            format: OutputFormat::default(),
+            mala: MalaConfig::default(),
+            recent_output_capacity: 0,
+            repeats: Some(3),
+        };
+        let miner = MantraMiner::new(options);
+        miner.start()?;
+        miner.wait()?;
+        assert_eq!(miner.count(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn with_repeated_preparation_and_conclusion() -> Result<()> {
+        let options = Options {
+            preparation: Some(PREPARATION.to_string()),
+            preparation_repeats: Some(3),
+            mantras: vec![simple_mantra()],
+            conclusion: Some(DEDICATION.to_string()),
+            conclusion_repeats: Some(3),
+            sections: Vec::new(),
+            selection: MantraSelection::All,
+            interleave: Interleave::Sequential,
+            multiplier: None,
+            rate: Duration::from_micros(1),
+            preparation_rate: None,
+            conclusion_rate: None,
+            jitter: None,
+            mantras_per_day: None,
+            pause_between_mantras: None,
+            pause_between_sadhanas: None,
+            max_cpu_percent: None,
+            thread_priority: ThreadPriority::Normal,
+            load_aware: false,
+            skip_output: false,
+            turbo: false,
+            precise_pacing: false,
+            normalization: Normalization::Nfc,
+            power_policy: PowerPolicy::Ignore,
+            restart_policy: RestartPolicy::Never,
+            error_policy: ErrorPolicy::Abort,
+            output_queue: None,
+            overflow_policy: OverflowPolicy::Block,
+            run_for: None,
+            target_mantras: None,
+            separator: None,
+            format: OutputFormat::default(),
+            mala: MalaConfig::default(),
+            recent_output_capacity: 0,
+            repeats: Some(3),
+        };
+        let miner = MantraMiner::new(options);
+        miner.start()?;
+        miner.wait()?;
+        assert_eq!(miner.count(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn using_repeated_mantra() -> Result<()> {
+        let options = Options {
+            preparation: Some(PREPARATION.to_string()),
+            preparation_repeats: None,
+            mantras: vec![repeated_mantra()],
+            conclusion: Some(DEDICATION.to_string()),
+            conclusion_repeats: None,
+            sections: Vec::new(),
+            selection: MantraSelection::All,
+            interleave: Interleave::Sequential,
+            multiplier: None,
+            rate: Duration::from_micros(1),
+            preparation_rate: None,
+            conclusion_rate: None,
+            jitter: None,
+            mantras_per_day: None,
+            pause_between_mantras: None,
+            pause_between_sadhanas: None,
+            max_cpu_percent: None,
+            thread_priority: ThreadPriority::Normal,
+            load_aware: false,
+            skip_output: false,
+            turbo: false,
+            precise_pacing: false,
+            normalization: Normalization::Nfc,
+            power_policy: PowerPolicy::Ignore,
+            restart_policy: RestartPolicy::Never,
+            error_policy: ErrorPolicy::Abort,
+            output_queue: None,
+            overflow_policy: OverflowPolicy::Block,
+            run_for: None,
+            target_mantras: None,
+            separator: None,
+            format: OutputFormat::default(),
+            mala: MalaConfig::default(),
+            recent_output_capacity: 0,
+            repeats: Some(3),
+        };
+        let miner = MantraMiner::new(options);
+        miner.start()?;
+        miner.wait()?;
+        assert_eq!(miner.count(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn last_error() -> Result<()> {
+        let options = Options {
+            mantras: vec![simple_mantra()],
+            rate: Duration::from_micros(1),
+            ..Default::default()
+        };
+        let miner = MantraMiner::with_output(options, Box::new(BrokenWriter));
+        miner.start()?;
+        while miner.state() == MinerState::Running {
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(miner.state(), MinerState::Finished);
+
+        // The output is buffered, so the error is returned when it's flushed after the last
+        // syllable of the sadhana.
+        let error = miner.last_error().unwrap();
+        assert_eq!(error.kind, std::io::ErrorKind::BrokenPipe);
+        assert_eq!(error.text, None);
+        let progress = error.progress.unwrap();
+        assert_eq!(progress.section, Section::Mantras);
+        assert_eq!(progress.syllable_index, Some(5));
+        assert_eq!(miner.count(), 0);
+        assert!(matches!(
+            miner.stop_and_join(),
+            Err(Error::RecitationFailed(_))
+        ));
+        assert!(miner.stop_and_join().is_ok());
+        assert!(miner.last_error().is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn with_output() -> Result<()> {
+        let options = Options {
+            preparation: Some("ready".to_string()),
+            preparation_repeats: None,
+            mantras: vec![simple_mantra()],
+            conclusion: Some("done".to_string()),
+            conclusion_repeats: None,
+            sections: Vec::new(),
+            selection: MantraSelection::All,
+            interleave: Interleave::Sequential,
+            multiplier: None,
+            rate: Duration::from_micros(1),
+            preparation_rate: None,
+            conclusion_rate: None,
+            jitter: None,
+            mantras_per_day: None,
+            pause_between_mantras: None,
+            pause_between_sadhanas: None,
+            max_cpu_percent: None,
+            thread_priority: ThreadPriority::Normal,
+            load_aware: false,
+            skip_output: false,
+            turbo: false,
+            precise_pacing: false,
+            normalization: Normalization::Nfc,
+            power_policy: PowerPolicy::Ignore,
+            restart_policy: RestartPolicy::Never,
+            error_policy: ErrorPolicy::Abort,
+            output_queue: None,
+            overflow_policy: OverflowPolicy::Block,
+            run_for: None,
+            target_mantras: None,
+            separator: None,
+            format: OutputFormat::default(),
+            mala: MalaConfig::default(),
+            recent_output_capacity: 0,
+            repeats: Some(2),
+        };
+        let buffer = SharedBuffer::default();
+        let miner = MantraMiner::with_output(options, Box::new(buffer.clone()));
+        miner.start()?;
+        miner.wait()?;
+        assert_eq!(miner.count(), 2);
+        assert_eq!(
+            String::from_utf8(buffer.0.lock().clone())?,
+            "readyom\nma\nni\npad\nme\nhum\ndone".repeat(2)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn output_format() -> Result<()> {
+        let options = Options {
+            preparation: Some("ready".to_string()),
+            mantras: vec![simple_mantra()],
+            conclusion: Some("done".to_string()),
+            rate: Duration::from_micros(1),
+            separator: Some(" ".to_string()),
+            format: OutputFormat {
+                syllable_prefix: "[".to_string(),
+                syllable_suffix: "]".to_string(),
+                section_newline: true,
+                timestamp: None,
+            },
+            repeats: Some(1),
+            ..Default::default()
+        };
+        let buffer = SharedBuffer::default();
+        let miner = MantraMiner::with_output(options, Box::new(buffer.clone()));
+        miner.start()?;
+        miner.wait()?;
+        assert_eq!(
+            String::from_utf8(buffer.0.lock().clone())?,
+            "ready\n[om] [ma] [ni] [pad] [me] [hum] done\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn voice() -> Result<()> {
+        /// A voice that records what it recites.
+        struct RecordingVoice(Arc<Mutex<Vec<String>>>);
+
+        impl Voice for RecordingVoice {
+            fn recite_syllable(&mut self, syllable: &str) -> std::io::Result<()> {
+                self.0.lock().push(syllable.to_string());
+                Ok(())
+            }
+
+            fn recite_character(&mut self, c: char) -> std::io::Result<()> {
+                self.0.lock().push(c.to_string());
+                Ok(())
+            }
+
+            fn end_section(&mut self) -> std::io::Result<()> {
+                self.0.lock().push("|".to_string());
+                Ok(())
+            }
+        }
+
+        let options = Options {
+            preparation: Some("ok".to_string()),
+            mantras: vec![simple_mantra()],
+            rate: Duration::from_micros(1),
+            repeats: Some(1),
+            ..Default::default()
+        };
+        let buffer = SharedBuffer::default();
+        let recited = Arc::new(Mutex::new(Vec::new()));
+        let miner = MantraMiner::with_output(options, Box::new(buffer.clone()));
+        miner.set_voice(Some(Box::new(RecordingVoice(recited.clone()))));
+        miner.start()?;
+        miner.wait()?;
+
+        // The voice replaces the output.
+        assert!(buffer.0.lock().is_empty());
+        assert_eq!(
+            *recited.lock(),
+            vec!["o", "k", "|", "om", "ma", "ni", "pad", "me", "hum"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn store() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("counts.toml");
+        let options = Options {
+            mantras: vec![simple_mantra()],
+            rate: Duration::from_micros(1),
+            repeats: Some(2),
+            ..Default::default()
+        };
+        let miner = MantraMiner::new(options.clone()).with_store(Box::new(FileStore::new(&path)));
+        miner.start()?;
+        miner.wait()?;
+        assert_eq!(miner.count(), 2);
+
+        // A new miner with the same store starts from the saved counts.
+        let miner = MantraMiner::new(options).with_store(Box::new(FileStore::new(&path)));
+        assert_eq!(miner.count(), 0);
+        miner.start()?;
+        miner.wait()?;
+        assert_eq!(miner.count(), 4);
+        assert_eq!(miner.syllable_count(), 24);
+
+        // The daily totals are saved along with the counts, so the streaks survive restarts.
+        let days = miner.daily_stats().days;
+        assert_eq!(days.iter().map(|day| day.sadhanas).sum::<u64>(), 4);
+        assert_eq!(FileStore::new(&path).load()?.days, days);
+        Ok(())
+    }
+
+    #[test]
+    fn cpu_percent() -> Result<()> {
+        assert_eq!(CpuPercent::new(0.1).map(CpuPercent::percent), Some(0.1));
+        assert_eq!(CpuPercent::new(100.0).map(CpuPercent::percent), Some(100.0));
+        for percent in [0.0, 0.0004, -1.0, 100.001, f64::NAN] {
+            assert_eq!(CpuPercent::new(percent), None);
+        }
+
+        // The budget is written as a percentage, and invalid ones are rejected when loaded.
+        let options = Options::from_toml_str("max_cpu_percent = 0.1")?;
+        assert_eq!(options.max_cpu_percent, CpuPercent::new(0.1));
+        assert!(Options::from_toml_str("max_cpu_percent = 1")?
+            .max_cpu_percent
+            .is_some());
+        assert!(Options::from_toml_str("max_cpu_percent = 0").is_err());
+        assert!(toml::to_string(&options)?.contains("max_cpu_percent = 0.1"));
+        Ok(())
+    }
+
+    #[test]
+    fn restart_keeps_counts() -> Result<()> {
+        /// A count store that keeps the saved counts in memory.
+        struct MemoryStore(Arc<Mutex<StoredCounts>>);
+
+        impl CountStore for MemoryStore {
+            fn load(&mut self) -> std::io::Result<StoredCounts> {
+                Ok(self.0.lock().clone())
+            }
+
+            fn save(&mut self, counts: &StoredCounts) -> std::io::Result<()> {
+                *self.0.lock() = counts.clone();
+                Ok(())
+            }
+        }
+
+        let options = Options {
+            mantras: vec![simple_mantra()],
+            rate: Duration::from_micros(1),
+            skip_output: true,
+            ..Default::default()
+        };
+        let saved = Arc::new(Mutex::new(StoredCounts::default()));
+        let miner = MantraMiner::new(options).with_store(Box::new(MemoryStore(saved.clone())));
+        miner.start()?;
+        thread::sleep(Duration::from_millis(50));
+        let recited = miner.syllable_count();
+
+        // The counts of the first run are saved as its thread exits, and then loaded again instead
+        // of the ones saved before it started.
+        miner.start()?;
+        assert!(miner.syllable_count() >= recited);
+        miner.stop_and_join()?;
+        assert!(saved.lock().syllables >= recited);
+        assert_eq!(saved.lock().syllables, miner.syllable_count());
+        Ok(())
+    }
+
+    #[test]
+    fn store_records_sadhanas() -> Result<()> {
+        /// A count store that keeps the recitations passed to it.
+        struct RecordingStore(Arc<Mutex<Vec<SadhanaRecord>>>);
+
+        impl CountStore for RecordingStore {
+            fn load(&mut self) -> std::io::Result<StoredCounts> {
+                Ok(StoredCounts::default())
+            }
+
+            fn save(&mut self, _counts: &StoredCounts) -> std::io::Result<()> {
+                Ok(())
+            }
+
+            fn record_sadhana(&mut self, record: &SadhanaRecord) -> std::io::Result<()> {
+                self.0.lock().push(record.clone());
+                Ok(())
+            }
+        }
+
+        let options = Options {
+            mantras: vec![simple_mantra()],
+            rate: Duration::from_micros(1),
+            repeats: Some(2),
+            ..Default::default()
+        };
+        let records = Arc::new(Mutex::new(Vec::new()));
+        let miner = MantraMiner::new(options).with_store(Box::new(RecordingStore(records.clone())));
+        miner.start()?;
+        miner.wait()?;
+
+        let records = records.lock();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].count, 2);
+        assert_eq!(records[1].mantras.len(), 1);
+        assert_eq!(records[1].mantras[0].completed, 1);
+        assert_eq!(records[1].mantras[0].syllables, 6);
+        Ok(())
+    }
+
+    #[test]
+    fn store_resumes_checkpoint() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("counts.toml");
+        let options = Options {
+            mantras: vec![simple_mantra(), repeated_mantra()],
+            rate: Duration::from_micros(1),
+            repeats: None,
+            target_mantras: Some(1),
+            ..Default::default()
+        };
+        let miner = MantraMiner::new(options.clone()).with_store(Box::new(FileStore::new(&path)));
+        miner.start()?;
+        miner.wait()?;
+        assert_eq!(miner.count(), 0);
+        let checkpoint = miner.checkpoint();
+        assert!(checkpoint.is_some());
+        drop(miner);
+        assert_eq!(FileStore::new(&path).load()?.checkpoint, checkpoint);
+
+        // A new miner with the same store resumes after the completed mantra instead of reciting
+        // it again.
+        let buffer = SharedBuffer::default();
+        let miner = MantraMiner::with_output(
+            Options {
+                repeats: Some(1),
+                target_mantras: None,
+                ..options
+            },
+            Box::new(buffer.clone()),
+        )
+        .with_store(Box::new(FileStore::new(&path)));
+        miner.start()?;
+        miner.wait()?;
+        assert_eq!(miner.count(), 1);
+        assert_eq!(miner.checkpoint(), None);
+        let stats = miner.stats();
+        assert_eq!(stats.mantras[0].completed, 0);
+        assert_eq!(stats.mantras[1].completed, 108);
+        assert!(!String::from_utf8(buffer.0.lock().clone())?.contains("om"));
+        assert_eq!(FileStore::new(&path).load()?.checkpoint, None);
+        Ok(())
+    }
+
+    #[test]
+    fn interrupted_sadhanas() -> Result<()> {
+        let options = Options {
+            mantras: vec![simple_mantra(), repeated_mantra()],
+            rate: Duration::from_micros(1),
+            repeats: Some(1),
+            target_mantras: Some(1),
+            ..Default::default()
+        };
+        let miner = MantraMiner::new(options.clone());
+        assert_eq!(miner.sadhana_progress(), None);
+        miner.start()?;
+        miner.wait()?;
+
+        // The run stopped after the six syllables of the first mantra, out of 114 in the sadhana.
+        assert_eq!(miner.count(), 0);
+        assert_eq!(miner.interrupted_sadhanas(), 1);
+        assert_eq!(miner.stats().interrupted_sadhanas, 1);
+        assert_eq!(miner.sadhana_progress(), Some(6.0 / 114.0));
+
+        // Completing the resumed sadhana clears its progress.
+        miner.update_options(Options {
+            target_mantras: None,
+            ..options
+        })?;
+        miner.start()?;
+        miner.wait()?;
+        assert_eq!(miner.count(), 1);
+        assert_eq!(miner.interrupted_sadhanas(), 1);
+        assert_eq!(miner.sadhana_progress(), None);
+        Ok(())
+    }
+
+    #[test]
+    fn store_load_failed() -> Result<()> {
+        let file = NamedTempFile::new()?;
+        std::fs::write(file.path(), "sadhanas = -1")?;
+        let miner = MantraMiner::new(Options {
+            mantras: vec![simple_mantra()],
+            rate: Duration::from_micros(1),
+            ..Default::default()
+        })
+        .with_store(Box::new(FileStore::new(file.path())));
+        assert!(matches!(miner.start(), Err(Error::StoreFailed(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn daily_stats() -> Result<()> {
+        let options = Options {
+            mantras: vec![simple_mantra()],
+            rate: Duration::from_micros(1),
+            repeats: Some(2),
+            ..Default::default()
+        };
+        let miner = MantraMiner::new(options);
+        assert_eq!(miner.daily_stats().days.len(), 0);
+        miner.start()?;
+        miner.wait()?;
+
+        let stats = miner.daily_stats();
+        assert_eq!(stats.days.len(), 1);
+        assert_eq!(stats.days[0].sadhanas, 2);
+        assert_eq!(stats.days[0].mantras, 2);
+        assert_eq!(stats.current_streak, 1);
+        assert_eq!(stats.longest_streak, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn output_queue() -> Result<()> {
+        let options = Options {
+            mantras: vec![simple_mantra()],
+            rate: Duration::from_micros(1),
+            output_queue: Some(8),
+            repeats: Some(3),
+            ..Default::default()
+        };
+        let buffer = SharedBuffer::default();
+        let miner = MantraMiner::with_output(options, Box::new(buffer.clone()));
+        miner.start()?;
+        miner.wait()?;
+
+        // The queue is written to the output before the run is over.
+        assert_eq!(
+            String::from_utf8(buffer.0.lock().clone())?,
+            "om\nma\nni\npad\nme\nhum\n".repeat(3)
+        );
+        assert_eq!(miner.dropped_bytes(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn count_watch() -> Result<()> {
+        let miner = MantraMiner::new(Options {
+            mantras: vec![simple_mantra()],
+            rate: Duration::from_millis(1),
+            repeats: Some(3),
+            ..Default::default()
+        });
+        let mut watch = miner.count_watch();
+        assert!(!watch.has_changed());
+        assert_eq!(watch.changed_timeout(Duration::from_millis(10)), None);
+
+        // Each change is returned once, and the watch catches up with the latest count.
+        miner.start()?;
+        let first = watch.changed().unwrap();
+        assert!(first >= 1);
+        miner.wait()?;
+        assert_eq!(watch.count(), 3);
+        if first < 3 {
+            assert_eq!(watch.changed(), Some(3));
+        }
+        assert!(!watch.has_changed());
+
+        // The watch stops waiting once the miner is dropped.
+        let waiter = thread::spawn(move || watch.changed());
+        thread::sleep(Duration::from_millis(10));
+        drop(miner);
+        assert_eq!(waiter.join().unwrap(), None);
+        Ok(())
+    }
+
+    #[test]
+    fn shared_miner() -> Result<()> {
+        let miner = Arc::new(MantraMiner::new(Options {
+            mantras: vec![simple_mantra()],
+            rate: Duration::from_micros(1),
+            ..Default::default()
+        }));
+
+        // The miner can be started, paused, and stopped from different threads without a lock.
+        let started = miner.clone();
+        thread::spawn(move || started.start()).join().unwrap()?;
+        assert!(miner.is_running());
+        while miner.count() == 0 {
+            thread::sleep(Duration::from_millis(1));
+        }
+        let paused = miner.clone();
+        thread::spawn(move || paused.pause()).join().unwrap()?;
+        assert_eq!(miner.state(), MinerState::Paused);
+        let stopped = miner.clone();
+        thread::spawn(move || stopped.stop_and_join())
+            .join()
+            .unwrap()?;
+        assert_eq!(miner.state(), MinerState::Idle);
+        Ok(())
+    }
+
+    #[test]
+    fn handle() -> Result<()> {
+        let miner = MantraMiner::new(Options {
+            mantras: vec![simple_mantra()],
+            rate: Duration::from_micros(1),
+            ..Default::default()
+        });
+        let handle = miner.handle();
+        assert!(!handle.is_running());
+        assert!(matches!(handle.pause(), Err(Error::NotRunning)));
+
+        // The handle controls the miner from another thread.
+        miner.start()?;
+        let control = handle.clone();
+        thread::spawn(move || -> Result<()> {
+            while control.count() == 0 {
+                thread::sleep(Duration::from_millis(1));
+            }
+            control.pause()?;
+            Ok(())
+        })
+        .join()
+        .unwrap()?;
+        assert!(miner.is_paused());
+        assert_eq!(miner.state(), MinerState::Paused);
+
+        // The step in progress when the miner was paused may still complete.
+        thread::sleep(Duration::from_millis(10));
+        let count = handle.count();
+        assert!(count > 0);
+        assert_eq!(handle.stats(), miner.stats());
+
+        handle.resume()?;
+        assert!(!miner.is_paused());
+        thread::spawn(move || handle.stop()).join().unwrap();
+        assert!(matches!(
+            miner.state(),
+            MinerState::Stopping | MinerState::Idle
+        ));
+        miner.stop_and_join()?;
+        assert_eq!(miner.state(), MinerState::Idle);
+        Ok(())
+    }
+
+    #[test]
+    fn scoped() -> Result<()> {
+        let options = Options {
+            mantras: vec![simple_mantra()],
+            rate: Duration::from_micros(1),
+            ..Default::default()
+        };
+        let events = {
+            let guard = MantraMiner::scoped(options.clone())?;
+            assert!(guard.is_running());
+            guard.subscribe()
+        };
+
+        // Dropping the guard stops the thread, which drops the senders of the events.
+        while events.try_recv().is_ok() {}
+        assert_eq!(events.try_recv(), Err(TryRecvError::Disconnected));
+
+        let guard = MantraMiner::scoped(options.clone())?;
+        while guard.count() == 0 {
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert!(guard.finish()?.sadhanas > 0);
+
+        assert!(matches!(
+            MantraMiner::scoped(Options {
+                mantras: vec![],
+                ..options
+            }),
+            Err(Error::InvalidOptions(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn recite_n() -> Result<()> {
+        let options = Options {
+            preparation: Some("ready".to_string()),
+            mantras: vec![simple_mantra()],
+            rate: Duration::from_micros(1),
+            repeats: None,
+            ..Default::default()
+        };
+        let buffer = SharedBuffer::default();
+        let miner = MantraMiner::with_output(options, Box::new(buffer.clone()));
+
+        let sadhana = "readyom\nma\nni\npad\nme\nhum\n".len() as u64;
+        let summary = miner.recite_once()?;
+        assert_eq!(summary.sadhanas, 1);
+        assert_eq!(summary.mantras, 1);
+        assert_eq!(summary.syllables, 5 + 6);
+        assert_eq!(summary.bytes_written, sadhana);
+        assert!(summary.duration > Duration::ZERO);
+
+        // The counts of the miner include every recitation, while each summary only covers its own.
+        let summary = miner.recite_n(2)?;
+        assert_eq!(summary.sadhanas, 2);
+        assert_eq!(summary.bytes_written, 2 * sadhana);
+        assert_eq!(miner.count(), 3);
+        assert_eq!(buffer.0.lock().len() as u64, 3 * sadhana);
+        assert_eq!(miner.state(), MinerState::Idle);
+
+        // The miner cannot recite on the calling thread while its thread is running.
+        miner.start()?;
+        assert!(matches!(miner.recite_once(), Err(Error::AlreadyRunning)));
+        miner.stop_and_join()?;
+        Ok(())
+    }
+
+    #[test]
+    fn multiplier() -> Result<()> {
+        let options = Options {
+            mantras: vec![Mantra {
+                repeats: Some(2),
+                ..Mantra::from_text("om ah")
+            }],
+            multiplier: Some(100_000),
+            rate: Duration::from_micros(1),
+            ..Default::default()
+        };
+        let buffer = SharedBuffer::default();
+        let miner = MantraMiner::with_output(options, Box::new(buffer.clone()));
+        let rx = miner.subscribe();
+        let summary = miner.recite_once()?;
+
+        // Only the recited repetitions are written, but each of them is credited many times.
+        assert_eq!(
+            String::from_utf8(buffer.0.lock().clone())?,
+            "om\nah\nom\nah\n"
+        );
+        assert_eq!(summary.mantras, 200_000);
+        let stats = miner.stats();
+        assert_eq!(stats.multiplier, 100_000);
+        assert_eq!(stats.mantras[0].completed, 200_000);
+        assert_eq!(miner.daily_stats().days[0].mantras, 200_000);
+        let credited: Vec<usize> = rx
+            .try_iter()
+            .filter_map(|event| match event {
+                MinerEvent::MantraCompleted { credited, .. } => Some(credited),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(credited, vec![100_000, 100_000]);
+        Ok(())
+    }
+
+    #[test]
+    fn playlist() -> Result<()> {
+        let sadhana = |name: &str, text: &str, recitations| PlaylistSadhana {
+            name: name.to_string(),
+            recitations,
+            starts_at: None,
+            options: Options {
+                mantras: vec![Mantra::from_text(text)],
+                rate: Duration::from_micros(1),
+                ..Default::default()
+            },
+        };
+        let playlist = Playlist {
+            order: PlaylistOrder::Sequential,
+            sadhanas: vec![
+                sadhana("Refuge", "om ah", Some(2)),
+                sadhana("Tara", "tam", None),
+            ],
+        };
+        let buffer = SharedBuffer::default();
+        let miner = MantraMiner::with_output(Options::default(), Box::new(buffer.clone()))
+            .with_playlist(playlist);
+        assert_eq!(miner.current_sadhana(), Some("Refuge".to_string()));
+
+        // The sadhanas are recited in turn, each as many times as set in the playlist.
+        miner.recite_n(4)?;
+        assert_eq!(
+            String::from_utf8(buffer.0.lock().clone())?,
+            "om\nah\nom\nah\ntam\nom\nah\n"
+        );
+        assert_eq!(miner.count(), 4);
+        assert_eq!(miner.current_sadhana(), Some("Refuge".to_string()));
+        let stats = miner.stats();
+        assert_eq!(
+            stats.playlist,
+            vec![
+                SadhanaStats {
+                    name: "Refuge".to_string(),
+                    completed: 3,
+                },
+                SadhanaStats {
+                    name: "Tara".to_string(),
+                    completed: 1,
+                },
+            ]
+        );
+        assert_eq!(miner.handle().current_sadhana(), miner.current_sadhana());
+
+        // An invalid playlist is rejected when the miner is started.
+        let miner = MantraMiner::new(Options::default()).with_playlist(Playlist::default());
+        assert!(matches!(miner.start(), Err(Error::InvalidOptions(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn recent_output() -> Result<()> {
+        let options = Options {
+            preparation: Some("ready".to_string()),
+            preparation_repeats: None,
+            mantras: vec![simple_mantra()],
+            conclusion: None,
+            conclusion_repeats: None,
+            sections: Vec::new(),
+            selection: MantraSelection::All,
+            interleave: Interleave::Sequential,
+            multiplier: None,
+            rate: Duration::from_micros(1),
+            preparation_rate: None,
+            conclusion_rate: None,
+            jitter: None,
+            mantras_per_day: None,
+            pause_between_mantras: None,
+            pause_between_sadhanas: None,
+            max_cpu_percent: None,
+            thread_priority: ThreadPriority::Normal,
+            load_aware: false,
+            skip_output: false,
+            turbo: false,
+            precise_pacing: false,
+            normalization: Normalization::Nfc,
+            power_policy: PowerPolicy::Ignore,
+            restart_policy: RestartPolicy::Never,
+            error_policy: ErrorPolicy::Abort,
+            output_queue: None,
+            overflow_policy: OverflowPolicy::Block,
+            run_for: None,
+            target_mantras: None,
+            separator: None,
+            format: OutputFormat::default(),
+            mala: MalaConfig::default(),
+            recent_output_capacity: 4,
+            repeats: Some(2),
+        };
+        let miner = MantraMiner::new(options);
+        assert!(miner.recent_output(4).is_empty());
+        miner.start()?;
+        miner.wait()?;
+        assert_eq!(miner.recent_output(2), vec!["me", "hum"]);
+        assert_eq!(miner.recent_output(10), vec!["ni", "pad", "me", "hum"]);
+        Ok(())
+    }
+
+    #[test]
+    fn recent_output_buffer() {
+        let buffer = RecentOutput::new(2);
+        buffer.push("om");
+        buffer.push("ah");
+        buffer.push("hum");
+        assert_eq!(buffer.recent(1), vec!["hum"]);
+        assert_eq!(buffer.recent(5), vec!["ah", "hum"]);
+
+        let disabled = RecentOutput::new(0);
+        disabled.push("om");
+        assert!(disabled.recent(5).is_empty());
+    }
+
+    #[test]
+    fn subscribe() -> Result<()> {
+        let options = Options {
+            preparation: None,
+            preparation_repeats: None,
+            mantras: vec![Mantra {
+                name: Some("Guru".to_string()),
+                syllables: ["om", "ah"].map(Arc::from).into(),
+                repeats: Some(2),
+                ..Default::default()
+            }],
+            conclusion: None,
+            conclusion_repeats: None,
+            sections: Vec::new(),
+            selection: MantraSelection::All,
+            interleave: Interleave::Sequential,
+            multiplier: None,
+            rate: Duration::from_micros(1),
+            preparation_rate: None,
+            conclusion_rate: None,
+            jitter: None,
+            mantras_per_day: None,
+            pause_between_mantras: None,
+            pause_between_sadhanas: None,
+            max_cpu_percent: None,
+            thread_priority: ThreadPriority::Normal,
+            load_aware: false,
+            skip_output: false,
+            turbo: false,
+            precise_pacing: false,
+            normalization: Normalization::Nfc,
+            power_policy: PowerPolicy::Ignore,
+            restart_policy: RestartPolicy::Never,
+            error_policy: ErrorPolicy::Abort,
+            output_queue: None,
+            overflow_policy: OverflowPolicy::Block,
+            run_for: None,
+            target_mantras: None,
+            separator: None,
+            format: OutputFormat::default(),
+            mala: MalaConfig::default(),
+            recent_output_capacity: 0,
+            repeats: Some(1),
+        };
+        let miner = MantraMiner::new(options);
+        let rx = miner.subscribe();
+        let dropped_rx = miner.subscribe();
+        drop(dropped_rx);
+        miner.start()?;
+        miner.wait()?;
+
+        let syllable = |s: &str| MinerEvent::SyllableRecited {
+            syllable: s.to_string(),
+        };
+        let events: Vec<MinerEvent> = rx.try_iter().collect();
+        assert_eq!(
+            events,
+            vec![
+                MinerEvent::SadhanaStarted { run: 0 },
+                syllable("om"),
+                syllable("ah"),
+                MinerEvent::MantraCompleted {
+                    index: 0,
+                    name: Some("Guru".to_string()),
+                    repeat: 0,
+                    credited: 1
+                },
+                syllable("om"),
+                syllable("ah"),
+                MinerEvent::MantraCompleted {
+                    index: 0,
+                    name: Some("Guru".to_string()),
+                    repeat: 1,
+                    credited: 1
+                },
+                MinerEvent::SadhanaCompleted { run: 0, count: 1 },
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn pause_and_resume() -> Result<()> {
+        let options = Options {
+            preparation: None,
+            preparation_repeats: None,
+            mantras: vec![simple_mantra()],
+            conclusion: None,
+            conclusion_repeats: None,
+            sections: Vec::new(),
+            selection: MantraSelection::All,
+            interleave: Interleave::Sequential,
+            multiplier: None,
+            rate: Duration::from_micros(1),
+            preparation_rate: None,
+            conclusion_rate: None,
+            jitter: None,
+            mantras_per_day: None,
+            pause_between_mantras: None,
+            pause_between_sadhanas: None,
+            max_cpu_percent: None,
+            thread_priority: ThreadPriority::Normal,
+            load_aware: false,
+            skip_output: false,
+            turbo: false,
+            precise_pacing: false,
+            normalization: Normalization::Nfc,
+            power_policy: PowerPolicy::Ignore,
+            restart_policy: RestartPolicy::Never,
+            error_policy: ErrorPolicy::Abort,
+            output_queue: None,
+            overflow_policy: OverflowPolicy::Block,
+            run_for: None,
+            target_mantras: None,
+            separator: None,
+            format: OutputFormat::default(),
+            mala: MalaConfig::default(),
+            recent_output_capacity: 0,
+            repeats: None,
+        };
+        let miner = MantraMiner::new(options);
+        assert!(matches!(miner.pause(), Err(Error::NotRunning)));
+        miner.start()?;
+        thread::sleep(Duration::from_millis(10));
+        miner.pause()?;
+        assert!(miner.is_paused());
 
-    /// The number of times the mantra miner has completed a recitation of the entire sadhana.
-    count: Arc<Mutex<usize>>,
+        // Wait for the thread to block before checking the count stays the same.
+        thread::sleep(Duration::from_millis(10));
+        let paused_count = miner.count();
+        thread::sleep(Duration::from_millis(20));
+        assert_eq!(miner.count(), paused_count);
 
-    /// The channel used to signal the thread to stop.
-    stop_channel: Option<Sender<()>>,
-}
+        miner.resume()?;
+        assert!(!miner.is_paused());
+        thread::sleep(Duration::from_millis(20));
+        assert!(miner.count() > paused_count);
 
-impl MantraMiner {
-    /// Returns a new instance of `MantraMiner` with the given options.
-    pub fn new(options: Options) -> MantraMiner {
-        MantraMiner {
-            options,
-            count: Arc::new(Mutex::new(0)),
-            stop_channel: None,
-        }
+        miner.pause()?;
+        miner.stop()?;
+        assert!(!miner.is_paused());
+        Ok(())
     }
 
-    /// Recites the optional string. Used to recite the preparation and conclusion.
-    fn recite_string<T>(
-        input: &Option<String>,
-        output: &mut BufWriter<T>,
-        rate: Duration,
-    ) -> Result<()>
-    where
-        T: Write,
-    {
-        match input {
-            None => Ok(()),
-            Some(input) => {
-                for c in input.chars() {
-                    let mut b = [0; 4];
-                    output.write_all(c.encode_utf8(&mut b).as_bytes())?;
-                    thread::sleep(rate);
-                }
-                Ok(())
-            }
+    #[test]
+    fn state() -> Result<()> {
+        let options = Options {
+            mantras: vec![simple_mantra()],
+            rate: Duration::from_millis(1),
+            repeats: Some(3),
+            ..Default::default()
+        };
+        let miner = MantraMiner::new(options.clone());
+        assert_eq!(miner.state(), MinerState::Idle);
+        assert!(!miner.is_running());
+
+        miner.start()?;
+        miner.pause()?;
+        assert_eq!(miner.state(), MinerState::Paused);
+        assert!(miner.is_running());
+        miner.resume()?;
+        miner.wait()?;
+        assert_eq!(miner.state(), MinerState::Finished);
+        assert!(!miner.is_running());
+
+        // A finite run is finished once its thread exits, even if nobody waits for it.
+        miner.start()?;
+        while miner.state() == MinerState::Running {
+            thread::sleep(Duration::from_millis(1));
         }
-    }
+        assert_eq!(miner.state(), MinerState::Finished);
 
-    /// Runs the mantra miner.
-    fn run(options: Options, total_count: Arc<Mutex<usize>>, rx: Receiver<()>) -> Result<()> {
-        let mut run_count = 0;
-        let mut output = BufWriter::new(sink());
-        let rate = Duration::from_nanos(options.rate_ns);
+        // Stopping the miner makes it idle once its thread exits.
+        let miner = MantraMiner::new(Options {
+            repeats: None,
+            ..options
+        });
+        miner.start()?;
+        assert_eq!(miner.state(), MinerState::Running);
+        miner.stop()?;
+        assert!(matches!(
+            miner.state(),
+            MinerState::Stopping | MinerState::Idle
+        ));
+        miner.stop_and_join()?;
+        assert_eq!(miner.state(), MinerState::Idle);
+        Ok(())
+    }
 
-        while options.should_repeat(run_count) {
-            match rx.try_recv() {
-                Ok(_) | Err(TryRecvError::Disconnected) => {
-                    break;
-                }
-                Err(TryRecvError::Empty) => {}
-            }
+    #[test]
+    fn progress() -> Result<()> {
+        let miner = MantraMiner::new(Options {
+            mantras: vec![simple_mantra()],
+            rate: Duration::from_micros(1),
+            repeats: Some(2),
+            ..Default::default()
+        });
+        assert_eq!(miner.progress(), None);
 
-            let preparation_repeats = options.preparation_repeats.unwrap_or(1);
-            for _ in 0..preparation_repeats {
-                Self::recite_string(&options.preparation, &mut output, rate)?;
-            }
+        miner.start()?;
+        miner.wait()?;
+        assert_eq!(
+            miner.progress(),
+            Some(Progress {
+                run: 1,
+                section: Section::Mantras,
+                section_index: 0,
+                mantra_index: Some(0),
+                repeat: 0,
+                syllable_index: Some(5),
+            })
+        );
+        Ok(())
+    }
 
-            for mantra in &options.mantras {
-                mantra.recite(&mut output, rate)?;
-            }
+    #[test]
+    fn timing() -> Result<()> {
+        let miner = MantraMiner::new(Options {
+            mantras: vec![simple_mantra()],
+            rate: Duration::from_millis(1),
+            repeats: Some(2),
+            ..Default::default()
+        });
+        assert_eq!(miner.started_at(), None);
+        assert_eq!(miner.elapsed(), Duration::ZERO);
 
-            let conclusion_repeats = options.conclusion_repeats.unwrap_or(1);
-            for _ in 0..conclusion_repeats {
-                Self::recite_string(&options.conclusion, &mut output, rate)?;
-            }
+        miner.start()?;
+        assert!(miner.started_at().is_some());
+        miner.wait()?;
+        let elapsed = miner.elapsed();
+        let average = miner.average_sadhana_duration().unwrap();
+        assert!(average >= Duration::from_millis(6));
+        assert!(elapsed >= 2 * average);
 
-            *total_count.lock() += 1;
-            run_count += 1;
-        }
+        // The elapsed time stops growing once the run has finished.
+        thread::sleep(Duration::from_millis(10));
+        assert_eq!(miner.elapsed(), elapsed);
         Ok(())
     }
 
-    /// Spawns a new thread to run the mantra miner.
-    pub fn start(&mut self) -> Result<()> {
-        // Stop any existing thread.
-        self.stop()?;
+    #[test]
+    fn estimated_remaining() -> Result<()> {
+        let options = Options {
+            mantras: vec![simple_mantra()],
+            rate: Duration::from_millis(5),
+            repeats: Some(4),
+            ..Default::default()
+        };
+        let miner = MantraMiner::new(options.clone());
+        assert_eq!(miner.estimated_remaining(), None);
 
-        let cloned_options = self.options.clone();
-        let cloned_count = self.count.clone();
-        let (tx, rx) = mpsc::channel();
-        thread::spawn(move || {
-            let _ = MantraMiner::run(cloned_options, cloned_count, rx);
+        // Each sadhana of 6 syllables takes about 30ms.
+        miner.start()?;
+        let remaining = miner.estimated_remaining().unwrap();
+        assert!(remaining <= Duration::from_millis(120));
+        assert!(remaining > Duration::from_millis(60));
+        miner.wait()?;
+        assert_eq!(miner.estimated_remaining(), Some(Duration::ZERO));
+
+        // There is no estimate for a sadhana that repeats indefinitely.
+        let miner = MantraMiner::new(Options {
+            repeats: None,
+            ..options
         });
-        self.stop_channel = Some(tx);
+        miner.start()?;
+        assert_eq!(miner.estimated_remaining(), None);
+        miner.stop_and_join()?;
         Ok(())
     }
 
-    /// Stops the thread running the mantra miner.
-    pub fn stop(&mut self) -> Result<()> {
-        if let Some(tx) = self.stop_channel.take() {
-            let _ = tx.send(());
-        }
+    #[test]
+    fn estimated_remaining_by_mantras() -> Result<()> {
+        let options = Options {
+            mantras: vec![Mantra {
+                repeats: Some(10),
+                ..simple_mantra()
+            }],
+            rate: Duration::from_millis(5),
+            repeats: Some(100),
+            target_mantras: Some(4),
+            ..Default::default()
+        };
+
+        // Each repetition of 6 syllables takes about 30ms, so the target of 4 repetitions is
+        // reached long before the 100 sadhanas are recited.
+        let miner = MantraMiner::new(options.clone());
+        miner.start()?;
+        let remaining = miner.estimated_remaining().unwrap();
+        assert!(remaining <= Duration::from_millis(120));
+        assert!(remaining > Duration::from_millis(60));
+        miner.wait()?;
+        assert_eq!(miner.estimated_remaining(), Some(Duration::ZERO));
+
+        // The target alone is enough for an estimate.
+        let miner = MantraMiner::new(Options {
+            repeats: None,
+            ..options
+        });
+        miner.start()?;
+        let remaining = miner.estimated_remaining().unwrap();
+        assert!(remaining <= Duration::from_millis(120));
+        miner.stop_and_join()?;
         Ok(())
     }
 
-    /// Returns the options used to configure this mantra miner.
-    pub fn options(&self) -> Options {
-        self.options.clone()
-    }
+    #[test]
+    fn run_for() -> Result<()> {
+        let miner = MantraMiner::new(Options {
+            mantras: vec![simple_mantra()],
+            rate: Duration::from_millis(1),
+            repeats: None,
+            run_for: Some(Duration::from_millis(100)),
+            ..Default::default()
+        });
+        miner.start()?;
+        let remaining = miner.estimated_remaining().unwrap();
+        assert!(remaining <= Duration::from_millis(100));
 
-    /// Returns the count of the mantra miner.
-    pub fn count(&self) -> usize {
-        *self.count.lock()
+        // The miner stops on its own once the time limit is reached.
+        miner.wait()?;
+        assert_eq!(miner.state(), MinerState::Finished);
+        assert!(miner.elapsed() >= Duration::from_millis(100));
+        assert_eq!(miner.estimated_remaining(), Some(Duration::ZERO));
+        assert_eq!(miner.finish_reason(), Some(FinishReason::TimeLimit));
+        Ok(())
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use anyhow::Result;
-    use std::{
-        io::{BufWriter, Write},
-        thread,
-        time::Duration,
-    };
+    #[test]
+    fn target_mantras() -> Result<()> {
+        let miner = MantraMiner::new(Options {
+            mantras: vec![simple_mantra()],
+            rate: Duration::from_micros(1),
+            repeats: None,
+            target_mantras: Some(5),
+            ..Default::default()
+        });
+        miner.start()?;
+        assert_eq!(miner.finish_reason(), None);
+        miner.wait()?;
+        assert_eq!(miner.finish_reason(), Some(FinishReason::TargetReached));
+        assert_eq!(miner.stats().mantras[0].completed, 5);
 
-    use crate::{Mantra, MantraMiner, Options};
+        // The repetitions of earlier runs count towards the target.
+        miner.start()?;
+        miner.wait()?;
+        assert_eq!(miner.stats().mantras[0].completed, 5);
 
-    const PREPARATION: &str = "I take refuge in the Three Jewels and arise bodhicitta.";
-    const DEDICATION: &str = "I dedicate the merit of this practice to all sentient beings.";
+        // A run stopped before reaching the target did not finish on its own.
+        miner.update_options(Options {
+            target_mantras: Some(u64::MAX),
+            ..miner.options()
+        })?;
+        miner.start()?;
+        miner.stop_and_join()?;
+        assert_eq!(miner.finish_reason(), None);
+        Ok(())
+    }
 
-    fn simple_mantra() -> Mantra {
-        Mantra {
-            syllables: vec![
-                "om".to_string(),
-                "ma".to_string(),
-                "ni".to_string(),
-                "pad".to_string(),
-                "me".to_string(),
-                "hum".to_string(),
-            ],
+    #[test]
+    fn stop_and_join() -> Result<()> {
+        let options = Options {
+            preparation: None,
+            preparation_repeats: None,
+            mantras: vec![simple_mantra()],
+            conclusion: None,
+            conclusion_repeats: None,
+            sections: Vec::new(),
+            selection: MantraSelection::All,
+            interleave: Interleave::Sequential,
+            multiplier: None,
+            rate: Duration::from_micros(1),
+            preparation_rate: None,
+            conclusion_rate: None,
+            jitter: None,
+            mantras_per_day: None,
+            pause_between_mantras: None,
+            pause_between_sadhanas: None,
+            max_cpu_percent: None,
+            thread_priority: ThreadPriority::Normal,
+            load_aware: false,
+            skip_output: false,
+            turbo: false,
+            precise_pacing: false,
+            normalization: Normalization::Nfc,
+            power_policy: PowerPolicy::Ignore,
+            restart_policy: RestartPolicy::Never,
+            error_policy: ErrorPolicy::Abort,
+            output_queue: None,
+            overflow_policy: OverflowPolicy::Block,
+            run_for: None,
+            target_mantras: None,
+            separator: None,
+            format: OutputFormat::default(),
+            mala: MalaConfig::default(),
+            recent_output_capacity: 0,
             repeats: None,
-        }
-    }
+        };
+        let miner = MantraMiner::new(options);
+        miner.start()?;
+        thread::sleep(Duration::from_millis(10));
+        miner.stop_and_join()?;
+        let count = miner.count();
+        thread::sleep(Duration::from_millis(10));
+        assert_eq!(miner.count(), count);
 
-    fn repeated_mantra() -> Mantra {
-        Mantra {
-            syllables: vec!["hri".to_string()],
-            repeats: Some(108),
-        }
+        // Joining a stopped miner does nothing.
+        miner.stop_and_join()?;
+        Ok(())
     }
 
     #[test]
-    fn should_repeat() {
-        let mut options = Options::default();
-
-        options.repeats = Some(10);
-        assert_eq!(options.should_repeat(5), true);
-        assert_eq!(options.should_repeat(10), false);
-        assert_eq!(options.should_repeat(50), false);
+    fn stop_with_timeout() -> Result<()> {
+        let options = Options {
+            preparation: None,
+            preparation_repeats: None,
+            mantras: vec![simple_mantra()],
+            conclusion: None,
+            conclusion_repeats: None,
+            sections: Vec::new(),
+            selection: MantraSelection::All,
+            interleave: Interleave::Sequential,
+            multiplier: None,
+            rate: Duration::from_micros(1),
+            preparation_rate: None,
+            conclusion_rate: None,
+            jitter: None,
+            mantras_per_day: None,
+            pause_between_mantras: None,
+            pause_between_sadhanas: None,
+            max_cpu_percent: None,
+            thread_priority: ThreadPriority::Normal,
+            load_aware: false,
+            skip_output: false,
+            turbo: false,
+            precise_pacing: false,
+            normalization: Normalization::Nfc,
+            power_policy: PowerPolicy::Ignore,
+            restart_policy: RestartPolicy::Never,
+            error_policy: ErrorPolicy::Abort,
+            output_queue: None,
+            overflow_policy: OverflowPolicy::Block,
+            run_for: None,
+            target_mantras: None,
+            separator: None,
+            format: OutputFormat::default(),
+            mala: MalaConfig::default(),
+            recent_output_capacity: 0,
+            repeats: None,
+        };
+        let miner = MantraMiner::with_output(options, Box::new(SlowWriter));
+        miner.start()?;
+        thread::sleep(Duration::from_millis(10));
 
-        options.repeats = None;
-        assert_eq!(options.should_repeat(5), true);
-        assert_eq!(options.should_repeat(10), true);
-        assert_eq!(options.should_repeat(50), true);
+        // The thread is blocked for 200ms while writing, so it cannot exit within 1ms.
+        assert!(matches!(
+            miner.stop_with_timeout(Duration::from_millis(1)),
+            Err(Error::Timeout(_))
+        ));
+        miner.stop_with_timeout(Duration::from_secs(1))?;
+        Ok(())
     }
 
     #[test]
-    fn recite_string() -> Result<()> {
-        let rate = Duration::from_nanos(10);
-        let buffer = Vec::with_capacity(100);
-        let mut output = BufWriter::new(buffer);
-        MantraMiner::recite_string(&Some(PREPARATION.to_string()), &mut output, rate)?;
-        output.flush()?;
-        assert_eq!(output.get_ref(), PREPARATION.as_bytes());
+    fn set_rate() -> Result<()> {
+        let options = Options {
+            mantras: vec![simple_mantra()],
+            rate: Duration::from_millis(1),
+            ..Default::default()
+        };
+        let miner = MantraMiner::new(options);
+        miner.start()?;
+        thread::sleep(Duration::from_millis(50));
+
+        // The new rate is used after the syllable that follows the current wait.
+        miner.set_rate(Duration::from_secs(60))?;
+        thread::sleep(Duration::from_millis(20));
+        let syllable_count = miner.syllable_count();
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(miner.syllable_count(), syllable_count);
+        assert_eq!(miner.options().rate, Duration::from_secs(60));
+
+        // A zero rate is rejected since the sadhana is repeated indefinitely.
+        assert!(matches!(
+            miner.set_rate(Duration::ZERO),
+            Err(Error::InvalidOptions(_))
+        ));
+        assert_eq!(miner.options().rate, Duration::from_secs(60));
+        miner.stop_and_join()?;
         Ok(())
     }
 
     #[test]
-    fn recite_mantra() -> Result<()> {
-        let mantra = simple_mantra();
-        let rate = Duration::from_nanos(10);
-        let buffer = Vec::with_capacity(100);
-        let mut output = BufWriter::new(buffer);
-        mantra.recite(&mut output, rate)?;
-        output.flush()?;
-        assert_eq!(output.get_ref(), "om\nma\nni\npad\nme\nhum\n".as_bytes());
+    fn update_options() -> Result<()> {
+        let mut mani = simple_mantra();
+        mani.name = Some("Mani".to_string());
+        let options = Options {
+            mantras: vec![mani.clone()],
+            rate: Duration::from_micros(1),
+            recent_output_capacity: 10,
+            ..Default::default()
+        };
+        let miner = MantraMiner::new(options.clone());
+        miner.start()?;
+        thread::sleep(Duration::from_millis(50));
+        let count = miner.count();
+        assert!(count > 0);
+
+        // The next sadhana recites the new mantras without resetting the counts.
+        let rx = miner.subscribe();
+        let hri = Mantra {
+            name: Some("Hri".to_string()),
+            ..Mantra::from_text("hri")
+        };
+        miner.update_options(Options {
+            mantras: vec![mani, hri],
+            ..options
+        })?;
+        // The first sadhana started may have begun before the options were updated.
+        let mut started = 0;
+        while started < 3 {
+            if let MinerEvent::SadhanaStarted { .. } = rx.recv()? {
+                started += 1;
+            }
+        }
+        assert!(miner.count() > count);
+        assert!(miner.recent_output(10).contains(&"hri".to_string()));
+
+        let stats = miner.stats();
+        assert_eq!(stats.mantras.len(), 2);
+        assert!(stats.mantras[0].completed as u64 >= count);
+        assert_eq!(stats.mantras[1].name.as_deref(), Some("Hri"));
+        assert!(stats.mantras[1].completed > 0);
+
+        // Invalid options are rejected and the miner keeps the current ones.
+        assert!(miner.update_options(Options::default()).is_err());
+        assert_eq!(miner.options().mantras.len(), 2);
+        miner.stop_and_join()?;
         Ok(())
     }
 
     #[test]
-    fn set_repeats() -> Result<()> {
+    fn stop_interrupts_sleep() -> Result<()> {
         let options = Options {
             preparation: None,
             preparation_repeats: None,
             mantras: vec![simple_mantra()],
             conclusion: None,
             conclusion_repeats: None,
-            rate_ns: 1000,
-            repeats: Some(10),
+            sections: Vec::new(),
+            selection: MantraSelection::All,
+            interleave: Interleave::Sequential,
+            multiplier: None,
+            rate: Duration::from_secs(60),
+            preparation_rate: None,
+            conclusion_rate: None,
+            jitter: None,
+            mantras_per_day: None,
+            pause_between_mantras: None,
+            pause_between_sadhanas: None,
+            max_cpu_percent: None,
+            thread_priority: ThreadPriority::Normal,
+            load_aware: false,
+            skip_output: false,
+            turbo: false,
+            precise_pacing: false,
+            normalization: Normalization::Nfc,
+            power_policy: PowerPolicy::Ignore,
+            restart_policy: RestartPolicy::Never,
+            error_policy: ErrorPolicy::Abort,
+            output_queue: None,
+            overflow_policy: OverflowPolicy::Block,
+            run_for: None,
+            target_mantras: None,
+            separator: None,
+            format: OutputFormat::default(),
+            mala: MalaConfig::default(),
+            recent_output_capacity: 0,
+            repeats: None,
         };
-        let mut miner = MantraMiner::new(options);
+        let miner = MantraMiner::new(options);
         miner.start()?;
         thread::sleep(Duration::from_millis(10));
-        miner.stop()?;
-        assert_eq!(miner.count(), 10);
+
+        // The thread is waiting for a minute after the first syllable but is woken up by the stop.
+        miner.stop_with_timeout(Duration::from_millis(100))?;
         Ok(())
     }
 
     #[test]
-    fn indefinite_repeats() -> Result<()> {
+    fn drop_stops_thread() -> Result<()> {
         let options = Options {
             preparation: None,
             preparation_repeats: None,
             mantras: vec![simple_mantra()],
             conclusion: None,
             conclusion_repeats: None,
-            rate_ns: 1000,
+            sections: Vec::new(),
+            selection: MantraSelection::All,
+            interleave: Interleave::Sequential,
+            multiplier: None,
+            rate: Duration::from_micros(1),
+            preparation_rate: None,
+            conclusion_rate: None,
+            jitter: None,
+            mantras_per_day: None,
+            pause_between_mantras: None,
+            pause_between_sadhanas: None,
+            max_cpu_percent: None,
+            thread_priority: ThreadPriority::Normal,
+            load_aware: false,
+            skip_output: false,
+            turbo: false,
+            precise_pacing: false,
+            normalization: Normalization::Nfc,
+            power_policy: PowerPolicy::Ignore,
+            restart_policy: RestartPolicy::Never,
+            error_policy: ErrorPolicy::Abort,
+            output_queue: None,
+            overflow_policy: OverflowPolicy::Block,
+            run_for: None,
+            target_mantras: None,
+            separator: None,
+            format: OutputFormat::default(),
+            mala: MalaConfig::default(),
+            recent_output_capacity: 0,
             repeats: None,
         };
-        let mut miner = MantraMiner::new(options);
+        let miner = MantraMiner::new(options);
+        let rx = miner.subscribe();
         miner.start()?;
         thread::sleep(Duration::from_millis(10));
-        miner.stop()?;
-        assert!(miner.count() > 10);
+        drop(miner);
+
+        // The thread has exited, so all the senders have been dropped.
+        rx.try_iter().for_each(drop);
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Disconnected));
         Ok(())
     }
 
     #[test]
-    fn with_preparation_and_conclusion() -> Result<()> {
+    fn stats() -> Result<()> {
+        let mut mani = simple_mantra();
+        mani.name = Some("Mani".to_string());
         let options = Options {
             preparation: Some(PREPARATION.to_string()),
             preparation_repeats: None,
-            mantras: vec![simple_mantra()],
-            conclusion: Some(DEDICATION.to_string()),
+            mantras: vec![mani, repeated_mantra()],
+            conclusion: None,
             conclusion_repeats: None,
-            rate_ns: 1000,
-            repeats: Some(3),
+            sections: Vec::new(),
+            selection: MantraSelection::All,
+            interleave: Interleave::Sequential,
+            multiplier: None,
+            rate: Duration::from_micros(1),
+            preparation_rate: None,
+            conclusion_rate: None,
+            jitter: None,
+            mantras_per_day: None,
+            pause_between_mantras: None,
+            pause_between_sadhanas: None,
+            max_cpu_percent: None,
+            thread_priority: ThreadPriority::Normal,
+            load_aware: false,
+            skip_output: false,
+            turbo: false,
+            precise_pacing: false,
+            normalization: Normalization::Nfc,
+            power_policy: PowerPolicy::Ignore,
+            restart_policy: RestartPolicy::Never,
+            error_policy: ErrorPolicy::Abort,
+            output_queue: None,
+            overflow_policy: OverflowPolicy::Block,
+            run_for: None,
+            target_mantras: None,
+            separator: None,
+            format: OutputFormat::default(),
+            mala: MalaConfig::default(),
+            recent_output_capacity: 0,
+            repeats: Some(2),
         };
-        let mut miner = MantraMiner::new(options);
+        let miner = MantraMiner::new(options);
         miner.start()?;
-        thread::sleep(Duration::from_millis(100));
-        miner.stop()?;
-        assert_eq!(miner.count(), 3);
+        thread::sleep(Duration::from_millis(200));
+        miner.stop_and_join()?;
+
+        assert_eq!(
+            miner.stats(),
+            MinerStats {
+                sadhanas: 2,
+                interrupted_sadhanas: 0,
+                mantras: vec![
+                    MantraStats {
+                        name: Some("Mani".to_string()),
+                        completed: 2,
+                        syllables: 12,
+                    },
+                    MantraStats {
+                        name: None,
+                        completed: 216,
+                        syllables: 216,
+                    },
+                ],
+                multiplier: 1,
+                days: miner.daily_stats().days,
+                playlist: Vec::new(),
+            }
+        );
+
+        let stats = miner.stats();
+        assert_eq!(stats.mantras[0].label(0), "Mani");
+        assert_eq!(stats.mantras[1].label(1), "#1");
         Ok(())
     }
 
     #[test]
-    fn with_repeated_preparation_and_conclusion() -> Result<()> {
+    fn syllable_count() -> Result<()> {
         let options = Options {
-            preparation: Some(PREPARATION.to_string()),
-            preparation_repeats: Some(3),
+            preparation: Some("ready".to_string()),
+            preparation_repeats: None,
             mantras: vec![simple_mantra()],
-            conclusion: Some(DEDICATION.to_string()),
-            conclusion_repeats: Some(3),
-            rate_ns: 1000,
+            conclusion: Some("done".to_string()),
+            conclusion_repeats: Some(2),
+            sections: Vec::new(),
+            selection: MantraSelection::All,
+            interleave: Interleave::Sequential,
+            multiplier: None,
+            rate: Duration::from_micros(1),
+            preparation_rate: None,
+            conclusion_rate: None,
+            jitter: None,
+            mantras_per_day: None,
+            pause_between_mantras: None,
+            pause_between_sadhanas: None,
+            max_cpu_percent: None,
+            thread_priority: ThreadPriority::Normal,
+            load_aware: false,
+            skip_output: false,
+            turbo: false,
+            precise_pacing: false,
+            normalization: Normalization::Nfc,
+            power_policy: PowerPolicy::Ignore,
+            restart_policy: RestartPolicy::Never,
+            error_policy: ErrorPolicy::Abort,
+            output_queue: None,
+            overflow_policy: OverflowPolicy::Block,
+            run_for: None,
+            target_mantras: None,
+            separator: None,
+            format: OutputFormat::default(),
+            mala: MalaConfig::default(),
+            recent_output_capacity: 0,
             repeats: Some(3),
         };
-        let mut miner = MantraMiner::new(options);
+        let miner = MantraMiner::new(options);
+        assert_eq!(miner.syllable_count(), 0);
         miner.start()?;
         thread::sleep(Duration::from_millis(100));
-        miner.stop()?;
-        assert_eq!(miner.count(), 3);
+        miner.stop_and_join()?;
+
+        // Each sadhana recites 5 characters, 6 syllables, and 8 characters.
+        assert_eq!(miner.syllable_count(), 3 * (5 + 6 + 8));
         Ok(())
     }
 
     #[test]
-    fn using_repeated_mantra() -> Result<()> {
+    fn mala_count() {
+        let config = MalaConfig::default();
+        assert_eq!(config.count(0), MalaCount::default());
+        assert_eq!(
+            config.count(3 * 108 + 5),
+            MalaCount {
+                bums: 0,
+                malas: 3,
+                beads: 5
+            }
+        );
+        assert_eq!(
+            config.count(101 * 108),
+            MalaCount {
+                bums: 1,
+                malas: 1,
+                beads: 0
+            }
+        );
+
+        let zero_beads = Options {
+            mantras: vec![simple_mantra()],
+            rate: Duration::from_micros(1),
+            mala: MalaConfig {
+                beads_per_mala: 0,
+                malas_per_bum: 100,
+            },
+            ..Default::default()
+        };
+        assert!(matches!(
+            zero_beads.validate(),
+            Err(Error::InvalidOptions(_))
+        ));
+    }
+
+    #[test]
+    fn malas() -> Result<()> {
         let options = Options {
-            preparation: Some(PREPARATION.to_string()),
+            preparation: None,
             preparation_repeats: None,
             mantras: vec![repeated_mantra()],
-            conclusion: Some(DEDICATION.to_string()),
+            conclusion: None,
             conclusion_repeats: None,
-            rate_ns: 1000,
+            sections: Vec::new(),
+            selection: MantraSelection::All,
+            interleave: Interleave::Sequential,
+            multiplier: None,
+            rate: Duration::from_micros(1),
+            preparation_rate: None,
+            conclusion_rate: None,
+            jitter: None,
+            mantras_per_day: None,
+            pause_between_mantras: None,
+            pause_between_sadhanas: None,
+            max_cpu_percent: None,
+            thread_priority: ThreadPriority::Normal,
+            load_aware: false,
+            skip_output: false,
+            turbo: false,
+            precise_pacing: false,
+            normalization: Normalization::Nfc,
+            power_policy: PowerPolicy::Ignore,
+            restart_policy: RestartPolicy::Never,
+            error_policy: ErrorPolicy::Abort,
+            output_queue: None,
+            overflow_policy: OverflowPolicy::Block,
+            run_for: None,
+            target_mantras: None,
+            separator: None,
+            format: OutputFormat::default(),
+            mala: MalaConfig {
+                beads_per_mala: 100,
+                malas_per_bum: 2,
+            },
+            recent_output_capacity: 0,
             repeats: Some(3),
         };
-        let mut miner = MantraMiner::new(options);
+        let miner = MantraMiner::new(options);
         miner.start()?;
-        thread::sleep(Duration::from_millis(100));
-        miner.stop()?;
-        assert_eq!(miner.count(), 3);
+        thread::sleep(Duration::from_millis(200));
+        miner.stop_and_join()?;
+        assert_eq!(
+            miner.malas(),
+            MalaCount {
+                bums: 1,
+                malas: 1,
+                beads: 24
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn wait() -> Result<()> {
+        let options = Options {
+            preparation: None,
+            preparation_repeats: None,
+            mantras: vec![simple_mantra()],
+            conclusion: None,
+            conclusion_repeats: None,
+            sections: Vec::new(),
+            selection: MantraSelection::All,
+            interleave: Interleave::Sequential,
+            multiplier: None,
+            rate: Duration::from_micros(1),
+            preparation_rate: None,
+            conclusion_rate: None,
+            jitter: None,
+            mantras_per_day: None,
+            pause_between_mantras: None,
+            pause_between_sadhanas: None,
+            max_cpu_percent: None,
+            thread_priority: ThreadPriority::Normal,
+            load_aware: false,
+            skip_output: false,
+            turbo: false,
+            precise_pacing: false,
+            normalization: Normalization::Nfc,
+            power_policy: PowerPolicy::Ignore,
+            restart_policy: RestartPolicy::Never,
+            error_policy: ErrorPolicy::Abort,
+            output_queue: None,
+            overflow_policy: OverflowPolicy::Block,
+            run_for: None,
+            target_mantras: None,
+            separator: None,
+            format: OutputFormat::default(),
+            mala: MalaConfig::default(),
+            recent_output_capacity: 0,
+            repeats: Some(5),
+        };
+        let miner = MantraMiner::new(options.clone());
+        assert!(matches!(miner.wait(), Err(Error::NotRunning)));
+        miner.start()?;
+        let stats = miner.wait()?;
+        assert_eq!(stats.sadhanas, 5);
+        assert_eq!(stats.mantras[0].completed, 5);
+        assert!(matches!(miner.wait(), Err(Error::NotRunning)));
+
+        let indefinite_miner = MantraMiner::new(Options {
+            repeats: None,
+            ..options
+        });
+        indefinite_miner.start()?;
+        assert!(matches!(
+            indefinite_miner.wait(),
+            Err(Error::InvalidOptions(_))
+        ));
         Ok(())
     }
 
@@ -390,11 +5253,146 @@ mod tests {
             mantras: vec![repeated_mantra()],
             conclusion: None,
             conclusion_repeats: None,
-            rate_ns: 1000,
+            sections: Vec::new(),
+            selection: MantraSelection::All,
+            interleave: Interleave::Sequential,
+            multiplier: None,
+            rate: Duration::from_micros(1),
+            preparation_rate: None,
+            conclusion_rate: None,
+            jitter: None,
+            mantras_per_day: None,
+            pause_between_mantras: None,
+            pause_between_sadhanas: None,
+            max_cpu_percent: None,
+            thread_priority: ThreadPriority::Normal,
+            load_aware: false,
+            skip_output: false,
+            turbo: false,
+            precise_pacing: false,
+            normalization: Normalization::Nfc,
+            power_policy: PowerPolicy::Ignore,
+            restart_policy: RestartPolicy::Never,
+            error_policy: ErrorPolicy::Abort,
+            output_queue: None,
+            overflow_policy: OverflowPolicy::Block,
+            run_for: None,
+            target_mantras: None,
+            separator: None,
+            format: OutputFormat::default(),
+            mala: MalaConfig::default(),
+            recent_output_capacity: 0,
             repeats: Some(3),
         };
         let options_clone = options.clone();
         let miner = MantraMiner::new(options);
         assert_eq!(miner.options(), options_clone);
     }
+
+    #[cfg(feature = "log")]
+    #[test]
+    fn log_records() -> Result<()> {
+        install_logger();
+        let options = Options {
+            mantras: vec![Mantra {
+                name: Some("mani".to_string()),
+                repeats: Some(4),
+                ..Mantra::from_text("om mani")
+            }],
+            mala: MalaConfig {
+                beads_per_mala: 2,
+                malas_per_bum: 100,
+            },
+            rate: Duration::from_micros(1),
+            repeats: Some(1),
+            ..Default::default()
+        };
+        let miner = MantraMiner::new(options);
+        miner.set_log_target("log_records");
+        miner.recite_once()?;
+
+        // The records use the target set on the miner instead of the default one.
+        assert_eq!(
+            logged("log_records"),
+            vec![
+                "completed repetition 0 of mantra mani (1 in total)",
+                "completed repetition 1 of mantra mani (2 in total)",
+                "completed mala (0 bums and 1 malas in total)",
+                "completed repetition 2 of mantra mani (3 in total)",
+                "completed repetition 3 of mantra mani (4 in total)",
+                "completed mala (0 bums and 2 malas in total)",
+                "completed sadhana 1",
+            ]
+        );
+        Ok(())
+    }
+
+    #[cfg(feature = "log")]
+    #[test]
+    fn log_malas_with_multiplier() -> Result<()> {
+        install_logger();
+        let options = Options {
+            mantras: vec![Mantra {
+                repeats: Some(23),
+                ..Mantra::from_text("om")
+            }],
+            mala: MalaConfig::default(),
+            multiplier: Some(5),
+            rate: Duration::from_micros(1),
+            repeats: Some(1),
+            ..Default::default()
+        };
+        let miner = MantraMiner::new(options);
+        miner.set_log_target("log_malas_with_multiplier");
+        miner.recite_once()?;
+
+        // The total goes from 105 to 110 without landing on 108, but the mala is still logged.
+        let malas: Vec<String> = logged("log_malas_with_multiplier")
+            .into_iter()
+            .filter(|message| message.starts_with("completed mala"))
+            .collect();
+        assert_eq!(malas, vec!["completed mala (0 bums and 1 malas in total)"]);
+        Ok(())
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn tracing_events() -> Result<()> {
+        let options = Options {
+            mantras: vec![Mantra {
+                name: Some("mani".to_string()),
+                repeats: Some(2),
+                ..Mantra::from_text("om mani")
+            }],
+            rate: Duration::from_micros(1),
+            repeats: Some(2),
+            ..Default::default()
+        };
+        let miner = MantraMiner::new(options);
+        let buffer = SharedBuffer::default();
+        let writer = buffer.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer(move || writer.clone())
+            .with_ansi(false)
+            .with_span_events(FmtSpan::NEW)
+            .without_time()
+            .finish();
+        tracing::subscriber::with_default(subscriber, || miner.recite_n(2))?;
+
+        // Each sadhana has its own span, and each mantra completion is an event inside it.
+        let output = String::from_utf8(buffer.0.lock().clone())?;
+        let lines: Vec<&str> = output.lines().map(str::trim).collect();
+        assert_eq!(
+            lines,
+            vec![
+                "INFO sadhana{run=0}: mantra_miner::recitation: new",
+                "INFO sadhana{run=0}: mantra_miner: mantra completed mantra=\"mani\" index=0 repeat=0 count=1",
+                "INFO sadhana{run=0}: mantra_miner: mantra completed mantra=\"mani\" index=0 repeat=1 count=2",
+                "INFO sadhana{run=1}: mantra_miner::recitation: new",
+                "INFO sadhana{run=1}: mantra_miner: mantra completed mantra=\"mani\" index=0 repeat=0 count=3",
+                "INFO sadhana{run=1}: mantra_miner: mantra completed mantra=\"mani\" index=0 repeat=1 count=4",
+            ]
+        );
+        Ok(())
+    }
 }
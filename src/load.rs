@@ -0,0 +1,130 @@
+//! Contains the load monitors used by the load-aware mode of the mantra miner, which slows down the
+//! recitation while the machine or the host program is busy and returns to the configured rate once
+//! the load drops.
+
+use std::time::{Duration, Instant};
+
+/// The load per core above which the recitation is slowed down.
+const LOAD_THRESHOLD: f64 = 0.75;
+
+/// The most the delay between syllables is multiplied by under heavy load.
+const MAX_SLOWDOWN: f64 = 100.0;
+
+/// The interval at which the load is sampled.
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Measures how busy the machine or the host program is, so that the mantra miner can recite more
+/// slowly while it is under pressure.
+pub trait LoadMonitor: Send {
+    /// Returns the current load as a fraction of the capacity available, where zero means idle
+    /// and one means fully busy. Values above one mean that work is waiting to run.
+    fn load(&mut self) -> f64;
+}
+
+impl<F: FnMut() -> f64 + Send> LoadMonitor for F {
+    fn load(&mut self) -> f64 {
+        self()
+    }
+}
+
+/// A load monitor that reads the load average of the system over the last minute, divided by the
+/// number of cores. The load average is only available on Linux, macOS, and FreeBSD, and other
+/// platforms always report no load. This is the default load monitor of the mantra miner.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemLoad;
+
+impl LoadMonitor for SystemLoad {
+    #[cfg(any(target_os = "linux", target_os = "macos", target_os = "freebsd"))]
+    fn load(&mut self) -> f64 {
+        let mut averages = [0.0; 3];
+        // SAFETY: The buffer has room for the single average requested.
+        if unsafe { libc::getloadavg(averages.as_mut_ptr(), 1) } < 1 {
+            return 0.0;
+        }
+        let cores = std::thread::available_parallelism().map_or(1, |cores| cores.get());
+        averages[0] / cores as f64
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "freebsd")))]
+    fn load(&mut self) -> f64 {
+        0.0
+    }
+}
+
+/// Samples a load monitor at regular intervals and turns the load into the factor by which the
+/// delay between syllables is multiplied.
+pub(crate) struct LoadSampler {
+    /// The time of the last sample.
+    last_sample: Option<Instant>,
+
+    /// The interval at which the load is sampled.
+    pub(crate) interval: Duration,
+
+    /// The slowdown computed from the last sample.
+    slowdown: f64,
+}
+
+impl Default for LoadSampler {
+    fn default() -> Self {
+        LoadSampler {
+            last_sample: None,
+            interval: SAMPLE_INTERVAL,
+            slowdown: 1.0,
+        }
+    }
+}
+
+impl LoadSampler {
+    /// Returns the factor by which the delay between syllables is multiplied, sampling the given
+    /// monitor if the last sample is too old. Below a load of 0.75, the delay is left unchanged.
+    /// Above it, the delay is multiplied by `(load / 0.75)^4`, up to a hundred times, so that the
+    /// miner backs off quickly as the machine becomes saturated.
+    pub(crate) fn slowdown(&mut self, monitor: &mut dyn LoadMonitor) -> f64 {
+        let now = Instant::now();
+        if self
+            .last_sample
+            .is_some_and(|last| now.saturating_duration_since(last) < self.interval)
+        {
+            return self.slowdown;
+        }
+
+        let load = monitor.load();
+        self.last_sample = Some(now);
+        self.slowdown = if load > LOAD_THRESHOLD {
+            (load / LOAD_THRESHOLD).powi(4).min(MAX_SLOWDOWN)
+        } else {
+            // Also covers a monitor that returns NaN.
+            1.0
+        };
+        self.slowdown
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::load::{LoadMonitor, LoadSampler, SystemLoad};
+
+    #[test]
+    fn slowdown() {
+        let mut sampler = LoadSampler {
+            interval: Duration::ZERO,
+            ..Default::default()
+        };
+        assert_eq!(sampler.slowdown(&mut || 0.5), 1.0);
+        assert_eq!(sampler.slowdown(&mut || 0.75), 1.0);
+        assert_eq!(sampler.slowdown(&mut || f64::NAN), 1.0);
+        assert_eq!(sampler.slowdown(&mut || 10.0), 100.0);
+        assert_eq!(sampler.slowdown(&mut || 1.5), 16.0);
+
+        // The load is not sampled again until the interval has elapsed.
+        sampler.interval = Duration::from_secs(60);
+        assert_eq!(sampler.slowdown(&mut || 0.0), 16.0);
+    }
+
+    #[test]
+    fn system_load() {
+        assert!(SystemLoad.load() >= 0.0);
+    }
+}
@@ -0,0 +1,298 @@
+//! Contains a voice that plays the recitation as MIDI notes, so that musicians can turn it into a
+//! generative soundscape that follows the rate of the miner. Each syllable is mapped to a note of
+//! a scale and held until the next one. The notes are sent to a `MidiSink`, either as raw MIDI
+//! bytes to a device such as `/dev/midi1` or recorded into a Standard MIDI File.
+
+use std::{
+    fs,
+    io::{self, Write},
+    path::PathBuf,
+    time::Instant,
+};
+
+use crate::Voice;
+
+/// The notes of the C major pentatonic scale over two octaves starting at middle C, which sound
+/// well together in any order.
+const PENTATONIC: [u8; 10] = [60, 62, 64, 67, 69, 72, 74, 76, 79, 81];
+
+/// The number of ticks per quarter note of the MIDI files.
+const TICKS_PER_QUARTER: u16 = 480;
+
+/// The tempo of the MIDI files, in microseconds per quarter note, which is the default of 120
+/// beats per minute.
+const TEMPO: u32 = 500_000;
+
+/// Receives the MIDI messages of a `MidiVoice`.
+pub trait MidiSink: Send {
+    /// Sends the given channel message, such as a note on or a note off, right away.
+    fn send(&mut self, message: [u8; 3]) -> io::Result<()>;
+
+    /// Called after each recitation of the sadhana and once the recitation finishes, when no note
+    /// is playing. Does nothing by default.
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A sink that writes the messages as raw MIDI bytes, such as to a MIDI device file or a serial
+/// port connected to a synthesizer.
+pub struct RawMidi<W: Write + Send>(pub W);
+
+impl<W: Write + Send> MidiSink for RawMidi<W> {
+    fn send(&mut self, message: [u8; 3]) -> io::Result<()> {
+        self.0.write_all(&message)?;
+        self.0.flush()
+    }
+}
+
+/// A sink that records the messages into a Standard MIDI File, timed by when they were sent. The
+/// file is rewritten each time the sink is flushed, so it can be opened while the miner runs.
+pub struct MidiFile {
+    /// The path of the file.
+    path: PathBuf,
+
+    /// The events of the track recorded so far, without the end of the track.
+    track: Vec<u8>,
+
+    /// The instant at which the last message was recorded, or `None` if none was.
+    last: Option<Instant>,
+}
+
+impl MidiFile {
+    /// Returns a sink that records into the file at the given path.
+    pub fn new(path: impl Into<PathBuf>) -> MidiFile {
+        MidiFile {
+            path: path.into(),
+            track: Vec::new(),
+            last: None,
+        }
+    }
+
+    /// Returns the contents of the file, with a single track that holds the recorded events.
+    fn contents(&self) -> Vec<u8> {
+        let tempo = TEMPO.to_be_bytes();
+        let mut track = vec![0x00, 0xff, 0x51, 0x03, tempo[1], tempo[2], tempo[3]];
+        track.extend_from_slice(&self.track);
+        track.extend_from_slice(&[0x00, 0xff, 0x2f, 0x00]);
+
+        let mut contents = b"MThd".to_vec();
+        contents.extend_from_slice(&6u32.to_be_bytes());
+        contents.extend_from_slice(&0u16.to_be_bytes());
+        contents.extend_from_slice(&1u16.to_be_bytes());
+        contents.extend_from_slice(&TICKS_PER_QUARTER.to_be_bytes());
+        contents.extend_from_slice(b"MTrk");
+        contents.extend_from_slice(&(track.len() as u32).to_be_bytes());
+        contents.extend_from_slice(&track);
+        contents
+    }
+}
+
+impl MidiSink for MidiFile {
+    fn send(&mut self, message: [u8; 3]) -> io::Result<()> {
+        let now = Instant::now();
+        let micros = self
+            .last
+            .map_or(0, |last| now.duration_since(last).as_micros());
+        self.last = Some(now);
+        let ticks = micros * TICKS_PER_QUARTER as u128 / TEMPO as u128;
+        write_variable(&mut self.track, ticks.min(0x0fff_ffff) as u32);
+        self.track.extend_from_slice(&message);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        fs::write(&self.path, self.contents())
+    }
+}
+
+/// Appends the given value as a variable-length quantity, as used for the times of MIDI events.
+fn write_variable(buf: &mut Vec<u8>, value: u32) {
+    let mut bytes = vec![(value & 0x7f) as u8];
+    let mut rest = value >> 7;
+    while rest > 0 {
+        bytes.push((rest & 0x7f) as u8 | 0x80);
+        rest >>= 7;
+    }
+    buf.extend(bytes.iter().rev());
+}
+
+/// A voice that plays each syllable of the mantras as a MIDI note, held until the next syllable,
+/// so the notes follow the rate of the miner. The same syllable always plays the same note. The
+/// preparation and conclusion are silent.
+pub struct MidiVoice<S: MidiSink> {
+    /// The sink to which the messages are sent.
+    sink: S,
+
+    /// The notes to which the syllables are mapped.
+    scale: Vec<u8>,
+
+    /// The zero-based MIDI channel of the notes.
+    channel: u8,
+
+    /// The velocity of the notes.
+    velocity: u8,
+
+    /// The note that is playing, if any.
+    playing: Option<u8>,
+}
+
+impl<S: MidiSink> MidiVoice<S> {
+    /// Returns a voice that plays the syllables on the C major pentatonic scale on the first
+    /// channel.
+    pub fn new(sink: S) -> MidiVoice<S> {
+        MidiVoice {
+            sink,
+            scale: PENTATONIC.to_vec(),
+            channel: 0,
+            velocity: 80,
+            playing: None,
+        }
+    }
+
+    /// Sets the notes to which the syllables are mapped. An empty scale keeps the current one.
+    pub fn scale(mut self, scale: Vec<u8>) -> MidiVoice<S> {
+        if !scale.is_empty() {
+            self.scale = scale.into_iter().map(|note| note.min(127)).collect();
+        }
+        self
+    }
+
+    /// Sets the zero-based MIDI channel of the notes, from 0 to 15.
+    pub fn channel(mut self, channel: u8) -> MidiVoice<S> {
+        self.channel = channel.min(15);
+        self
+    }
+
+    /// Sets the velocity of the notes, from 1 to 127.
+    pub fn velocity(mut self, velocity: u8) -> MidiVoice<S> {
+        self.velocity = velocity.clamp(1, 127);
+        self
+    }
+
+    /// Returns the note of the scale to which the syllable is mapped.
+    pub fn note(&self, syllable: &str) -> u8 {
+        // The FNV-1a hash of the syllable, which is stable across runs and platforms.
+        let hash = syllable.bytes().fold(0x811c_9dc5u32, |hash, byte| {
+            (hash ^ byte as u32).wrapping_mul(0x0100_0193)
+        });
+        self.scale[hash as usize % self.scale.len()]
+    }
+
+    /// Stops the note that is playing, if any.
+    fn stop_note(&mut self) -> io::Result<()> {
+        if let Some(note) = self.playing.take() {
+            self.sink.send([0x80 | self.channel, note, 0])?;
+        }
+        Ok(())
+    }
+}
+
+impl<S: MidiSink> Voice for MidiVoice<S> {
+    fn recite_syllable(&mut self, syllable: &str) -> io::Result<()> {
+        self.stop_note()?;
+        let note = self.note(syllable);
+        self.sink.send([0x90 | self.channel, note, self.velocity])?;
+        self.playing = Some(note);
+        Ok(())
+    }
+
+    fn recite_character(&mut self, _: char) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn end_section(&mut self) -> io::Result<()> {
+        self.stop_note()
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stop_note()?;
+        self.sink.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use std::{fs, time::Duration};
+
+    use crate::{
+        midi::{write_variable, PENTATONIC},
+        Mantra, MantraMiner, MidiFile, MidiVoice, Options, RawMidi, Voice,
+    };
+
+    #[test]
+    fn variable_length() {
+        for (value, expected) in [
+            (0, vec![0x00]),
+            (0x7f, vec![0x7f]),
+            (0x80, vec![0x81, 0x00]),
+            (0x3fff, vec![0xff, 0x7f]),
+            (0x0fff_ffff, vec![0xff, 0xff, 0xff, 0x7f]),
+        ] {
+            let mut buf = Vec::new();
+            write_variable(&mut buf, value);
+            assert_eq!(buf, expected);
+        }
+    }
+
+    #[test]
+    fn notes() -> Result<()> {
+        let mut voice = MidiVoice::new(RawMidi(Vec::new())).channel(2).velocity(100);
+        let (om, ah) = (voice.note("om"), voice.note("ah"));
+        assert!(PENTATONIC.contains(&om));
+        assert_eq!(MidiVoice::new(RawMidi(Vec::new())).note("om"), om);
+
+        // Each note is held until the next syllable, and the last one stops when flushing.
+        voice.recite_syllable("om")?;
+        voice.recite_character('x')?;
+        voice.recite_syllable("ah")?;
+        voice.flush()?;
+        assert_eq!(
+            voice.sink.0,
+            [0x92, om, 100, 0x82, om, 0, 0x92, ah, 100, 0x82, ah, 0]
+        );
+
+        let voice = MidiVoice::new(RawMidi(Vec::new())).scale(vec![50]);
+        assert_eq!(voice.note("om"), 50);
+        Ok(())
+    }
+
+    #[test]
+    fn midi_file() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("recitation.mid");
+        let options = Options {
+            mantras: vec![Mantra::from_text("om ah hum")],
+            rate: Duration::from_micros(1),
+            repeats: Some(1),
+            ..Default::default()
+        };
+        let miner = MantraMiner::new(options);
+        miner.set_voice(Some(Box::new(MidiVoice::new(MidiFile::new(&path)))));
+        miner.start()?;
+        miner.wait()?;
+
+        // The file has a header and a track with the tempo, a note on and off for each of the
+        // three syllables, and the end of the track.
+        let contents = fs::read(&path)?;
+        assert_eq!(&contents[..14], b"MThd\0\0\0\x06\0\0\0\x01\x01\xe0");
+        assert_eq!(&contents[14..18], b"MTrk");
+        let track = &contents[22..];
+        assert_eq!(
+            u32::from_be_bytes(contents[18..22].try_into()?) as usize,
+            track.len()
+        );
+        assert_eq!(&track[..7], [0x00, 0xff, 0x51, 0x03, 0x07, 0xa1, 0x20]);
+        assert!(track.ends_with(&[0x00, 0xff, 0x2f, 0x00]));
+        let mut events = &track[7..track.len() - 4];
+        let mut statuses = Vec::new();
+        while !events.is_empty() {
+            let delta = events.iter().position(|byte| byte & 0x80 == 0).unwrap();
+            statuses.push(events[delta + 1]);
+            events = &events[delta + 4..];
+        }
+        assert_eq!(statuses, [0x90, 0x80, 0x90, 0x80, 0x90, 0x80]);
+        Ok(())
+    }
+}
@@ -0,0 +1,275 @@
+//! Defines the UniFFI bindings used to run the mantra miner in Android and iOS apps. The types in
+//! this module mirror the ones in the root of the crate, using only the types UniFFI can pass to
+//! Kotlin and Swift.
+
+use parking_lot::Mutex;
+use std::{sync::Arc, time::Duration};
+
+use crate::{
+    Error as CoreError, Mantra as CoreMantra, MantraMiner as CoreMiner, Options as CoreOptions,
+};
+
+/// An error returned by the mantra miner.
+#[derive(Debug, thiserror::Error, uniffi::Error)]
+#[uniffi(flat_error)]
+pub enum MinerError {
+    /// The options used to configure the mantra miner are invalid.
+    #[error("{0}")]
+    InvalidOptions(String),
+
+    /// The operation requires the mantra miner to be running, but it is stopped.
+    #[error("the mantra miner is not running")]
+    NotRunning,
+
+    /// The operation failed for any other reason.
+    #[error("{0}")]
+    Failed(String),
+}
+
+impl From<CoreError> for MinerError {
+    fn from(error: CoreError) -> Self {
+        match error {
+            CoreError::InvalidOptions(message) => MinerError::InvalidOptions(message),
+            CoreError::NotRunning => MinerError::NotRunning,
+            error => MinerError::Failed(error.to_string()),
+        }
+    }
+}
+
+/// A mantra to be recited by the miner.
+#[derive(Clone, Debug, Eq, PartialEq, uniffi::Record)]
+pub struct Mantra {
+    /// An optional name used to identify the mantra.
+    pub name: Option<String>,
+
+    /// The deity associated with the mantra, if any.
+    pub deity: Option<String>,
+
+    /// The language or script in which the syllables are written.
+    pub language: Option<String>,
+
+    /// The text in which the mantra is found, if known.
+    pub source: Option<String>,
+
+    /// The syllables of the mantra.
+    pub syllables: Vec<String>,
+
+    /// The number of times to repeat the mantra. If it's `None`, the mantra is recited once.
+    pub repeats: Option<u64>,
+}
+
+impl From<Mantra> for CoreMantra {
+    fn from(mantra: Mantra) -> Self {
+        CoreMantra {
+            name: mantra.name,
+            deity: mantra.deity,
+            language: mantra.language,
+            source: mantra.source,
+            syllables: mantra.syllables.into_iter().map(Into::into).collect(),
+            repeats: mantra.repeats.map(|repeats| repeats as usize),
+            weight: None,
+            frequency: None,
+        }
+    }
+}
+
+/// The options used to configure the mantra miner.
+#[derive(Clone, Debug, Eq, PartialEq, uniffi::Record)]
+pub struct Options {
+    /// The preparation recited before the mantras, if any.
+    pub preparation: Option<String>,
+
+    /// The mantras recited in the main body of the sadhana.
+    pub mantras: Vec<Mantra>,
+
+    /// The conclusion recited after the mantras, if any.
+    pub conclusion: Option<String>,
+
+    /// The number of times to repeat the entire sadhana. If it's `None`, the sadhana is repeated
+    /// until the miner is stopped.
+    pub repeats: Option<u64>,
+
+    /// The number of nanoseconds to wait between each syllable or character.
+    pub rate_ns: u64,
+}
+
+impl From<Options> for CoreOptions {
+    fn from(options: Options) -> Self {
+        CoreOptions {
+            preparation: options.preparation,
+            mantras: options.mantras.into_iter().map(CoreMantra::from).collect(),
+            conclusion: options.conclusion,
+            repeats: options.repeats.map(|repeats| repeats as usize),
+            rate: Duration::from_nanos(options.rate_ns),
+            ..Default::default()
+        }
+    }
+}
+
+/// The statistics of the recitations of a single mantra.
+#[derive(Clone, Debug, Eq, PartialEq, uniffi::Record)]
+pub struct MantraStats {
+    /// The name of the mantra, if any.
+    pub name: Option<String>,
+
+    /// The number of completed repetitions of the mantra.
+    pub completed: u64,
+
+    /// The number of syllables of the mantra that have been recited.
+    pub syllables: u64,
+}
+
+/// The statistics of the mantra miner.
+#[derive(Clone, Debug, Eq, PartialEq, uniffi::Record)]
+pub struct MinerStats {
+    /// The number of completed recitations of the entire sadhana.
+    pub sadhanas: u64,
+
+    /// The statistics of each mantra, in the same order as in the options.
+    pub mantras: Vec<MantraStats>,
+}
+
+/// A mantra miner that recites the sadhana in a background thread. Apps should call
+/// `on_background` and `on_foreground` from their lifecycle callbacks, so that the miner does not
+/// keep a thread busy while the app is suspended.
+#[derive(uniffi::Object)]
+pub struct MantraMiner {
+    /// The mantra miner being wrapped.
+    miner: CoreMiner,
+
+    /// Whether the miner was paused because the app moved to the background.
+    paused_in_background: Mutex<bool>,
+}
+
+#[uniffi::export]
+impl MantraMiner {
+    /// Returns a new mantra miner with the given options.
+    #[uniffi::constructor]
+    pub fn new(options: Options) -> Arc<Self> {
+        Arc::new(MantraMiner {
+            miner: CoreMiner::new(options.into()),
+            paused_in_background: Mutex::new(false),
+        })
+    }
+
+    /// Starts running the mantra miner in a background thread.
+    pub fn start(&self) -> Result<(), MinerError> {
+        *self.paused_in_background.lock() = false;
+        Ok(self.miner.start()?)
+    }
+
+    /// Stops the mantra miner and waits for its thread to exit.
+    pub fn stop(&self) -> Result<(), MinerError> {
+        *self.paused_in_background.lock() = false;
+        Ok(self.miner.stop_and_join()?)
+    }
+
+    /// Pauses the mantra miner when the app moves to the background. Does nothing if the miner is
+    /// not running or was already paused.
+    pub fn on_background(&self) {
+        if !self.miner.is_paused() && self.miner.pause().is_ok() {
+            *self.paused_in_background.lock() = true;
+        }
+    }
+
+    /// Resumes the mantra miner when the app returns to the foreground, if it was paused by
+    /// `on_background`.
+    pub fn on_foreground(&self) {
+        let mut paused_in_background = self.paused_in_background.lock();
+        if *paused_in_background {
+            *paused_in_background = false;
+            let _ = self.miner.resume();
+        }
+    }
+
+    /// Returns the number of completed recitations of the entire sadhana.
+    pub fn count(&self) -> u64 {
+        self.miner.count()
+    }
+
+    /// Returns the statistics of the mantra miner.
+    pub fn stats(&self) -> MinerStats {
+        let stats = self.miner.stats();
+        MinerStats {
+            sadhanas: stats.sadhanas,
+            mantras: stats
+                .mantras
+                .into_iter()
+                .map(|mantra| MantraStats {
+                    name: mantra.name,
+                    completed: mantra.completed as u64,
+                    syllables: mantra.syllables as u64,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{thread, time::Duration};
+
+    use crate::mobile::{Mantra, MantraMiner, MinerError, Options};
+
+    fn options(mantras: Vec<Mantra>) -> Options {
+        Options {
+            preparation: None,
+            mantras,
+            conclusion: None,
+            repeats: None,
+            rate_ns: 1000,
+        }
+    }
+
+    fn mantra() -> Mantra {
+        Mantra {
+            name: Some("Tara".to_string()),
+            deity: Some("Green Tara".to_string()),
+            language: None,
+            source: None,
+            syllables: vec!["om".to_string(), "tare".to_string()],
+            repeats: None,
+        }
+    }
+
+    #[test]
+    fn run_miner() {
+        let miner = MantraMiner::new(options(vec![mantra()]));
+        miner.start().unwrap();
+        thread::sleep(Duration::from_millis(100));
+        miner.stop().unwrap();
+
+        let stats = miner.stats();
+        assert!(miner.count() > 0);
+        assert_eq!(stats.sadhanas, miner.count());
+        assert_eq!(stats.mantras[0].name.as_deref(), Some("Tara"));
+        assert!(stats.mantras[0].syllables >= 2 * stats.mantras[0].completed);
+    }
+
+    #[test]
+    fn background_and_foreground() {
+        let miner = MantraMiner::new(options(vec![mantra()]));
+
+        // The lifecycle callbacks do nothing while the miner is not running.
+        miner.on_background();
+        miner.on_foreground();
+
+        miner.start().unwrap();
+        miner.on_background();
+        thread::sleep(Duration::from_millis(10));
+        let count = miner.count();
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(miner.count(), count);
+
+        miner.on_foreground();
+        thread::sleep(Duration::from_millis(50));
+        assert!(miner.count() > count);
+        miner.stop().unwrap();
+    }
+
+    #[test]
+    fn invalid_options() {
+        let miner = MantraMiner::new(options(Vec::new()));
+        assert!(matches!(miner.start(), Err(MinerError::InvalidOptions(_))));
+    }
+}
@@ -0,0 +1,268 @@
+//! Contains the publisher of the events and counters of the mantra miner to an MQTT broker, so that
+//! prayer wheel installations and dashboards can follow the recitation of a machine in real time.
+//! The events are published as JSON as they happen, and the counters are retained so that new
+//! subscribers receive the latest values right away.
+
+use rumqttc::{Client, ConnectionError, MqttOptions, QoS};
+use serde_json::json;
+use std::{
+    io,
+    sync::mpsc::{Receiver, RecvTimeoutError},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use crate::{MantraMiner, MinerEvent, MinerHandle};
+
+/// How often the publisher checks whether the miner is still running when no events are received.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How long the connection to the broker waits before reconnecting after an error.
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// The capacity of the queue of messages waiting to be sent to the broker.
+const QUEUE_CAPACITY: usize = 64;
+
+/// Publishes messages to an MQTT broker.
+pub trait MqttSink: Send {
+    /// Publishes the payload to the given topic, retaining it in the broker if `retain` is true.
+    fn publish(&mut self, topic: &str, payload: Vec<u8>, retain: bool) -> io::Result<()>;
+}
+
+impl MqttSink for Client {
+    fn publish(&mut self, topic: &str, payload: Vec<u8>, retain: bool) -> io::Result<()> {
+        Client::publish(self, topic, QoS::AtLeastOnce, retain, payload).map_err(io::Error::other)
+    }
+}
+
+/// The topics to which `MqttPublisher` publishes.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MqttTopics {
+    /// The topic of the events, each published as a JSON object with an `event` field naming it.
+    pub events: String,
+
+    /// The topic of the count of the miner, published as a number and retained.
+    pub count: String,
+
+    /// The topic of the statistics of the miner, published as JSON and retained.
+    pub stats: String,
+}
+
+impl MqttTopics {
+    /// Returns the topics `events`, `count`, and `stats` under the given prefix, such as
+    /// `temple/wheel-1`.
+    pub fn with_prefix(prefix: &str) -> MqttTopics {
+        MqttTopics {
+            events: format!("{prefix}/events"),
+            count: format!("{prefix}/count"),
+            stats: format!("{prefix}/stats"),
+        }
+    }
+}
+
+impl Default for MqttTopics {
+    /// Returns the topics under the `mantra-miner` prefix.
+    fn default() -> MqttTopics {
+        MqttTopics::with_prefix("mantra-miner")
+    }
+}
+
+/// Publishes the events and counters of a mantra miner to an MQTT broker. The counters are
+/// published each time a mantra or a recitation of the sadhana is completed. The recited syllables
+/// are only published if enabled, since they are sent at the rate of the miner.
+pub struct MqttPublisher<S: MqttSink = Client> {
+    /// The sink to which the messages are published.
+    sink: S,
+
+    /// The topics to which the messages are published.
+    topics: MqttTopics,
+
+    /// Whether to publish an event for each recited syllable.
+    syllables: bool,
+
+    /// The events of the miner.
+    events: Receiver<MinerEvent>,
+
+    /// The handle used to read the counters of the miner and check whether it's still running.
+    handle: MinerHandle,
+}
+
+impl MqttPublisher {
+    /// Connects to the broker with the given options and returns a publisher for the given miner.
+    /// The connection is kept by a background thread, which reconnects after errors and exits once
+    /// the publisher is dropped.
+    pub fn connect(options: MqttOptions, miner: &MantraMiner) -> MqttPublisher {
+        let (client, mut connection) = Client::new(options, QUEUE_CAPACITY);
+        thread::spawn(move || {
+            for notification in connection.iter() {
+                if let Err(err) = notification {
+                    if matches!(err, ConnectionError::RequestsDone) {
+                        break;
+                    }
+                    thread::sleep(RECONNECT_DELAY);
+                }
+            }
+        });
+        MqttPublisher::new(client, miner)
+    }
+}
+
+impl<S: MqttSink + 'static> MqttPublisher<S> {
+    /// Returns a publisher that publishes to the given sink under the default topics. It only
+    /// receives the events emitted after it's created, so it should be created before the miner is
+    /// started.
+    pub fn new(sink: S, miner: &MantraMiner) -> MqttPublisher<S> {
+        MqttPublisher {
+            sink,
+            topics: MqttTopics::default(),
+            syllables: false,
+            events: miner.subscribe(),
+            handle: miner.handle(),
+        }
+    }
+
+    /// Sets the topics to which the messages are published.
+    pub fn topics(mut self, topics: MqttTopics) -> MqttPublisher<S> {
+        self.topics = topics;
+        self
+    }
+
+    /// Sets whether to publish an event for each recited syllable.
+    pub fn syllables(mut self, syllables: bool) -> MqttPublisher<S> {
+        self.syllables = syllables;
+        self
+    }
+
+    /// Publishes the given event, followed by the counters if it completes a mantra or a sadhana.
+    fn publish(&mut self, event: MinerEvent) -> io::Result<()> {
+        let counters = matches!(
+            event,
+            MinerEvent::MantraCompleted { .. } | MinerEvent::SadhanaCompleted { .. }
+        );
+        let payload = match event {
+            MinerEvent::SadhanaStarted { run } => json!({ "event": "sadhana_started", "run": run }),
+            MinerEvent::MantraCompleted {
+                index,
+                name,
+                repeat,
+                credited,
+            } => json!({
+                "event": "mantra_completed",
+                "index": index,
+                "name": name,
+                "repeat": repeat,
+                "credited": credited,
+            }),
+            MinerEvent::SyllableRecited { syllable } if self.syllables => {
+                json!({ "event": "syllable_recited", "syllable": syllable })
+            }
+            MinerEvent::SyllableRecited { .. } => return Ok(()),
+            MinerEvent::SadhanaCompleted { run, count } => {
+                json!({ "event": "sadhana_completed", "run": run, "count": count })
+            }
+        };
+        self.sink
+            .publish(&self.topics.events, payload.to_string().into_bytes(), false)?;
+
+        if counters {
+            let count = self.handle.count().to_string();
+            self.sink
+                .publish(&self.topics.count, count.into_bytes(), true)?;
+            let stats = serde_json::to_vec(&self.handle.stats()).map_err(io::Error::other)?;
+            self.sink.publish(&self.topics.stats, stats, true)?;
+        }
+        Ok(())
+    }
+
+    /// Publishes the events as they are received until the miner stops. Call it after starting
+    /// the miner. Returns the first error returned by the sink, which stops the publisher.
+    pub fn run(mut self) -> io::Result<()> {
+        loop {
+            match self.events.recv_timeout(POLL_INTERVAL) {
+                Ok(event) => self.publish(event)?,
+                Err(RecvTimeoutError::Timeout) if self.handle.is_running() => {}
+                Err(_) => break,
+            }
+        }
+        while let Ok(event) = self.events.try_recv() {
+            self.publish(event)?;
+        }
+        Ok(())
+    }
+
+    /// Runs the publisher in a new thread. Same as `run`.
+    pub fn spawn(self) -> JoinHandle<io::Result<()>> {
+        thread::spawn(move || self.run())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use parking_lot::Mutex;
+    use std::{io, sync::Arc, time::Duration};
+
+    use crate::{Mantra, MantraMiner, MqttPublisher, MqttSink, MqttTopics, Options};
+
+    /// A sink that records the messages it publishes.
+    #[derive(Clone, Default)]
+    struct RecordingSink(Arc<Mutex<Vec<(String, String, bool)>>>);
+
+    impl MqttSink for RecordingSink {
+        fn publish(&mut self, topic: &str, payload: Vec<u8>, retain: bool) -> io::Result<()> {
+            let payload = String::from_utf8(payload).map_err(io::Error::other)?;
+            self.0.lock().push((topic.to_string(), payload, retain));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn publish() -> Result<()> {
+        let options = Options {
+            mantras: vec![Mantra {
+                name: Some("Guru".to_string()),
+                ..Mantra::from_text("om ah")
+            }],
+            rate: Duration::from_micros(1),
+            repeats: Some(1),
+            ..Default::default()
+        };
+        let miner = MantraMiner::new(options);
+        let sink = RecordingSink::default();
+        let publisher = MqttPublisher::new(sink.clone(), &miner)
+            .topics(MqttTopics::with_prefix("temple/wheel"))
+            .syllables(true);
+        miner.start()?;
+        publisher.spawn().join().unwrap()?;
+
+        let messages = sink.0.lock();
+        let events: Vec<&str> = messages
+            .iter()
+            .filter(|(topic, _, _)| topic == "temple/wheel/events")
+            .map(|(_, payload, _)| payload.as_str())
+            .collect();
+        assert_eq!(
+            events,
+            [
+                r#"{"event":"sadhana_started","run":0}"#,
+                r#"{"event":"syllable_recited","syllable":"om"}"#,
+                r#"{"event":"syllable_recited","syllable":"ah"}"#,
+                r#"{"credited":1,"event":"mantra_completed","index":0,"name":"Guru","repeat":0}"#,
+                r#"{"count":1,"event":"sadhana_completed","run":0}"#,
+            ]
+        );
+
+        // The counters are retained, and the last ones are those of the completed sadhana.
+        let (topic, count, retain) = &messages[messages.len() - 2];
+        assert_eq!(
+            (topic.as_str(), count.as_str(), *retain),
+            ("temple/wheel/count", "1", true)
+        );
+        let (topic, stats, retain) = messages.last().unwrap();
+        assert_eq!((topic.as_str(), *retain), ("temple/wheel/stats", true));
+        let stats: serde_json::Value = serde_json::from_str(stats)?;
+        assert_eq!(stats["sadhanas"], 1);
+        assert_eq!(stats["mantras"][0]["completed"], 1);
+        Ok(())
+    }
+}
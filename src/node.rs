@@ -0,0 +1,124 @@
+//! Defines the N-API addon used to run the mantra miner from Node.js and Electron apps.
+
+use napi::{Error as NapiError, Result};
+use napi_derive::napi;
+use std::time::Duration;
+
+use crate::{Error, Mantra, MantraMiner, Options};
+
+impl From<Error> for NapiError {
+    fn from(error: Error) -> Self {
+        NapiError::from_reason(error.to_string())
+    }
+}
+
+/// The options used to configure the mantra miner from JavaScript.
+#[napi(object)]
+pub struct NodeOptions {
+    /// The mantras to recite, each given as text split into syllables on whitespace and hyphens.
+    pub mantras: Vec<String>,
+
+    /// The preparation recited before the mantras, if any.
+    pub preparation: Option<String>,
+
+    /// The conclusion recited after the mantras, if any.
+    pub conclusion: Option<String>,
+
+    /// The number of times to repeat the entire sadhana. If missing, the sadhana is repeated until
+    /// the miner is stopped.
+    pub repeats: Option<u32>,
+
+    /// The number of nanoseconds to wait between each syllable or character.
+    pub rate_ns: i64,
+}
+
+impl TryFrom<NodeOptions> for Options {
+    type Error = NapiError;
+
+    fn try_from(options: NodeOptions) -> Result<Self> {
+        let rate_ns = u64::try_from(options.rate_ns)
+            .map_err(|_| NapiError::from_reason("the rate must not be negative"))?;
+        Ok(Options {
+            preparation: options.preparation,
+            mantras: options
+                .mantras
+                .iter()
+                .map(|text| Mantra::from_text(text))
+                .collect(),
+            conclusion: options.conclusion,
+            repeats: options.repeats.map(|repeats| repeats as usize),
+            rate: Duration::from_nanos(rate_ns),
+            ..Default::default()
+        })
+    }
+}
+
+/// A mantra miner that recites the sadhana in a background thread, exposed to JavaScript as the
+/// `MantraMiner` class.
+#[napi(js_name = "MantraMiner")]
+pub struct NodeMantraMiner {
+    /// The mantra miner being wrapped.
+    miner: MantraMiner,
+}
+
+#[napi]
+impl NodeMantraMiner {
+    /// Returns a new mantra miner with the given options.
+    #[napi(constructor)]
+    pub fn new(options: NodeOptions) -> Result<Self> {
+        Ok(NodeMantraMiner {
+            miner: MantraMiner::new(options.try_into()?),
+        })
+    }
+
+    /// Starts running the mantra miner in a background thread.
+    #[napi]
+    pub fn start(&self) -> Result<()> {
+        Ok(self.miner.start()?)
+    }
+
+    /// Stops the mantra miner and waits for its thread to exit.
+    #[napi]
+    pub fn stop(&self) -> Result<()> {
+        Ok(self.miner.stop_and_join()?)
+    }
+
+    /// Returns the number of completed recitations of the entire sadhana.
+    #[napi]
+    pub fn count(&self) -> i64 {
+        self.miner.count() as i64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{thread, time::Duration};
+
+    use crate::node::{NodeMantraMiner, NodeOptions};
+
+    fn options(mantras: Vec<String>, rate_ns: i64) -> NodeOptions {
+        NodeOptions {
+            mantras,
+            preparation: None,
+            conclusion: None,
+            repeats: None,
+            rate_ns,
+        }
+    }
+
+    #[test]
+    fn run_miner() {
+        let miner = NodeMantraMiner::new(options(vec!["om ah hum".to_string()], 1000)).unwrap();
+        miner.start().unwrap();
+        thread::sleep(Duration::from_millis(100));
+        miner.stop().unwrap();
+        assert!(miner.count() > 0);
+    }
+
+    #[test]
+    fn invalid_options() {
+        assert!(NodeMantraMiner::new(options(vec!["om".to_string()], -1)).is_err());
+        let miner = NodeMantraMiner::new(options(Vec::new(), 1000)).unwrap();
+        assert!(miner.start().is_err());
+    }
+}
@@ -0,0 +1,133 @@
+//! Contains the Unicode normalization of the texts in the options, so that a mantra typed with
+//! precomposed characters and the same mantra typed with combining marks are recited, counted, and
+//! measured the same way.
+
+use alloc::{string::String, sync::Arc};
+use unicode_normalization::{is_nfc, is_nfd, UnicodeNormalization};
+
+use crate::{sadhana::Section, Normalization, Options};
+
+/// Returns the given text in the given normalization form, or `None` if it's already in it.
+fn normalize(text: &str, form: Normalization) -> Option<String> {
+    match form {
+        Normalization::Nfc if !is_nfc(text) => Some(text.nfc().collect()),
+        Normalization::Nfd if !is_nfd(text) => Some(text.nfd().collect()),
+        _ => None,
+    }
+}
+
+/// Replaces the given text with its normalized form, if it's not normalized already.
+fn normalize_text(text: &mut String, form: Normalization) {
+    if let Some(normalized) = normalize(text, form) {
+        *text = normalized;
+    }
+}
+
+/// Normalizes the texts of the given sections and of the sections of their groups.
+fn normalize_sections(sections: &mut [Section], form: Normalization) {
+    for section in sections {
+        match section {
+            Section::Text { text, .. } => normalize_text(text, form),
+            Section::Group { sections, .. } => normalize_sections(sections, form),
+            Section::Mantra { .. } | Section::Silence { .. } => {}
+        }
+    }
+}
+
+/// Returns the options with the syllables of their mantras, their preparation and conclusion, and
+/// the texts of their sections in the normalization form set in them. The syllables of a mantra
+/// that are already normalized keep being shared with the options they were cloned from.
+pub(crate) fn normalized(mut options: Options) -> Options {
+    let form = options.normalization;
+    for mantra in &mut options.mantras {
+        if mantra
+            .syllables
+            .iter()
+            .all(|syllable| normalize(syllable, form).is_none())
+        {
+            continue;
+        }
+        mantra.syllables = mantra
+            .syllables
+            .iter()
+            .map(|syllable| normalize(syllable, form).map_or_else(|| syllable.clone(), Arc::from))
+            .collect();
+    }
+    for text in [&mut options.preparation, &mut options.conclusion]
+        .into_iter()
+        .flatten()
+    {
+        normalize_text(text, form);
+    }
+    normalize_sections(&mut options.sections, form);
+    options
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::ToString, sync::Arc, vec};
+
+    use crate::{normalize::normalized, sadhana::Section, Mantra, Normalization, Options};
+
+    #[test]
+    fn normalize() {
+        // The same syllable typed with a precomposed character and with a combining mark.
+        let precomposed = "\u{e9}";
+        let combining = "e\u{301}";
+        let options = Options {
+            preparation: Some(combining.to_string()),
+            mantras: vec![
+                Mantra::from_text(&format!("om {combining}")),
+                Mantra::from_text("hri"),
+            ],
+            sections: vec![Section::Group {
+                sections: vec![Section::Text {
+                    text: combining.to_string(),
+                    repeats: None,
+                    rate: None,
+                    frequency: None,
+                }],
+                repeats: None,
+                frequency: None,
+            }],
+            ..Default::default()
+        };
+
+        let nfc = normalized(options.clone());
+        assert_eq!(nfc.preparation.as_deref(), Some(precomposed));
+        assert_eq!(
+            *nfc.mantras[0].syllables,
+            ["om", precomposed].map(Arc::<str>::from)
+        );
+        let Section::Group { sections, .. } = &nfc.sections[0] else {
+            panic!("expected a group");
+        };
+        assert_eq!(
+            sections[0],
+            Section::Text {
+                text: precomposed.to_string(),
+                repeats: None,
+                rate: None,
+                frequency: None,
+            }
+        );
+
+        // The syllables that are already normalized are still shared.
+        assert!(Arc::ptr_eq(
+            &nfc.mantras[1].syllables,
+            &options.mantras[1].syllables
+        ));
+
+        // The texts can be decomposed instead, or left as they are.
+        let nfd = normalized(Options {
+            normalization: Normalization::Nfd,
+            ..nfc.clone()
+        });
+        assert_eq!(nfd.preparation.as_deref(), Some(combining));
+        let unchanged = normalized(Options {
+            normalization: Normalization::Unchanged,
+            ..nfc
+        });
+        assert_eq!(unchanged.preparation.as_deref(), Some(precomposed));
+    }
+}
@@ -0,0 +1,170 @@
+//! Contains the OpenTelemetry instruments of the mantra miner, available with the `otel` feature.
+//! The counters and spans are recorded with the global meter and tracer providers, so they are
+//! exported by whatever pipeline the host program installs, and are dropped if it installs none.
+
+use opentelemetry::{
+    global::{self, BoxedSpan, BoxedTracer},
+    metrics::{Counter, Meter},
+    trace::{Span, Tracer},
+    KeyValue,
+};
+use std::sync::Arc;
+
+/// The name of the instrumentation scope of the meter and tracer.
+const SCOPE: &str = "mantra-miner";
+
+/// The counters and the tracer updated as the mantra miner recites the sadhana.
+#[derive(Clone)]
+pub(crate) struct Instruments {
+    /// The tracer that starts the span of each sadhana.
+    tracer: Arc<BoxedTracer>,
+
+    /// The number of completed recitations of the sadhana.
+    sadhanas: Counter<u64>,
+
+    /// The number of completed repetitions of each mantra.
+    mantras: Counter<u64>,
+
+    /// The number of syllables and characters recited.
+    syllables: Counter<u64>,
+}
+
+impl Default for Instruments {
+    fn default() -> Self {
+        Instruments::new(&global::meter(SCOPE), global::tracer(SCOPE))
+    }
+}
+
+impl Instruments {
+    /// Returns the instruments created with the given meter and tracer.
+    fn new(meter: &Meter, tracer: BoxedTracer) -> Self {
+        Instruments {
+            tracer: Arc::new(tracer),
+            sadhanas: meter
+                .u64_counter("mantra_miner.sadhanas")
+                .with_description("The number of completed recitations of the sadhana.")
+                .build(),
+            mantras: meter
+                .u64_counter("mantra_miner.mantras")
+                .with_description("The number of completed repetitions of each mantra.")
+                .build(),
+            syllables: meter
+                .u64_counter("mantra_miner.syllables")
+                .with_description("The number of syllables and characters recited.")
+                .build(),
+        }
+    }
+
+    /// Records that a syllable or character has been recited.
+    pub(crate) fn record_syllable(&self) {
+        self.syllables.add(1, &[]);
+    }
+
+    /// Records that a repetition of the mantra with the given label has been completed.
+    pub(crate) fn record_mantra(&self, label: String, repetitions: u64) {
+        self.mantras
+            .add(repetitions, &[KeyValue::new("mantra", label)]);
+    }
+
+    /// Records that a recitation of the sadhana has been completed.
+    pub(crate) fn record_sadhana(&self) {
+        self.sadhanas.add(1, &[]);
+    }
+
+    /// Starts the span that covers the recitation of the sadhana in the given run. The span ends
+    /// when it's dropped.
+    pub(crate) fn sadhana_span(&self, run: usize) -> BoxedSpan {
+        let mut span = self.tracer.start("sadhana");
+        span.set_attribute(KeyValue::new("run", run as i64));
+        span
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use opentelemetry::{
+        global::BoxedTracer, metrics::MeterProvider, trace::TracerProvider, KeyValue, Value,
+    };
+    use opentelemetry_sdk::{
+        metrics::{
+            data::{AggregatedMetrics, MetricData},
+            InMemoryMetricExporter, PeriodicReader, SdkMeterProvider,
+        },
+        trace::{InMemorySpanExporter, SdkTracerProvider},
+    };
+    use std::time::Duration;
+
+    use crate::{otel::Instruments, Mantra, MantraMiner, Options};
+
+    #[test]
+    fn export_spans_and_counters() -> Result<()> {
+        let span_exporter = InMemorySpanExporter::default();
+        let tracer_provider = SdkTracerProvider::builder()
+            .with_simple_exporter(span_exporter.clone())
+            .build();
+        let metric_exporter = InMemoryMetricExporter::default();
+        let meter_provider = SdkMeterProvider::builder()
+            .with_reader(PeriodicReader::builder(metric_exporter.clone()).build())
+            .build();
+
+        let options = Options {
+            mantras: vec![Mantra {
+                name: Some("mani".to_string()),
+                repeats: Some(3),
+                ..Mantra::from_text("om mani")
+            }],
+            rate: Duration::from_micros(1),
+            repeats: Some(2),
+            ..Default::default()
+        };
+        let mut miner = MantraMiner::new(options);
+        miner.state.otel = Instruments::new(
+            &meter_provider.meter("mantra-miner"),
+            BoxedTracer::new(Box::new(tracer_provider.tracer("mantra-miner"))),
+        );
+        miner.recite_n(2)?;
+        meter_provider.force_flush()?;
+
+        // Each sadhana is exported as a span with the number of its run.
+        let runs: Vec<Option<Value>> = span_exporter
+            .get_finished_spans()?
+            .iter()
+            .filter(|span| span.name == "sadhana")
+            .map(|span| {
+                span.attributes
+                    .iter()
+                    .find(|attribute| attribute.key.as_str() == "run")
+                    .map(|attribute| attribute.value.clone())
+            })
+            .collect();
+        assert_eq!(runs, vec![Some(Value::I64(0)), Some(Value::I64(1))]);
+
+        // The counters hold the totals of the recitation, with the mantras counted by name.
+        let metrics = metric_exporter.get_finished_metrics()?;
+        let sum = |name: &str| -> Vec<(u64, Vec<KeyValue>)> {
+            let Some(resource_metrics) = metrics.last() else {
+                return Vec::new();
+            };
+            resource_metrics
+                .scope_metrics()
+                .flat_map(|scope| scope.metrics())
+                .filter(|metric| metric.name() == name)
+                .flat_map(|metric| match metric.data() {
+                    AggregatedMetrics::U64(MetricData::Sum(sum)) => sum
+                        .data_points()
+                        .map(|point| (point.value(), point.attributes().cloned().collect()))
+                        .collect(),
+                    _ => Vec::new(),
+                })
+                .collect()
+        };
+        assert_eq!(sum("mantra_miner.sadhanas"), vec![(2, Vec::new())]);
+        assert_eq!(sum("mantra_miner.syllables"), vec![(12, Vec::new())]);
+        assert_eq!(
+            sum("mantra_miner.mantras"),
+            vec![(6, vec![KeyValue::new("mantra", "mani")])]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,481 @@
+//! Contains the playlists of the mantra miner, which cycle through several named sadhanas, either
+//! one after the other or following a daily schedule, and keep count of the recitations of each of
+//! them.
+
+use serde::{Deserialize, Serialize};
+use std::{fmt, fs, path::Path};
+
+use crate::{Error, Options, Result};
+
+/// A time of day in the local time zone, written as `"HH:MM"` in playlist files.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct TimeOfDay {
+    /// The hour, from 0 to 23.
+    pub hour: u8,
+
+    /// The minute, from 0 to 59.
+    pub minute: u8,
+}
+
+impl TimeOfDay {
+    /// Returns the number of minutes since midnight.
+    fn minutes(self) -> u32 {
+        u32::from(self.hour) * 60 + u32::from(self.minute)
+    }
+}
+
+impl fmt::Display for TimeOfDay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02}:{:02}", self.hour, self.minute)
+    }
+}
+
+impl TryFrom<String> for TimeOfDay {
+    type Error = String;
+
+    fn try_from(value: String) -> std::result::Result<TimeOfDay, String> {
+        let invalid = || format!("invalid time of day {:?}, expected HH:MM", value);
+        let (hour, minute) = value.split_once(':').ok_or_else(invalid)?;
+        let hour: u8 = hour.trim().parse().map_err(|_| invalid())?;
+        let minute: u8 = minute.trim().parse().map_err(|_| invalid())?;
+        if hour > 23 || minute > 59 {
+            return Err(invalid());
+        }
+        Ok(TimeOfDay { hour, minute })
+    }
+}
+
+impl From<TimeOfDay> for String {
+    fn from(time: TimeOfDay) -> String {
+        time.to_string()
+    }
+}
+
+/// The order in which a playlist moves through its sadhanas.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlaylistOrder {
+    /// Each sadhana is recited the number of times set in its `recitations` before moving on to
+    /// the next one, starting over with the first after the last.
+    #[default]
+    Sequential,
+
+    /// Each sadhana is recited from its `starts_at` time until the next sadhana starts, wrapping
+    /// around midnight. The playlist checks the time before each recitation of a sadhana.
+    Schedule,
+}
+
+/// A sadhana of a playlist, identified by its name.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct PlaylistSadhana {
+    /// The name of the sadhana, which must be unique in the playlist.
+    pub name: String,
+
+    /// The number of consecutive recitations of the sadhana before moving on to the next one when
+    /// the playlist is sequential. If it's `None`, it's recited once.
+    #[serde(default)]
+    pub recitations: Option<usize>,
+
+    /// The time of day at which the sadhana starts when the playlist follows a schedule.
+    #[serde(default)]
+    pub starts_at: Option<TimeOfDay>,
+
+    /// The options describing the sadhana, written in the same table as the name in playlist
+    /// files. Their `repeats` are ignored, since the playlist decides when to move on.
+    #[serde(flatten)]
+    pub options: Options,
+}
+
+/// A list of named sadhanas that the mantra miner cycles through, set with
+/// `MantraMiner::with_playlist`. In TOML format, a playlist looks like:
+///
+/// ```toml
+/// order = "sequential"
+///
+/// [[sadhanas]]
+/// name = "Chenrezig"
+/// recitations = 3
+///
+/// [[sadhanas.mantras]]
+/// syllables = ["om", "ma", "ni", "pad", "me", "hum"]
+/// repeats = 108
+///
+/// [[sadhanas]]
+/// name = "Tara"
+///
+/// [[sadhanas.mantras]]
+/// syllables = ["om", "ta", "re", "tu", "ta", "re", "tu", "re", "so", "ha"]
+/// repeats = 21
+/// ```
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct Playlist {
+    /// The order in which the sadhanas are recited.
+    pub order: PlaylistOrder,
+
+    /// The sadhanas of the playlist.
+    pub sadhanas: Vec<PlaylistSadhana>,
+}
+
+impl Playlist {
+    /// Parses the playlist from a string in TOML format. The options of each sadhana are written
+    /// as in [`Options::from_toml_str`].
+    pub fn from_toml_str(contents: &str) -> Result<Playlist> {
+        Ok(toml::from_str(contents)?)
+    }
+
+    /// Reads and parses the playlist from the file in TOML format at the given path.
+    pub fn from_toml_file<P: AsRef<Path>>(path: P) -> Result<Playlist> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path).map_err(|source| Error::ReadFailed {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        Self::from_toml_str(&contents)
+    }
+
+    /// Checks that the playlist can be recited. It must have at least one sadhana, the names of
+    /// the sadhanas must be unique, their options must pass `Options::validate` when repeated
+    /// indefinitely, and their number of recitations must be greater than zero. When the playlist
+    /// follows a schedule, every sadhana must have a start time. Returns `Error::InvalidOptions`
+    /// describing the first problem found.
+    pub fn validate(&self) -> Result<()> {
+        if self.sadhanas.is_empty() {
+            return Err(Error::InvalidOptions(
+                "the playlist has no sadhanas".to_string(),
+            ));
+        }
+
+        for (index, sadhana) in self.sadhanas.iter().enumerate() {
+            let name = &sadhana.name;
+            if self.sadhanas[..index]
+                .iter()
+                .any(|other| other.name == *name)
+            {
+                return Err(Error::InvalidOptions(format!(
+                    "the playlist has more than one sadhana named {:?}",
+                    name
+                )));
+            }
+            if sadhana.recitations == Some(0) {
+                return Err(Error::InvalidOptions(format!(
+                    "the number of recitations of the sadhana {:?} must be greater than zero",
+                    name
+                )));
+            }
+            if self.order == PlaylistOrder::Schedule && sadhana.starts_at.is_none() {
+                return Err(Error::InvalidOptions(format!(
+                    "the sadhana {:?} has no start time",
+                    name
+                )));
+            }
+            match sadhana.playlist_options().validate() {
+                Err(Error::InvalidOptions(message)) => {
+                    return Err(Error::InvalidOptions(format!(
+                        "the sadhana {:?} is invalid: {}",
+                        name, message
+                    )));
+                }
+                result => result?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the index of the sadhana scheduled at the given number of minutes since midnight,
+    /// which is the one that started last, or the last one to start if none has started yet
+    /// today.
+    fn scheduled(&self, minute: u32) -> usize {
+        let starts = |(_, sadhana): &(usize, &PlaylistSadhana)| {
+            sadhana.starts_at.map_or(0, TimeOfDay::minutes)
+        };
+        let sadhanas = || self.sadhanas.iter().enumerate();
+        sadhanas()
+            .filter(|entry| starts(entry) <= minute)
+            .max_by_key(starts)
+            .or_else(|| sadhanas().max_by_key(starts))
+            .map_or(0, |(index, _)| index)
+    }
+
+    /// Returns the index of the sadhana to recite next, given the index of the current one, the
+    /// number of its recitations completed in a row, and the number of minutes since midnight.
+    fn next(&self, current: usize, in_row: usize, minute: u32) -> usize {
+        match self.order {
+            PlaylistOrder::Sequential => {
+                let recitations = self
+                    .sadhanas
+                    .get(current)
+                    .map_or(1, |sadhana| sadhana.recitations.unwrap_or(1));
+                if in_row >= recitations {
+                    (current + 1) % self.sadhanas.len().max(1)
+                } else {
+                    current
+                }
+            }
+            PlaylistOrder::Schedule => self.scheduled(minute),
+        }
+    }
+}
+
+impl PlaylistSadhana {
+    /// Returns the options of the sadhana as recited by the playlist, which repeats it
+    /// indefinitely.
+    pub(crate) fn playlist_options(&self) -> Options {
+        Options {
+            repeats: None,
+            ..self.options.clone()
+        }
+    }
+}
+
+/// The statistics of a sadhana of the playlist.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct SadhanaStats {
+    /// The name of the sadhana.
+    pub name: String,
+
+    /// The number of completed recitations of the sadhana.
+    pub completed: u64,
+}
+
+/// The position of the mantra miner within its playlist.
+pub(crate) struct PlaylistState {
+    /// The playlist.
+    playlist: Playlist,
+
+    /// The index of the sadhana being recited.
+    current: usize,
+
+    /// The number of recitations of the current sadhana completed since the playlist moved to it.
+    in_row: usize,
+
+    /// The statistics of each sadhana, in the same order as in the playlist.
+    stats: Vec<SadhanaStats>,
+}
+
+impl PlaylistState {
+    /// Returns the state of a playlist that starts with its first sadhana.
+    pub(crate) fn new(playlist: Playlist) -> PlaylistState {
+        let stats = playlist
+            .sadhanas
+            .iter()
+            .map(|sadhana| SadhanaStats {
+                name: sadhana.name.clone(),
+                completed: 0,
+            })
+            .collect();
+        PlaylistState {
+            playlist,
+            current: 0,
+            in_row: 0,
+            stats,
+        }
+    }
+
+    /// Returns the playlist.
+    pub(crate) fn playlist(&self) -> &Playlist {
+        &self.playlist
+    }
+
+    /// Moves to the sadhana to recite next at the given number of minutes since midnight, and
+    /// returns its options if it's not the one being recited.
+    pub(crate) fn advance(&mut self, minute: u32) -> Option<Options> {
+        let next = self.playlist.next(self.current, self.in_row, minute);
+        if next == self.current {
+            return None;
+        }
+        self.current = next;
+        self.in_row = 0;
+        self.playlist
+            .sadhanas
+            .get(next)
+            .map(PlaylistSadhana::playlist_options)
+    }
+
+    /// Records that a recitation of the current sadhana has been completed.
+    pub(crate) fn record_sadhana(&mut self) {
+        self.in_row += 1;
+        if let Some(stats) = self.stats.get_mut(self.current) {
+            stats.completed += 1;
+        }
+    }
+
+    /// Returns the name of the sadhana being recited.
+    pub(crate) fn current_name(&self) -> Option<&str> {
+        self.playlist
+            .sadhanas
+            .get(self.current)
+            .map(|sadhana| sadhana.name.as_str())
+    }
+
+    /// Returns the statistics of each sadhana.
+    pub(crate) fn stats(&self) -> &[SadhanaStats] {
+        &self.stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use std::{sync::Arc, time::Duration};
+
+    use crate::{
+        playlist::{Playlist, PlaylistOrder, PlaylistSadhana, PlaylistState, TimeOfDay},
+        Error, Mantra, Options,
+    };
+
+    /// Returns a sadhana of the given name that recites a single mantra.
+    fn sadhana(name: &str, starts_at: Option<TimeOfDay>) -> PlaylistSadhana {
+        PlaylistSadhana {
+            name: name.to_string(),
+            recitations: None,
+            starts_at,
+            options: Options {
+                mantras: vec![Mantra::from_text(name)],
+                rate: Duration::from_millis(1),
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Returns the given time of day.
+    fn at(hour: u8, minute: u8) -> Option<TimeOfDay> {
+        Some(TimeOfDay { hour, minute })
+    }
+
+    #[test]
+    fn time_of_day() {
+        assert_eq!(
+            TimeOfDay::try_from("06:30".to_string()),
+            Ok(at(6, 30).unwrap())
+        );
+        assert_eq!(at(6, 5).unwrap().to_string(), "06:05");
+        assert!(TimeOfDay::try_from("24:00".to_string()).is_err());
+        assert!(TimeOfDay::try_from("6".to_string()).is_err());
+    }
+
+    #[test]
+    fn parse_playlist() -> Result<()> {
+        let playlist = Playlist::from_toml_str(
+            r#"
+            order = "schedule"
+
+            [[sadhanas]]
+            name = "Morning"
+            starts_at = "06:00"
+            rate = "2ms"
+
+            [[sadhanas.mantras]]
+            syllables = ["om", "ah", "hum"]
+
+            [[sadhanas]]
+            name = "Evening"
+            starts_at = "18:30"
+            recitations = 2
+
+            [[sadhanas.mantras]]
+            syllables = ["hri"]
+            "#,
+        )?;
+        assert_eq!(playlist.order, PlaylistOrder::Schedule);
+        assert_eq!(playlist.sadhanas.len(), 2);
+        assert_eq!(playlist.sadhanas[0].name, "Morning");
+        assert_eq!(playlist.sadhanas[0].options.rate, Duration::from_millis(2));
+        assert_eq!(playlist.sadhanas[1].starts_at, at(18, 30));
+        assert_eq!(playlist.sadhanas[1].recitations, Some(2));
+        assert_eq!(
+            *playlist.sadhanas[1].options.mantras[0].syllables,
+            ["hri"].map(Arc::<str>::from)
+        );
+
+        // The playlist is written back in the same format.
+        let contents = toml::to_string(&playlist)?;
+        assert_eq!(Playlist::from_toml_str(&contents)?, playlist);
+        Ok(())
+    }
+
+    #[test]
+    fn validate() {
+        let valid = Playlist {
+            order: PlaylistOrder::Sequential,
+            sadhanas: vec![sadhana("om", None), sadhana("hri", None)],
+        };
+        assert!(valid.validate().is_ok());
+
+        let invalid = [
+            Playlist::default(),
+            Playlist {
+                sadhanas: vec![sadhana("om", None), sadhana("om", None)],
+                ..valid.clone()
+            },
+            Playlist {
+                order: PlaylistOrder::Schedule,
+                ..valid.clone()
+            },
+            Playlist {
+                sadhanas: vec![PlaylistSadhana {
+                    recitations: Some(0),
+                    ..sadhana("om", None)
+                }],
+                ..valid.clone()
+            },
+            Playlist {
+                sadhanas: vec![PlaylistSadhana {
+                    options: Options::default(),
+                    ..sadhana("om", None)
+                }],
+                ..valid.clone()
+            },
+        ];
+        for playlist in invalid {
+            assert!(matches!(playlist.validate(), Err(Error::InvalidOptions(_))));
+        }
+    }
+
+    #[test]
+    fn sequential() {
+        let mut playlist = Playlist {
+            order: PlaylistOrder::Sequential,
+            sadhanas: vec![sadhana("om", None), sadhana("hri", None)],
+        };
+        playlist.sadhanas[0].recitations = Some(2);
+        let mut state = PlaylistState::new(playlist);
+        assert_eq!(state.current_name(), Some("om"));
+
+        let mut recited = Vec::new();
+        for _ in 0..5 {
+            if let Some(options) = state.advance(0) {
+                assert_eq!(options.repeats, None);
+            }
+            recited.push(state.current_name().unwrap().to_string());
+            state.record_sadhana();
+        }
+        assert_eq!(recited, ["om", "om", "hri", "om", "om"]);
+        assert_eq!(state.stats()[0].completed, 4);
+        assert_eq!(state.stats()[1].completed, 1);
+    }
+
+    #[test]
+    fn schedule() {
+        let playlist = Playlist {
+            order: PlaylistOrder::Schedule,
+            sadhanas: vec![sadhana("evening", at(18, 0)), sadhana("morning", at(6, 0))],
+        };
+
+        // Before the first sadhana of the day, the last one of the previous day continues.
+        assert_eq!(playlist.scheduled(5 * 60), 0);
+        assert_eq!(playlist.scheduled(6 * 60), 1);
+        assert_eq!(playlist.scheduled(12 * 60), 1);
+        assert_eq!(playlist.scheduled(23 * 60), 0);
+
+        let mut state = PlaylistState::new(playlist);
+        assert!(state.advance(5 * 60).is_none());
+        let options = state.advance(7 * 60).unwrap();
+        assert_eq!(
+            *options.mantras[0].syllables,
+            ["morning"].map(Arc::<str>::from)
+        );
+        assert_eq!(state.current_name(), Some("morning"));
+    }
+}
@@ -0,0 +1,210 @@
+//! Contains the power monitors used to pause the mantra miner while a laptop runs on battery. The
+//! state of the power supply is read from sysfs on Linux and from `GetSystemPowerStatus` on
+//! Windows. Other platforms report no battery, and host programs can provide their own monitor.
+
+use std::time::{Duration, Instant};
+
+use crate::PowerPolicy;
+
+/// The interval at which the power supply is sampled.
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// The state of the power supply of the machine.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct PowerStatus {
+    /// Whether the machine is running on battery rather than on AC power.
+    pub on_battery: bool,
+
+    /// The charge of the battery in percent, if known.
+    pub charge_percent: Option<u8>,
+}
+
+/// Reads the state of the power supply, so that the mantra miner can pause while running on
+/// battery.
+pub trait PowerMonitor: Send {
+    /// Returns the current state of the power supply, or `None` if it's unknown or the machine
+    /// has no battery.
+    fn status(&mut self) -> Option<PowerStatus>;
+}
+
+impl<F: FnMut() -> Option<PowerStatus> + Send> PowerMonitor for F {
+    fn status(&mut self) -> Option<PowerStatus> {
+        self()
+    }
+}
+
+/// A power monitor that reads the state of the power supply from the operating system. This is
+/// the default power monitor of the mantra miner.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemPower;
+
+impl PowerMonitor for SystemPower {
+    #[cfg(target_os = "linux")]
+    fn status(&mut self) -> Option<PowerStatus> {
+        use std::fs;
+
+        // The first battery found is used, and the machine is on battery while it discharges.
+        for entry in fs::read_dir("/sys/class/power_supply").ok()?.flatten() {
+            let path = entry.path();
+            let read = |name: &str| {
+                fs::read_to_string(path.join(name))
+                    .ok()
+                    .map(|value| value.trim().to_string())
+            };
+            if read("type").as_deref() != Some("Battery") {
+                continue;
+            }
+            return Some(PowerStatus {
+                on_battery: read("status").as_deref() == Some("Discharging"),
+                charge_percent: read("capacity").and_then(|capacity| capacity.parse().ok()),
+            });
+        }
+        None
+    }
+
+    #[cfg(windows)]
+    fn status(&mut self) -> Option<PowerStatus> {
+        use windows_sys::Win32::System::Power::{GetSystemPowerStatus, SYSTEM_POWER_STATUS};
+
+        /// The battery flag set when the machine has no battery.
+        const NO_BATTERY: u8 = 128;
+
+        let mut status = SYSTEM_POWER_STATUS::default();
+        // SAFETY: The pointer refers to a valid structure that the call fills in.
+        if unsafe { GetSystemPowerStatus(&mut status) } == 0 || status.BatteryFlag & NO_BATTERY != 0
+        {
+            return None;
+        }
+        Some(PowerStatus {
+            on_battery: status.ACLineStatus == 0,
+            // The charge is 255 when unknown.
+            charge_percent: (status.BatteryLifePercent <= 100).then_some(status.BatteryLifePercent),
+        })
+    }
+
+    #[cfg(not(any(target_os = "linux", windows)))]
+    fn status(&mut self) -> Option<PowerStatus> {
+        None
+    }
+}
+
+impl PowerPolicy {
+    /// Returns whether the miner should pause with the power supply in the given state. The miner
+    /// keeps running if the state is unknown.
+    pub fn should_pause(&self, status: Option<PowerStatus>) -> bool {
+        let Some(status) = status.filter(|status| status.on_battery) else {
+            return false;
+        };
+        match self {
+            PowerPolicy::Ignore => false,
+            PowerPolicy::PauseOnBattery => true,
+            PowerPolicy::PauseBelowCharge(threshold) => status
+                .charge_percent
+                .is_some_and(|charge| charge < *threshold),
+        }
+    }
+}
+
+/// Samples a power monitor at regular intervals to decide whether the recitation should pause.
+pub(crate) struct PowerSampler {
+    /// The time of the last sample.
+    last_sample: Option<Instant>,
+
+    /// The interval at which the power supply is sampled.
+    pub(crate) interval: Duration,
+
+    /// Whether the last sample called for a pause.
+    paused: bool,
+}
+
+impl Default for PowerSampler {
+    fn default() -> Self {
+        PowerSampler {
+            last_sample: None,
+            interval: SAMPLE_INTERVAL,
+            paused: false,
+        }
+    }
+}
+
+impl PowerSampler {
+    /// Returns whether the recitation should pause under the given policy, sampling the given
+    /// monitor if the last sample is too old. The monitor is not sampled if the policy ignores
+    /// the power supply.
+    pub(crate) fn should_pause(
+        &mut self,
+        policy: PowerPolicy,
+        monitor: &mut dyn PowerMonitor,
+    ) -> bool {
+        if policy == PowerPolicy::Ignore {
+            return false;
+        }
+        let now = Instant::now();
+        if self
+            .last_sample
+            .is_none_or(|last| now.saturating_duration_since(last) >= self.interval)
+        {
+            self.paused = policy.should_pause(monitor.status());
+            self.last_sample = Some(now);
+        }
+        self.paused
+    }
+
+    /// Returns the interval after which the power supply should be checked again while paused.
+    pub(crate) fn interval(&self) -> Duration {
+        self.interval
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{
+        power::{PowerSampler, PowerStatus, SystemPower},
+        PowerMonitor, PowerPolicy,
+    };
+
+    const BATTERY: Option<PowerStatus> = Some(PowerStatus {
+        on_battery: true,
+        charge_percent: Some(50),
+    });
+    const AC: Option<PowerStatus> = Some(PowerStatus {
+        on_battery: false,
+        charge_percent: Some(50),
+    });
+
+    #[test]
+    fn policies() {
+        assert!(!PowerPolicy::Ignore.should_pause(BATTERY));
+        assert!(PowerPolicy::PauseOnBattery.should_pause(BATTERY));
+        assert!(!PowerPolicy::PauseOnBattery.should_pause(AC));
+        assert!(!PowerPolicy::PauseOnBattery.should_pause(None));
+        assert!(PowerPolicy::PauseBelowCharge(60).should_pause(BATTERY));
+        assert!(!PowerPolicy::PauseBelowCharge(40).should_pause(BATTERY));
+        assert!(!PowerPolicy::PauseBelowCharge(60).should_pause(AC));
+    }
+
+    #[test]
+    fn sampler() {
+        let mut sampler = PowerSampler {
+            interval: Duration::ZERO,
+            ..Default::default()
+        };
+        assert!(sampler.should_pause(PowerPolicy::PauseOnBattery, &mut || BATTERY));
+        assert!(!sampler.should_pause(PowerPolicy::PauseOnBattery, &mut || AC));
+        assert!(!sampler.should_pause(PowerPolicy::Ignore, &mut || BATTERY));
+        assert!(sampler.should_pause(PowerPolicy::PauseOnBattery, &mut || BATTERY));
+
+        // The power supply is not sampled again until the interval has elapsed.
+        sampler.interval = Duration::from_secs(60);
+        assert!(sampler.should_pause(PowerPolicy::PauseOnBattery, &mut || AC));
+    }
+
+    #[test]
+    fn system_power() {
+        if let Some(status) = SystemPower.status() {
+            assert!(status.charge_percent.is_none_or(|charge| charge <= 100));
+        }
+    }
+}
@@ -0,0 +1,74 @@
+//! Contains traditional prayers recited before and after the mantras, ready to be used as the
+//! preparation and conclusion of a sadhana. For example, `prayers::refuge(Language::English)` can
+//! be used as the preparation, and `prayers::dedication(Language::English)` as the conclusion.
+
+use alloc::string::{String, ToString};
+
+/// The language in which a prayer is written.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Language {
+    /// An English translation.
+    English,
+
+    /// The Tibetan text, transliterated phonetically into the Latin alphabet.
+    Tibetan,
+
+    /// The Sanskrit text, transliterated into the Latin alphabet without diacritics.
+    Sanskrit,
+}
+
+/// Returns the prayer of refuge and bodhicitta, which takes refuge in the Three Jewels and
+/// generates the wish to attain enlightenment for the benefit of all beings.
+pub fn refuge(language: Language) -> String {
+    match language {
+        Language::English => {
+            "I take refuge until I am enlightened in the Buddha, the Dharma, and the Supreme \
+             Assembly. By the merit of generosity and the other perfections, may I attain \
+             Buddhahood for the benefit of all beings."
+        }
+        Language::Tibetan => {
+            "sang gye cho dang tsok kyi chok nam la jang chub bar du dak ni kyab su chi dak gi \
+             jin sok gyi pe so nam kyi dro la pen chir sang gye drub par shok"
+        }
+        Language::Sanskrit => {
+            "buddham sharanam gacchami dharmam sharanam gacchami sangham sharanam gacchami"
+        }
+    }
+    .to_string()
+}
+
+/// Returns the prayer of dedication, which dedicates the merit of the practice to the
+/// enlightenment of all beings.
+pub fn dedication(language: Language) -> String {
+    match language {
+        Language::English => {
+            "By this merit may all beings obtain omniscience, defeat the enemy of wrongdoing, and \
+             be liberated from the ocean of samsara, from the stormy waves of birth, old age, \
+             sickness, and death."
+        }
+        Language::Tibetan => {
+            "so nam di yi tam che zik pa nyi tob ne nye pe dra nam pam je ne kye ga na chi ba lab \
+             truk pa yi si pe tso le dro wa drol war shok"
+        }
+        Language::Sanskrit => "anena punyena sarve sattvah sarvajnatam prapnuvantu",
+    }
+    .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prayers::{self, Language};
+
+    #[test]
+    fn prayers() {
+        for language in [Language::English, Language::Tibetan, Language::Sanskrit] {
+            let refuge = prayers::refuge(language);
+            let dedication = prayers::dedication(language);
+            assert!(!refuge.is_empty());
+            assert!(!dedication.is_empty());
+            assert!(!refuge.contains("  "));
+            assert!(!dedication.contains("  "));
+        }
+        assert!(prayers::refuge(Language::English).starts_with("I take refuge"));
+    }
+}
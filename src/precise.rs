@@ -0,0 +1,62 @@
+//! Contains the precise pacing, which ends each wait between syllables more accurately than the
+//! sleeps of the operating system, whose granularity ranges from tens of microseconds to several
+//! milliseconds. Each wait is slept until shortly before its end and spun for the rest, so it ends
+//! within a few microseconds of its deadline unless the thread is preempted, at the cost of keeping
+//! a core busy for up to [`SPIN_MARGIN`] of each wait. Since the recitation schedules each wait
+//! from the deadline of the previous one, the average rate also matches the configured one for
+//! sub-millisecond rates, as long as a syllable can be written within the rate.
+
+use spin_sleep::{SpinSleeper, SpinStrategy};
+use std::time::{Duration, Instant};
+
+/// The part at the end of each wait that is spun instead of slept, which covers the granularity of
+/// the sleeps of most operating systems.
+pub(crate) const SPIN_MARGIN: Duration = Duration::from_millis(2);
+
+/// Waits for the given delay, first with the given function, which sleeps for the duration it's
+/// given and returns whether the wait was interrupted, and then by spinning until the delay is
+/// over. Returns whether the wait was interrupted.
+pub(crate) fn wait(delay: Duration, sleep: impl FnOnce(Duration) -> bool) -> bool {
+    let Some(end) = Instant::now().checked_add(delay) else {
+        return sleep(delay);
+    };
+    if let Some(coarse) = delay.checked_sub(SPIN_MARGIN) {
+        if sleep(coarse) {
+            return true;
+        }
+    }
+    SpinSleeper::new(SPIN_MARGIN.subsec_nanos())
+        .with_spin_strategy(SpinStrategy::YieldThread)
+        .sleep_until(end);
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use crate::precise::{self, SPIN_MARGIN};
+
+    #[test]
+    fn wait() {
+        // Only the start of the wait is slept.
+        let started = Instant::now();
+        let mut slept = Duration::ZERO;
+        let interrupted = precise::wait(Duration::from_millis(5), |delay| {
+            slept = delay;
+            thread::sleep(delay);
+            false
+        });
+        assert!(!interrupted);
+        assert_eq!(slept, Duration::from_millis(5) - SPIN_MARGIN);
+        assert!(started.elapsed() >= Duration::from_millis(5));
+
+        // An interrupted wait ends right away.
+        let started = Instant::now();
+        assert!(precise::wait(Duration::from_secs(10), |_| true));
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,120 @@
+//! Contains well-known mantras ready to be added to the options of the miner. The syllables follow
+//! the Tibetan pronunciation of the Sanskrit common in Western dharma centers, and each mantra is
+//! repeated once per bead of a mala by default.
+
+use alloc::{string::ToString, vec, vec::Vec};
+
+use crate::{MalaConfig, Mantra};
+
+/// Returns a mantra with the given metadata and the syllables in the given text, repeated once per
+/// bead of a mala.
+fn preset(name: &str, deity: &str, source: Option<&str>, text: &str) -> Mantra {
+    Mantra {
+        name: Some(name.to_string()),
+        deity: Some(deity.to_string()),
+        language: Some("Sanskrit".to_string()),
+        source: source.map(ToString::to_string),
+        repeats: Some(MalaConfig::default().beads_per_mala),
+        ..Mantra::from_text(text)
+    }
+}
+
+/// Returns the mantra of Avalokiteshvara, the bodhisattva of compassion.
+pub fn mani() -> Mantra {
+    preset(
+        "Mani",
+        "Avalokiteshvara",
+        Some("Karandavyuha Sutra"),
+        "om ma-ni pad-me hum",
+    )
+}
+
+/// Returns the Vajra Guru mantra of Padmasambhava.
+pub fn vajra_guru() -> Mantra {
+    preset(
+        "Vajra Guru",
+        "Padmasambhava",
+        None,
+        "om ah hum ben-dza gu-ru pe-ma sid-dhi hum",
+    )
+}
+
+/// Returns the mantra of Green Tara, who protects from fear and danger.
+pub fn tara() -> Mantra {
+    preset("Tara", "Green Tara", None, "om ta-re tut-ta-re tu-re so-ha")
+}
+
+/// Returns the mantra of Tara Sarasvati, the manifestation of Tara associated with wisdom, music,
+/// learning, and the arts. This is the mantra recited by Trane.
+pub fn tara_sarasvati() -> Mantra {
+    preset(
+        "Tara Sarasvati",
+        "Tara Sarasvati",
+        None,
+        "om a-rya ta-re ben-de ta-re sa-ra-sva-ti hrim hrim so-ha",
+    )
+}
+
+/// Returns the mantra of the Medicine Buddha.
+pub fn medicine_buddha() -> Mantra {
+    preset(
+        "Medicine Buddha",
+        "Bhaisajyaguru",
+        Some("Medicine Buddha Sutra"),
+        "te-ya-ta om be-kan-dze be-kan-dze ma-ha be-kan-dze ra-dza sa-mung-ga-te so-ha",
+    )
+}
+
+/// Returns the mantra of Amitabha, the Buddha of Infinite Light.
+pub fn amitabha() -> Mantra {
+    preset("Amitabha", "Amitabha", None, "om a-mi-de-wa hri")
+}
+
+/// Returns all the preset mantras.
+pub fn all() -> Vec<Mantra> {
+    vec![
+        mani(),
+        vajra_guru(),
+        tara(),
+        tara_sarasvati(),
+        medicine_buddha(),
+        amitabha(),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::sync::Arc;
+    use core::time::Duration;
+
+    use crate::{presets, Options};
+
+    #[test]
+    fn syllables() {
+        assert_eq!(
+            *presets::mani().syllables,
+            ["om", "ma", "ni", "pad", "me", "hum"].map(Arc::<str>::from)
+        );
+        assert_eq!(presets::vajra_guru().syllables.len(), 12);
+        assert_eq!(presets::tara().syllables.len(), 10);
+        assert_eq!(presets::tara_sarasvati().syllables.len(), 17);
+        assert_eq!(presets::medicine_buddha().syllables.len(), 23);
+        assert_eq!(presets::amitabha().syllables.len(), 6);
+    }
+
+    #[test]
+    fn valid_presets() {
+        for mantra in presets::all() {
+            assert!(mantra.name.is_some());
+            assert!(mantra.deity.is_some());
+            assert_eq!(mantra.language.as_deref(), Some("Sanskrit"));
+            assert_eq!(mantra.repeats, Some(108));
+            let options = Options {
+                mantras: vec![mantra],
+                rate: Duration::from_micros(1),
+                ..Default::default()
+            };
+            assert!(options.validate().is_ok());
+        }
+    }
+}
@@ -0,0 +1,76 @@
+//! Sets the operating system priority of the thread that recites the sadhana. Only Linux, Android,
+//! and Windows are supported, and the priority is left unchanged on other platforms.
+
+use crate::ThreadPriority;
+
+/// Sets the priority of the current thread. Failures are ignored, since the miner works the same
+/// at any priority.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(crate) fn set_current_thread_priority(priority: ThreadPriority) {
+    // SAFETY: The calls only change the scheduling of the current thread, whose ID is returned by
+    // `gettid`, and the parameters passed by reference outlive them.
+    unsafe {
+        match priority {
+            ThreadPriority::Normal => {}
+            ThreadPriority::Low => {
+                // On Linux, the nice value set for a thread ID only applies to that thread.
+                let _ = libc::setpriority(libc::PRIO_PROCESS, libc::gettid() as libc::id_t, 19);
+            }
+            ThreadPriority::Idle => {
+                let param = libc::sched_param { sched_priority: 0 };
+                let _ = libc::sched_setscheduler(0, libc::SCHED_IDLE, &param);
+            }
+        }
+    }
+}
+
+/// Sets the priority of the current thread. Failures are ignored, since the miner works the same
+/// at any priority.
+#[cfg(windows)]
+pub(crate) fn set_current_thread_priority(priority: ThreadPriority) {
+    use windows_sys::Win32::System::Threading::{
+        GetCurrentThread, SetThreadPriority, THREAD_MODE_BACKGROUND_BEGIN, THREAD_PRIORITY_LOWEST,
+    };
+
+    let value = match priority {
+        ThreadPriority::Normal => return,
+        ThreadPriority::Low => THREAD_PRIORITY_LOWEST,
+        ThreadPriority::Idle => THREAD_MODE_BACKGROUND_BEGIN,
+    };
+    // SAFETY: The pseudo handle returned by `GetCurrentThread` is always valid for the current
+    // thread.
+    unsafe {
+        let _ = SetThreadPriority(GetCurrentThread(), value);
+    }
+}
+
+/// Leaves the priority of the current thread unchanged, since the platform is not supported.
+#[cfg(not(any(target_os = "linux", target_os = "android", windows)))]
+pub(crate) fn set_current_thread_priority(_priority: ThreadPriority) {}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use std::thread;
+
+    use crate::{priority::set_current_thread_priority, ThreadPriority};
+
+    #[test]
+    fn idle_priority() {
+        // The priority is set on a new thread, so that the other tests keep their priority.
+        let policy = thread::spawn(|| {
+            set_current_thread_priority(ThreadPriority::Idle);
+            unsafe { libc::sched_getscheduler(0) }
+        })
+        .join()
+        .unwrap();
+        assert_eq!(policy, libc::SCHED_IDLE);
+
+        let nice = thread::spawn(|| {
+            set_current_thread_priority(ThreadPriority::Low);
+            unsafe { libc::getpriority(libc::PRIO_PROCESS, libc::gettid() as libc::id_t) }
+        })
+        .join()
+        .unwrap();
+        assert_eq!(nice, 19);
+    }
+}
@@ -0,0 +1,138 @@
+//! Contains the adapter that drives an `indicatif` progress bar from the events of the mantra
+//! miner, so that command-line tools can show the recitations completed out of the total, with the
+//! estimated time left, while a finite number of sadhanas is recited.
+
+use indicatif::{ProgressBar, ProgressStyle};
+use std::{
+    sync::mpsc::{Receiver, RecvTimeoutError},
+    time::Duration,
+};
+
+use crate::{MantraMiner, MinerEvent, MinerHandle};
+
+/// The template of the progress bars created by `ProgressReporter::new`.
+const TEMPLATE: &str = "[{elapsed_precise}] {wide_bar} {pos}/{len} sadhanas (ETA {eta}) {msg}";
+
+/// How often `ProgressReporter::follow` checks whether the miner is still running when no events
+/// are received.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Drives a progress bar from the events of a mantra miner. The position of the bar is the number
+/// of recitations of the sadhana completed since the miner was started, its length is the number of
+/// repeats set in the options, and its message is the mantra that was last completed. If the
+/// sadhana repeats indefinitely, the bar has no length.
+pub struct ProgressReporter {
+    /// The progress bar updated from the events.
+    bar: ProgressBar,
+
+    /// The events of the miner.
+    events: Receiver<MinerEvent>,
+
+    /// The handle used to check whether the miner is still running.
+    handle: MinerHandle,
+}
+
+impl ProgressReporter {
+    /// Returns a reporter that draws a new progress bar for the given miner to the terminal.
+    pub fn new(miner: &MantraMiner) -> ProgressReporter {
+        let bar = ProgressBar::no_length().with_style(
+            ProgressStyle::with_template(TEMPLATE).expect("the progress bar template is valid"),
+        );
+        Self::with_bar(miner, bar)
+    }
+
+    /// Returns a reporter that updates the given progress bar, whose style and draw target are left
+    /// untouched, for the given miner. The reporter only receives the events emitted after it's
+    /// created, so it should be created before the miner is started.
+    pub fn with_bar(miner: &MantraMiner, bar: ProgressBar) -> ProgressReporter {
+        match miner.options().repeats {
+            Some(repeats) => bar.set_length(repeats as u64),
+            None => bar.unset_length(),
+        }
+        ProgressReporter {
+            bar,
+            events: miner.subscribe(),
+            handle: miner.handle(),
+        }
+    }
+
+    /// Returns the progress bar updated by the reporter.
+    pub fn bar(&self) -> &ProgressBar {
+        &self.bar
+    }
+
+    /// Updates the progress bar with the given event.
+    fn apply(&self, event: MinerEvent) {
+        match event {
+            MinerEvent::SadhanaStarted { run } => self.bar.set_position(run as u64),
+            MinerEvent::MantraCompleted { index, name, .. } => self
+                .bar
+                .set_message(name.unwrap_or_else(|| format!("#{index}"))),
+            MinerEvent::SyllableRecited { .. } => {}
+            MinerEvent::SadhanaCompleted { run, .. } => self.bar.set_position(run as u64 + 1),
+        }
+    }
+
+    /// Updates the progress bar with the events received since the last update, without blocking.
+    /// Applications that already have a loop, such as a user interface, can call it on each
+    /// iteration.
+    pub fn update(&self) {
+        for event in self.events.try_iter() {
+            self.apply(event);
+        }
+    }
+
+    /// Updates the progress bar as the events are received until the miner stops, and then
+    /// finishes it. Call it after starting the miner, usually from the thread that would otherwise
+    /// wait for it.
+    pub fn follow(self) {
+        loop {
+            match self.events.recv_timeout(POLL_INTERVAL) {
+                Ok(event) => self.apply(event),
+                Err(RecvTimeoutError::Timeout) if self.handle.is_running() => {}
+                Err(_) => break,
+            }
+        }
+        self.update();
+        self.bar.finish();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use indicatif::ProgressBar;
+    use std::{io, time::Duration};
+
+    use crate::{Mantra, MantraMiner, Options, ProgressReporter};
+
+    #[test]
+    fn follow() -> Result<()> {
+        let options = Options {
+            mantras: vec![Mantra {
+                name: Some("Mani".to_string()),
+                ..Mantra::from_text("om ah hum")
+            }],
+            rate: Duration::from_micros(1),
+            repeats: Some(3),
+            ..Default::default()
+        };
+        let miner = MantraMiner::with_output(options, Box::new(io::sink()));
+        let reporter = ProgressReporter::with_bar(&miner, ProgressBar::hidden());
+        let bar = reporter.bar().clone();
+        assert_eq!(bar.length(), Some(3));
+        miner.start()?;
+        reporter.follow();
+        assert_eq!(bar.position(), 3);
+        assert_eq!(bar.message(), "Mani");
+        assert!(bar.is_finished());
+        Ok(())
+    }
+
+    #[test]
+    fn unbounded() {
+        let miner = MantraMiner::with_output(Options::default(), Box::new(io::sink()));
+        let reporter = ProgressReporter::with_bar(&miner, ProgressBar::hidden());
+        assert_eq!(reporter.bar().length(), None);
+    }
+}
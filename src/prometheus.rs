@@ -0,0 +1,174 @@
+//! Contains the rendering of the counters of the mantra miner in the Prometheus text exposition
+//! format, so that a daemon running the miner can expose them to be scraped and graphed.
+
+use std::{fmt::Write, sync::atomic::Ordering};
+
+use crate::SharedState;
+
+/// Writes the help and type lines that precede the samples of a metric.
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+}
+
+/// Escapes the given text so it can be used as the value of a label.
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Returns the counters in the given state in the Prometheus text exposition format.
+pub(crate) fn render(state: &SharedState) -> String {
+    let mut out = String::new();
+    header(
+        &mut out,
+        "mantra_miner_sadhanas_total",
+        "counter",
+        "The number of completed recitations of the sadhana.",
+    );
+    let _ = writeln!(out, "mantra_miner_sadhanas_total {}", state.count());
+
+    header(
+        &mut out,
+        "mantra_miner_interrupted_sadhanas_total",
+        "counter",
+        "The number of recitations of the sadhana interrupted before their completion.",
+    );
+    let _ = writeln!(
+        out,
+        "mantra_miner_interrupted_sadhanas_total {}",
+        state.interrupted_sadhanas()
+    );
+
+    header(
+        &mut out,
+        "mantra_miner_syllables_total",
+        "counter",
+        "The number of syllables and characters recited.",
+    );
+    let _ = writeln!(out, "mantra_miner_syllables_total {}", state.syllables());
+
+    header(
+        &mut out,
+        "mantra_miner_dropped_bytes_total",
+        "counter",
+        "The number of bytes of output dropped because the output queue was full.",
+    );
+    let _ = writeln!(
+        out,
+        "mantra_miner_dropped_bytes_total {}",
+        state.dropped_bytes.load(Ordering::Relaxed)
+    );
+
+    header(
+        &mut out,
+        "mantra_miner_playlist_sadhanas_total",
+        "counter",
+        "The number of completed recitations of each sadhana of the playlist.",
+    );
+    if let Some(playlist) = &*state.playlist.lock() {
+        for sadhana in playlist.stats() {
+            let _ = writeln!(
+                out,
+                "mantra_miner_playlist_sadhanas_total{{sadhana=\"{}\"}} {}",
+                escape_label(&sadhana.name),
+                sadhana.completed
+            );
+        }
+    }
+
+    let stats = state.mantra_stats.lock();
+    header(
+        &mut out,
+        "mantra_miner_mantras_total",
+        "counter",
+        "The number of completed repetitions of each mantra.",
+    );
+    for (index, mantra) in stats.iter().enumerate() {
+        let _ = writeln!(
+            out,
+            "mantra_miner_mantras_total{{index=\"{index}\",mantra=\"{}\"}} {}",
+            escape_label(&mantra.label(index)),
+            mantra.completed
+        );
+    }
+    header(
+        &mut out,
+        "mantra_miner_mantra_syllables_total",
+        "counter",
+        "The number of syllables of each mantra recited.",
+    );
+    for (index, mantra) in stats.iter().enumerate() {
+        let _ = writeln!(
+            out,
+            "mantra_miner_mantra_syllables_total{{index=\"{index}\",mantra=\"{}\"}} {}",
+            escape_label(&mantra.label(index)),
+            mantra.syllables
+        );
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::{
+        playlist::PlaylistState,
+        prometheus::{escape_label, render},
+        Mantra, Options, Playlist, PlaylistSadhana, SharedState,
+    };
+
+    #[test]
+    fn escape() {
+        assert_eq!(escape_label("om"), "om");
+        assert_eq!(escape_label("a\"b\\c\nd"), "a\\\"b\\\\c\\nd");
+    }
+
+    #[test]
+    fn render_counters() {
+        let options = Options {
+            mantras: vec![
+                Mantra {
+                    name: Some("Mani".to_string()),
+                    syllables: ["om", "hum"].map(Arc::from).into(),
+                    ..Default::default()
+                },
+                Mantra {
+                    syllables: ["ah"].map(Arc::from).into(),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        let state = SharedState::new(&options);
+        *state.playlist.lock() = Some(PlaylistState::new(Playlist {
+            sadhanas: vec![PlaylistSadhana {
+                name: "Morning".to_string(),
+                recitations: None,
+                starts_at: None,
+                options: options.clone(),
+            }],
+            ..Default::default()
+        }));
+        state.record_syllable(0, "om");
+        state.record_syllable(0, "hum");
+        state.record_mantra(0, 0, 1);
+        state.record_sadhana(0);
+
+        let text = render(&state);
+        assert!(text.contains("# TYPE mantra_miner_sadhanas_total counter\n"));
+        assert!(text.contains("\nmantra_miner_sadhanas_total 1\n"));
+        assert!(text.contains("\nmantra_miner_interrupted_sadhanas_total 0\n"));
+        assert!(text.contains("\nmantra_miner_syllables_total 2\n"));
+        assert!(text.contains("\nmantra_miner_dropped_bytes_total 0\n"));
+        assert!(text.contains("\nmantra_miner_playlist_sadhanas_total{sadhana=\"Morning\"} 1\n"));
+        assert!(text.contains("\nmantra_miner_mantras_total{index=\"0\",mantra=\"Mani\"} 1\n"));
+        assert!(text.contains("\nmantra_miner_mantras_total{index=\"1\",mantra=\"#1\"} 0\n"));
+        assert!(
+            text.contains("\nmantra_miner_mantra_syllables_total{index=\"0\",mantra=\"Mani\"} 2\n")
+        );
+    }
+}
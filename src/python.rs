@@ -0,0 +1,353 @@
+//! Defines the Python bindings of the mantra miner, which expose `Mantra`, `Options`, and
+//! `MantraMiner` as classes of the `mantra_miner` Python module.
+
+use pyo3::{
+    exceptions::{PyRuntimeError, PyValueError},
+    prelude::*,
+};
+use std::time::Duration;
+
+use crate::{Error, Mantra, MantraMiner, Options, Result};
+
+impl From<Error> for PyErr {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::InvalidOptions(_) | Error::ParseFailed(_) => {
+                PyValueError::new_err(error.to_string())
+            }
+            _ => PyRuntimeError::new_err(error.to_string()),
+        }
+    }
+}
+
+/// A mantra to be recited by the miner.
+#[pyclass(name = "Mantra", module = "mantra_miner")]
+#[derive(Clone)]
+pub struct PyMantra(Mantra);
+
+#[pymethods]
+impl PyMantra {
+    /// Returns a new mantra with the given syllables.
+    #[new]
+    #[pyo3(signature = (
+        syllables,
+        name = None,
+        repeats = None,
+        deity = None,
+        language = None,
+        source = None,
+    ))]
+    fn new(
+        syllables: Vec<String>,
+        name: Option<String>,
+        repeats: Option<usize>,
+        deity: Option<String>,
+        language: Option<String>,
+        source: Option<String>,
+    ) -> Self {
+        PyMantra(Mantra {
+            name,
+            deity,
+            language,
+            source,
+            syllables: syllables.into_iter().map(Into::into).collect(),
+            repeats,
+            weight: None,
+            frequency: None,
+        })
+    }
+
+    /// Returns a new mantra whose syllables are obtained by splitting the given text on whitespace
+    /// and hyphens.
+    #[staticmethod]
+    fn from_text(text: &str) -> Self {
+        PyMantra(Mantra::from_text(text))
+    }
+
+    /// The optional name used to identify the mantra.
+    #[getter]
+    fn name(&self) -> Option<String> {
+        self.0.name.clone()
+    }
+
+    #[setter]
+    fn set_name(&mut self, name: Option<String>) {
+        self.0.name = name;
+    }
+
+    /// The deity associated with the mantra, if any.
+    #[getter]
+    fn deity(&self) -> Option<String> {
+        self.0.deity.clone()
+    }
+
+    #[setter]
+    fn set_deity(&mut self, deity: Option<String>) {
+        self.0.deity = deity;
+    }
+
+    /// The language or script in which the syllables are written.
+    #[getter]
+    fn language(&self) -> Option<String> {
+        self.0.language.clone()
+    }
+
+    #[setter]
+    fn set_language(&mut self, language: Option<String>) {
+        self.0.language = language;
+    }
+
+    /// The text in which the mantra is found, if known.
+    #[getter]
+    fn source(&self) -> Option<String> {
+        self.0.source.clone()
+    }
+
+    #[setter]
+    fn set_source(&mut self, source: Option<String>) {
+        self.0.source = source;
+    }
+
+    /// The syllables of the mantra.
+    #[getter]
+    fn syllables(&self) -> Vec<String> {
+        self.0.syllables.iter().map(ToString::to_string).collect()
+    }
+
+    #[setter]
+    fn set_syllables(&mut self, syllables: Vec<String>) {
+        self.0.syllables = syllables.into_iter().map(Into::into).collect();
+    }
+
+    /// The number of times to repeat the mantra, or `None` to recite it once.
+    #[getter]
+    fn repeats(&self) -> Option<usize> {
+        self.0.repeats
+    }
+
+    #[setter]
+    fn set_repeats(&mut self, repeats: Option<usize>) {
+        self.0.repeats = repeats;
+    }
+
+    fn __repr__(&self) -> String {
+        format!("{:?}", self.0)
+    }
+}
+
+/// The options used to configure the mantra miner.
+#[pyclass(name = "Options", module = "mantra_miner")]
+#[derive(Clone)]
+pub struct PyOptions(Options);
+
+#[pymethods]
+impl PyOptions {
+    /// Returns new options with the given values. Missing values take their defaults.
+    #[new]
+    #[pyo3(signature = (
+        mantras = Vec::new(),
+        preparation = None,
+        preparation_repeats = None,
+        conclusion = None,
+        conclusion_repeats = None,
+        repeats = None,
+        rate_ns = 0,
+    ))]
+    fn new(
+        mantras: Vec<PyMantra>,
+        preparation: Option<String>,
+        preparation_repeats: Option<usize>,
+        conclusion: Option<String>,
+        conclusion_repeats: Option<usize>,
+        repeats: Option<usize>,
+        rate_ns: u64,
+    ) -> Self {
+        PyOptions(Options {
+            preparation,
+            preparation_repeats,
+            mantras: mantras.into_iter().map(|mantra| mantra.0).collect(),
+            conclusion,
+            conclusion_repeats,
+            repeats,
+            rate: Duration::from_nanos(rate_ns),
+            ..Default::default()
+        })
+    }
+
+    /// Parses the options from a string containing a sadhana in TOML format.
+    #[staticmethod]
+    fn from_toml_str(contents: &str) -> Result<Self> {
+        Options::from_toml_str(contents).map(PyOptions)
+    }
+
+    /// The mantras recited in the main body of the sadhana.
+    #[getter]
+    fn mantras(&self) -> Vec<PyMantra> {
+        self.0.mantras.iter().cloned().map(PyMantra).collect()
+    }
+
+    #[setter]
+    fn set_mantras(&mut self, mantras: Vec<PyMantra>) {
+        self.0.mantras = mantras.into_iter().map(|mantra| mantra.0).collect();
+    }
+
+    /// The preparation recited before the mantras, if any.
+    #[getter]
+    fn preparation(&self) -> Option<String> {
+        self.0.preparation.clone()
+    }
+
+    #[setter]
+    fn set_preparation(&mut self, preparation: Option<String>) {
+        self.0.preparation = preparation;
+    }
+
+    /// The conclusion recited after the mantras, if any.
+    #[getter]
+    fn conclusion(&self) -> Option<String> {
+        self.0.conclusion.clone()
+    }
+
+    #[setter]
+    fn set_conclusion(&mut self, conclusion: Option<String>) {
+        self.0.conclusion = conclusion;
+    }
+
+    /// The number of times to repeat the entire sadhana, or `None` to repeat it indefinitely.
+    #[getter]
+    fn repeats(&self) -> Option<usize> {
+        self.0.repeats
+    }
+
+    #[setter]
+    fn set_repeats(&mut self, repeats: Option<usize>) {
+        self.0.repeats = repeats;
+    }
+
+    /// The number of nanoseconds to wait between each syllable or character.
+    #[getter]
+    fn rate_ns(&self) -> u64 {
+        self.0.rate.as_nanos().try_into().unwrap_or(u64::MAX)
+    }
+
+    #[setter]
+    fn set_rate_ns(&mut self, rate_ns: u64) {
+        self.0.rate = Duration::from_nanos(rate_ns);
+    }
+
+    /// Raises `ValueError` if the options do not describe a sadhana that can be recited.
+    fn validate(&self) -> Result<()> {
+        self.0.validate()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("{:?}", self.0)
+    }
+}
+
+/// A mantra miner that recites the sadhana in a background thread.
+#[pyclass(name = "MantraMiner", module = "mantra_miner")]
+pub struct PyMantraMiner(MantraMiner);
+
+#[pymethods]
+impl PyMantraMiner {
+    /// Returns a new mantra miner with the given options.
+    #[new]
+    fn new(options: PyOptions) -> Self {
+        PyMantraMiner(MantraMiner::new(options.0))
+    }
+
+    /// Starts running the mantra miner in a background thread.
+    fn start(&self) -> Result<()> {
+        self.0.start()
+    }
+
+    /// Stops the mantra miner and waits for its thread to exit.
+    fn stop(&self, py: Python<'_>) -> Result<()> {
+        py.allow_threads(|| self.0.stop_and_join())
+    }
+
+    /// Pauses the mantra miner before it recites the next syllable or character.
+    fn pause(&self) -> Result<()> {
+        self.0.pause()
+    }
+
+    /// Resumes the mantra miner after a call to `pause`.
+    fn resume(&self) -> Result<()> {
+        self.0.resume()
+    }
+
+    /// Returns whether the mantra miner is paused.
+    fn is_paused(&self) -> bool {
+        self.0.is_paused()
+    }
+
+    /// Returns the number of completed recitations of the entire sadhana.
+    fn count(&self) -> u64 {
+        self.0.count()
+    }
+
+    /// Returns the number of syllables and characters that have been recited.
+    fn syllable_count(&self) -> u64 {
+        self.0.syllable_count()
+    }
+
+    /// Returns the options used to configure the mantra miner.
+    fn options(&self) -> PyOptions {
+        PyOptions(self.0.options())
+    }
+}
+
+/// The `mantra_miner` Python module.
+#[pymodule]
+fn mantra_miner(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyMantra>()?;
+    m.add_class::<PyOptions>()?;
+    m.add_class::<PyMantraMiner>()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{thread, time::Duration};
+
+    use crate::python::{PyMantra, PyMantraMiner, PyOptions};
+
+    #[test]
+    fn run_miner() {
+        pyo3::prepare_freethreaded_python();
+        let options = PyOptions::new(
+            vec![PyMantra::from_text("om ah hum")],
+            None,
+            None,
+            None,
+            None,
+            None,
+            1000,
+        );
+        let miner = PyMantraMiner::new(options);
+        miner.start().unwrap();
+        thread::sleep(Duration::from_millis(100));
+        pyo3::Python::with_gil(|py| miner.stop(py)).unwrap();
+        assert!(miner.count() > 0);
+        assert_eq!(miner.options().mantras()[0].syllables().len(), 3);
+    }
+
+    #[test]
+    fn invalid_options() {
+        pyo3::prepare_freethreaded_python();
+        let miner = PyMantraMiner::new(PyOptions::new(
+            Vec::new(),
+            None,
+            None,
+            None,
+            None,
+            None,
+            1000,
+        ));
+        let err = pyo3::PyErr::from(miner.start().unwrap_err());
+        pyo3::Python::with_gil(|py| {
+            assert!(err.is_instance_of::<pyo3::exceptions::PyValueError>(py));
+        });
+    }
+}
@@ -0,0 +1,372 @@
+//! Contains the queue used to write the output of the mantra miner from a dedicated thread, so that
+//! the time taken by slow outputs, such as files on network storage or sockets, does not distort
+//! the pace of the recitation.
+
+use parking_lot::{Condvar, Mutex, MutexGuard};
+use std::{
+    collections::VecDeque,
+    io::{self, Write},
+    sync::{atomic::Ordering, Arc},
+    thread::{self, JoinHandle},
+};
+
+use crate::{Options, OverflowPolicy, SharedOutput, SharedState};
+
+/// The part of the queue protected by its lock.
+#[derive(Default)]
+struct QueueState {
+    /// The chunks of output waiting to be written, oldest first.
+    chunks: VecDeque<Vec<u8>>,
+
+    /// The total number of bytes in the queued chunks.
+    bytes: usize,
+
+    /// Whether the output should be flushed once the queued chunks have been written.
+    flush: bool,
+
+    /// Whether the recitation is done writing, so that the writer thread exits once the queue is
+    /// empty.
+    closed: bool,
+
+    /// The last error returned by the output, reported by the next write to the queue.
+    error: Option<io::Error>,
+}
+
+impl QueueState {
+    /// Returns the last error returned by the output, if any, and clears it.
+    fn take_error(&mut self) -> io::Result<()> {
+        match self.error.take() {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The queue shared between the recitation and the writer thread.
+#[derive(Default)]
+struct Queue {
+    /// The state of the queue.
+    state: Mutex<QueueState>,
+
+    /// The condition variable notified whenever the state of the queue changes.
+    changed: Condvar,
+}
+
+/// An output that queues the writes of the recitation and returns immediately, while a dedicated
+/// thread writes them to the output of the mantra miner. Flushing only asks the thread to flush
+/// once the writes queued so far are done. An error returned by the output is reported by the
+/// next write or flush. Dropping the queued output waits for the queue to be written.
+pub(crate) struct QueuedOutput {
+    /// The queue shared with the writer thread.
+    queue: Arc<Queue>,
+
+    /// The number of bytes the queue can hold before the overflow policy applies.
+    capacity: usize,
+
+    /// What to do with a write that does not fit in the queue.
+    policy: OverflowPolicy,
+
+    /// The state shared with the mantra miner, used to count the bytes dropped.
+    state: SharedState,
+
+    /// The thread that writes the queued chunks to the output.
+    writer: Option<JoinHandle<()>>,
+}
+
+impl QueuedOutput {
+    /// Returns a queued output that holds up to `capacity` bytes and writes them to the given
+    /// output from a new thread. Returns an error if the thread cannot be spawned.
+    fn new(
+        output: SharedOutput,
+        capacity: usize,
+        policy: OverflowPolicy,
+        state: SharedState,
+    ) -> io::Result<QueuedOutput> {
+        let queue = Arc::new(Queue::default());
+        let writer = thread::Builder::new()
+            .name("mantra-miner-writer".to_string())
+            .spawn({
+                let queue = queue.clone();
+                move || write_queued(&queue, output)
+            })?;
+        Ok(QueuedOutput {
+            queue,
+            capacity,
+            policy,
+            state,
+            writer: Some(writer),
+        })
+    }
+
+    /// Records that the given number of bytes were dropped because the queue was full.
+    fn drop_bytes(&self, bytes: usize) {
+        self.state
+            .dropped_bytes
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+}
+
+impl Write for QueuedOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut state = self.queue.state.lock();
+        state.take_error()?;
+
+        // A write larger than the whole queue is accepted once the queue is empty.
+        while state.bytes > 0 && state.bytes + buf.len() > self.capacity {
+            match self.policy {
+                OverflowPolicy::Block => self.queue.changed.wait(&mut state),
+                OverflowPolicy::DropNewest => {
+                    self.drop_bytes(buf.len());
+                    return Ok(buf.len());
+                }
+                OverflowPolicy::DropOldest => {
+                    let oldest = state.chunks.pop_front().unwrap_or_default();
+                    state.bytes -= oldest.len();
+                    self.drop_bytes(oldest.len());
+                }
+            }
+        }
+        state.bytes += buf.len();
+        state.chunks.push_back(buf.to_vec());
+        self.queue.changed.notify_all();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let mut state = self.queue.state.lock();
+        state.take_error()?;
+        state.flush = true;
+        self.queue.changed.notify_all();
+        Ok(())
+    }
+}
+
+impl Drop for QueuedOutput {
+    /// Waits for the writer thread to write and flush the queued chunks, so that the output is
+    /// complete once the recitation is dropped.
+    fn drop(&mut self) {
+        {
+            let mut state = self.queue.state.lock();
+            state.closed = true;
+            state.flush = true;
+            self.queue.changed.notify_all();
+        }
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+    }
+}
+
+/// Writes the chunks in the queue to the given output until the queue is closed and empty. The
+/// lock is released while writing, so that the recitation can keep queueing.
+fn write_queued(queue: &Queue, mut output: SharedOutput) {
+    let mut state = queue.state.lock();
+    loop {
+        let result = if let Some(chunk) = state.chunks.pop_front() {
+            state.bytes -= chunk.len();
+            queue.changed.notify_all();
+            MutexGuard::unlocked(&mut state, || output.write_all(&chunk))
+        } else if state.flush {
+            state.flush = false;
+            MutexGuard::unlocked(&mut state, || output.flush())
+        } else if state.closed {
+            return;
+        } else {
+            queue.changed.wait(&mut state);
+            continue;
+        };
+        if let Err(error) = result {
+            state.error = Some(error);
+        }
+    }
+}
+
+/// The output to which a run of the mantra miner recites, which is either the output of the miner
+/// or a queue in front of it.
+pub(crate) enum RunOutput {
+    /// The output of the mantra miner, written to by the recitation itself.
+    Direct(SharedOutput),
+
+    /// A queue written to the output of the mantra miner by a dedicated thread.
+    Queued(Box<QueuedOutput>),
+}
+
+impl RunOutput {
+    /// Returns the output for a run with the given options. The writes are queued if the options
+    /// set the size of the output queue, unless the writer thread cannot be spawned, as on targets
+    /// without threads.
+    pub(crate) fn new(output: SharedOutput, options: &Options, state: &SharedState) -> RunOutput {
+        let Some(capacity) = options.output_queue else {
+            return RunOutput::Direct(output);
+        };
+        let direct = SharedOutput(output.0.clone());
+        match QueuedOutput::new(output, capacity, options.overflow_policy, state.clone()) {
+            Ok(queued) => RunOutput::Queued(Box::new(queued)),
+            Err(_) => RunOutput::Direct(direct),
+        }
+    }
+}
+
+impl Write for RunOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            RunOutput::Direct(output) => output.write(buf),
+            RunOutput::Queued(output) => output.write(buf),
+        }
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        match self {
+            RunOutput::Direct(output) => output.write_all(buf),
+            RunOutput::Queued(output) => output.write_all(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            RunOutput::Direct(output) => output.flush(),
+            RunOutput::Queued(output) => output.flush(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use parking_lot::Mutex;
+    use std::{
+        io::{self, Write},
+        sync::{atomic::Ordering, Arc},
+        thread,
+        time::Duration,
+    };
+
+    use crate::{
+        queue::{QueuedOutput, RunOutput},
+        Options, OverflowPolicy, SharedOutput, SharedState,
+    };
+
+    /// An output that waits until it's released before accepting writes, and fails if the first
+    /// byte written is zero.
+    #[derive(Clone, Default)]
+    struct GatedOutput {
+        written: Arc<Mutex<Vec<u8>>>,
+        released: Arc<Mutex<bool>>,
+    }
+
+    impl Write for GatedOutput {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            while !*self.released.lock() {
+                thread::sleep(Duration::from_millis(1));
+            }
+            if buf.first() == Some(&0) {
+                return Err(io::Error::other("rejected"));
+            }
+            self.written.lock().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Returns a queued output of the given capacity writing to the given output.
+    fn queued(output: &GatedOutput, capacity: usize, policy: OverflowPolicy) -> QueuedOutput {
+        let options = Options::default();
+        let shared = SharedOutput(Arc::new(Mutex::new(Box::new(output.clone()))));
+        QueuedOutput::new(shared, capacity, policy, SharedState::new(&options)).unwrap()
+    }
+
+    #[test]
+    fn writes_in_order() -> Result<()> {
+        let output = GatedOutput::default();
+        let mut queued = queued(&output, 4, OverflowPolicy::Block);
+        *output.released.lock() = true;
+        for chunk in ["om", "ah", "hum"] {
+            queued.write_all(chunk.as_bytes())?;
+        }
+        queued.flush()?;
+        drop(queued);
+        assert_eq!(*output.written.lock(), b"omahhum");
+        Ok(())
+    }
+
+    #[test]
+    fn overflow_policies() -> Result<()> {
+        // The writer thread takes the first chunk and waits for the output, leaving the queue
+        // with room for one more chunk.
+        let fill = |policy| -> Result<(GatedOutput, QueuedOutput)> {
+            let output = GatedOutput::default();
+            let mut queued = queued(&output, 2, policy);
+            queued.write_all(b"om")?;
+            while queued.queue.state.lock().bytes > 0 {
+                thread::sleep(Duration::from_millis(1));
+            }
+            queued.write_all(b"ah")?;
+            Ok((output, queued))
+        };
+
+        let (output, mut queued) = fill(OverflowPolicy::DropNewest)?;
+        queued.write_all(b"hu")?;
+        *output.released.lock() = true;
+        let state = queued.state.clone();
+        drop(queued);
+        assert_eq!(*output.written.lock(), b"omah");
+        assert_eq!(state.dropped_bytes.load(Ordering::Relaxed), 2);
+
+        let (output, mut queued) = fill(OverflowPolicy::DropOldest)?;
+        queued.write_all(b"hu")?;
+        *output.released.lock() = true;
+        let state = queued.state.clone();
+        drop(queued);
+        assert_eq!(*output.written.lock(), b"omhu");
+        assert_eq!(state.dropped_bytes.load(Ordering::Relaxed), 2);
+
+        // The write blocks until the writer thread makes room.
+        let (output, mut queued) = fill(OverflowPolicy::Block)?;
+        let release = output.clone();
+        let releaser = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            *release.released.lock() = true;
+        });
+        queued.write_all(b"hu")?;
+        releaser.join().unwrap();
+        drop(queued);
+        assert_eq!(*output.written.lock(), b"omahhu");
+        Ok(())
+    }
+
+    #[test]
+    fn reports_errors() -> Result<()> {
+        let output = GatedOutput::default();
+        *output.released.lock() = true;
+        let mut queued = queued(&output, 16, OverflowPolicy::Block);
+        queued.write_all(&[0])?;
+        while queued.queue.state.lock().error.is_none() {
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert!(queued.write_all(b"om").is_err());
+        assert!(queued.write_all(b"om").is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn run_output() {
+        let options = Options::default();
+        let state = SharedState::new(&options);
+        let shared = || SharedOutput(Arc::new(Mutex::new(Box::new(io::sink()))));
+        assert!(matches!(
+            RunOutput::new(shared(), &options, &state),
+            RunOutput::Direct(_)
+        ));
+        let options = Options {
+            output_queue: Some(1024),
+            ..options
+        };
+        assert!(matches!(
+            RunOutput::new(shared(), &options, &state),
+            RunOutput::Queued(_)
+        ));
+    }
+}
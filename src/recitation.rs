@@ -0,0 +1,1188 @@
+//! Contains the recitation used by the mantra miner, which drives the `no_std` reciter, writes the
+//! recited syllables and characters to the output, and records them in the state shared with the
+//! miner. The recitation does not wait between syllables nor check whether it should stop, so that
+//! it can be driven by a thread, an asynchronous task, or any other scheduler.
+
+use std::{
+    collections::hash_map::RandomState,
+    hash::BuildHasher,
+    io::{self, BufWriter, Write},
+    time::{Duration, Instant},
+};
+
+#[cfg(feature = "power")]
+use crate::power::PowerSampler;
+use crate::{
+    load::LoadSampler,
+    timing,
+    voice::{PreparedSyllables, Voice, WriterVoice},
+    Checkpoint, Error, ErrorPolicy, FinishReason, MinerEvent, Options, RecitationError, Reciter,
+    ReciterEvent, Result, SharedState,
+};
+
+/// A single unit recited by a call to [`Recitation::step`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum Recited {
+    /// A character of the preparation or conclusion.
+    Character(char),
+
+    /// A grapheme cluster of the preparation or conclusion made of more than one character.
+    Grapheme {
+        /// The index of the section whose text contains the grapheme cluster.
+        section_index: usize,
+
+        /// The byte offset of the start of the grapheme cluster in the text.
+        start: usize,
+
+        /// The byte offset of the end of the grapheme cluster in the text.
+        end: usize,
+    },
+
+    /// A syllable of a mantra.
+    Syllable {
+        /// The index of the mantra in the options.
+        mantra_index: usize,
+
+        /// The zero-based number of the repetition of the mantra.
+        repeat: usize,
+
+        /// The index of the syllable in the mantra.
+        syllable_index: usize,
+    },
+
+    /// Nothing, because writing to the output failed and the write will be retried after the
+    /// delay returned by [`Recitation::next_delay`].
+    Retry,
+}
+
+/// A write to the output made by the recitation.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum PendingWrite {
+    /// A character of the preparation or conclusion.
+    Character(char),
+
+    /// A grapheme cluster of the preparation or conclusion made of more than one character.
+    Grapheme {
+        section_index: usize,
+        start: usize,
+        end: usize,
+    },
+
+    /// A syllable of a mantra.
+    Syllable {
+        mantra_index: usize,
+        repeat: usize,
+        syllable_index: usize,
+    },
+
+    /// The end of a recitation of the preparation or conclusion.
+    SectionEnd,
+
+    /// The flush of the output after the recitation of the sadhana in the given run.
+    Flush(usize),
+}
+
+/// A write that failed and is retried by the next call to [`Recitation::step`].
+#[derive(Clone, Copy, Debug)]
+struct Retry {
+    /// The write that failed.
+    write: PendingWrite,
+
+    /// The number of times the write has been retried.
+    attempts: u32,
+}
+
+/// The delay before the first retry of a failed write, doubled after each retry.
+const RETRY_DELAY: Duration = Duration::from_millis(10);
+
+/// The longest the recitation can fall behind its schedule before the schedule is reset, so that
+/// the miner does not rush to catch up after a long pause.
+const MAX_LAG: Duration = Duration::from_secs(1);
+
+/// The recitation of the sadhana described by the options. Each call to `step` recites the next
+/// syllable or character and records it in the state shared with the mantra miner.
+pub(crate) struct Recitation<T: Write + Send> {
+    /// The reciter that walks through the sadhana.
+    pub(crate) reciter: Reciter,
+
+    /// The buffered output to which the sadhana is recited.
+    pub(crate) output: BufWriter<T>,
+
+    /// The state shared with the mantra miner.
+    pub(crate) state: SharedState,
+
+    /// The bytes written by the default voice for each syllable of the mantras in the options.
+    prepared: PreparedSyllables,
+
+    /// The time at which the last wait should end, from which the next one is scheduled.
+    deadline: Option<Instant>,
+
+    /// The time at which the last call to `step` started, along with the CPU time used by the
+    /// thread at that point if it's available, used to keep the miner under its CPU budget.
+    step_started: Option<(Instant, Option<Duration>)>,
+
+    /// The instant at which the run of the miner that created the recitation started.
+    started: Option<Instant>,
+
+    /// The instant from which the time limit set with `run_for` is measured.
+    run_started: Instant,
+
+    /// The write that failed and is retried by the next step, if any.
+    retry: Option<Retry>,
+
+    /// The delay before retrying the write that failed, returned by the next call to
+    /// `next_delay`.
+    retry_delay: Option<Duration>,
+
+    /// The sampler of the load monitor used in the load-aware mode.
+    pub(crate) load: LoadSampler,
+
+    /// The sampler of the power monitor used to pause on battery.
+    #[cfg(feature = "power")]
+    pub(crate) power: PowerSampler,
+
+    /// The span of the recitation of the sadhana in progress, closed once it completes.
+    #[cfg(feature = "tracing")]
+    sadhana_span: Option<tracing::Span>,
+
+    /// The OpenTelemetry span of the recitation of the sadhana in progress, ended once it
+    /// completes.
+    #[cfg(feature = "otel")]
+    otel_span: Option<opentelemetry::global::BoxedSpan>,
+}
+
+impl<T: Write + Send> Recitation<T> {
+    /// Returns a new recitation of the sadhana that writes to the given output.
+    pub(crate) fn new(options: Options, output: T, state: SharedState) -> Recitation<T> {
+        let started = state.timing.lock().started;
+        let reciter = Reciter::with_seed(options, RandomState::new().hash_one(0));
+        Recitation {
+            prepared: PreparedSyllables::new(reciter.options()),
+            reciter,
+            output: BufWriter::new(output),
+            state,
+            deadline: None,
+            step_started: None,
+            started,
+            run_started: started.unwrap_or_else(Instant::now),
+            retry: None,
+            retry_delay: None,
+            load: LoadSampler::default(),
+            #[cfg(feature = "power")]
+            power: PowerSampler::default(),
+            #[cfg(feature = "tracing")]
+            sadhana_span: None,
+            #[cfg(feature = "otel")]
+            otel_span: None,
+        }
+    }
+
+    /// Returns the time to wait after the last syllable or character recited by `step`, including
+    /// the jitter set in the options. The delay is measured from the time at which the previous
+    /// wait should have ended, so that the time spent writing and the time the operating system
+    /// overslept are subtracted from it instead of slowing the recitation down. When a CPU
+    /// budget is set, the delay is lengthened as needed so that the time spent in the last step
+    /// stays within the budget. In the load-aware mode, the delay is lengthened while the load is
+    /// high. After a failed write that will be retried, the delay before the retry is returned
+    /// instead. The delay never extends past the time limit set with `run_for`, and it's zero in
+    /// the turbo mode.
+    pub(crate) fn next_delay(&mut self) -> Duration {
+        let delay = match self.retry_delay.take() {
+            Some(delay) => delay,
+            None => self.scheduled_delay(),
+        };
+        if self.reciter.options().turbo {
+            return Duration::ZERO;
+        }
+        self.time_left().map_or(delay, |left| delay.min(left))
+    }
+
+    /// Returns the time to wait after the last step, before applying the time limit.
+    fn scheduled_delay(&mut self) -> Duration {
+        if let Some(rate) = self.state.rate.lock().take() {
+            self.reciter.set_rate(rate);
+        }
+        let mut delay = self.reciter.next_delay();
+        if self.reciter.options().load_aware {
+            let slowdown = self.load.slowdown(&mut **self.state.load_monitor.lock());
+            delay = Duration::try_from_secs_f64(delay.as_secs_f64() * slowdown)
+                .unwrap_or(Duration::MAX);
+        }
+        let now = Instant::now();
+        let min_delay = self.min_delay(now);
+
+        // A wait that ended early, such as one interrupted by the caller, is not made up for.
+        let start = match self.deadline {
+            Some(deadline) if deadline <= now && deadline + MAX_LAG >= now => deadline,
+            _ => now,
+        };
+        let Some(earliest) = now.checked_add(min_delay) else {
+            return min_delay;
+        };
+        let deadline = (start + delay).max(earliest);
+        self.deadline = Some(deadline);
+        deadline.saturating_duration_since(now)
+    }
+
+    /// Returns the shortest wait that keeps the CPU time used by the last step within the CPU budget
+    /// set in the options, or zero if there is no budget. The time spent in the step is used when
+    /// the CPU time of the thread is not available.
+    fn min_delay(&mut self, now: Instant) -> Duration {
+        let step_started = self.step_started.take();
+        let (Some(budget), Some((started, cpu_started))) =
+            (self.reciter.options().max_cpu_percent, step_started)
+        else {
+            return Duration::ZERO;
+        };
+
+        // Working for `busy` and then waiting for `busy * (100 - percent) / percent` uses exactly
+        // `percent` percent of the time.
+        let busy = match (cpu_started, timing::thread_cpu_time()) {
+            (Some(cpu_started), Some(cpu_now)) => cpu_now.saturating_sub(cpu_started),
+            _ => now.saturating_duration_since(started),
+        };
+        let percent = budget.percent();
+        Duration::try_from_secs_f64(busy.as_secs_f64() * (100.0 - percent) / percent)
+            .unwrap_or(Duration::MAX)
+    }
+
+    /// Returns the time left before the limit set with `run_for` is reached, or `None` if there is
+    /// no limit.
+    fn time_left(&self) -> Option<Duration> {
+        self.reciter
+            .options()
+            .run_for
+            .map(|run_for| run_for.saturating_sub(self.run_started.elapsed()))
+    }
+
+    /// Returns how long to wait before checking again if the recitation should pause because the
+    /// machine runs on battery, or `None` if it can continue.
+    pub(crate) fn power_pause(&mut self) -> Option<Duration> {
+        #[cfg(feature = "power")]
+        {
+            let policy = self.reciter.options().power_policy;
+            let monitor = &mut **self.state.power_monitor.lock();
+            if self.power.should_pause(policy, monitor) {
+                let interval = self.power.interval();
+                return Some(self.time_left().map_or(interval, |left| interval.min(left)));
+            }
+        }
+        None
+    }
+
+    /// Returns whether the total number of mantra repetitions set with `target_mantras` has been
+    /// reached.
+    fn target_reached(&self) -> bool {
+        self.reciter
+            .options()
+            .target_mantras
+            .is_some_and(|target| self.state.completed_mantras() >= target)
+    }
+
+    /// Flushes the output and records why the recitation finished.
+    fn finish(&mut self, reason: FinishReason) -> Result<Option<Recited>> {
+        *self.state.finish_reason.lock() = Some(reason);
+        self.flush()?;
+        Ok(None)
+    }
+
+    /// Replaces the options of the reciter with the given ones, and prepares the syllables of their
+    /// mantras.
+    pub(crate) fn set_options(&mut self, options: Options) {
+        self.state.update_mantra_stats(&options);
+        self.reciter.set_options(options);
+        self.prepared = PreparedSyllables::new(self.reciter.options());
+    }
+
+    /// Flushes the voice, recording the failure if it returns an error.
+    fn flush(&mut self) -> Result<()> {
+        self.with_voice(|voice, _| voice.flush())
+            .map_err(|error| self.fail(None, &error))
+    }
+
+    /// Calls the given function with the voice set with `MantraMiner::set_voice`, or with the
+    /// default voice that writes to the output if none is set, and with the options.
+    fn with_voice<R>(&mut self, f: impl FnOnce(&mut dyn Voice, &Options) -> R) -> R {
+        let options = self.reciter.options();
+        let mut custom = self.state.voice.lock();
+        match &mut *custom {
+            Some(voice) => f(voice.as_mut(), options),
+            None => f(
+                &mut WriterVoice {
+                    output: &mut self.output,
+                    options,
+                    started: self.run_started,
+                },
+                options,
+            ),
+        }
+    }
+
+    /// Recites the given syllable of the given mantra with the voice set with
+    /// `MantraMiner::set_voice`, or by writing its prepared bytes to the output if none is set.
+    fn recite_syllable(&mut self, mantra_index: usize, syllable_index: usize) -> io::Result<()> {
+        let options = self.reciter.options();
+        let syllable = &options.mantras[mantra_index].syllables[syllable_index];
+        let mut custom = self.state.voice.lock();
+        match (
+            &mut *custom,
+            self.prepared.get(mantra_index, syllable_index),
+        ) {
+            (Some(voice), _) => voice.recite_syllable(syllable),
+            (None, Some(bytes)) => self.output.write_all(bytes),
+            (None, None) => WriterVoice {
+                output: &mut self.output,
+                options,
+                started: self.run_started,
+            }
+            .recite_syllable(syllable),
+        }
+    }
+
+    /// Passes the error returned by the output while writing the given text, or while flushing if
+    /// there is no text, to the callback set with `MantraMiner::on_error` and returns it. The
+    /// position of the reciter is that of the syllable or character being written.
+    fn report(&self, text: Option<&str>, error: &io::Error) -> RecitationError {
+        let error = RecitationError::new(self.reciter.progress(), text, error);
+        if let Some(callback) = &mut *self.state.error_callback.lock() {
+            callback(&error);
+        }
+        error
+    }
+
+    /// Reports the error returned by the output as in `report`, records it in the state shared
+    /// with the miner as the error that stopped the recitation, and returns it.
+    fn fail(&self, text: Option<&str>, error: &io::Error) -> Error {
+        let error = self.report(text, error);
+        *self.state.last_error.lock() = Some(error.clone());
+        Error::RecitationFailed(error)
+    }
+
+    /// Performs the given write and records it once it succeeds. Returns what `step` should
+    /// return, or `None` if the recitation should move on to the next event. An error is handled
+    /// according to the error policy in the options: a skipped write is dropped, a write retried
+    /// returns `Recited::Retry`, and otherwise the error stops the recitation. When the options
+    /// skip the output, the write is recorded without being performed.
+    fn write(&mut self, write: PendingWrite, attempts: u32) -> Result<Option<Recited>> {
+        if self.reciter.options().skip_output {
+            return Ok(self.record(write));
+        }
+        let result = match write {
+            PendingWrite::Syllable {
+                mantra_index,
+                syllable_index,
+                ..
+            } => self.recite_syllable(mantra_index, syllable_index),
+            PendingWrite::Character(c) => self.with_voice(|voice, _| voice.recite_character(c)),
+            PendingWrite::Grapheme {
+                section_index,
+                start,
+                end,
+            } => {
+                let text = self.reciter.text(section_index)[start..end].to_string();
+                self.with_voice(|voice, _| voice.recite_grapheme(&text))
+            }
+            PendingWrite::SectionEnd => self.with_voice(|voice, _| voice.end_section()),
+            PendingWrite::Flush(_) => self.with_voice(|voice, _| voice.flush()),
+        };
+
+        let error = match result {
+            Ok(()) => return Ok(self.record(write)),
+            Err(error) => error,
+        };
+        let text = match write {
+            PendingWrite::Character(c) => Some(c.to_string()),
+            PendingWrite::Grapheme {
+                section_index,
+                start,
+                end,
+            } => Some(self.reciter.text(section_index)[start..end].to_string()),
+            PendingWrite::Syllable {
+                mantra_index,
+                syllable_index,
+                ..
+            } => Some(
+                self.reciter.options().mantras[mantra_index].syllables[syllable_index].to_string(),
+            ),
+            PendingWrite::SectionEnd => Some("\n".to_string()),
+            PendingWrite::Flush(_) => None,
+        };
+        match self.reciter.options().error_policy {
+            ErrorPolicy::Skip => {
+                self.report(text.as_deref(), &error);
+                // The sadhana was recited even if the output could not be flushed, and the
+                // buffered text is written by the next flush that succeeds.
+                if let PendingWrite::Flush(run) = write {
+                    self.state.record_sadhana(run);
+                }
+                Ok(None)
+            }
+            ErrorPolicy::Retry(max_attempts) if attempts < max_attempts => {
+                self.report(text.as_deref(), &error);
+                self.retry = Some(Retry {
+                    write,
+                    attempts: attempts + 1,
+                });
+                self.retry_delay = Some(RETRY_DELAY.saturating_mul(1 << attempts.min(16)));
+                Ok(Some(Recited::Retry))
+            }
+            _ => Err(self.fail(text.as_deref(), &error)),
+        }
+    }
+
+    /// Records the given write, which succeeded, in the state shared with the miner. Returns the
+    /// unit recited, or `None` if the write was a flush.
+    fn record(&mut self, write: PendingWrite) -> Option<Recited> {
+        let recited = match write {
+            PendingWrite::Character(c) => {
+                self.state.record_character();
+                Recited::Character(c)
+            }
+            PendingWrite::Grapheme {
+                section_index,
+                start,
+                end,
+            } => {
+                self.state.record_character();
+                Recited::Grapheme {
+                    section_index,
+                    start,
+                    end,
+                }
+            }
+            PendingWrite::Syllable {
+                mantra_index,
+                repeat,
+                syllable_index,
+            } => {
+                let syllable =
+                    &self.reciter.options().mantras[mantra_index].syllables[syllable_index];
+                self.state.record_syllable(mantra_index, syllable);
+                Recited::Syllable {
+                    mantra_index,
+                    repeat,
+                    syllable_index,
+                }
+            }
+            PendingWrite::SectionEnd => return None,
+            PendingWrite::Flush(run) => {
+                self.state.record_sadhana(run);
+                return None;
+            }
+        };
+        *self.state.progress.lock() = self.reciter.progress();
+        self.save_position();
+        Some(recited)
+    }
+
+    /// Copies the checkpoint of the reciter and the progress of the sadhana to the shared state.
+    fn save_position(&self) {
+        *self.state.checkpoint.lock() = self.reciter.checkpoint();
+        *self.state.sadhana_progress.lock() = self.reciter.sadhana_progress();
+    }
+
+    /// Moves the reciter to the given checkpoint, from which the recitation continues.
+    pub(crate) fn resume(&mut self, checkpoint: Checkpoint) {
+        self.reciter.resume(checkpoint);
+        self.save_position();
+    }
+
+    /// Records that the sadhana in progress was interrupted, if part of it has been recited by
+    /// this recitation.
+    fn record_interruption(&self) {
+        if self.reciter.syllable_count() > 0 && self.reciter.checkpoint().is_some() {
+            self.state.record_interruption();
+        }
+    }
+
+    /// Logs the number of malas completed so far if the last mantra repetition, which brought the
+    /// total from the given number of repetitions, completed a mala. With a multiplier, a single
+    /// repetition can step over the end of a mala without landing on it.
+    #[cfg(feature = "log")]
+    fn log_mala(&self, previous: u64) {
+        let mala = &self.reciter.options().mala;
+        if mala.beads_per_mala == 0 || mala.malas_per_bum == 0 {
+            return;
+        }
+        let repetitions = self.state.completed_mantras() as usize;
+        if repetitions / mala.beads_per_mala > previous as usize / mala.beads_per_mala {
+            let count = mala.count(repetitions);
+            log::debug!(
+                target: &self.state.log_target.lock(),
+                "completed mala ({} bums and {} malas in total)",
+                count.bums,
+                count.malas
+            );
+        }
+    }
+
+    /// Restarts the recitation from the beginning of the sadhana, after a panic left the reciter
+    /// in the middle of it. The counts and statistics recorded so far are kept.
+    pub(crate) fn restart(&mut self) {
+        self.record_interruption();
+        let options = self.reciter.options().clone();
+        self.reciter = Reciter::with_seed(options, RandomState::new().hash_one(0));
+        self.state.progress.lock().take();
+        self.save_position();
+        self.deadline = None;
+        self.step_started = None;
+        self.retry = None;
+        self.retry_delay = None;
+    }
+
+    /// Recites the next syllable or character of the sadhana. Returns `None` once the sadhana has
+    /// been recited the number of times set in the options, the time limit set with `run_for` has
+    /// been reached, or the total number of mantra repetitions set with `target_mantras` has been
+    /// reached. The output is flushed after every recitation of the sadhana. Returns
+    /// `Recited::Retry` if a write failed and the error policy retries it.
+    pub(crate) fn step(&mut self) -> Result<Option<Recited>> {
+        self.step_started = Some((Instant::now(), timing::thread_cpu_time()));
+        if let Some(retry) = self.retry.take() {
+            if let Some(recited) = self.write(retry.write, retry.attempts)? {
+                return Ok(Some(recited));
+            }
+        }
+        if self.time_left() == Some(Duration::ZERO) {
+            return self.finish(FinishReason::TimeLimit);
+        }
+        if self.target_reached() {
+            return self.finish(FinishReason::TargetReached);
+        }
+        while let Some(event) = self.reciter.next_event() {
+            match event {
+                ReciterEvent::SadhanaStarted { run } => {
+                    // The playlist decides which sadhana to recite, but not how many times.
+                    if let Some(options) = self.state.next_playlist_options() {
+                        let options = Options {
+                            repeats: self.reciter.options().repeats,
+                            ..options
+                        };
+                        self.set_options(options);
+                    }
+                    let options = self.state.options.lock().take();
+                    if let Some(options) = options {
+                        self.set_options(options);
+                    }
+                    self.state.start_sadhana();
+                    #[cfg(feature = "tracing")]
+                    {
+                        self.sadhana_span = Some(tracing::info_span!("sadhana", run));
+                    }
+                    #[cfg(feature = "otel")]
+                    {
+                        self.otel_span = Some(self.state.otel.sadhana_span(run));
+                    }
+                    self.state
+                        .subscribers
+                        .emit(MinerEvent::SadhanaStarted { run });
+                }
+                ReciterEvent::Character(c) => {
+                    if let Some(recited) = self.write(PendingWrite::Character(c), 0)? {
+                        return Ok(Some(recited));
+                    }
+                }
+                ReciterEvent::Grapheme(grapheme) => {
+                    // The checkpoint is right after the grapheme cluster, in its text.
+                    let length = grapheme.len();
+                    let Some(checkpoint) = self.reciter.checkpoint() else {
+                        continue;
+                    };
+                    let write = PendingWrite::Grapheme {
+                        section_index: checkpoint.section_index,
+                        start: checkpoint.unit - length,
+                        end: checkpoint.unit,
+                    };
+                    if let Some(recited) = self.write(write, 0)? {
+                        return Ok(Some(recited));
+                    }
+                }
+                ReciterEvent::TextCompleted { text } => {
+                    self.state.record_text(text);
+                    self.save_position();
+                    if let Some(recited) = self.write(PendingWrite::SectionEnd, 0)? {
+                        return Ok(Some(recited));
+                    }
+                }
+                ReciterEvent::Syllable {
+                    mantra_index,
+                    repeat,
+                    syllable_index,
+                    ..
+                } => {
+                    let write = PendingWrite::Syllable {
+                        mantra_index,
+                        repeat,
+                        syllable_index,
+                    };
+                    if let Some(recited) = self.write(write, 0)? {
+                        return Ok(Some(recited));
+                    }
+                }
+                ReciterEvent::MantraCompleted { index, repeat } => {
+                    #[cfg(feature = "log")]
+                    let previous = self.state.completed_mantras();
+                    {
+                        #[cfg(feature = "tracing")]
+                        let _entered = self.sadhana_span.as_ref().map(tracing::Span::enter);
+                        let credited = self.reciter.options().multiplier();
+                        self.state.record_mantra(index, repeat, credited);
+                    }
+                    self.save_position();
+                    #[cfg(feature = "log")]
+                    self.log_mala(previous);
+                    if self.target_reached() {
+                        return self.finish(FinishReason::TargetReached);
+                    }
+                }
+                ReciterEvent::SadhanaCompleted { run } => {
+                    let recited = self.write(PendingWrite::Flush(run), 0)?;
+                    #[cfg(feature = "tracing")]
+                    self.sadhana_span.take();
+                    #[cfg(feature = "otel")]
+                    self.otel_span.take();
+                    if let Some(recited) = recited {
+                        return Ok(Some(recited));
+                    }
+                }
+            }
+        }
+        self.finish(FinishReason::Repeats)
+    }
+}
+
+impl<T: Write + Send> Drop for Recitation<T> {
+    /// Records that the run has ended, since the recitation is dropped once its task exits, counts
+    /// the sadhana it interrupted, if any, and saves the checkpoint to the count store.
+    fn drop(&mut self) {
+        self.state.timing.lock().end(self.started);
+        self.record_interruption();
+        self.state.save_checkpoint();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use parking_lot::Mutex;
+    use std::{
+        collections::VecDeque,
+        io::{self, BufWriter, Write},
+        sync::Arc,
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use crate::{
+        recitation::{Recitation, Recited},
+        sadhana, timing, CpuPercent, Error, ErrorPolicy, FinishReason, Mantra, Options,
+        OutputFormat, RecitationError, SharedState, Timestamp,
+    };
+
+    fn recitation(options: Options) -> Recitation<Vec<u8>> {
+        let state = SharedState::new(&options);
+        Recitation::new(options, Vec::new(), state)
+    }
+
+    /// Runs the recitation to completion and returns the recited units.
+    fn recite_all(recitation: &mut Recitation<Vec<u8>>) -> Result<Vec<Recited>> {
+        let mut recited = Vec::new();
+        while let Some(unit) = recitation.step()? {
+            recited.push(unit);
+        }
+        Ok(recited)
+    }
+
+    #[test]
+    fn recite_sadhana() -> Result<()> {
+        let mut recitation = recitation(Options {
+            preparation: Some("ab".to_string()),
+            preparation_repeats: Some(2),
+            mantras: vec![Mantra::from_text("om ah hum"), Mantra::from_text("hri")],
+            conclusion: Some("é".to_string()),
+            repeats: Some(2),
+            ..Default::default()
+        });
+        let recited = recite_all(&mut recitation)?;
+        assert_eq!(recited.len(), 2 * (4 + 3 + 1 + 1));
+        assert_eq!(recited[0], Recited::Character('a'));
+        assert_eq!(
+            recited[6],
+            Recited::Syllable {
+                mantra_index: 0,
+                repeat: 0,
+                syllable_index: 2
+            }
+        );
+        assert_eq!(recited[8], Recited::Character('é'));
+        assert_eq!(
+            String::from_utf8(recitation.output.get_ref().clone())?,
+            "ababom\nah\nhum\nhri\né".repeat(2)
+        );
+        assert_eq!(recitation.state.count(), 2);
+
+        // The recitation is complete, so further steps do nothing.
+        assert_eq!(recitation.step()?, None);
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "graphemes")]
+    fn recite_graphemes() -> Result<()> {
+        let mut recitation = recitation(Options {
+            preparation: Some("ཨོཾ".to_string()),
+            mantras: vec![Mantra::from_text("hri")],
+            repeats: Some(1),
+            ..Default::default()
+        });
+        let recited = recite_all(&mut recitation)?;
+        assert_eq!(
+            recited[0],
+            Recited::Grapheme {
+                section_index: 0,
+                start: 0,
+                end: "ཨོཾ".len()
+            }
+        );
+        assert_eq!(recited.len(), 2);
+        assert_eq!(
+            String::from_utf8(recitation.output.get_ref().clone())?,
+            "ཨོཾhri\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn skip_output() -> Result<()> {
+        let mut recitation = recitation(Options {
+            mantras: vec![Mantra::from_text("om ah hum")],
+            skip_output: true,
+            repeats: Some(2),
+            ..Default::default()
+        });
+        let recited = recite_all(&mut recitation)?;
+        assert_eq!(recited.len(), 6);
+        assert!(recitation.output.get_ref().is_empty());
+        assert_eq!(recitation.state.count(), 2);
+        assert_eq!(recitation.state.completed_mantras(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn turbo() -> Result<()> {
+        let mut recitation = recitation(Options {
+            mantras: vec![Mantra::from_text("om ah hum")],
+            rate: Duration::from_secs(1),
+            jitter: Some(Duration::from_millis(100)),
+            turbo: true,
+            repeats: Some(1),
+            ..Default::default()
+        });
+        let mut delays = Vec::new();
+        while recitation.step()?.is_some() {
+            delays.push(recitation.next_delay());
+        }
+        assert_eq!(delays, vec![Duration::ZERO; 3]);
+        assert_eq!(
+            String::from_utf8(recitation.output.get_ref().clone())?,
+            "om\nah\nhum\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn write_error() -> Result<()> {
+        /// A writer that accepts the given number of writes and rejects the rest.
+        struct FailingWriter(usize);
+
+        impl Write for FailingWriter {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                if self.0 == 0 {
+                    return Err(io::Error::other("disk full"));
+                }
+                self.0 -= 1;
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let options = Options {
+            mantras: vec![Mantra::from_text("om ah hum")],
+            ..Default::default()
+        };
+        let state = SharedState::new(&options);
+        let mut recitation = Recitation::new(options, FailingWriter(0), state);
+        recitation.output = BufWriter::with_capacity(0, FailingWriter(1));
+
+        // The first syllable is written along with its newline, and the second syllable fails.
+        recitation.step()?;
+        let Err(Error::RecitationFailed(error)) = recitation.step() else {
+            panic!("expected the recitation to fail");
+        };
+        assert_eq!(error.text.as_deref(), Some("ah"));
+        assert_eq!(error.progress.unwrap().syllable_index, Some(1));
+        assert_eq!(*recitation.state.last_error.lock(), Some(error.clone()));
+        assert_eq!(
+            error.to_string(),
+            "failed to write \"ah\" at syllable 1 of repetition 0 of mantra 0 of sadhana 0: disk full"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn write_error_in_text() -> Result<()> {
+        /// A writer that rejects the writes of the letter `c`.
+        struct FailingWriter;
+
+        impl Write for FailingWriter {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                if buf == b"c" {
+                    return Err(io::Error::other("disk full"));
+                }
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        // The text between the two sections of mantras is reported by its index.
+        let mantra = || sadhana::Section::Mantra {
+            index: 0,
+            repeats: Some(1),
+            frequency: None,
+        };
+        let text = |text: &str| sadhana::Section::Text {
+            text: text.to_string(),
+            repeats: None,
+            rate: None,
+            frequency: None,
+        };
+        let options = Options {
+            mantras: vec![Mantra::from_text("om")],
+            sections: vec![text("ab"), mantra(), text("cd"), mantra()],
+            ..Default::default()
+        };
+        let state = SharedState::new(&options);
+        let mut recitation = Recitation::new(options, FailingWriter, state);
+        recitation.output = BufWriter::with_capacity(0, FailingWriter);
+        let error = loop {
+            if let Err(Error::RecitationFailed(error)) = recitation.step() {
+                break error;
+            }
+        };
+        assert_eq!(error.progress.unwrap().section_index, 2);
+        assert_eq!(
+            error.to_string(),
+            "failed to write \"c\" in section 2 of sadhana 0: disk full"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn error_policy() -> Result<()> {
+        /// A writer whose writes succeed or fail in the given order, and succeed afterwards.
+        struct ScriptedWriter(VecDeque<bool>);
+
+        impl Write for ScriptedWriter {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                match self.0.pop_front() {
+                    Some(false) => Err(io::Error::other("disk full")),
+                    _ => Ok(buf.len()),
+                }
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let recitation = |error_policy, script: &[bool]| {
+            let options = Options {
+                mantras: vec![Mantra::from_text("om ah hum")],
+                error_policy,
+                repeats: Some(1),
+                ..Default::default()
+            };
+            let state = SharedState::new(&options);
+            let errors = Arc::new(Mutex::new(Vec::new()));
+            let reported = errors.clone();
+            *state.error_callback.lock() = Some(Box::new(move |error: &RecitationError| {
+                reported.lock().push(error.text.clone().unwrap_or_default());
+            }));
+            let mut recitation = Recitation::new(options, ScriptedWriter(VecDeque::new()), state);
+            recitation.output =
+                BufWriter::with_capacity(0, ScriptedWriter(script.iter().copied().collect()));
+            (recitation, errors)
+        };
+        let om = Recited::Syllable {
+            mantra_index: 0,
+            repeat: 0,
+            syllable_index: 0,
+        };
+
+        // The syllables that cannot be written are skipped, and the sadhana is still counted.
+        let (mut skip, errors) = recitation(ErrorPolicy::Skip, &[true, false, false]);
+        assert_eq!(skip.step()?, Some(om));
+        assert_eq!(skip.step()?, None);
+        assert_eq!(*errors.lock(), ["ah", "hum"]);
+        assert_eq!(skip.state.syllables(), 1);
+        assert_eq!(skip.state.count(), 1);
+        assert_eq!(*skip.state.last_error.lock(), None);
+
+        // The syllable is written once a retry succeeds, waiting longer after each retry.
+        let (mut retry, errors) = recitation(ErrorPolicy::Retry(2), &[false, false]);
+        assert_eq!(retry.step()?, Some(Recited::Retry));
+        assert_eq!(retry.next_delay(), Duration::from_millis(10));
+        assert_eq!(retry.step()?, Some(Recited::Retry));
+        assert_eq!(retry.next_delay(), Duration::from_millis(20));
+        assert_eq!(retry.step()?, Some(om));
+        assert_eq!(*errors.lock(), ["om", "om"]);
+
+        // The recitation fails once the retries are exhausted.
+        let (mut abort, errors) = recitation(ErrorPolicy::Retry(1), &[false, false]);
+        assert_eq!(abort.step()?, Some(Recited::Retry));
+        assert!(matches!(abort.step(), Err(Error::RecitationFailed(_))));
+        assert_eq!(*errors.lock(), ["om", "om"]);
+        assert!(abort.state.last_error.lock().is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn timestamps() -> Result<()> {
+        let recite = |timestamp| -> Result<String> {
+            let mut recitation = recitation(Options {
+                mantras: vec![Mantra::from_text("om ah")],
+                format: OutputFormat {
+                    timestamp: Some(timestamp),
+                    ..Default::default()
+                },
+                repeats: Some(1),
+                ..Default::default()
+            });
+            recite_all(&mut recitation)?;
+            Ok(String::from_utf8(recitation.output.get_ref().clone())?)
+        };
+
+        let output = recite(Timestamp::Monotonic)?;
+        let lines: Vec<_> = output.lines().collect();
+        assert_eq!(lines.len(), 2);
+        for (line, syllable) in lines.iter().zip(["om", "ah"]) {
+            let (timestamp, text) = line.split_once(' ').unwrap();
+            assert_eq!(text, syllable);
+            let seconds = timestamp
+                .strip_prefix('+')
+                .unwrap()
+                .strip_suffix('s')
+                .unwrap();
+            assert!(seconds.parse::<f64>()? < 60.0);
+        }
+
+        let output = recite(Timestamp::WallClock)?;
+        for line in output.lines() {
+            let (timestamp, _) = line.split_once(' ').unwrap();
+            assert!(humantime::parse_rfc3339(timestamp).is_ok());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn recite_repeated_mantra() -> Result<()> {
+        let mantra = Mantra {
+            repeats: Some(3),
+            ..Mantra::from_text("om ah")
+        };
+        let mut recitation = recitation(Options {
+            mantras: vec![mantra],
+            repeats: Some(1),
+            ..Default::default()
+        });
+        recite_all(&mut recitation)?;
+        assert_eq!(recitation.output.get_ref(), "om\nah\n".repeat(3).as_bytes());
+        assert_eq!(recitation.state.mantra_stats.lock()[0].completed, 3);
+        Ok(())
+    }
+
+    #[test]
+    fn target_schedule() -> Result<()> {
+        // One mantra of two syllables per second is one syllable every 500ms.
+        let mut recitation = recitation(Options {
+            mantras: vec![Mantra::from_text("om ah")],
+            mantras_per_day: Some(86_400),
+            ..Default::default()
+        });
+        recitation.step()?;
+        let first = recitation.next_delay();
+        assert!(first <= Duration::from_millis(500));
+        assert!(first > Duration::from_millis(450));
+
+        // The time spent before asking for the next delay is subtracted from it.
+        thread::sleep(first + Duration::from_millis(100));
+        recitation.step()?;
+        assert!(recitation.next_delay() <= Duration::from_millis(400));
+        Ok(())
+    }
+
+    #[test]
+    fn deadline_pacing() -> Result<()> {
+        let mut recitation = recitation(Options {
+            mantras: vec![Mantra::from_text("om ah hum")],
+            rate: Duration::from_millis(100),
+            ..Default::default()
+        });
+        recitation.step()?;
+        let first = recitation.next_delay();
+        assert_eq!(first, Duration::from_millis(100));
+
+        // The time by which the last wait overran is subtracted from the next one, so that the
+        // recitation keeps to the rate over time.
+        thread::sleep(first + Duration::from_millis(40));
+        recitation.step()?;
+        assert!(recitation.next_delay() <= Duration::from_millis(60));
+
+        // After a long pause, the schedule starts over instead of rushing to catch up.
+        thread::sleep(Duration::from_millis(1200));
+        recitation.step()?;
+        assert!(recitation.next_delay() > Duration::from_millis(90));
+        Ok(())
+    }
+
+    #[test]
+    fn run_for() -> Result<()> {
+        let mut recitation = recitation(Options {
+            mantras: vec![Mantra::from_text("om ah")],
+            rate: Duration::from_secs(60),
+            run_for: Some(Duration::from_millis(50)),
+            ..Default::default()
+        });
+
+        // The wait does not extend past the time limit, after which the recitation ends.
+        assert!(recitation.step()?.is_some());
+        let delay = recitation.next_delay();
+        assert!(delay <= Duration::from_millis(50));
+        thread::sleep(delay);
+        assert_eq!(recitation.step()?, None);
+        assert_eq!(recitation.output.get_ref(), b"om\n");
+        assert_eq!(
+            *recitation.state.finish_reason.lock(),
+            Some(FinishReason::TimeLimit)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn target_mantras() -> Result<()> {
+        let mut recitation = recitation(Options {
+            mantras: vec![Mantra::from_text("om ah"), Mantra::from_text("hri")],
+            target_mantras: Some(3),
+            ..Default::default()
+        });
+
+        // The recitation stops as soon as the third repetition is completed, in the second sadhana.
+        let recited = recite_all(&mut recitation)?;
+        assert_eq!(recited.len(), 2 + 1 + 2);
+        assert_eq!(recitation.output.get_ref(), b"om\nah\nhri\nom\nah\n");
+        assert_eq!(
+            *recitation.state.finish_reason.lock(),
+            Some(FinishReason::TargetReached)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn cpu_budget() -> Result<()> {
+        let mut recitation = recitation(Options {
+            mantras: vec![Mantra::from_text("om ah")],
+            rate: Duration::from_micros(1),
+            max_cpu_percent: CpuPercent::new(1.0),
+            ..Default::default()
+        });
+
+        // A step that uses 10ms of CPU time must be followed by a wait of at least 990ms to use 1%
+        // of a core.
+        recitation.step()?;
+        let cpu_started = timing::thread_cpu_time();
+        let started = Instant::now();
+        let busy = || match (cpu_started, timing::thread_cpu_time()) {
+            (Some(cpu_started), Some(cpu_now)) => cpu_now.saturating_sub(cpu_started),
+            _ => started.elapsed(),
+        };
+        while busy() < Duration::from_millis(10) {}
+        assert!(recitation.next_delay() >= Duration::from_millis(990));
+
+        // The time the thread spends sleeping does not use the budget.
+        if timing::thread_cpu_time().is_some() {
+            recitation.step()?;
+            thread::sleep(Duration::from_millis(50));
+            assert!(recitation.next_delay() < Duration::from_millis(990));
+        }
+
+        // Without a budget, the rate is used as is.
+        let options = Options {
+            max_cpu_percent: None,
+            ..recitation.reciter.options().clone()
+        };
+        recitation.set_options(options);
+        recitation.step()?;
+        thread::sleep(Duration::from_millis(10));
+        assert!(recitation.next_delay() < Duration::from_millis(10));
+        Ok(())
+    }
+
+    #[test]
+    fn load_aware() -> Result<()> {
+        let mut recitation = recitation(Options {
+            mantras: vec![Mantra::from_text("om ah")],
+            rate: Duration::from_millis(10),
+            load_aware: true,
+            ..Default::default()
+        });
+        *recitation.state.load_monitor.lock() = Box::new(|| 1.5);
+
+        // A load of 1.5 per core is twice the threshold, so the delay is 2^4 times longer.
+        recitation.step()?;
+        assert_eq!(recitation.next_delay(), Duration::from_millis(160));
+
+        // The configured rate is used again once the load drops.
+        recitation.load.interval = Duration::ZERO;
+        *recitation.state.load_monitor.lock() = Box::new(|| 0.1);
+        recitation.step()?;
+        assert_eq!(recitation.next_delay(), Duration::from_millis(10));
+        Ok(())
+    }
+
+    #[cfg(feature = "power")]
+    #[test]
+    fn power_pause() -> Result<()> {
+        use crate::{PowerPolicy, PowerStatus};
+
+        let mut recitation = recitation(Options {
+            mantras: vec![Mantra::from_text("om ah")],
+            rate: Duration::from_millis(10),
+            power_policy: PowerPolicy::PauseBelowCharge(20),
+            ..Default::default()
+        });
+        recitation.power.interval = Duration::ZERO;
+        let status = |on_battery, charge| {
+            move || {
+                Some(PowerStatus {
+                    on_battery,
+                    charge_percent: Some(charge),
+                })
+            }
+        };
+
+        *recitation.state.power_monitor.lock() = Box::new(status(true, 50));
+        assert_eq!(recitation.power_pause(), None);
+        *recitation.state.power_monitor.lock() = Box::new(status(true, 10));
+        assert_eq!(recitation.power_pause(), Some(Duration::ZERO));
+
+        // The recitation resumes once the machine is plugged in.
+        *recitation.state.power_monitor.lock() = Box::new(status(false, 10));
+        assert_eq!(recitation.power_pause(), None);
+        Ok(())
+    }
+}
@@ -0,0 +1,1491 @@
+//! Contains the `no_std` core of the mantra miner, which walks through the sadhana described by the
+//! options one syllable or character at a time and keeps count of what has been recited. The core
+//! does not write to any output nor wait between syllables, so that it can be driven by a thread,
+//! an asynchronous task, or the timers of a microcontroller.
+
+use alloc::{collections::VecDeque, vec::Vec};
+use core::{ops::Range, time::Duration};
+use serde::{Deserialize, Serialize};
+
+use crate::{sadhana, Interleave, Options};
+
+/// The seed of the generator used for the jitter when none is given.
+const DEFAULT_SEED: u64 = 0x9e37_79b9_7f4a_7c15;
+
+/// The part of the sadhana being recited. With the sections set in `Options::sections`, the text
+/// sections before the first mantra are part of the preparation, and those after it are part of
+/// the conclusion.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Section {
+    /// The preparation, recited character by character, or any other section before the first
+    /// section of mantras.
+    #[default]
+    Preparation,
+
+    /// The mantras, recited syllable by syllable.
+    Mantras,
+
+    /// The conclusion, recited character by character, or any other section after the first
+    /// section of mantras, including those between two sections of mantras.
+    Conclusion,
+}
+
+/// The position of the reciter within the sadhana.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+struct Position {
+    /// The number of recitations of the sadhana completed since the reciter was created.
+    run: usize,
+
+    /// Whether the recitation of the current sadhana has started.
+    started: bool,
+
+    /// The index of the section being recited in the sections of the sadhana.
+    section: usize,
+
+    /// The zero-based number of the repetition of the section being recited, or of the turn being
+    /// taken if the mantras of the section are interleaved.
+    repeat: usize,
+
+    /// The index of the next syllable of the mantra or of the turn, or the byte offset of the next
+    /// character of the text.
+    unit: usize,
+
+    /// The index of the mantra drawn for the current sadhana, if a single mantra is recited in
+    /// each.
+    selected: Option<usize>,
+
+    /// The number of the current recitation of the sadhana, which decides the sections recited
+    /// only every few sadhanas that are part of it.
+    cycle: usize,
+}
+
+/// The position within the sadhana of the last character or syllable produced by the reciter.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Progress {
+    /// The number of recitations of the sadhana completed before the current one.
+    pub run: usize,
+
+    /// The part of the sadhana being recited.
+    pub section: Section,
+
+    /// The index of the section being recited in the sections of the sadhana returned by
+    /// `Options::sadhana_sections`.
+    pub section_index: usize,
+
+    /// The index in the options of the mantra being recited, if the section is the mantras.
+    pub mantra_index: Option<usize>,
+
+    /// The zero-based number of the repetition of the section being recited.
+    pub repeat: usize,
+
+    /// The index of the syllable in the mantra, if the section is the mantras.
+    pub syllable_index: Option<usize>,
+}
+
+/// The position within a sadhana right after the last event produced by the reciter, from which
+/// the recitation can be resumed with [`Reciter::resume`]. It can be serialized to resume the
+/// recitation after the program restarts.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Checkpoint {
+    /// The index of the section being recited in the sections of the sadhana returned by
+    /// `Options::sadhana_sections`.
+    pub section_index: usize,
+
+    /// The zero-based number of the repetition of the section being recited, or of the turn being
+    /// taken if the mantras of the section are interleaved.
+    pub repeat: usize,
+
+    /// The index of the next syllable of the mantra or of the turn, or the byte offset of the next
+    /// character of the text.
+    pub unit: usize,
+
+    /// The index of the mantra drawn for the sadhana, if the options recite a single mantra in
+    /// each.
+    #[serde(default)]
+    pub selected_mantra: Option<usize>,
+
+    /// The number of the recitation of the sadhana, which decides the sections recited only every
+    /// few sadhanas that are part of it. The sadhanas that follow a resumed one are numbered from
+    /// it.
+    #[serde(default)]
+    pub cycle: usize,
+}
+
+impl Checkpoint {
+    /// Returns the fraction of the sadhana described by the options that has been recited up to
+    /// this checkpoint, counting each character of a text and each syllable of a mantra as one
+    /// unit. The fraction is between zero and one.
+    pub fn fraction(&self, options: &Options) -> f64 {
+        let sections = options.sadhana_sections();
+        self.fraction_of(&sections, &options.interleaved(&sections), options)
+    }
+
+    /// Returns the fraction of the sadhana made of the given sections recited up to this
+    /// checkpoint, with the mantras in the given ranges of sections interleaved. The sections of
+    /// the mantras that were not drawn and those not recited in this cycle are not part of the
+    /// sadhana.
+    fn fraction_of(
+        &self,
+        sections: &[sadhana::Section],
+        interleaved: &[Range<usize>],
+        options: &Options,
+    ) -> f64 {
+        let mantras = &options.mantras;
+        let total_units = |section: &sadhana::Section| {
+            if options.recites(section, self.selected_mantra, self.cycle) {
+                section.units(mantras) * section.repeats(mantras)
+            } else {
+                0
+            }
+        };
+        let total: usize = sections.iter().map(total_units).sum();
+        if total == 0 {
+            return 0.0;
+        }
+
+        // The units of the sections before the checkpoint, and of the repetitions completed in the
+        // current section and of the current one so far. When the mantras are interleaved, those
+        // of the turns taken by each of them.
+        let range = interleaved_range(interleaved, self.section_index);
+        let start = range.map_or(self.section_index, |range| range.start);
+        let mut recited: usize = sections.iter().take(start).map(total_units).sum();
+        if let Some(range) = range {
+            for index in range.clone() {
+                let (turns, length) =
+                    options.turns(&sections[index], self.selected_mantra, self.cycle);
+                let taken = if index < self.section_index {
+                    self.repeat + 1
+                } else {
+                    self.repeat
+                };
+                recited += taken.min(turns) * length;
+            }
+            recited += self.unit;
+        } else if let Some(section) = sections.get(self.section_index) {
+            recited += self.repeat * section.units(mantras);
+            recited += match section {
+                sadhana::Section::Text { text, .. } => {
+                    text.get(..self.unit).map_or(0, sadhana::count_units)
+                }
+                sadhana::Section::Mantra { .. } => self.unit,
+                sadhana::Section::Silence { .. } | sadhana::Section::Group { .. } => 0,
+            };
+        }
+        (recited as f64 / total as f64).min(1.0)
+    }
+}
+
+/// Returns the range among the given sorted ranges of interleaved mantras that contains the
+/// section at the given index, if any.
+fn interleaved_range(interleaved: &[Range<usize>], section: usize) -> Option<&Range<usize>> {
+    let next = interleaved.partition_point(|range| range.end <= section);
+    interleaved
+        .get(next)
+        .filter(|range| range.contains(&section))
+}
+
+/// An event produced by [`Reciter::next_event`] as the reciter walks through the sadhana.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ReciterEvent<'a> {
+    /// A new recitation of the sadhana has started.
+    SadhanaStarted {
+        /// The number of recitations completed since the reciter was created.
+        run: usize,
+    },
+
+    /// A character of a text should be recited.
+    Character(char),
+
+    /// A grapheme cluster of a text made of more than one character, such as a letter followed by
+    /// its combining marks, should be recited. It's only produced with the `graphemes` feature,
+    /// which recites texts one grapheme cluster at a time instead of one character at a time.
+    Grapheme(&'a str),
+
+    /// A repetition of a text has been recited.
+    TextCompleted {
+        /// The full text.
+        text: &'a str,
+    },
+
+    /// A syllable of a mantra should be recited.
+    Syllable {
+        /// The index of the mantra in the options.
+        mantra_index: usize,
+
+        /// The zero-based number of the repetition of the mantra.
+        repeat: usize,
+
+        /// The index of the syllable in the mantra.
+        syllable_index: usize,
+
+        /// The text of the syllable.
+        syllable: &'a str,
+    },
+
+    /// A repetition of a mantra has been completed.
+    MantraCompleted {
+        /// The position of the mantra in the options.
+        index: usize,
+
+        /// The zero-based number of the repetition.
+        repeat: usize,
+    },
+
+    /// A recitation of the sadhana has been completed.
+    SadhanaCompleted {
+        /// The number of recitations completed since the reciter was created, not counting this
+        /// one.
+        run: usize,
+    },
+}
+
+/// A single step of the reciter, which only refers to the options by index so that the position
+/// can keep advancing while it is held.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Step {
+    /// See [`ReciterEvent::SadhanaStarted`].
+    SadhanaStarted { run: usize },
+
+    /// See [`ReciterEvent::Character`] and [`ReciterEvent::Grapheme`]. Contains the index of the
+    /// section and the range of its text to recite.
+    Character {
+        section: usize,
+        start: usize,
+        end: usize,
+    },
+
+    /// See [`ReciterEvent::TextCompleted`]. Contains the index of the section whose text was
+    /// recited.
+    TextCompleted(usize),
+
+    /// See [`ReciterEvent::Syllable`].
+    Syllable {
+        mantra_index: usize,
+        repeat: usize,
+        syllable_index: usize,
+    },
+
+    /// See [`ReciterEvent::MantraCompleted`].
+    MantraCompleted { index: usize, repeat: usize },
+
+    /// See [`ReciterEvent::SadhanaCompleted`].
+    SadhanaCompleted { run: usize },
+}
+
+/// Walks through the sadhana described by the options, producing one event at a time. Only
+/// [`ReciterEvent::Character`] and [`ReciterEvent::Syllable`] are meant to be recited, and the
+/// driver of the reciter should wait for [`Reciter::next_delay`] after each of them. This type
+/// only requires `alloc`, so that it can be used on targets without the standard library.
+#[derive(Clone, Debug)]
+pub struct Reciter {
+    /// The options describing the sadhana.
+    options: Options,
+
+    /// The sections of the sadhana described by the options.
+    sections: Vec<sadhana::Section>,
+
+    /// The mantras from which one is drawn for each sadhana, or `None` if every mantra is recited.
+    selection: Option<sadhana::Selection>,
+
+    /// The ranges of sections whose mantras take turns.
+    interleaved: Vec<Range<usize>>,
+
+    /// The position of the reciter within the sadhana.
+    position: Position,
+
+    /// The number of completed recitations of the entire sadhana.
+    count: usize,
+
+    /// The number of syllables and characters that have been recited.
+    syllable_count: usize,
+
+    /// The state of the xorshift generator used to randomize the delays when jitter is set.
+    random_state: u64,
+
+    /// The delay used for every syllable and character when a target throughput is set.
+    target_rate: Option<Duration>,
+
+    /// The steps read ahead by [`Reciter::next_delay`] to find the pauses that follow the last
+    /// character or syllable, which are returned before advancing any further, with the position
+    /// right after each of them.
+    lookahead: VecDeque<(Step, Position)>,
+
+    /// The position of the last character or syllable produced by [`Reciter::next_event`].
+    progress: Option<Progress>,
+
+    /// The position right after the last event produced by [`Reciter::next_event`], until the
+    /// sadhana it belongs to is completed.
+    checkpoint: Option<Checkpoint>,
+
+    /// The length of the silences passed since the last step, which are kept after the character
+    /// or syllable that precedes them.
+    silence: Duration,
+}
+
+impl Reciter {
+    /// Returns a new reciter positioned at the start of the sadhana described by the options. The
+    /// jitter, if any, is drawn from a generator with a fixed seed.
+    pub fn new(options: Options) -> Reciter {
+        Self::with_seed(options, DEFAULT_SEED)
+    }
+
+    /// Returns a new reciter whose jitter is drawn from a generator with the given seed, so that
+    /// the delays of different reciters are not synchronized.
+    pub fn with_seed(options: Options, seed: u64) -> Reciter {
+        #[cfg(feature = "normalization")]
+        let options = crate::normalize::normalized(options);
+        let sections = options.sadhana_sections();
+        Reciter {
+            target_rate: options.target_rate(),
+            selection: options.selection(&sections),
+            interleaved: options.interleaved(&sections),
+            sections,
+            options,
+            position: Position::default(),
+            count: 0,
+            syllable_count: 0,
+            random_state: if seed == 0 { DEFAULT_SEED } else { seed },
+            lookahead: VecDeque::new(),
+            progress: None,
+            checkpoint: None,
+            silence: Duration::ZERO,
+        }
+    }
+
+    /// Returns the options describing the sadhana.
+    pub fn options(&self) -> &Options {
+        &self.options
+    }
+
+    /// Returns the number of completed recitations of the entire sadhana.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Returns the number of syllables and characters that have been recited.
+    pub fn syllable_count(&self) -> usize {
+        self.syllable_count
+    }
+
+    /// Returns the position within the sadhana of the last character or syllable produced by
+    /// [`Reciter::next_event`], or `None` if there was none yet.
+    pub fn progress(&self) -> Option<Progress> {
+        self.progress
+    }
+
+    /// Returns the position right after the last event produced by [`Reciter::next_event`], or
+    /// `None` if nothing of the current sadhana has been produced yet or the last sadhana was
+    /// completed.
+    pub fn checkpoint(&self) -> Option<Checkpoint> {
+        self.checkpoint
+    }
+
+    /// Returns the fraction of the current sadhana recited so far, between zero and one, or `None`
+    /// if no sadhana is in progress. See [`Checkpoint::fraction`].
+    pub fn sadhana_progress(&self) -> Option<f64> {
+        self.checkpoint.map(|checkpoint| {
+            checkpoint.fraction_of(&self.sections, &self.interleaved, &self.options)
+        })
+    }
+
+    /// Moves the reciter to the given checkpoint, so that the next recitation of the sadhana
+    /// starts with the character or syllable that follows it instead of from the beginning.
+    /// [`ReciterEvent::SadhanaStarted`] is still produced first. A checkpoint that does not fit
+    /// the text of its section resumes from the start of the section.
+    pub fn resume(&mut self, checkpoint: Checkpoint) {
+        self.lookahead.clear();
+        self.silence = Duration::ZERO;
+        self.position.started = false;
+        self.enter_section(checkpoint.section_index);
+        self.position.selected = checkpoint.selected_mantra;
+        self.position.cycle = checkpoint.cycle;
+        self.position.repeat = checkpoint.repeat;
+        let fits = match self.sections.get(checkpoint.section_index) {
+            Some(sadhana::Section::Text { text, .. }) => text.is_char_boundary(checkpoint.unit),
+            _ => true,
+        };
+        if fits {
+            self.position.unit = checkpoint.unit;
+        } else {
+            self.position.repeat = 0;
+        }
+        self.checkpoint = Some(checkpoint);
+    }
+
+    /// Returns the time to wait after the last character or syllable produced by
+    /// [`Reciter::next_event`], which depends on the section of the sadhana it belongs to unless a
+    /// target throughput is set in the options.
+    pub fn rate(&self) -> Duration {
+        if let Some(rate) = self.target_rate {
+            return rate;
+        }
+        let rate = match self.sections.get(self.position.section) {
+            Some(sadhana::Section::Text { rate, .. }) => *rate,
+            _ => None,
+        };
+        rate.unwrap_or(self.options.rate)
+    }
+
+    /// Replaces the options describing the sadhana, keeping the counts. The current sadhana
+    /// restarts from its beginning with the new options, so they should be replaced right after
+    /// [`ReciterEvent::SadhanaStarted`] to avoid reciting part of it twice.
+    pub fn set_options(&mut self, options: Options) {
+        #[cfg(feature = "normalization")]
+        let options = crate::normalize::normalized(options);
+        self.target_rate = options.target_rate();
+        self.sections = options.sadhana_sections();
+        self.selection = options.selection(&self.sections);
+        self.interleaved = options.interleaved(&self.sections);
+        self.options = options;
+        self.lookahead.clear();
+        self.silence = Duration::ZERO;
+        self.enter_section(0);
+        self.position.selected = self.draw_mantra();
+    }
+
+    /// Changes the rate of the syllables of the mantras, which is also used for the texts that
+    /// have no rate of their own.
+    pub fn set_rate(&mut self, rate: Duration) {
+        self.options.rate = rate;
+    }
+
+    /// Returns the next number of the xorshift generator.
+    fn next_random(&mut self) -> u64 {
+        let mut x = self.random_state;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.random_state = x;
+        x
+    }
+
+    /// Draws the mantra to recite in the next sadhana, or returns `None` if every mantra is
+    /// recited.
+    fn draw_mantra(&mut self) -> Option<usize> {
+        self.selection.as_ref()?;
+        let random = self.next_random();
+        self.selection.as_ref()?.draw(random)
+    }
+
+    /// Reads ahead until the next character or syllable and returns the pauses set in the options
+    /// for the mantras and sadhanas completed before it, and the silences passed before it.
+    fn pending_pause(&mut self) -> Duration {
+        if !self.lookahead.is_empty() {
+            return Duration::ZERO;
+        }
+
+        let mut pause = Duration::ZERO;
+        while let Some(step) = self.step() {
+            self.lookahead.push_back((step, self.position));
+            pause += core::mem::take(&mut self.silence);
+            match step {
+                Step::SadhanaStarted { .. } | Step::TextCompleted(_) => {}
+                Step::MantraCompleted { .. } => {
+                    pause += self.options.pause_between_mantras.unwrap_or_default();
+                }
+                Step::SadhanaCompleted { .. } => {
+                    pause += self.options.pause_between_sadhanas.unwrap_or_default();
+                }
+                Step::Character { .. } | Step::Syllable { .. } => break,
+            }
+        }
+        pause
+    }
+
+    /// Returns the time to actually wait after the last character or syllable, which is
+    /// [`Reciter::rate`] moved by a random amount within the jitter set in the options, plus the
+    /// pauses between mantras and sadhanas and the silences that follow the character or syllable.
+    /// The delay never goes below zero.
+    pub fn next_delay(&mut self) -> Duration {
+        let rate = self.rate();
+        let pause = self.pending_pause();
+        let jitter = match self.options.jitter {
+            Some(jitter) if !jitter.is_zero() => jitter.as_nanos(),
+            _ => return rate + pause,
+        };
+        let offset = u128::from(self.next_random()) % (2 * jitter + 1);
+        let nanos = (rate.as_nanos() + offset).saturating_sub(jitter);
+        Duration::from_nanos(nanos.try_into().unwrap_or(u64::MAX)) + pause
+    }
+
+    /// Moves the position to the start of the section at the given index.
+    fn enter_section(&mut self, section: usize) {
+        self.position.section = section;
+        self.position.repeat = 0;
+        self.position.unit = 0;
+    }
+
+    /// Returns the part of the sadhana to which the section at the given index belongs.
+    fn part(&self, section: usize) -> Section {
+        let is_mantra =
+            |section: &sadhana::Section| matches!(section, sadhana::Section::Mantra { .. });
+        if self.sections.get(section).is_some_and(is_mantra) {
+            Section::Mantras
+        } else if self.sections.iter().take(section).any(is_mantra) {
+            Section::Conclusion
+        } else {
+            Section::Preparation
+        }
+    }
+
+    /// Returns the text of the section at the given index, or an empty text if it's not a text.
+    pub(crate) fn text(&self, section: usize) -> &str {
+        match self.sections.get(section) {
+            Some(sadhana::Section::Text { text, .. }) => text,
+            _ => "",
+        }
+    }
+
+    /// Advances through the interleaved mantras of the given range of sections, in which each
+    /// section in turn recites a repetition or a syllable of its mantra until every one of them has
+    /// taken all its turns. Returns `None` once they are done, with the position on the last
+    /// section of the range.
+    fn step_interleaved(&mut self, range: Range<usize>) -> Option<Step> {
+        loop {
+            let index = self.position.section;
+            let turn = self.position.repeat;
+            let section = &self.sections[index];
+            let (turns, turn_length) =
+                self.options
+                    .turns(section, self.position.selected, self.position.cycle);
+            if let (true, sadhana::Section::Mantra { index, .. }) = (turn < turns, section) {
+                let mantra_index = *index;
+                let length = section.units(&self.options.mantras);
+                let (repeat, syllable_index) = match self.options.interleave {
+                    Interleave::Syllables => (turn / length, turn % length),
+                    _ => (turn, self.position.unit),
+                };
+                if self.position.unit < turn_length {
+                    self.position.unit += 1;
+                    return Some(Step::Syllable {
+                        mantra_index,
+                        repeat,
+                        syllable_index,
+                    });
+                }
+                self.position.unit = 0;
+                self.next_turn(range.clone());
+                if self.options.interleave != Interleave::Syllables || syllable_index + 1 == length
+                {
+                    return Some(Step::MantraCompleted {
+                        index: mantra_index,
+                        repeat,
+                    });
+                }
+            } else if !self.next_turn(range.clone()) {
+                return None;
+            }
+        }
+    }
+
+    /// Moves the position to the next section of the given range of interleaved mantras, or to the
+    /// first one in the next turn. Returns `false` if no section takes the next turn, in which case
+    /// the position is left on the last section of the range.
+    fn next_turn(&mut self, range: Range<usize>) -> bool {
+        if self.position.section + 1 < range.end {
+            self.position.section += 1;
+            return true;
+        }
+        self.position.repeat += 1;
+        let turn = self.position.repeat;
+        let (selected, cycle) = (self.position.selected, self.position.cycle);
+        if self.sections[range.clone()]
+            .iter()
+            .any(|section| self.options.turns(section, selected, cycle).0 > turn)
+        {
+            self.position.section = range.start;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Advances through the current section. Returns `None` once the section has been recited the
+    /// number of times set for it, or right away if it's not recited in the current sadhana. A
+    /// silence is added to the silences passed instead of being returned, and a group is skipped,
+    /// since the sections of the sadhana have their groups expanded.
+    fn step_section(&mut self) -> Option<Step> {
+        let index = self.position.section;
+        if let Some(range) = interleaved_range(&self.interleaved, index) {
+            return self.step_interleaved(range.clone());
+        }
+        let section = self.sections.get(index)?;
+        let repeat = self.position.repeat;
+        if repeat >= section.repeats(&self.options.mantras)
+            || !self
+                .options
+                .recites(section, self.position.selected, self.position.cycle)
+        {
+            return None;
+        }
+
+        match section {
+            sadhana::Section::Text { text, .. } => {
+                let start = self.position.unit;
+                if let Some(unit) = sadhana::first_unit(&text[start..]) {
+                    self.position.unit += unit.len();
+                    return Some(Step::Character {
+                        section: index,
+                        start,
+                        end: self.position.unit,
+                    });
+                }
+                self.position.repeat += 1;
+                self.position.unit = 0;
+                Some(Step::TextCompleted(index))
+            }
+            sadhana::Section::Mantra { index, .. } => {
+                let mantra_index = *index;
+                let length = self.options.mantras.get(mantra_index)?.syllables.len();
+                if self.position.unit < length {
+                    let syllable_index = self.position.unit;
+                    self.position.unit += 1;
+                    return Some(Step::Syllable {
+                        mantra_index,
+                        repeat,
+                        syllable_index,
+                    });
+                }
+                self.position.repeat += 1;
+                self.position.unit = 0;
+                Some(Step::MantraCompleted {
+                    index: mantra_index,
+                    repeat,
+                })
+            }
+            sadhana::Section::Silence { duration, .. } => {
+                self.silence += *duration;
+                self.position.repeat += 1;
+                None
+            }
+            sadhana::Section::Group { .. } => None,
+        }
+    }
+
+    /// Advances to the next step of the sadhana. Returns `None` once the sadhana has been recited
+    /// the number of times set in the options.
+    fn step(&mut self) -> Option<Step> {
+        while self.options.should_repeat(self.position.run) {
+            if !self.position.started {
+                self.position.started = true;
+                // A sadhana resumed from a checkpoint keeps the mantra drawn for it.
+                if self.position.section == 0
+                    && self.position.repeat == 0
+                    && self.position.unit == 0
+                {
+                    self.position.selected = self.draw_mantra();
+                }
+                return Some(Step::SadhanaStarted {
+                    run: self.position.run,
+                });
+            }
+
+            if let Some(step) = self.step_section() {
+                return Some(step);
+            }
+
+            if self.position.section + 1 < self.sections.len() {
+                self.enter_section(self.position.section + 1);
+                continue;
+            }
+            let run = self.position.run;
+            self.enter_section(0);
+            self.position.run += 1;
+            self.position.cycle = self.position.cycle.wrapping_add(1);
+            self.position.started = false;
+            return Some(Step::SadhanaCompleted { run });
+        }
+        None
+    }
+
+    /// Returns the next event of the sadhana, or `None` once the sadhana has been recited the
+    /// number of times set in the options.
+    pub fn next_event(&mut self) -> Option<ReciterEvent<'_>> {
+        let (step, position) = match self.lookahead.pop_front() {
+            Some(ahead) => ahead,
+            None => {
+                let step = self.step()?;
+                // The silences that were not read ahead follow no character or syllable, so there
+                // is nothing to keep them after.
+                self.silence = Duration::ZERO;
+                (step, self.position)
+            }
+        };
+        // The position is right after the step, so for a character or syllable its section and
+        // repetition are the ones of the unit being produced.
+        match step {
+            Step::Character { .. } => {
+                self.syllable_count += 1;
+                self.progress = Some(Progress {
+                    run: position.run,
+                    section: self.part(position.section),
+                    section_index: position.section,
+                    mantra_index: None,
+                    repeat: position.repeat,
+                    syllable_index: None,
+                });
+            }
+            Step::Syllable {
+                mantra_index,
+                repeat,
+                syllable_index,
+            } => {
+                self.syllable_count += 1;
+                self.progress = Some(Progress {
+                    run: position.run,
+                    section: Section::Mantras,
+                    section_index: position.section,
+                    mantra_index: Some(mantra_index),
+                    repeat,
+                    syllable_index: Some(syllable_index),
+                });
+            }
+            Step::SadhanaCompleted { .. } => self.count += 1,
+            _ => {}
+        }
+        self.checkpoint = match step {
+            Step::SadhanaStarted { .. } | Step::SadhanaCompleted { .. } => None,
+            _ => Some(Checkpoint {
+                section_index: position.section,
+                repeat: position.repeat,
+                unit: position.unit,
+                selected_mantra: position.selected,
+                cycle: position.cycle,
+            }),
+        };
+
+        let event = match step {
+            Step::SadhanaStarted { run } => ReciterEvent::SadhanaStarted { run },
+            Step::Character {
+                section,
+                start,
+                end,
+            } => {
+                let unit = &self.text(section)[start..end];
+                let mut chars = unit.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => ReciterEvent::Character(c),
+                    _ => ReciterEvent::Grapheme(unit),
+                }
+            }
+            Step::TextCompleted(section) => ReciterEvent::TextCompleted {
+                text: self.text(section),
+            },
+            Step::Syllable {
+                mantra_index,
+                repeat,
+                syllable_index,
+            } => ReciterEvent::Syllable {
+                mantra_index,
+                repeat,
+                syllable_index,
+                syllable: &self.options.mantras[mantra_index].syllables[syllable_index],
+            },
+            Step::MantraCompleted { index, repeat } => {
+                ReciterEvent::MantraCompleted { index, repeat }
+            }
+            Step::SadhanaCompleted { run } => ReciterEvent::SadhanaCompleted { run },
+        };
+        Some(event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::ToString, vec, vec::Vec};
+    use core::time::Duration;
+
+    use crate::{
+        reciter::{Checkpoint, Progress, Reciter, ReciterEvent, Section},
+        sadhana, Interleave, Mantra, MantraSelection, Options,
+    };
+
+    #[test]
+    fn recite_sadhana() {
+        let mut reciter = Reciter::new(Options {
+            preparation: Some("ab".to_string()),
+            mantras: vec![Mantra {
+                repeats: Some(2),
+                ..Mantra::from_text("om ah")
+            }],
+            conclusion: Some("é".to_string()),
+            repeats: Some(1),
+            ..Default::default()
+        });
+
+        let mut events = Vec::new();
+        while let Some(event) = reciter.next_event() {
+            events.push(format!("{:?}", event));
+        }
+        assert_eq!(
+            events,
+            vec![
+                "SadhanaStarted { run: 0 }",
+                "Character('a')",
+                "Character('b')",
+                "TextCompleted { text: \"ab\" }",
+                "Syllable { mantra_index: 0, repeat: 0, syllable_index: 0, syllable: \"om\" }",
+                "Syllable { mantra_index: 0, repeat: 0, syllable_index: 1, syllable: \"ah\" }",
+                "MantraCompleted { index: 0, repeat: 0 }",
+                "Syllable { mantra_index: 0, repeat: 1, syllable_index: 0, syllable: \"om\" }",
+                "Syllable { mantra_index: 0, repeat: 1, syllable_index: 1, syllable: \"ah\" }",
+                "MantraCompleted { index: 0, repeat: 1 }",
+                "Character('é')",
+                "TextCompleted { text: \"é\" }",
+                "SadhanaCompleted { run: 0 }",
+            ]
+        );
+        assert_eq!(reciter.count(), 1);
+        assert_eq!(reciter.syllable_count(), 7);
+
+        // The recitation is complete, so there are no further events.
+        assert_eq!(reciter.next_event(), None);
+    }
+
+    #[test]
+    fn count_runs() {
+        let mut reciter = Reciter::new(Options {
+            mantras: vec![Mantra::from_text("hri")],
+            repeats: Some(3),
+            ..Default::default()
+        });
+        let mut completed = 0;
+        while let Some(event) = reciter.next_event() {
+            if let ReciterEvent::SadhanaCompleted { run } = event {
+                assert_eq!(run, completed);
+                completed += 1;
+            }
+        }
+        assert_eq!(completed, 3);
+        assert_eq!(reciter.count(), 3);
+        assert_eq!(reciter.syllable_count(), 3);
+    }
+
+    #[test]
+    fn section_rates() {
+        let mut reciter = Reciter::new(Options {
+            preparation: Some("ab".to_string()),
+            mantras: vec![Mantra::from_text("om ah")],
+            conclusion: Some("c".to_string()),
+            repeats: Some(1),
+            rate: Duration::from_nanos(10),
+            preparation_rate: Some(Duration::from_nanos(20)),
+            conclusion_rate: Some(Duration::from_nanos(30)),
+            ..Default::default()
+        });
+
+        let mut rates = Vec::new();
+        while let Some(event) = reciter.next_event() {
+            if matches!(
+                event,
+                ReciterEvent::Character(_) | ReciterEvent::Syllable { .. }
+            ) {
+                rates.push(reciter.rate().as_nanos());
+            }
+        }
+        assert_eq!(rates, vec![20, 20, 10, 10, 30]);
+
+        // Sections without their own rate fall back to the rate of the mantras.
+        let mut reciter = Reciter::new(Options {
+            preparation: Some("a".to_string()),
+            mantras: vec![Mantra::from_text("om")],
+            rate: Duration::from_nanos(10),
+            ..Default::default()
+        });
+        reciter.next_event();
+        assert!(matches!(
+            reciter.next_event(),
+            Some(ReciterEvent::Character('a'))
+        ));
+        assert_eq!(reciter.rate(), Duration::from_nanos(10));
+    }
+
+    #[test]
+    fn jitter() {
+        let options = Options {
+            mantras: vec![Mantra::from_text("om")],
+            rate: Duration::from_millis(10),
+            jitter: Some(Duration::from_millis(3)),
+            ..Default::default()
+        };
+        let mut reciter = Reciter::new(options.clone());
+        let delays: Vec<Duration> = (0..100).map(|_| reciter.next_delay()).collect();
+        assert!(
+            delays
+                .iter()
+                .all(|delay| *delay >= Duration::from_millis(7)
+                    && *delay <= Duration::from_millis(13))
+        );
+        assert!(delays.iter().any(|delay| *delay != delays[0]));
+
+        // Reciters with different seeds produce different delays.
+        let mut other = Reciter::with_seed(options.clone(), 42);
+        let other_delays: Vec<Duration> = (0..100).map(|_| other.next_delay()).collect();
+        assert_ne!(delays, other_delays);
+
+        // The delay does not go below zero when the jitter is larger than the rate.
+        let mut reciter = Reciter::new(Options {
+            jitter: Some(Duration::from_millis(30)),
+            ..options
+        });
+        assert!((0..100).all(|_| reciter.next_delay() <= Duration::from_millis(40)));
+    }
+
+    #[test]
+    fn pauses() {
+        let mut reciter = Reciter::new(Options {
+            mantras: vec![Mantra {
+                repeats: Some(2),
+                ..Mantra::from_text("om ah")
+            }],
+            conclusion: Some("c".to_string()),
+            repeats: Some(2),
+            rate: Duration::from_millis(1),
+            pause_between_mantras: Some(Duration::from_millis(10)),
+            pause_between_sadhanas: Some(Duration::from_millis(100)),
+            ..Default::default()
+        });
+
+        let mut delays = Vec::new();
+        let mut completed = 0;
+        while let Some(event) = reciter.next_event() {
+            match event {
+                ReciterEvent::Character(_) | ReciterEvent::Syllable { .. } => {
+                    delays.push(reciter.next_delay().as_millis());
+                }
+                ReciterEvent::SadhanaCompleted { .. } => completed += 1,
+                _ => {}
+            }
+        }
+        assert_eq!(delays, vec![1, 11, 1, 11, 101, 1, 11, 1, 11, 101]);
+        assert_eq!(completed, 2);
+        assert_eq!(reciter.count(), 2);
+        assert_eq!(reciter.syllable_count(), 10);
+    }
+
+    #[test]
+    fn progress() {
+        let mut reciter = Reciter::new(Options {
+            preparation: Some("a".to_string()),
+            preparation_repeats: Some(2),
+            mantras: vec![Mantra {
+                repeats: Some(2),
+                ..Mantra::from_text("om ah")
+            }],
+            conclusion: Some("c".to_string()),
+            repeats: Some(2),
+            pause_between_mantras: Some(Duration::from_millis(10)),
+            ..Default::default()
+        });
+        assert_eq!(reciter.progress(), None);
+
+        // The delay is read after every unit, so that the lookahead moves the position past it.
+        let mut progress = Vec::new();
+        while let Some(event) = reciter.next_event() {
+            if let ReciterEvent::Character(_) | ReciterEvent::Syllable { .. } = event {
+                let Progress {
+                    run,
+                    section,
+                    section_index: _,
+                    mantra_index,
+                    repeat,
+                    syllable_index,
+                } = reciter.progress().unwrap();
+                progress.push((run, section, mantra_index, repeat, syllable_index));
+                reciter.next_delay();
+            }
+        }
+
+        let mut expected = Vec::new();
+        for run in 0..2 {
+            expected.extend([
+                (run, Section::Preparation, None, 0, None),
+                (run, Section::Preparation, None, 1, None),
+                (run, Section::Mantras, Some(0), 0, Some(0)),
+                (run, Section::Mantras, Some(0), 0, Some(1)),
+                (run, Section::Mantras, Some(0), 1, Some(0)),
+                (run, Section::Mantras, Some(0), 1, Some(1)),
+                (run, Section::Conclusion, None, 0, None),
+            ]);
+        }
+        assert_eq!(progress, expected);
+    }
+
+    #[test]
+    fn resume() {
+        let options = Options {
+            preparation: Some("aé".to_string()),
+            mantras: vec![Mantra {
+                repeats: Some(2),
+                ..Mantra::from_text("om ah")
+            }],
+            conclusion: Some("c".to_string()),
+            repeats: Some(1),
+            pause_between_mantras: Some(Duration::from_millis(10)),
+            ..Default::default()
+        };
+        let mut events = Vec::new();
+        let mut reciter = Reciter::new(options.clone());
+        while let Some(event) = reciter.next_event() {
+            events.push(format!("{:?}", event));
+        }
+        assert_eq!(reciter.checkpoint(), None);
+
+        // Resuming after any character or syllable recites the rest of the sadhana.
+        let units = events
+            .iter()
+            .filter(|event| event.starts_with("Character") || event.starts_with("Syllable"))
+            .count();
+        for recited in 1..=units {
+            let mut reciter = Reciter::new(options.clone());
+            let mut seen = 0;
+            let mut position = 0;
+            while seen < recited {
+                let event = reciter.next_event().unwrap();
+                if let ReciterEvent::Character(_) | ReciterEvent::Syllable { .. } = event {
+                    seen += 1;
+                    reciter.next_delay();
+                }
+                position += 1;
+            }
+            let checkpoint = reciter.checkpoint().unwrap();
+
+            let mut resumed = Reciter::new(options.clone());
+            resumed.resume(checkpoint);
+            let mut rest = Vec::new();
+            while let Some(event) = resumed.next_event() {
+                rest.push(format!("{:?}", event));
+            }
+            assert_eq!(rest[0], "SadhanaStarted { run: 0 }");
+            assert_eq!(rest[1..], events[position..]);
+        }
+    }
+
+    #[test]
+    fn resume_invalid_checkpoint() {
+        let mut reciter = Reciter::new(Options {
+            preparation: Some("aé".to_string()),
+            mantras: vec![Mantra::from_text("om")],
+            repeats: Some(1),
+            ..Default::default()
+        });
+
+        // The offset is in the middle of a character, so the preparation starts over.
+        reciter.resume(Checkpoint {
+            section_index: 0,
+            repeat: 0,
+            unit: 2,
+            selected_mantra: None,
+            cycle: 0,
+        });
+        reciter.next_event();
+        assert_eq!(reciter.next_event(), Some(ReciterEvent::Character('a')));
+    }
+
+    #[test]
+    #[cfg(feature = "graphemes")]
+    fn graphemes() {
+        // The letter and its combining mark are recited together, and resumed after.
+        let options = Options {
+            preparation: Some("ཨོཾ a".to_string()),
+            mantras: vec![Mantra::from_text("om")],
+            repeats: Some(1),
+            ..Default::default()
+        };
+        let mut reciter = Reciter::new(options.clone());
+        reciter.next_event();
+        assert_eq!(reciter.next_event(), Some(ReciterEvent::Grapheme("ཨོཾ")));
+        let checkpoint = reciter.checkpoint().unwrap();
+        assert_eq!(checkpoint.unit, "ཨོཾ".len());
+        assert_eq!(reciter.sadhana_progress(), Some(0.25));
+
+        let mut reciter = Reciter::new(options);
+        reciter.resume(checkpoint);
+        reciter.next_event();
+        assert_eq!(reciter.next_event(), Some(ReciterEvent::Character(' ')));
+    }
+
+    #[test]
+    fn sadhana_progress() {
+        let mut reciter = Reciter::new(Options {
+            preparation: Some("ab".to_string()),
+            mantras: vec![Mantra {
+                repeats: Some(2),
+                ..Mantra::from_text("om ah")
+            }],
+            conclusion: Some("é".to_string()),
+            repeats: Some(1),
+            ..Default::default()
+        });
+        assert_eq!(reciter.sadhana_progress(), None);
+
+        // Each of the seven units of the sadhana advances the progress by a seventh.
+        let mut fractions = Vec::new();
+        while let Some(event) = reciter.next_event() {
+            if matches!(
+                event,
+                ReciterEvent::Character(_) | ReciterEvent::Syllable { .. }
+            ) {
+                fractions.push(reciter.sadhana_progress().unwrap() * 7.0);
+            }
+        }
+        assert_eq!(fractions, vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0]);
+        assert_eq!(reciter.sadhana_progress(), None);
+    }
+
+    #[test]
+    fn conditional_mantras() {
+        let options = Options {
+            mantras: vec![
+                Mantra::from_text("om"),
+                Mantra {
+                    frequency: Some(2),
+                    ..Mantra::from_text("a b")
+                },
+            ],
+            repeats: Some(3),
+            ..Default::default()
+        };
+        let mut reciter = Reciter::new(options.clone());
+
+        // The dharani is only recited in the first and third sadhanas.
+        let mut completed = Vec::new();
+        let mut run = 0;
+        while let Some(event) = reciter.next_event() {
+            match event {
+                ReciterEvent::SadhanaStarted { run: started } => run = started,
+                ReciterEvent::MantraCompleted { index, .. } => completed.push((run, index)),
+                _ => {}
+            }
+        }
+        assert_eq!(completed, vec![(0, 0), (0, 1), (1, 0), (2, 0), (2, 1)]);
+        assert_eq!(reciter.syllable_count(), 7);
+
+        // A resumed sadhana keeps its number, so the sadhana without the dharani is completed by
+        // its single syllable, and the one after it recites the dharani again.
+        let mut reciter = Reciter::new(Options {
+            repeats: Some(2),
+            ..options
+        });
+        reciter.resume(Checkpoint {
+            section_index: 0,
+            repeat: 0,
+            unit: 1,
+            selected_mantra: None,
+            cycle: 1,
+        });
+        assert_eq!(reciter.sadhana_progress(), Some(1.0));
+        let mut completed = Vec::new();
+        while let Some(event) = reciter.next_event() {
+            if let ReciterEvent::MantraCompleted { index, .. } = event {
+                completed.push(index);
+            }
+        }
+        assert_eq!(completed, vec![0, 0, 1]);
+    }
+
+    #[test]
+    fn custom_sections() {
+        let options = Options {
+            mantras: vec![Mantra::from_text("om ah"), Mantra::from_text("hri")],
+            sections: vec![
+                sadhana::Section::Text {
+                    text: "a".to_string(),
+                    repeats: Some(2),
+                    rate: Some(Duration::from_millis(2)),
+                    frequency: None,
+                },
+                sadhana::Section::Mantra {
+                    index: 1,
+                    repeats: None,
+                    frequency: None,
+                },
+                sadhana::Section::Silence {
+                    duration: Duration::from_millis(100),
+                    frequency: None,
+                },
+                sadhana::Section::Mantra {
+                    index: 0,
+                    repeats: Some(2),
+                    frequency: None,
+                },
+                sadhana::Section::Text {
+                    text: "b".to_string(),
+                    repeats: None,
+                    rate: None,
+                    frequency: None,
+                },
+                sadhana::Section::Mantra {
+                    index: 1,
+                    repeats: None,
+                    frequency: None,
+                },
+            ],
+            repeats: Some(1),
+            rate: Duration::from_millis(1),
+            ..Default::default()
+        };
+        let mut reciter = Reciter::new(options);
+
+        // The silence is added to the delay after the syllable that precedes it.
+        let mut recited = Vec::new();
+        while let Some(event) = reciter.next_event() {
+            let unit = match event {
+                ReciterEvent::Character(c) => c.to_string(),
+                ReciterEvent::Syllable { syllable, .. } => syllable.to_string(),
+                _ => continue,
+            };
+            let Progress {
+                section,
+                section_index,
+                ..
+            } = reciter.progress().unwrap();
+            let delay = reciter.next_delay().as_millis();
+            recited.push((unit, section, section_index, delay));
+        }
+        let expected = [
+            ("a", Section::Preparation, 0, 2),
+            ("a", Section::Preparation, 0, 2),
+            ("hri", Section::Mantras, 1, 101),
+            ("om", Section::Mantras, 3, 1),
+            ("ah", Section::Mantras, 3, 1),
+            ("om", Section::Mantras, 3, 1),
+            ("ah", Section::Mantras, 3, 1),
+            ("b", Section::Conclusion, 4, 1),
+            ("hri", Section::Mantras, 5, 1),
+        ];
+        let expected: Vec<_> = expected
+            .into_iter()
+            .map(|(unit, section, index, delay)| (unit.to_string(), section, index, delay))
+            .collect();
+        assert_eq!(recited, expected);
+        assert_eq!(reciter.count(), 1);
+    }
+
+    #[test]
+    fn resume_custom_sections() {
+        let options = Options {
+            mantras: vec![Mantra::from_text("om ah")],
+            sections: vec![
+                sadhana::Section::Mantra {
+                    index: 0,
+                    repeats: None,
+                    frequency: None,
+                },
+                sadhana::Section::Text {
+                    text: "b".to_string(),
+                    repeats: None,
+                    rate: None,
+                    frequency: None,
+                },
+                sadhana::Section::Mantra {
+                    index: 0,
+                    repeats: None,
+                    frequency: None,
+                },
+            ],
+            repeats: Some(1),
+            ..Default::default()
+        };
+        let mut reciter = Reciter::new(options);
+        reciter.resume(Checkpoint {
+            section_index: 2,
+            repeat: 0,
+            unit: 1,
+            selected_mantra: None,
+            cycle: 0,
+        });
+        assert_eq!(reciter.sadhana_progress(), Some(4.0 / 5.0));
+
+        let mut events = Vec::new();
+        while let Some(event) = reciter.next_event() {
+            events.push(format!("{:?}", event));
+        }
+        assert_eq!(
+            events,
+            vec![
+                "SadhanaStarted { run: 0 }",
+                "Syllable { mantra_index: 0, repeat: 0, syllable_index: 1, syllable: \"ah\" }",
+                "MantraCompleted { index: 0, repeat: 0 }",
+                "SadhanaCompleted { run: 0 }",
+            ]
+        );
+    }
+
+    #[test]
+    fn mantra_garland() {
+        let mantra = |index, repeats| sadhana::Section::Mantra {
+            index,
+            repeats: Some(repeats),
+            frequency: None,
+        };
+        let options = Options {
+            mantras: vec![Mantra::from_text("om"), Mantra::from_text("hri")],
+            sections: vec![sadhana::Section::Group {
+                sections: vec![mantra(0, 2), mantra(1, 1)],
+                repeats: Some(2),
+                frequency: None,
+            }],
+            repeats: Some(1),
+            ..Default::default()
+        };
+        let mut reciter = Reciter::new(options);
+
+        // The mantras are recited in turn, and each pass through the group is its own section.
+        let mut completed = Vec::new();
+        while let Some(event) = reciter.next_event() {
+            if let ReciterEvent::MantraCompleted { index, repeat } = event {
+                let section_index = reciter.checkpoint().unwrap().section_index;
+                completed.push((index, repeat, section_index));
+            }
+        }
+        assert_eq!(
+            completed,
+            vec![
+                (0, 0, 0),
+                (0, 1, 0),
+                (1, 0, 1),
+                (0, 0, 2),
+                (0, 1, 2),
+                (1, 0, 3)
+            ]
+        );
+        assert_eq!(reciter.count(), 1);
+        assert_eq!(reciter.syllable_count(), 6);
+    }
+
+    /// Recites two mantras interleaved in the given way from the given checkpoint, and returns the
+    /// syllables recited, the mantras completed, and the reciter.
+    fn recite_interleaved(
+        interleave: Interleave,
+        checkpoint: Option<Checkpoint>,
+    ) -> (String, Vec<(usize, usize)>, Reciter) {
+        let options = Options {
+            mantras: vec![
+                Mantra {
+                    repeats: Some(2),
+                    ..Mantra::from_text("om ah hum")
+                },
+                Mantra {
+                    repeats: Some(1),
+                    ..Mantra::from_text("om mani")
+                },
+            ],
+            interleave,
+            repeats: Some(1),
+            ..Default::default()
+        };
+        let mut reciter = Reciter::new(options);
+        if let Some(checkpoint) = checkpoint {
+            reciter.resume(checkpoint);
+        }
+        let mut syllables = Vec::new();
+        let mut completed = Vec::new();
+        while let Some(event) = reciter.next_event() {
+            match event {
+                ReciterEvent::Syllable { syllable, .. } => syllables.push(syllable.to_string()),
+                ReciterEvent::MantraCompleted { index, repeat } => completed.push((index, repeat)),
+                _ => {}
+            }
+        }
+        (syllables.join(" "), completed, reciter)
+    }
+
+    #[test]
+    fn interleaved_repetitions() {
+        let (syllables, completed, reciter) = recite_interleaved(Interleave::Repetitions, None);
+        assert_eq!(syllables, "om ah hum om mani om ah hum");
+        assert_eq!(completed, vec![(0, 0), (1, 0), (0, 1)]);
+        assert_eq!(reciter.count(), 1);
+    }
+
+    #[test]
+    fn interleaved_syllables() {
+        // Each mantra keeps its own repetitions, and the shorter one drops out once it's done.
+        let (syllables, completed, _) = recite_interleaved(Interleave::Syllables, None);
+        assert_eq!(syllables, "om om ah mani hum om ah hum");
+        assert_eq!(completed, vec![(1, 0), (0, 0), (0, 1)]);
+    }
+
+    #[test]
+    fn resume_interleaved() {
+        // Two turns have been taken by the first mantra and one by the second.
+        let checkpoint = Checkpoint {
+            section_index: 0,
+            repeat: 1,
+            unit: 1,
+            selected_mantra: None,
+            cycle: 0,
+        };
+        let options = recite_interleaved(Interleave::Syllables, None)
+            .2
+            .options()
+            .clone();
+        assert_eq!(checkpoint.fraction(&options), 3.0 / 8.0);
+
+        let (syllables, completed, _) = recite_interleaved(Interleave::Syllables, Some(checkpoint));
+        assert_eq!(syllables, "mani hum om ah hum");
+        assert_eq!(completed, vec![(1, 0), (0, 0), (0, 1)]);
+    }
+
+    #[test]
+    fn weighted_selection() {
+        let options = Options {
+            preparation: Some("a".to_string()),
+            mantras: vec![
+                Mantra {
+                    weight: Some(3),
+                    ..Mantra::from_text("om")
+                },
+                Mantra::from_text("hri"),
+            ],
+            selection: MantraSelection::Weighted,
+            repeats: Some(400),
+            ..Default::default()
+        };
+        let mut reciter = Reciter::with_seed(options, 42);
+
+        // Each sadhana recites its preparation and a single mantra, drawn by weight.
+        let mut characters = 0;
+        let mut completed = [0; 2];
+        while let Some(event) = reciter.next_event() {
+            match event {
+                ReciterEvent::Character(_) => characters += 1,
+                ReciterEvent::MantraCompleted { index, .. } => completed[index] += 1,
+                _ => {}
+            }
+        }
+        assert_eq!(characters, 400);
+        assert_eq!(completed[0] + completed[1], 400);
+        assert!((250..350).contains(&completed[0]), "{:?}", completed);
+    }
+
+    #[test]
+    fn resume_selected_mantra() {
+        let options = Options {
+            mantras: vec![Mantra::from_text("om ah"), Mantra::from_text("hri")],
+            selection: MantraSelection::Random,
+            repeats: Some(1),
+            ..Default::default()
+        };
+        let mut reciter = Reciter::new(options);
+        reciter.resume(Checkpoint {
+            section_index: 0,
+            repeat: 0,
+            unit: 1,
+            selected_mantra: Some(0),
+            cycle: 0,
+        });
+        assert_eq!(reciter.sadhana_progress(), Some(0.5));
+
+        // The resumed sadhana keeps the mantra drawn for it.
+        let mut syllables = Vec::new();
+        while let Some(event) = reciter.next_event() {
+            if let ReciterEvent::Syllable { syllable, .. } = event {
+                syllables.push(syllable.to_string());
+            }
+        }
+        assert_eq!(syllables, ["ah"]);
+    }
+}
@@ -0,0 +1,700 @@
+//! Contains the sections from which a sadhana can be composed. By default, the sadhana is made of
+//! the preparation, the mantras, and the conclusion set in the options, but practices such as the
+//! seven-branch prayer, visualizations, or several dedication verses can be modeled by listing the
+//! sections in `Options::sections` instead. Sections can be grouped into blocks with their own
+//! repeats, such as the mantra garlands in which several mantras are recited in turn.
+
+use alloc::{string::String, vec::Vec};
+use core::{ops::Range, time::Duration};
+use serde::{Deserialize, Serialize};
+
+use crate::{duration, Interleave, Mantra, MantraSelection, Options};
+
+/// The largest number of sections into which the groups of a sadhana can expand, which keeps the
+/// expanded sections held by the reciter to a reasonable size.
+pub(crate) const MAX_EXPANDED_SECTIONS: usize = 1_000_000;
+
+/// A section of the sadhana. In sadhana files, the kind of each section is given by its `kind`
+/// key. For example:
+///
+/// ```toml
+/// [[sections]]
+/// kind = "text"
+/// text = "I take refuge in the Three Jewels and arise bodhicitta."
+/// repeats = 3
+///
+/// [[sections]]
+/// kind = "silence"
+/// duration = "30s"
+/// frequency = 10
+///
+/// [[sections]]
+/// kind = "mantra"
+/// index = 0
+/// repeats = 108
+///
+/// [[sections]]
+/// kind = "group"
+/// repeats = 3
+///
+/// [[sections.sections]]
+/// kind = "mantra"
+/// index = 1
+/// repeats = 7
+///
+/// [[sections.sections]]
+/// kind = "mantra"
+/// index = 2
+/// repeats = 21
+/// ```
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Section {
+    /// A passage recited character by character, such as a prayer or a dedication verse.
+    Text {
+        /// The text of the passage.
+        text: String,
+
+        /// The number of times to recite the passage. If it's `None`, it's recited once.
+        #[serde(default)]
+        repeats: Option<usize>,
+
+        /// The time to wait between each character of the passage. If it's `None`,
+        /// `Options::rate` is used instead.
+        #[serde(default, with = "duration::human_option")]
+        rate: Option<Duration>,
+
+        /// Recites the passage only in every `frequency`th recitation of the sadhana, starting with
+        /// the first. If it's `None`, it's recited in every sadhana.
+        #[serde(default)]
+        frequency: Option<usize>,
+    },
+
+    /// The repetitions of the mantra at position `index` in `Options::mantras`. The same mantra can
+    /// appear in several sections, and its statistics include the repetitions of all of them.
+    Mantra {
+        /// The index of the mantra in the options.
+        index: usize,
+
+        /// The number of times to repeat the mantra. If it's `None`, the repeats of the mantra are
+        /// used instead.
+        #[serde(default)]
+        repeats: Option<usize>,
+
+        /// Recites the mantra only in every `frequency`th recitation of the sadhana, starting with
+        /// the first. If it's `None`, the frequency of the mantra is used instead.
+        #[serde(default)]
+        frequency: Option<usize>,
+    },
+
+    /// A passage during which nothing is recited, such as a visualization or a meditation. The
+    /// silence is kept after the character or syllable that precedes it, on top of the rate, so a
+    /// silence at the very start of the first recitation of the sadhana is skipped.
+    Silence {
+        /// The length of the silence.
+        #[serde(with = "duration::human")]
+        duration: Duration,
+
+        /// Keeps the silence only in every `frequency`th recitation of the sadhana, starting with
+        /// the first. If it's `None`, it's kept in every sadhana.
+        #[serde(default)]
+        frequency: Option<usize>,
+    },
+
+    /// A block of sections recited in order a number of times, such as a garland of mantras in
+    /// which each mantra is repeated in turn before the whole block is repeated. Groups can be
+    /// nested.
+    Group {
+        /// The sections of the group.
+        sections: Vec<Section>,
+
+        /// The number of times to recite the group. If it's `None`, it's recited once.
+        #[serde(default)]
+        repeats: Option<usize>,
+
+        /// Recites the group only in every `frequency`th recitation of the sadhana, starting with
+        /// the first. The sections of the group with a frequency of their own are only recited
+        /// when both frequencies agree. If it's `None`, the group is recited in every sadhana.
+        #[serde(default)]
+        frequency: Option<usize>,
+    },
+}
+
+/// Returns the first unit in which the given text is recited, which is its first grapheme cluster
+/// with the `graphemes` feature, so that a base letter is never written apart from its combining
+/// marks, and its first character without it. Returns `None` if the text is empty.
+pub(crate) fn first_unit(text: &str) -> Option<&str> {
+    #[cfg(feature = "graphemes")]
+    {
+        unicode_segmentation::UnicodeSegmentation::graphemes(text, true).next()
+    }
+    #[cfg(not(feature = "graphemes"))]
+    {
+        let c = text.chars().next()?;
+        Some(&text[..c.len_utf8()])
+    }
+}
+
+/// Returns the number of units in which the given text is recited, as returned by `first_unit`.
+pub(crate) fn count_units(text: &str) -> usize {
+    #[cfg(feature = "graphemes")]
+    {
+        unicode_segmentation::UnicodeSegmentation::graphemes(text, true).count()
+    }
+    #[cfg(not(feature = "graphemes"))]
+    {
+        text.chars().count()
+    }
+}
+
+impl Section {
+    /// Returns the number of times the section is recited in each recitation of the sadhana.
+    pub(crate) fn repeats(&self, mantras: &[Mantra]) -> usize {
+        match self {
+            Section::Text { repeats, .. } => repeats.unwrap_or(1),
+            Section::Mantra { index, repeats, .. } => repeats
+                .or_else(|| mantras.get(*index).and_then(|mantra| mantra.repeats))
+                .unwrap_or(1),
+            Section::Silence { .. } => 1,
+            Section::Group { repeats, .. } => repeats.unwrap_or(1),
+        }
+    }
+
+    /// Returns the number of recitations of the sadhana between each one that recites the section,
+    /// which is one if it's recited in every sadhana.
+    pub(crate) fn frequency(&self, mantras: &[Mantra]) -> usize {
+        match self {
+            Section::Text { frequency, .. }
+            | Section::Silence { frequency, .. }
+            | Section::Group { frequency, .. } => frequency.unwrap_or(1),
+            Section::Mantra {
+                index, frequency, ..
+            } => frequency
+                .or_else(|| mantras.get(*index).and_then(|mantra| mantra.frequency))
+                .unwrap_or(1),
+        }
+    }
+
+    /// Returns the section with its frequency replaced by the given one.
+    fn with_frequency(mut self, new: usize) -> Section {
+        match &mut self {
+            Section::Text { frequency, .. }
+            | Section::Mantra { frequency, .. }
+            | Section::Silence { frequency, .. }
+            | Section::Group { frequency, .. } => *frequency = Some(new),
+        }
+        self
+    }
+
+    /// Returns the number of characters or syllables recited in each repetition of the section.
+    pub(crate) fn units(&self, mantras: &[Mantra]) -> usize {
+        match self {
+            Section::Text { text, .. } => count_units(text),
+            Section::Mantra { index, .. } => mantras
+                .get(*index)
+                .map_or(0, |mantra| mantra.syllables.len()),
+            Section::Silence { .. } => 0,
+            Section::Group { sections, .. } => sections
+                .iter()
+                .map(|section| section.units(mantras) * section.repeats(mantras))
+                .sum(),
+        }
+    }
+
+    /// Returns the largest index of a mantra the section refers to, including those in groups, or
+    /// `None` if it refers to none.
+    pub(crate) fn max_mantra_index(&self) -> Option<usize> {
+        match self {
+            Section::Mantra { index, .. } => Some(*index),
+            Section::Text { .. } | Section::Silence { .. } => None,
+            Section::Group { sections, .. } => {
+                sections.iter().filter_map(Section::max_mantra_index).max()
+            }
+        }
+    }
+
+    /// Returns the number of sections into which the section expands once its groups are
+    /// expanded, saturating at `usize::MAX`.
+    pub(crate) fn expanded_len(&self) -> usize {
+        match self {
+            Section::Group {
+                sections, repeats, ..
+            } => sections
+                .iter()
+                .fold(0, |len: usize, section| {
+                    len.saturating_add(section.expanded_len())
+                })
+                .saturating_mul(repeats.unwrap_or(1)),
+            _ => 1,
+        }
+    }
+}
+
+/// Returns the least common multiple of the given numbers, saturating at `usize::MAX`, which is
+/// the frequency of a section recited only when two frequencies agree.
+fn lcm(a: usize, b: usize) -> usize {
+    let (mut x, mut y) = (a, b);
+    while y != 0 {
+        (x, y) = (y, x % y);
+    }
+    a.checked_div(x).map_or(0, |a| a.saturating_mul(b))
+}
+
+/// Appends the given sections, which belong to groups recited every `frequency`th sadhana, to
+/// `expanded`, replacing each group with its sections repeated the number of times set for it.
+fn expand(sections: &[Section], mantras: &[Mantra], frequency: usize, expanded: &mut Vec<Section>) {
+    for section in sections {
+        match section {
+            Section::Group {
+                sections, repeats, ..
+            } => {
+                let frequency = lcm(frequency, section.frequency(mantras));
+                for _ in 0..repeats.unwrap_or(1) {
+                    expand(sections, mantras, frequency, expanded);
+                }
+            }
+            section if frequency != 1 => expanded.push(
+                section
+                    .clone()
+                    .with_frequency(lcm(frequency, section.frequency(mantras))),
+            ),
+            section => expanded.push(section.clone()),
+        }
+    }
+}
+
+/// The mantras from which one is drawn for each recitation of the sadhana when the options do not
+/// recite every mantra, with their weights.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct Selection {
+    /// The index in the options and the weight of each mantra that can be drawn.
+    weights: Vec<(usize, u64)>,
+
+    /// The sum of the weights.
+    total: u64,
+}
+
+impl Selection {
+    /// Returns the index of the mantra drawn with the given random number, or `None` if no mantra
+    /// can be drawn.
+    pub(crate) fn draw(&self, random: u64) -> Option<usize> {
+        if self.total == 0 {
+            return None;
+        }
+        let mut pick = random % self.total;
+        for &(index, weight) in &self.weights {
+            if pick < weight {
+                return Some(index);
+            }
+            pick -= weight;
+        }
+        None
+    }
+
+    /// Returns the sum of the weights.
+    pub(crate) fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// Returns the weight of the given section, out of the total, in an average recitation of the
+    /// sadhana. A mantra is only recited when it's drawn, while any other section is always
+    /// recited.
+    pub(crate) fn weight(&self, section: &Section) -> u64 {
+        match section {
+            Section::Mantra { index, .. } => self
+                .weights
+                .iter()
+                .find(|(mantra, _)| mantra == index)
+                .map_or(0, |(_, weight)| *weight),
+            _ => self.total,
+        }
+    }
+}
+
+impl Options {
+    /// Returns the mantras from which one is drawn for each recitation of the sadhana made of the
+    /// given sections, or `None` if every mantra is recited. Only the mantras recited by the
+    /// sections can be drawn.
+    pub(crate) fn selection(&self, sections: &[Section]) -> Option<Selection> {
+        if self.selection == MantraSelection::All {
+            return None;
+        }
+        let mut weights: Vec<(usize, u64)> = Vec::new();
+        for section in sections {
+            let Section::Mantra { index, .. } = section else {
+                continue;
+            };
+            if weights.iter().any(|(mantra, _)| mantra == index) {
+                continue;
+            }
+            let weight = match self.selection {
+                MantraSelection::Weighted => self
+                    .mantras
+                    .get(*index)
+                    .map_or(0, |mantra| mantra.weight.unwrap_or(1)),
+                _ => 1,
+            };
+            weights.push((*index, u64::from(weight)));
+        }
+        let total = weights.iter().map(|(_, weight)| weight).sum();
+        Some(Selection { weights, total })
+    }
+
+    /// Returns the ranges of the given sections whose mantras take turns, which are the runs of two
+    /// or more consecutive mantra sections, or no range if the mantras are recited in sequence.
+    pub(crate) fn interleaved(&self, sections: &[Section]) -> Vec<Range<usize>> {
+        let mut ranges = Vec::new();
+        if self.interleave == Interleave::Sequential {
+            return ranges;
+        }
+        let mut start = 0;
+        for (index, section) in sections.iter().enumerate() {
+            if !matches!(section, Section::Mantra { .. }) {
+                if index - start >= 2 {
+                    ranges.push(start..index);
+                }
+                start = index + 1;
+            }
+        }
+        if sections.len().saturating_sub(start) >= 2 {
+            ranges.push(start..sections.len());
+        }
+        ranges
+    }
+
+    /// Returns whether the given section is part of the recitation of the sadhana with the given
+    /// number, in which the mantra at index `selected` was drawn if one mantra is drawn for each
+    /// sadhana.
+    pub(crate) fn recites(&self, section: &Section, selected: Option<usize>, cycle: usize) -> bool {
+        if let (Section::Mantra { index, .. }, Some(selected)) = (section, selected) {
+            if *index != selected {
+                return false;
+            }
+        }
+        cycle.is_multiple_of(section.frequency(&self.mantras))
+    }
+
+    /// Returns the weight of each of the given sections in an average recitation of the sadhana,
+    /// and the total weight of a sadhana, so that averages can be computed with integers. The
+    /// weights account for the mantra drawn for each sadhana and for the sections recited only
+    /// every few sadhanas.
+    pub(crate) fn average_weights(&self, sections: &[Section]) -> (Vec<u128>, u128) {
+        let selection = self.selection(sections);
+        let scale = sections.iter().fold(1, |scale, section| {
+            lcm(scale, section.frequency(&self.mantras).max(1))
+        });
+        let weights = sections
+            .iter()
+            .map(|section| {
+                let weight = selection
+                    .as_ref()
+                    .map_or(1, |selection| selection.weight(section));
+                u128::from(weight) * (scale / section.frequency(&self.mantras).max(1)) as u128
+            })
+            .collect();
+        let total = selection.as_ref().map_or(1, Selection::total);
+        (weights, u128::from(total) * scale as u128)
+    }
+
+    /// Returns the number of turns taken by the given mantra section when the mantras are
+    /// interleaved in the sadhana with the given number, and the number of syllables recited in
+    /// each turn. A mantra that is not recited in the sadhana takes no turns.
+    pub(crate) fn turns(
+        &self,
+        section: &Section,
+        selected: Option<usize>,
+        cycle: usize,
+    ) -> (usize, usize) {
+        if !matches!(section, Section::Mantra { .. }) || !self.recites(section, selected, cycle) {
+            return (0, 0);
+        }
+        let length = section.units(&self.mantras);
+        let repeats = section.repeats(&self.mantras);
+        match self.interleave {
+            Interleave::Syllables => (length.saturating_mul(repeats), 1),
+            _ => (repeats, length),
+        }
+    }
+
+    /// Returns the sections of the sadhana in the order in which they are recited, which are those
+    /// in `sections` if it's not empty, with each group replaced by its sections as many times as
+    /// it's repeated. Otherwise, they are the preparation, each of the mantras, and the
+    /// conclusion, with their repeats and rates. The returned sections never contain a group.
+    pub fn sadhana_sections(&self) -> Vec<Section> {
+        if !self.sections.is_empty() {
+            let mut expanded = Vec::new();
+            expand(&self.sections, &self.mantras, 1, &mut expanded);
+            return expanded;
+        }
+
+        let mut sections = Vec::with_capacity(self.mantras.len() + 2);
+        if let Some(text) = &self.preparation {
+            sections.push(Section::Text {
+                text: text.clone(),
+                repeats: self.preparation_repeats,
+                rate: self.preparation_rate,
+                frequency: None,
+            });
+        }
+        sections.extend((0..self.mantras.len()).map(|index| Section::Mantra {
+            index,
+            repeats: None,
+            frequency: None,
+        }));
+        if let Some(text) = &self.conclusion {
+            sections.push(Section::Text {
+                text: text.clone(),
+                repeats: self.conclusion_repeats,
+                rate: self.conclusion_rate,
+                frequency: None,
+            });
+        }
+        sections
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::ToString, vec};
+    use core::time::Duration;
+
+    use crate::{sadhana::Section, Mantra, MantraSelection, Options};
+
+    #[test]
+    fn default_sections() {
+        let options = Options {
+            preparation: Some("ab".to_string()),
+            preparation_repeats: Some(2),
+            mantras: vec![Mantra::from_text("om"), Mantra::from_text("hri")],
+            conclusion: Some("c".to_string()),
+            conclusion_rate: Some(Duration::from_millis(5)),
+            ..Default::default()
+        };
+        assert_eq!(
+            options.sadhana_sections(),
+            vec![
+                Section::Text {
+                    text: "ab".to_string(),
+                    repeats: Some(2),
+                    rate: None,
+                    frequency: None,
+                },
+                Section::Mantra {
+                    index: 0,
+                    repeats: None,
+                    frequency: None,
+                },
+                Section::Mantra {
+                    index: 1,
+                    repeats: None,
+                    frequency: None,
+                },
+                Section::Text {
+                    text: "c".to_string(),
+                    repeats: None,
+                    rate: Some(Duration::from_millis(5)),
+                    frequency: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn expand_groups() {
+        let mantra = |index, repeats| Section::Mantra {
+            index,
+            repeats: Some(repeats),
+            frequency: None,
+        };
+        let options = Options {
+            mantras: vec![Mantra::from_text("om ah"), Mantra::from_text("hri")],
+            sections: vec![
+                mantra(0, 1),
+                Section::Group {
+                    sections: vec![
+                        mantra(0, 7),
+                        Section::Group {
+                            sections: vec![mantra(1, 21)],
+                            repeats: Some(2),
+                            frequency: None,
+                        },
+                    ],
+                    repeats: Some(2),
+                    frequency: None,
+                },
+                Section::Group {
+                    sections: vec![mantra(1, 1)],
+                    repeats: Some(0),
+                    frequency: None,
+                },
+            ],
+            ..Default::default()
+        };
+        assert_eq!(
+            options.sadhana_sections(),
+            vec![
+                mantra(0, 1),
+                mantra(0, 7),
+                mantra(1, 21),
+                mantra(1, 21),
+                mantra(0, 7),
+                mantra(1, 21),
+                mantra(1, 21),
+            ]
+        );
+        assert_eq!(options.sections[1].units(&options.mantras), 14 + 42);
+        assert_eq!(options.sections[1].expanded_len(), 6);
+        assert_eq!(options.sections[2].expanded_len(), 0);
+        assert_eq!(options.sections[1].max_mantra_index(), Some(1));
+    }
+
+    #[test]
+    fn group_frequencies() {
+        let options = Options {
+            mantras: vec![
+                Mantra {
+                    frequency: Some(3),
+                    ..Mantra::from_text("om")
+                },
+                Mantra::from_text("hri"),
+            ],
+            sections: vec![Section::Group {
+                sections: vec![
+                    Section::Mantra {
+                        index: 0,
+                        repeats: None,
+                        frequency: None,
+                    },
+                    Section::Mantra {
+                        index: 1,
+                        repeats: None,
+                        frequency: None,
+                    },
+                ],
+                repeats: None,
+                frequency: Some(2),
+            }],
+            ..Default::default()
+        };
+
+        // The sections of the group are recited when both their frequency and the group's agree.
+        let sections = options.sadhana_sections();
+        let frequencies: Vec<usize> = sections
+            .iter()
+            .map(|section| section.frequency(&options.mantras))
+            .collect();
+        assert_eq!(frequencies, vec![6, 2]);
+        assert!(options.recites(&sections[0], None, 12));
+        assert!(!options.recites(&sections[0], None, 4));
+        assert!(options.recites(&sections[1], None, 4));
+
+        // The average sadhana recites a sixth of the first mantra and half of the second.
+        assert_eq!(options.average_weights(&sections), (vec![1, 3], 6));
+    }
+
+    #[test]
+    fn weighted_selection() {
+        let mut options = Options {
+            preparation: Some("ab".to_string()),
+            mantras: vec![
+                Mantra {
+                    weight: Some(3),
+                    ..Mantra::from_text("om")
+                },
+                Mantra::from_text("hri"),
+            ],
+            selection: MantraSelection::Weighted,
+            ..Default::default()
+        };
+        let sections = options.sadhana_sections();
+        let selection = options.selection(&sections).unwrap();
+        assert_eq!(selection.total(), 4);
+        assert_eq!(selection.weight(&sections[0]), 4);
+        assert_eq!(selection.weight(&sections[1]), 3);
+        assert_eq!(selection.weight(&sections[2]), 1);
+        let drawn: Vec<_> = (0..5).map(|random| selection.draw(random)).collect();
+        assert_eq!(drawn, [Some(0), Some(0), Some(0), Some(1), Some(0)]);
+
+        // Every mantra has the same chance when drawn at random, and none is drawn when every
+        // mantra is recited.
+        options.selection = MantraSelection::Random;
+        let selection = options.selection(&sections).unwrap();
+        assert_eq!(selection.total(), 2);
+        assert_eq!(selection.draw(3), Some(1));
+        options.selection = MantraSelection::All;
+        assert!(options.selection(&sections).is_none());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn parse_sections() -> crate::Result<()> {
+        let options = Options::from_toml_str(
+            r#"
+            [[mantras]]
+            syllables = ["om", "ah", "hum"]
+            repeats = 7
+
+            [[sections]]
+            kind = "text"
+            text = "I prostrate."
+            rate = "2ms"
+
+            [[sections]]
+            kind = "silence"
+            duration = "1.5s"
+
+            [[sections]]
+            kind = "mantra"
+            index = 0
+
+            [[sections]]
+            kind = "group"
+            repeats = 3
+
+            [[sections.sections]]
+            kind = "mantra"
+            index = 0
+            repeats = 2
+            "#,
+        )?;
+        assert_eq!(
+            options.sections,
+            vec![
+                Section::Text {
+                    text: "I prostrate.".to_string(),
+                    repeats: None,
+                    rate: Some(Duration::from_millis(2)),
+                    frequency: None,
+                },
+                Section::Silence {
+                    duration: Duration::from_millis(1500),
+                    frequency: None,
+                },
+                Section::Mantra {
+                    index: 0,
+                    repeats: None,
+                    frequency: None,
+                },
+                Section::Group {
+                    sections: vec![Section::Mantra {
+                        index: 0,
+                        repeats: Some(2),
+                        frequency: None,
+                    }],
+                    repeats: Some(3),
+                    frequency: None,
+                },
+            ]
+        );
+        assert_eq!(options.sections[3].repeats(&options.mantras), 3);
+        assert_eq!(options.sections[3].units(&options.mantras), 6);
+        assert_eq!(options.sections[2].repeats(&options.mantras), 7);
+        assert_eq!(options.sections[2].units(&options.mantras), 3);
+
+        // The sections are written back in the same format.
+        let contents = toml::to_string(&options).unwrap();
+        assert!(contents.contains("kind = \"silence\""));
+        assert_eq!(Options::from_toml_str(&contents)?, options);
+        Ok(())
+    }
+}
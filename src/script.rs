@@ -0,0 +1,820 @@
+//! Contains the conversion of mantras and prayers between scripts, so that they can be configured
+//! in a romanization that is easy to type and recited in the script of the practitioner's choice.
+//! Texts written in the Extended Wylie Transliteration Scheme (EWTS) are converted to Tibetan
+//! script, and texts are converted both ways between the International Alphabet of Sanskrit
+//! Transliteration (IAST) and Devanagari. A [`Transliteration`] applies any of these conversions to
+//! a mantra or, as a layer of a [`Pipeline`](crate::Pipeline), to the output of the miner.
+//! Romanized Sanskrit and Tibetan texts can also be split into syllables, so that mantras do not
+//! need to be split by hand.
+
+use alloc::{string::String, sync::Arc, vec::Vec};
+
+use crate::Mantra;
+
+/// The consonants of Wylie and their Tibetan letters, with the longest spellings first so that
+/// `tsh` is not read as `ts` followed by `h`.
+const CONSONANTS: [(&str, char); 40] = [
+    ("tsh", '\u{f5a}'),
+    ("dzh", '\u{f5c}'),
+    ("kSh", '\u{f69}'),
+    ("kh", '\u{f41}'),
+    ("gh", '\u{f43}'),
+    ("ng", '\u{f44}'),
+    ("ch", '\u{f46}'),
+    ("ny", '\u{f49}'),
+    ("Th", '\u{f4b}'),
+    ("Dh", '\u{f4d}'),
+    ("th", '\u{f50}'),
+    ("dh", '\u{f52}'),
+    ("ph", '\u{f55}'),
+    ("bh", '\u{f57}'),
+    ("ts", '\u{f59}'),
+    ("dz", '\u{f5b}'),
+    ("zh", '\u{f5e}'),
+    ("sh", '\u{f64}'),
+    ("Sh", '\u{f65}'),
+    ("k", '\u{f40}'),
+    ("g", '\u{f42}'),
+    ("c", '\u{f45}'),
+    ("j", '\u{f47}'),
+    ("T", '\u{f4a}'),
+    ("D", '\u{f4c}'),
+    ("N", '\u{f4e}'),
+    ("t", '\u{f4f}'),
+    ("d", '\u{f51}'),
+    ("n", '\u{f53}'),
+    ("p", '\u{f54}'),
+    ("b", '\u{f56}'),
+    ("m", '\u{f58}'),
+    ("w", '\u{f5d}'),
+    ("z", '\u{f5f}'),
+    ("'", '\u{f60}'),
+    ("y", '\u{f61}'),
+    ("r", '\u{f62}'),
+    ("l", '\u{f63}'),
+    ("s", '\u{f66}'),
+    ("h", '\u{f67}'),
+];
+
+/// The vowels of Wylie and the Tibetan vowel signs written after a consonant, with the longest
+/// spellings first. The inherent `a` has no sign.
+const VOWELS: [(&str, &str); 12] = [
+    ("-I", "\u{f71}\u{f80}"),
+    ("-i", "\u{f80}"),
+    ("ai", "\u{f7b}"),
+    ("au", "\u{f7d}"),
+    ("A", "\u{f71}"),
+    ("I", "\u{f71}\u{f72}"),
+    ("U", "\u{f71}\u{f74}"),
+    ("a", ""),
+    ("i", "\u{f72}"),
+    ("u", "\u{f74}"),
+    ("e", "\u{f7a}"),
+    ("o", "\u{f7c}"),
+];
+
+/// The marks written after a vowel, such as the anusvara of `oM`, with the longest spellings first.
+const MARKS: [(&str, char); 3] = [("~M", '\u{f83}'), ("M", '\u{f7e}'), ("H", '\u{f7f}')];
+
+/// The Tibetan letter that carries a vowel with no consonant before it.
+const A_CHEN: char = '\u{f68}';
+
+/// The tsheg, which separates Tibetan syllables.
+const TSHEG: char = '\u{f0b}';
+
+/// The shad, which ends a Tibetan phrase and is written `/` in Wylie.
+const SHAD: char = '\u{f0d}';
+
+/// A part of a Wylie syllable.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Token<'a> {
+    /// A consonant, given by its Wylie spelling.
+    Consonant(&'a str),
+
+    /// A vowel, given by its Tibetan vowel sign.
+    Vowel(&'a str),
+
+    /// A mark written after a vowel, given by its Tibetan sign.
+    Mark(char),
+
+    /// A `+`, which stacks the consonants on each side of it.
+    Plus,
+
+    /// A `.`, which keeps the consonants on each side of it from being stacked, as in `g.yag`.
+    Dot,
+
+    /// Any other character, which is converted on its own.
+    Other(char),
+}
+
+/// Returns the entry of the table whose spelling starts the given text, if any.
+fn find<T: Copy>(table: &[(&'static str, T)], text: &str) -> Option<(&'static str, T)> {
+    table
+        .iter()
+        .find(|(spelling, _)| text.starts_with(spelling))
+        .copied()
+}
+
+/// Splits the given Wylie syllable into its parts.
+fn tokenize(syllable: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut rest = syllable;
+    while let Some(c) = rest.chars().next() {
+        let (token, length) = if let Some((spelling, sign)) = find(&VOWELS, rest) {
+            (Token::Vowel(sign), spelling.len())
+        } else if let Some((spelling, _)) = find(&CONSONANTS, rest) {
+            (Token::Consonant(&rest[..spelling.len()]), spelling.len())
+        } else if let Some((spelling, mark)) = find(&MARKS, rest) {
+            (Token::Mark(mark), spelling.len())
+        } else {
+            let token = match c {
+                '+' => Token::Plus,
+                '.' => Token::Dot,
+                c => Token::Other(c),
+            };
+            (token, c.len_utf8())
+        };
+        tokens.push(token);
+        rest = &rest[length..];
+    }
+    tokens
+}
+
+/// Returns the Tibetan letter of the given Wylie consonant.
+fn letter(consonant: &str) -> char {
+    find(&CONSONANTS, consonant).map_or(A_CHEN, |(_, letter)| letter)
+}
+
+/// Returns the subjoined form of the given Tibetan letter, written under the letter before it in a
+/// stack.
+fn subjoined(letter: char) -> char {
+    char::from_u32(u32::from(letter) + 0x50).unwrap_or(letter)
+}
+
+/// Returns whether the given consonant can be written above the given root letter.
+fn is_superscript(consonant: &str, root: &str) -> bool {
+    let roots: &[&str] = match consonant {
+        "r" => &[
+            "k", "g", "ng", "j", "ny", "t", "d", "n", "b", "m", "ts", "dz",
+        ],
+        "l" => &["k", "g", "ng", "c", "j", "t", "d", "p", "b", "h"],
+        "s" => &["k", "g", "ng", "ny", "t", "d", "n", "p", "b", "m", "ts"],
+        _ => &[],
+    };
+    roots.contains(&root)
+}
+
+/// Returns whether the given consonant can be written under the given root letter.
+fn is_subscript(consonant: &str, root: &str) -> bool {
+    let roots: &[&str] = match consonant {
+        "y" => &["k", "kh", "g", "p", "ph", "b", "m", "h"],
+        "r" => &[
+            "k", "kh", "g", "t", "th", "d", "n", "p", "ph", "b", "m", "sh", "s", "h",
+        ],
+        "l" => &["k", "g", "b", "r", "s", "z"],
+        "w" => return true,
+        _ => &[],
+    };
+    roots.contains(&root)
+}
+
+/// Returns whether the given consonant can be written before the root letter of a syllable.
+fn is_prefix(consonant: &str) -> bool {
+    ["g", "d", "b", "m", "'"].contains(&consonant)
+}
+
+/// Returns whether the given consonants form a single stack of a Tibetan syllable: an optional
+/// superscript, the root letter, and up to two subscripts.
+fn is_stack(consonants: &[&str]) -> bool {
+    let rest = match consonants {
+        [superscript, root, ..] if is_superscript(superscript, root) => &consonants[1..],
+        _ => consonants,
+    };
+    match rest {
+        [] => false,
+        [root, subscripts @ ..] => {
+            subscripts.len() <= 2
+                && subscripts
+                    .iter()
+                    .all(|subscript| is_subscript(subscript, root))
+        }
+    }
+}
+
+/// Writes the given consonants as a single stack.
+fn push_stack(output: &mut String, consonants: &[&str]) {
+    for (i, consonant) in consonants.iter().enumerate() {
+        let letter = letter(consonant);
+        output.push(if i == 0 { letter } else { subjoined(letter) });
+    }
+}
+
+/// Writes the given consonants, each of which is either stacked under the one before it or written
+/// on its own.
+fn push_groups(output: &mut String, consonants: &[(&str, bool)]) {
+    for (consonant, stacked) in consonants {
+        let letter = letter(consonant);
+        output.push(if *stacked { subjoined(letter) } else { letter });
+    }
+}
+
+/// Writes the consonants before the first vowel of a syllable, finding the root letter among the
+/// prefix, superscript, and subscripts around it unless the stacks are given explicitly with `+`
+/// and `.`.
+fn push_head(output: &mut String, consonants: &[(&str, bool)], explicit: bool) {
+    let letters: Vec<&str> = consonants.iter().map(|(consonant, _)| *consonant).collect();
+    if explicit || letters.len() == 1 {
+        push_groups(output, consonants);
+    } else if is_stack(&letters) {
+        push_stack(output, &letters);
+    } else if is_prefix(letters[0]) && is_stack(&letters[1..]) {
+        push_stack(output, &letters[..1]);
+        push_stack(output, &letters[1..]);
+    } else {
+        push_groups(output, consonants);
+    }
+}
+
+/// Converts a single Wylie syllable, which contains no spaces, to Tibetan script.
+fn convert_syllable(syllable: &str) -> String {
+    let tokens = tokenize(syllable);
+    let mut output = String::new();
+    let mut consonants: Vec<(&str, bool)> = Vec::new();
+    let mut stack_next = false;
+    let mut explicit = false;
+    let mut in_head = true;
+    for token in tokens {
+        match token {
+            Token::Consonant(consonant) => {
+                consonants.push((consonant, stack_next && !consonants.is_empty()));
+                stack_next = false;
+            }
+            Token::Plus => {
+                stack_next = true;
+                explicit = true;
+            }
+            Token::Dot => {
+                stack_next = false;
+                explicit = true;
+            }
+            Token::Vowel(sign) => {
+                if consonants.is_empty() {
+                    output.push(A_CHEN);
+                } else if in_head {
+                    push_head(&mut output, &consonants, explicit);
+                } else {
+                    push_groups(&mut output, &consonants);
+                }
+                output.push_str(sign);
+                consonants.clear();
+                in_head = false;
+            }
+            Token::Mark(mark) => {
+                push_groups(&mut output, &consonants);
+                consonants.clear();
+                output.push(mark);
+            }
+            Token::Other(c) => {
+                push_groups(&mut output, &consonants);
+                consonants.clear();
+                let digit = c
+                    .to_digit(10)
+                    .and_then(|digit| char::from_u32(0xf20 + digit));
+                output.push(digit.unwrap_or(c));
+            }
+        }
+    }
+    push_groups(&mut output, &consonants);
+    output
+}
+
+/// Converts the given text written in Wylie to Tibetan script. Syllables are separated by spaces,
+/// which become tshegs, including a space at the end of the text, and `/` becomes a shad. Letters
+/// are stacked following the rules of Tibetan spelling, or as marked with `+` for the stacks of
+/// Sanskrit words, as in `pad+me`, and `.` keeps a prefix apart from the root letter, as in
+/// `g.yag`. Characters that are not part of Wylie are kept as they are.
+pub fn wylie_to_tibetan(wylie: &str) -> String {
+    let mut output = String::new();
+    let mut syllable = String::new();
+    let mut chars = wylie.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != ' ' && c != '/' && !c.is_whitespace() {
+            syllable.push(c);
+            continue;
+        }
+        let ends_with_nga = syllable.ends_with("ng");
+        output.push_str(&convert_syllable(&syllable));
+        let had_syllable = !syllable.is_empty();
+        syllable.clear();
+        match c {
+            '/' => output.push(SHAD),
+            ' ' if had_syllable => {
+                // A tsheg separates two syllables, and stays before a shad only after a nga.
+                while chars.peek() == Some(&' ') {
+                    chars.next();
+                }
+                match chars.peek() {
+                    Some('/') if ends_with_nga => output.push(TSHEG),
+                    Some('/') => {}
+                    _ => output.push(TSHEG),
+                }
+            }
+            c => output.push(c),
+        }
+    }
+    output.push_str(&convert_syllable(&syllable));
+    output
+}
+
+/// The consonants of IAST and their Devanagari letters, with the aspirated consonants first so that
+/// `kh` is not read as `k` followed by `h`.
+const IAST_CONSONANTS: [(&str, char); 33] = [
+    ("kh", '\u{916}'),
+    ("gh", '\u{918}'),
+    ("ch", '\u{91b}'),
+    ("jh", '\u{91d}'),
+    ("ṭh", '\u{920}'),
+    ("ḍh", '\u{922}'),
+    ("th", '\u{925}'),
+    ("dh", '\u{927}'),
+    ("ph", '\u{92b}'),
+    ("bh", '\u{92d}'),
+    ("k", '\u{915}'),
+    ("g", '\u{917}'),
+    ("ṅ", '\u{919}'),
+    ("c", '\u{91a}'),
+    ("j", '\u{91c}'),
+    ("ñ", '\u{91e}'),
+    ("ṭ", '\u{91f}'),
+    ("ḍ", '\u{921}'),
+    ("ṇ", '\u{923}'),
+    ("t", '\u{924}'),
+    ("d", '\u{926}'),
+    ("n", '\u{928}'),
+    ("p", '\u{92a}'),
+    ("b", '\u{92c}'),
+    ("m", '\u{92e}'),
+    ("y", '\u{92f}'),
+    ("r", '\u{930}'),
+    ("l", '\u{932}'),
+    ("v", '\u{935}'),
+    ("ś", '\u{936}'),
+    ("ṣ", '\u{937}'),
+    ("s", '\u{938}'),
+    ("h", '\u{939}'),
+];
+
+/// The vowels of IAST with their independent Devanagari letters and the signs written after a
+/// consonant, with the diphthongs first so that `ai` is not read as `a` followed by `i`. The
+/// inherent `a` has no sign.
+const IAST_VOWELS: [(&str, char, Option<char>); 14] = [
+    ("ai", '\u{910}', Some('\u{948}')),
+    ("au", '\u{914}', Some('\u{94c}')),
+    ("a", '\u{905}', None),
+    ("ā", '\u{906}', Some('\u{93e}')),
+    ("i", '\u{907}', Some('\u{93f}')),
+    ("ī", '\u{908}', Some('\u{940}')),
+    ("u", '\u{909}', Some('\u{941}')),
+    ("ū", '\u{90a}', Some('\u{942}')),
+    ("ṛ", '\u{90b}', Some('\u{943}')),
+    ("ṝ", '\u{960}', Some('\u{944}')),
+    ("ḷ", '\u{90c}', Some('\u{962}')),
+    ("ḹ", '\u{961}', Some('\u{963}')),
+    ("e", '\u{90f}', Some('\u{947}')),
+    ("o", '\u{913}', Some('\u{94b}')),
+];
+
+/// The marks and punctuation of IAST and their Devanagari signs, with the longest spellings first.
+/// The anusvara is spelled both `ṃ` and `ṁ`, and read back as `ṃ`.
+const IAST_MARKS: [(&str, char); 7] = [
+    ("m\u{310}", '\u{901}'),
+    ("||", '\u{965}'),
+    ("ṃ", '\u{902}'),
+    ("ṁ", '\u{902}'),
+    ("ḥ", '\u{903}'),
+    ("'", '\u{93d}'),
+    ("|", '\u{964}'),
+];
+
+/// The virama, which removes the inherent vowel of a Devanagari consonant.
+const VIRAMA: char = '\u{94d}';
+
+/// Converts the given text written in IAST to Devanagari. A consonant that is not followed by a
+/// vowel takes a virama, so that consonant clusters such as the `dm` of `padme` are written as
+/// conjuncts. Capital letters are read as lowercase, `|` and `||` become dandas, and characters
+/// that are not part of IAST are kept as they are. The text is expected to be composed, as with
+/// the default normalization of the options.
+pub fn iast_to_devanagari(iast: &str) -> String {
+    let text = iast.to_lowercase();
+    let mut output = String::new();
+    let mut rest = text.as_str();
+    let mut after_consonant = false;
+    while let Some(c) = rest.chars().next() {
+        if let Some((spelling, letter, sign)) = IAST_VOWELS
+            .iter()
+            .find(|(spelling, ..)| rest.starts_with(spelling))
+        {
+            match (after_consonant, sign) {
+                (true, Some(sign)) => output.push(*sign),
+                (true, None) => {}
+                (false, _) => output.push(*letter),
+            }
+            after_consonant = false;
+            rest = &rest[spelling.len()..];
+            continue;
+        }
+        if after_consonant {
+            output.push(VIRAMA);
+            after_consonant = false;
+        }
+        let length = if let Some((spelling, mark)) = find(&IAST_MARKS, rest) {
+            output.push(mark);
+            spelling.len()
+        } else if let Some((spelling, letter)) = find(&IAST_CONSONANTS, rest) {
+            output.push(letter);
+            after_consonant = true;
+            spelling.len()
+        } else {
+            let digit = c
+                .to_digit(10)
+                .and_then(|digit| char::from_u32(0x966 + digit));
+            output.push(digit.unwrap_or(c));
+            c.len_utf8()
+        };
+        rest = &rest[length..];
+    }
+    if after_consonant {
+        output.push(VIRAMA);
+    }
+    output
+}
+
+/// Converts the given text written in Devanagari to IAST. A consonant without a vowel sign or a
+/// virama after it takes the inherent `a`, and characters that are not part of Devanagari are kept
+/// as they are.
+pub fn devanagari_to_iast(devanagari: &str) -> String {
+    let mut output = String::new();
+    let mut chars = devanagari.chars().peekable();
+    while let Some(c) = chars.next() {
+        if let Some((spelling, _)) = IAST_CONSONANTS.iter().find(|(_, letter)| *letter == c) {
+            output.push_str(spelling);
+            let next = chars.peek().copied();
+            let vowel = IAST_VOWELS
+                .iter()
+                .find(|(.., sign)| sign.is_some() && *sign == next);
+            match (vowel, next) {
+                (Some((spelling, ..)), _) => {
+                    output.push_str(spelling);
+                    chars.next();
+                }
+                (None, Some(VIRAMA)) => {
+                    chars.next();
+                }
+                (None, _) => output.push('a'),
+            }
+        } else if let Some((spelling, ..)) = IAST_VOWELS.iter().find(|(_, letter, _)| *letter == c)
+        {
+            output.push_str(spelling);
+        } else if let Some((spelling, _)) = IAST_MARKS.iter().find(|(_, mark)| *mark == c) {
+            output.push_str(spelling);
+        } else if let Some(digit) = ('\u{966}'..='\u{96f}')
+            .contains(&c)
+            .then(|| char::from_u32(u32::from(c) - 0x966 + u32::from('0')))
+            .flatten()
+        {
+            output.push(digit);
+        } else {
+            output.push(c);
+        }
+    }
+    output
+}
+
+/// A conversion of texts from one script to another.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Transliteration {
+    /// From Wylie to Tibetan script. See [`wylie_to_tibetan`].
+    WylieToTibetan,
+
+    /// From IAST to Devanagari. See [`iast_to_devanagari`].
+    IastToDevanagari,
+
+    /// From Devanagari to IAST. See [`devanagari_to_iast`].
+    DevanagariToIast,
+}
+
+impl Transliteration {
+    /// Returns the function that converts a text.
+    fn function(self) -> fn(&str) -> String {
+        match self {
+            Transliteration::WylieToTibetan => wylie_to_tibetan,
+            Transliteration::IastToDevanagari => iast_to_devanagari,
+            Transliteration::DevanagariToIast => devanagari_to_iast,
+        }
+    }
+
+    /// Converts the given text, such as a prayer.
+    pub fn apply(self, text: &str) -> String {
+        (self.function())(text)
+    }
+
+    /// Returns the given mantra with each of its syllables converted.
+    pub fn apply_to_mantra(self, mantra: &Mantra) -> Mantra {
+        Mantra {
+            syllables: mantra
+                .syllables
+                .iter()
+                .map(|syllable| Arc::from(self.apply(syllable)))
+                .collect(),
+            ..mantra.clone()
+        }
+    }
+
+    /// Returns a layer that converts the text written to it, so that the output of the miner can
+    /// be written in another script than the one of the options. The text is held back until the
+    /// next whitespace, so that a word is converted whole even if it's split across writes, such
+    /// as when the buffer of the miner fills up. The rest is converted when the layer is flushed.
+    /// Wylie is held back until the end of the line instead, since a space becomes a tsheg or not
+    /// depending on what follows it.
+    #[cfg(feature = "std")]
+    pub fn layer(self) -> crate::MapText<fn(&str) -> String> {
+        let boundary: fn(char) -> bool = match self {
+            Transliteration::WylieToTibetan => |c| c.is_whitespace() && c != ' ',
+            _ => char::is_whitespace,
+        };
+        crate::MapText::new(self.function()).boundary(boundary)
+    }
+}
+
+/// A part of a word of romanized Sanskrit, used to split it into syllables.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Letter {
+    /// A vowel, including the diphthongs `ai` and `au`.
+    Vowel,
+
+    /// A consonant, including the aspirated consonants such as `dh`.
+    Consonant,
+
+    /// A mark that belongs to the syllable before it, such as the anusvara, or any other character.
+    Mark,
+}
+
+/// Splits the given word of romanized Sanskrit into its letters, returning the kind of each letter
+/// and its length in bytes.
+fn sanskrit_letters(word: &str) -> Vec<(Letter, usize)> {
+    let lowercase = word.to_lowercase();
+    let mut letters = Vec::new();
+    let mut rest = lowercase.as_str();
+    while let Some(c) = rest.chars().next() {
+        let letter = if let Some((spelling, ..)) = IAST_VOWELS
+            .iter()
+            .find(|(spelling, ..)| rest.starts_with(spelling))
+        {
+            (Letter::Vowel, spelling.len())
+        } else if let Some((spelling, _)) = find(&IAST_MARKS, rest) {
+            (Letter::Mark, spelling.len())
+        } else if let Some((spelling, _)) = find(&IAST_CONSONANTS, rest) {
+            (Letter::Consonant, spelling.len())
+        } else {
+            (Letter::Mark, c.len_utf8())
+        };
+        letters.push(letter);
+        rest = &rest[letter.1..];
+    }
+    letters
+}
+
+/// Splits the given word of romanized Sanskrit into syllables. Each syllable ends after a vowel and
+/// its marks, except that the first consonant of a cluster between two vowels closes the syllable
+/// before it, as in `pad-me`, and that the consonants at the end of the word close its last
+/// syllable.
+fn split_word(word: &str) -> Vec<&str> {
+    // The letters are read from the lowercase word, so their lengths only apply to the word if
+    // lowercasing it keeps its length.
+    if word.to_lowercase().len() != word.len() {
+        return Vec::from([word]);
+    }
+    let letters = sanskrit_letters(word);
+
+    let vowels: Vec<usize> = letters
+        .iter()
+        .enumerate()
+        .filter(|(_, (letter, _))| *letter == Letter::Vowel)
+        .map(|(i, _)| i)
+        .collect();
+    let offsets: Vec<usize> = letters
+        .iter()
+        .scan(0, |offset, (_, length)| {
+            let start = *offset;
+            *offset += length;
+            Some(start)
+        })
+        .collect();
+    let mut syllables = Vec::new();
+    let mut start = 0;
+    for pair in vowels.windows(2) {
+        let consonants: Vec<usize> = (pair[0] + 1..pair[1])
+            .filter(|i| letters[*i].0 == Letter::Consonant)
+            .collect();
+        let boundary = match consonants.as_slice() {
+            [] => pair[1],
+            [consonant] => *consonant,
+            [_, second, ..] => *second,
+        };
+        syllables.push(&word[start..offsets[boundary]]);
+        start = offsets[boundary];
+    }
+    syllables.push(&word[start..]);
+    syllables
+}
+
+/// Splits the given text of romanized Sanskrit, in IAST or in plain ASCII, into syllables. Words
+/// are separated by whitespace, and hyphens can be used to split a word explicitly where the rules
+/// would split it differently. For example, `"oṃ maṇi padme hūṃ"` results in the syllables `oṃ`,
+/// `ma`, `ṇi`, `pad`, `me`, and `hūṃ`.
+pub fn sanskrit_syllables(text: &str) -> Vec<&str> {
+    text.split(|c: char| c.is_whitespace() || c == '-')
+        .filter(|word| !word.is_empty())
+        .flat_map(split_word)
+        .collect()
+}
+
+/// Splits the given Tibetan text, in Wylie or in Tibetan script, into syllables, which are
+/// separated by whitespace, tshegs, or shads.
+pub fn tibetan_syllables(text: &str) -> Vec<&str> {
+    text.split(|c: char| c.is_whitespace() || ['/', TSHEG, SHAD].contains(&c))
+        .filter(|syllable| !syllable.is_empty())
+        .collect()
+}
+
+impl Mantra {
+    /// Returns a new mantra whose syllables are obtained by splitting the given text written in
+    /// romanized Sanskrit with [`sanskrit_syllables`]. The syllables are kept in the romanization
+    /// they were written in. For example, `"om mani padme hum"` results in the syllables `om`,
+    /// `ma`, `ni`, `pad`, `me`, and `hum`.
+    pub fn from_sanskrit(text: &str) -> Mantra {
+        Mantra {
+            language: Some("Sanskrit".into()),
+            syllables: sanskrit_syllables(text)
+                .into_iter()
+                .map(Arc::from)
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Returns a new mantra whose syllables are obtained by splitting the given text with
+    /// [`tibetan_syllables`] and converting each syllable written in Wylie to Tibetan script.
+    /// Syllables already written in Tibetan script are kept as they are. For example,
+    /// `"oM ma Ni pad+me hUM"` results in the syllables `ཨོཾ`, `མ`, `ཎི`, `པདྨེ`, and `ཧཱུཾ`.
+    pub fn from_wylie(wylie: &str) -> Mantra {
+        Mantra {
+            language: Some("Tibetan".into()),
+            syllables: tibetan_syllables(wylie)
+                .into_iter()
+                .map(|syllable| Arc::from(wylie_to_tibetan(syllable)))
+                .collect(),
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::sync::Arc;
+
+    use crate::{
+        script::{
+            devanagari_to_iast, iast_to_devanagari, sanskrit_syllables, tibetan_syllables,
+            wylie_to_tibetan, Transliteration,
+        },
+        Mantra,
+    };
+
+    #[test]
+    fn wylie() {
+        assert_eq!(wylie_to_tibetan("oM ma Ni pad+me hUM"), "ཨོཾ་མ་ཎི་པདྨེ་ཧཱུཾ");
+
+        // The root letter is found among the prefix, superscript, and subscripts around it.
+        assert_eq!(wylie_to_tibetan("bsgrubs"), "བསྒྲུབས");
+        assert_eq!(wylie_to_tibetan("rgyal ba"), "རྒྱལ་བ");
+        assert_eq!(wylie_to_tibetan("bla ma"), "བླ་མ");
+        assert_eq!(wylie_to_tibetan("dpal ldan"), "དཔལ་ལྡན");
+        assert_eq!(wylie_to_tibetan("mkhyen"), "མཁྱེན");
+        assert_eq!(wylie_to_tibetan("gya g.yag"), "གྱ་གཡག");
+
+        // A shad ends the phrase, with a tsheg before it only after a nga.
+        assert_eq!(
+            wylie_to_tibetan("sangs rgyas chos dang tshogs kyi mchog rnams la /"),
+            "སངས་རྒྱས་ཆོས་དང་ཚོགས་ཀྱི་མཆོག་རྣམས་ལ།"
+        );
+        assert_eq!(wylie_to_tibetan("dbang /"), "དབང་།");
+        assert_eq!(wylie_to_tibetan("oM ma "), "ཨོཾ་མ་");
+
+        // Marks, digits, and characters outside Wylie.
+        assert_eq!(wylie_to_tibetan("hoH 108"), "ཧོཿ་༡༠༨");
+        assert_eq!(wylie_to_tibetan("hU~M"), "ཧཱུྃ");
+        assert_eq!(wylie_to_tibetan("om\nA"), "ཨོམ\nཨཱ");
+    }
+
+    #[test]
+    fn from_wylie() {
+        let mantra = Mantra::from_wylie("oM A hUM");
+        assert_eq!(*mantra.syllables, ["ཨོཾ", "ཨཱ", "ཧཱུཾ"].map(Arc::<str>::from));
+        assert_eq!(mantra.language.as_deref(), Some("Tibetan"));
+    }
+
+    #[test]
+    fn iast() {
+        let pairs = [
+            ("oṃ maṇi padme hūṃ", "ओं मणि पद्मे हूं"),
+            (
+                "gate gate pāragate pārasaṃgate bodhi svāhā",
+                "गते गते पारगते पारसंगते बोधि स्वाहा",
+            ),
+            (
+                "oṃ āḥ hūṃ vajra guru padma siddhi hūṃ",
+                "ओं आः हूं वज्र गुरु पद्म सिद्धि हूं",
+            ),
+            ("phaṭ ||", "फट् ॥"),
+            ("kṣetra jñāna aiśvarya 108", "क्षेत्र ज्ञान ऐश्वर्य १०८"),
+        ];
+        for (iast, devanagari) in pairs {
+            assert_eq!(iast_to_devanagari(iast), devanagari);
+            assert_eq!(devanagari_to_iast(devanagari), iast);
+        }
+
+        // Capitals are read as lowercase, and other characters are kept.
+        assert_eq!(iast_to_devanagari("Oṃ, Tāre!"), "ओं, तारे!");
+    }
+
+    #[test]
+    fn transliteration() {
+        let mantra = Transliteration::IastToDevanagari.apply_to_mantra(&Mantra {
+            name: Some("Mani".to_string()),
+            ..Mantra::from_text("oṃ ma-ṇi pad-me hūṃ")
+        });
+        assert_eq!(mantra.name.as_deref(), Some("Mani"));
+        assert_eq!(
+            *mantra.syllables,
+            ["ओं", "म", "णि", "पद्", "मे", "हूं"].map(Arc::<str>::from)
+        );
+        assert_eq!(
+            Transliteration::DevanagariToIast.apply(&mantra.syllables.concat()),
+            "oṃmaṇipadmehūṃ"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn layer() -> std::io::Result<()> {
+        use crate::Layer;
+
+        let mut layer = Transliteration::IastToDevanagari.layer();
+        let mut output = Vec::new();
+        layer.write("oṃ\npad\n".as_bytes(), &mut output)?;
+        layer.write("me\n".as_bytes(), &mut output)?;
+        assert_eq!(String::from_utf8_lossy(&output), "ओं\nपद्\nमे\n");
+        Ok(())
+    }
+
+    #[test]
+    fn syllables() {
+        let pairs = [
+            (
+                "oṃ maṇi padme hūṃ",
+                vec!["oṃ", "ma", "ṇi", "pad", "me", "hūṃ"],
+            ),
+            (
+                "om mani padme hum",
+                vec!["om", "ma", "ni", "pad", "me", "hum"],
+            ),
+            ("OM AH HUM", vec!["OM", "AH", "HUM"]),
+            (
+                "vajra guru siddhi",
+                vec!["vaj", "ra", "gu", "ru", "sid", "dhi"],
+            ),
+            (
+                "pārasaṃgate svāhā",
+                vec!["pā", "ra", "saṃ", "ga", "te", "svā", "hā"],
+            ),
+            ("ta-dya-thā", vec!["ta", "dya", "thā"]),
+        ];
+        for (text, syllables) in pairs {
+            assert_eq!(sanskrit_syllables(text), syllables);
+        }
+
+        assert_eq!(
+            tibetan_syllables("oM ma Ni/ ཧཱུཾ་ཕཊ།"),
+            ["oM", "ma", "Ni", "ཧཱུཾ", "ཕཊ"]
+        );
+        assert_eq!(
+            *Mantra::from_sanskrit("om tare tuttare").syllables,
+            ["om", "ta", "re", "tut", "ta", "re"].map(Arc::<str>::from)
+        );
+        assert_eq!(
+            *Mantra::from_wylie("oM A hUM་ཧྲཱིཿ").syllables,
+            ["ཨོཾ", "ཨཱ", "ཧཱུཾ", "ཧྲཱིཿ"].map(Arc::<str>::from)
+        );
+    }
+}
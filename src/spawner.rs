@@ -0,0 +1,504 @@
+//! Defines the `Spawner` trait used to decouple the recitation of the mantra miner from the
+//! threading model that drives it.
+
+use parking_lot::{Condvar, Mutex};
+use std::{
+    any::Any,
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        mpsc::{Receiver, RecvTimeoutError, TryRecvError},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{priority, queue::RunOutput, recitation::Recitation};
+
+/// The interval at which a paused task asks to be stepped again when driven by
+/// [`RecitationTask::step`].
+const PAUSE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The delay before the first restart of a recitation that panicked, doubled after each restart.
+const RESTART_DELAY: Duration = Duration::from_millis(100);
+
+/// The longest delay before restarting a recitation that panicked.
+const MAX_RESTART_DELAY: Duration = Duration::from_secs(60);
+
+/// Returns the message of a panic from its payload.
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+/// A latch that records when a recitation task has finished and whether it panicked.
+#[derive(Clone, Default)]
+pub(crate) struct Completion(Arc<(Mutex<Option<bool>>, Condvar)>);
+
+impl Completion {
+    /// Marks the task as finished and wakes up all the threads waiting for it.
+    fn finish(&self, panicked: bool) {
+        let (lock, condvar) = &*self.0;
+        *lock.lock() = Some(panicked);
+        condvar.notify_all();
+    }
+
+    /// Returns whether the task has finished.
+    pub(crate) fn is_finished(&self) -> bool {
+        self.0 .0.lock().is_some()
+    }
+
+    /// Blocks until the task has finished and returns whether it panicked.
+    pub(crate) fn wait(&self) -> bool {
+        let (lock, condvar) = &*self.0;
+        let mut finished = lock.lock();
+        loop {
+            if let Some(panicked) = *finished {
+                return panicked;
+            }
+            condvar.wait(&mut finished);
+        }
+    }
+
+    /// Blocks until the task has finished or the timeout elapses. Returns whether the task
+    /// panicked, or `None` if it is still running after the timeout.
+    pub(crate) fn wait_timeout(&self, timeout: Duration) -> Option<bool> {
+        let deadline = Instant::now() + timeout;
+        let (lock, condvar) = &*self.0;
+        let mut finished = lock.lock();
+        loop {
+            if let Some(panicked) = *finished {
+                return Some(panicked);
+            }
+            if condvar.wait_until(&mut finished, deadline).timed_out() {
+                return *finished;
+            }
+        }
+    }
+}
+
+/// The background work of a running mantra miner, handed to a [`Spawner`] to be driven to
+/// completion. The task can either be run on a dedicated thread with
+/// [`RecitationTask::run_blocking`] or stepped one syllable at a time by a scheduler with
+/// [`RecitationTask::step`].
+pub struct RecitationTask {
+    /// The recitation of the sadhana.
+    recitation: Recitation<RunOutput>,
+
+    /// The channel used to receive the signal to stop.
+    stop_channel: Receiver<()>,
+
+    /// Whether the signal to stop has been received.
+    stopped: bool,
+
+    /// The number of times the recitation was restarted after a panic.
+    restarts: u32,
+
+    /// The latch set when the task is dropped. Declared last so that it is only set once the
+    /// recitation and the state it shares with the miner have been dropped.
+    _completion: CompletionGuard,
+}
+
+impl RecitationTask {
+    /// Returns a new task that runs the given recitation.
+    pub(crate) fn new(
+        recitation: Recitation<RunOutput>,
+        stop_channel: Receiver<()>,
+        completion: Completion,
+    ) -> RecitationTask {
+        RecitationTask {
+            recitation,
+            stop_channel,
+            stopped: false,
+            restarts: 0,
+            _completion: CompletionGuard(completion),
+        }
+    }
+
+    /// Returns whether the task should stop, either because the signal to stop was received or
+    /// because the miner that sends it was dropped.
+    fn should_stop(&mut self) -> bool {
+        if !self.stopped {
+            match self.stop_channel.try_recv() {
+                Ok(_) | Err(TryRecvError::Disconnected) => self.stopped = true,
+                Err(TryRecvError::Empty) => {}
+            }
+        }
+        self.stopped
+    }
+
+    /// Waits for the delay after the last syllable or character recited. The wait is interrupted as
+    /// soon as the signal to stop is received, so that a long rate does not delay stopping the
+    /// miner. The end of the wait is spun if the options enable the precise pacing.
+    fn sleep(&mut self) {
+        let delay = self.recitation.next_delay();
+        #[cfg(feature = "precise")]
+        if self.recitation.reciter.options().precise_pacing {
+            crate::precise::wait(delay, |delay| {
+                self.wait(delay);
+                self.stopped
+            });
+            return;
+        }
+        self.wait(delay);
+    }
+
+    /// Waits for the given delay, unless the signal to stop is received first.
+    fn wait(&mut self, delay: Duration) {
+        if self.stopped {
+            return;
+        }
+        match self.stop_channel.recv_timeout(delay) {
+            Ok(_) | Err(RecvTimeoutError::Disconnected) => self.stopped = true,
+            Err(RecvTimeoutError::Timeout) => {}
+        }
+    }
+
+    /// Records the panic with the given payload and restarts the recitation if the restart policy
+    /// in the options allows it. Returns how long to wait before resuming, or `None` if the
+    /// recitation should not be restarted.
+    fn recover(&mut self, payload: &(dyn Any + Send)) -> Option<Duration> {
+        let state = &self.recitation.state;
+        *state.last_panic.lock() = Some(panic_message(payload));
+        let policy = self.recitation.reciter.options().restart_policy;
+        if !policy.allows(self.restarts) {
+            return None;
+        }
+        let delay = RESTART_DELAY
+            .saturating_mul(1 << self.restarts.min(16))
+            .min(MAX_RESTART_DELAY);
+        self.restarts += 1;
+        *state.restarts.lock() = self.restarts;
+        self.recitation.restart();
+        Some(delay)
+    }
+
+    /// Recites the next syllable or character and returns how long to wait before calling this
+    /// method again, or `None` once the task is finished, either because the sadhana was recited
+    /// the number of times set in the options or because the miner was stopped. While the miner
+    /// is paused, nothing is recited and a short interval is returned instead. The same happens
+    /// while the miner pauses because the machine runs on battery. Used by schedulers that cannot
+    /// block the current thread. If the recitation panics and the restart policy allows it, the
+    /// delay before the restart is returned, and the panic is resumed otherwise.
+    pub fn step(&mut self) -> Option<Duration> {
+        match panic::catch_unwind(AssertUnwindSafe(|| self.step_once())) {
+            Ok(delay) => delay,
+            Err(payload) => match self.recover(&*payload) {
+                Some(delay) => Some(delay),
+                None => panic::resume_unwind(payload),
+            },
+        }
+    }
+
+    /// Recites the next syllable or character as described in `step`, without catching panics.
+    fn step_once(&mut self) -> Option<Duration> {
+        if self.should_stop() {
+            return None;
+        }
+        if self.recitation.state.pause_flag.is_paused() {
+            return Some(PAUSE_POLL_INTERVAL);
+        }
+        if let Some(delay) = self.recitation.power_pause() {
+            return Some(delay);
+        }
+        match self.recitation.step() {
+            Ok(Some(_)) => Some(self.recitation.next_delay()),
+            // A write error is recorded by the recitation, so that the miner can report it.
+            Ok(None) | Err(_) => None,
+        }
+    }
+
+    /// Runs the task to completion on the current thread, blocking while the miner is paused and
+    /// between syllables. Stopping the miner interrupts any wait. The priority of the thread is
+    /// first set to the one in the options, so the thread should not be reused for other work. If
+    /// the recitation panics and the restart policy allows it, it's restarted after a delay, and
+    /// the panic is resumed otherwise.
+    pub fn run_blocking(mut self) {
+        priority::set_current_thread_priority(self.recitation.reciter.options().thread_priority);
+        loop {
+            match panic::catch_unwind(AssertUnwindSafe(|| self.run_until_done())) {
+                Ok(()) => break,
+                Err(payload) => match self.recover(&*payload) {
+                    Some(delay) => self.wait(delay),
+                    None => panic::resume_unwind(payload),
+                },
+            }
+        }
+    }
+
+    /// Runs the recitation as described in `run_blocking`, without catching panics.
+    fn run_until_done(&mut self) {
+        loop {
+            self.recitation.state.pause_flag.wait();
+            if self.should_stop() {
+                break;
+            }
+            if let Some(delay) = self.recitation.power_pause() {
+                self.wait(delay);
+                continue;
+            }
+            match self.recitation.step() {
+                Ok(Some(_)) => self.sleep(),
+                Ok(None) | Err(_) => break,
+            }
+        }
+    }
+}
+
+/// Marks the task that owns it as finished when dropped.
+struct CompletionGuard(Completion);
+
+impl Drop for CompletionGuard {
+    /// Marks the task as finished, so that the mantra miner can tell when it can no longer be
+    /// running. A task dropped while unwinding is recorded as having panicked.
+    fn drop(&mut self) {
+        self.0.finish(thread::panicking());
+    }
+}
+
+/// Drives the background work of the mantra miner. Implementations decide whether the
+/// recitation runs on a dedicated thread, on an asynchronous runtime, or through timers provided
+/// by the host environment.
+pub trait Spawner: Send + Sync {
+    /// Starts running the given task in the background. The task is finished once it is dropped.
+    fn spawn(&self, task: RecitationTask);
+
+    /// Returns whether the mantra miner can block the current thread to wait for a spawned task to
+    /// finish. Spawners that run their tasks on the current thread must return false, since the
+    /// task could never finish while the thread is blocked.
+    fn can_block(&self) -> bool {
+        true
+    }
+}
+
+/// A spawner that runs each task on a new `std::thread`. This is the default spawner of the
+/// mantra miner.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ThreadSpawner;
+
+impl Spawner for ThreadSpawner {
+    fn spawn(&self, task: RecitationTask) {
+        thread::spawn(move || task.run_blocking());
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl Spawner for tokio::runtime::Handle {
+    /// Runs the task as an asynchronous task in the runtime, waiting between syllables with
+    /// `tokio::time::sleep`. Stopping the miner takes effect after the current wait.
+    fn spawn(&self, mut task: RecitationTask) {
+        tokio::runtime::Handle::spawn(self, async move {
+            while let Some(delay) = task.step() {
+                tokio::time::sleep(delay).await;
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use parking_lot::Mutex;
+    use std::{
+        io::{self, sink, Write},
+        sync::Arc,
+        thread,
+        time::Duration,
+    };
+
+    use crate::{Error, Mantra, MantraMiner, Options, RecitationTask, RestartPolicy, Spawner};
+
+    /// An output that panics the given number of times before accepting writes.
+    struct PanickingWriter(usize);
+
+    impl Write for PanickingWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.0 > 0 {
+                self.0 -= 1;
+                panic!("the output panicked");
+            }
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// A spawner that keeps the tasks so that the tests can step them manually.
+    #[derive(Clone, Default)]
+    struct ManualSpawner(Arc<Mutex<Vec<RecitationTask>>>);
+
+    impl Spawner for ManualSpawner {
+        fn spawn(&self, task: RecitationTask) {
+            self.0.lock().push(task);
+        }
+    }
+
+    /// A manual spawner that does not allow the miner to block waiting for its tasks.
+    #[derive(Clone, Default)]
+    struct NonBlockingSpawner(ManualSpawner);
+
+    impl Spawner for NonBlockingSpawner {
+        fn spawn(&self, task: RecitationTask) {
+            self.0.spawn(task);
+        }
+
+        fn can_block(&self) -> bool {
+            false
+        }
+    }
+
+    fn options(repeats: Option<usize>) -> Options {
+        Options {
+            mantras: vec![Mantra::from_text("om ah hum")],
+            rate: Duration::from_micros(1),
+            repeats,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn step_manually() -> Result<()> {
+        let spawner = ManualSpawner::default();
+        let miner = MantraMiner::with_spawner(
+            options(Some(2)),
+            Box::new(sink()),
+            Box::new(spawner.clone()),
+        );
+        miner.start()?;
+        let mut task = spawner.0.lock().pop().unwrap();
+
+        // The steps are not waited for, so the delays make up for the time by which they are late.
+        for _ in 0..6 {
+            let delay = task.step();
+            assert!(delay.is_some_and(|delay| delay <= Duration::from_nanos(1000)));
+        }
+        assert_eq!(task.step(), None);
+        assert_eq!(miner.count(), 2);
+        assert_eq!(miner.syllable_count(), 6);
+
+        // Dropping the task marks it as finished.
+        drop(task);
+        assert_eq!(miner.wait()?.sadhanas, 2);
+        Ok(())
+    }
+
+    #[test]
+    fn step_after_stop_and_pause() -> Result<()> {
+        let spawner = ManualSpawner::default();
+        let miner =
+            MantraMiner::with_spawner(options(None), Box::new(sink()), Box::new(spawner.clone()));
+        miner.start()?;
+        let mut task = spawner.0.lock().pop().unwrap();
+        task.step();
+
+        miner.pause()?;
+        assert!(task.step().is_some());
+        assert_eq!(miner.syllable_count(), 1);
+        miner.resume()?;
+
+        miner.stop()?;
+        assert_eq!(task.step(), None);
+        assert_eq!(miner.syllable_count(), 1);
+        drop(task);
+        miner.stop_and_join()?;
+        Ok(())
+    }
+
+    #[test]
+    fn panicked_task() -> Result<()> {
+        let spawner = ManualSpawner::default();
+        let miner =
+            MantraMiner::with_spawner(options(None), Box::new(sink()), Box::new(spawner.clone()));
+        miner.start()?;
+        let task = spawner.0.lock().pop().unwrap();
+        let _ = thread::spawn(move || {
+            let _task = task;
+            panic!("the task panicked");
+        })
+        .join();
+        assert!(matches!(miner.stop_and_join(), Err(Error::ThreadPanicked)));
+        Ok(())
+    }
+
+    #[test]
+    fn restart_after_panic() -> Result<()> {
+        // The output is flushed after each sadhana, so the first one panics and is recited again.
+        let miner = MantraMiner::with_output(
+            Options {
+                restart_policy: RestartPolicy::UpTo(1),
+                ..options(Some(2))
+            },
+            Box::new(PanickingWriter(1)),
+        );
+        miner.start()?;
+        miner.wait()?;
+        assert_eq!(miner.count(), 2);
+        assert_eq!(miner.restarts(), 1);
+        assert_eq!(miner.last_panic().as_deref(), Some("the output panicked"));
+
+        // Without restarts left, the panic stops the miner.
+        let miner = MantraMiner::with_output(
+            Options {
+                restart_policy: RestartPolicy::UpTo(1),
+                ..options(Some(2))
+            },
+            Box::new(PanickingWriter(2)),
+        );
+        miner.start()?;
+        assert!(matches!(miner.wait(), Err(Error::ThreadPanicked)));
+        assert_eq!(miner.count(), 0);
+        assert_eq!(miner.restarts(), 1);
+        assert_eq!(miner.last_panic().as_deref(), Some("the output panicked"));
+
+        assert!(!RestartPolicy::Never.allows(0));
+        assert!(RestartPolicy::Always.allows(u32::MAX));
+        Ok(())
+    }
+
+    #[test]
+    fn non_blocking_spawner() -> Result<()> {
+        let spawner = NonBlockingSpawner::default();
+        let miner = MantraMiner::with_spawner(
+            options(Some(1)),
+            Box::new(sink()),
+            Box::new(spawner.clone()),
+        );
+        miner.start()?;
+        let mut task = spawner.0 .0.lock().pop().unwrap();
+        assert!(matches!(miner.wait(), Err(Error::BlockingUnsupported)));
+
+        // The task is still running after the failed wait.
+        miner.pause()?;
+        miner.resume()?;
+
+        // Stopping returns without waiting for the task.
+        assert!(matches!(
+            miner.stop_and_join(),
+            Err(Error::BlockingUnsupported)
+        ));
+        assert_eq!(task.step(), None);
+        assert!(matches!(miner.pause(), Err(Error::NotRunning)));
+        Ok(())
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn tokio_spawner() -> Result<()> {
+        let miner = MantraMiner::with_spawner(
+            options(Some(3)),
+            Box::new(sink()),
+            Box::new(tokio::runtime::Handle::current()),
+        );
+        miner.start()?;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(miner.count(), 3);
+        Ok(())
+    }
+}
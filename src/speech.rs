@@ -0,0 +1,290 @@
+//! Contains a voice that speaks the recitation through a text-to-speech engine, so the miner can be
+//! followed by ear as an audible practice aid. The default engine runs the speech synthesizer of
+//! the operating system, and applications can plug in their own by implementing `SpeechEngine`.
+
+use std::{
+    io::{self, Write},
+    process::{Command, Stdio},
+    sync::Arc,
+};
+
+use crate::{Options, Voice};
+
+/// Speaks text aloud. Speaking should block until the text has been spoken, so that the pacing of
+/// the miner is kept. A rate faster than the engine can speak slows the recitation down to it.
+pub trait SpeechEngine: Send {
+    /// Speaks the given text.
+    fn speak(&mut self, text: &str) -> io::Result<()>;
+}
+
+/// The speech synthesizer of the operating system, run as a command that reads the text to speak
+/// from its standard input: `say` on macOS, `System.Speech` through PowerShell on Windows, and
+/// `espeak` everywhere else.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SystemSpeech {
+    /// The program to run.
+    program: String,
+
+    /// The arguments of the program.
+    args: Vec<String>,
+}
+
+impl SystemSpeech {
+    /// Returns an engine that runs the given program with the given arguments for each text and
+    /// writes the text to its standard input, such as `espeak-ng --stdin -v sa`.
+    pub fn new(
+        program: impl Into<String>,
+        args: impl IntoIterator<Item = impl Into<String>>,
+    ) -> SystemSpeech {
+        SystemSpeech {
+            program: program.into(),
+            args: args.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl Default for SystemSpeech {
+    fn default() -> SystemSpeech {
+        if cfg!(target_os = "macos") {
+            SystemSpeech::new("say", ["-f", "-"])
+        } else if cfg!(windows) {
+            SystemSpeech::new(
+                "powershell",
+                [
+                    "-NoProfile",
+                    "-Command",
+                    "Add-Type -AssemblyName System.Speech; \
+                     (New-Object System.Speech.Synthesis.SpeechSynthesizer)\
+                     .Speak([Console]::In.ReadToEnd())",
+                ],
+            )
+        } else {
+            SystemSpeech::new("espeak", ["--stdin"])
+        }
+    }
+}
+
+impl SpeechEngine for SystemSpeech {
+    fn speak(&mut self, text: &str) -> io::Result<()> {
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(text.as_bytes())?;
+        }
+        let status = child.wait()?;
+        if !status.success() {
+            return Err(io::Error::other(format!(
+                "{} exited with {status}",
+                self.program
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// How much of the mantras `SpeechVoice` speaks at a time.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum SpeechUnit {
+    /// Each syllable is spoken as soon as it's recited.
+    #[default]
+    Syllable,
+
+    /// The syllables of each repetition of a mantra are spoken together, once its last syllable
+    /// is recited, which sounds more natural with most engines.
+    Mantra,
+}
+
+/// A voice that speaks the recitation through a speech engine. The preparation and conclusion are
+/// spoken as a whole at the end of each recitation of them, rather than one character at a time.
+pub struct SpeechVoice<E: SpeechEngine = SystemSpeech> {
+    /// The engine that speaks the recitation.
+    engine: E,
+
+    /// How much of the mantras is spoken at a time.
+    unit: SpeechUnit,
+
+    /// The syllables of each mantra in the options, used to find the end of each repetition when
+    /// whole mantras are spoken.
+    mantras: Vec<Arc<[Arc<str>]>>,
+
+    /// The syllables recited since the last repetition of a mantra was spoken.
+    syllables: Vec<String>,
+
+    /// The characters of the preparation or conclusion recited since the start of the section.
+    section: String,
+}
+
+impl<E: SpeechEngine> SpeechVoice<E> {
+    /// Returns a voice that speaks the mantras in the options through the given engine.
+    pub fn new(engine: E, unit: SpeechUnit, options: &Options) -> SpeechVoice<E> {
+        SpeechVoice {
+            engine,
+            unit,
+            mantras: options
+                .mantras
+                .iter()
+                .map(|mantra| mantra.syllables.clone())
+                .collect(),
+            syllables: Vec::new(),
+            section: String::new(),
+        }
+    }
+
+    /// Returns the mantras that start with the syllables recited since the last repetition was
+    /// spoken.
+    fn extensions(&self) -> impl Iterator<Item = &Arc<[Arc<str>]>> {
+        self.mantras.iter().filter(|mantra| {
+            mantra.len() >= self.syllables.len()
+                && mantra.iter().zip(&self.syllables).all(|(a, b)| **a == **b)
+        })
+    }
+
+    /// Speaks the syllables that have been recited since the last repetition was spoken.
+    fn speak_syllables(&mut self) -> io::Result<()> {
+        if self.syllables.is_empty() {
+            return Ok(());
+        }
+        let text = self.syllables.join(" ");
+        self.syllables.clear();
+        self.engine.speak(&text)
+    }
+}
+
+impl<E: SpeechEngine> Voice for SpeechVoice<E> {
+    fn recite_syllable(&mut self, syllable: &str) -> io::Result<()> {
+        if self.unit == SpeechUnit::Syllable {
+            return self.engine.speak(syllable);
+        }
+
+        // If the syllable can't continue the repetition, the repetition ended with the previous
+        // syllable, for example because a mantra is the start of another one.
+        self.syllables.push(syllable.to_string());
+        if self.extensions().next().is_none() && self.syllables.len() > 1 {
+            let last = self.syllables.pop().unwrap_or_default();
+            self.speak_syllables()?;
+            self.syllables.push(last);
+        }
+
+        // The repetition is spoken once it's a whole mantra that no other mantra continues, or
+        // right away if it can't be the start of any mantra, for example after the options were
+        // changed.
+        let len = self.syllables.len();
+        if self.extensions().all(|mantra| mantra.len() == len) {
+            self.speak_syllables()?;
+        }
+        Ok(())
+    }
+
+    fn recite_character(&mut self, c: char) -> io::Result<()> {
+        self.section.push(c);
+        Ok(())
+    }
+
+    fn recite_grapheme(&mut self, grapheme: &str) -> io::Result<()> {
+        self.section.push_str(grapheme);
+        Ok(())
+    }
+
+    fn end_section(&mut self) -> io::Result<()> {
+        let text = std::mem::take(&mut self.section);
+        if text.trim().is_empty() {
+            return Ok(());
+        }
+        self.engine.speak(&text)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.speak_syllables()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use parking_lot::Mutex;
+    use std::{io, sync::Arc, time::Duration};
+
+    use crate::{
+        Mantra, MantraMiner, Options, SpeechEngine, SpeechUnit, SpeechVoice, SystemSpeech, Voice,
+    };
+
+    /// An engine that records what it speaks.
+    #[derive(Clone, Default)]
+    struct RecordingEngine(Arc<Mutex<Vec<String>>>);
+
+    impl SpeechEngine for RecordingEngine {
+        fn speak(&mut self, text: &str) -> io::Result<()> {
+            self.0.lock().push(text.to_string());
+            Ok(())
+        }
+    }
+
+    /// Returns options with the given mantras, a preparation, and a conclusion.
+    fn options(mantras: &[&str]) -> Options {
+        Options {
+            preparation: Some("ready".to_string()),
+            mantras: mantras.iter().map(|text| Mantra::from_text(text)).collect(),
+            conclusion: Some("done".to_string()),
+            rate: Duration::from_micros(1),
+            repeats: Some(1),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn syllables() -> Result<()> {
+        let engine = RecordingEngine::default();
+        let options = options(&["om ah hum"]);
+        let miner = MantraMiner::with_output(options.clone(), Box::new(io::sink()));
+        miner.set_voice(Some(Box::new(SpeechVoice::new(
+            engine.clone(),
+            SpeechUnit::Syllable,
+            &options,
+        ))));
+        miner.start()?;
+        miner.wait()?;
+        assert_eq!(*engine.0.lock(), ["ready", "om", "ah", "hum", "done"]);
+        Ok(())
+    }
+
+    #[test]
+    fn mantras() -> Result<()> {
+        // Each repetition is spoken once it's complete, including that of a mantra whose syllables
+        // start another one.
+        let engine = RecordingEngine::default();
+        let mut voice = SpeechVoice::new(
+            engine.clone(),
+            SpeechUnit::Mantra,
+            &options(&["om ah hum", "om ah"]),
+        );
+        for syllable in ["om", "ah", "hum", "om", "ah", "om", "ah", "hum"] {
+            voice.recite_syllable(syllable)?;
+        }
+        assert_eq!(*engine.0.lock(), ["om ah hum", "om ah", "om ah hum"]);
+
+        // Syllables that don't belong to any mantra are spoken on their own, and the rest are
+        // spoken when the voice is flushed.
+        voice.recite_syllable("tam")?;
+        voice.recite_syllable("om")?;
+        voice.flush()?;
+        assert_eq!(engine.0.lock()[3..], ["tam", "om"]);
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn system_speech() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("spoken.txt");
+        let mut engine = SystemSpeech::new("sh", ["-c", &format!("cat > {}", path.display())]);
+        engine.speak("om ah hum")?;
+        assert_eq!(std::fs::read_to_string(&path)?, "om ah hum");
+
+        let mut failing = SystemSpeech::new("sh", ["-c", "exit 1"]);
+        assert!(failing.speak("om").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,290 @@
+//! Contains a count store backed by a SQLite database, available with the `sqlite` feature. Besides
+//! the totals, it records every completed recitation of the sadhana with the time it completed,
+//! how long it took, and how many times each mantra was repeated, so that long-term statistics
+//! can be queried without the host program keeping its own history.
+
+use rusqlite::{params, Connection, OptionalExtension};
+use std::{
+    io,
+    path::Path,
+    time::{Duration, UNIX_EPOCH},
+};
+
+use crate::{CountStore, Date, DayStats, SadhanaRecord, StoredCounts};
+
+/// The statements that create the tables of the database if they do not exist yet.
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS counts (
+        id INTEGER PRIMARY KEY CHECK (id = 1),
+        sadhanas INTEGER NOT NULL,
+        syllables INTEGER NOT NULL,
+        interrupted_sadhanas INTEGER NOT NULL,
+        checkpoint TEXT
+    );
+    CREATE TABLE IF NOT EXISTS sadhanas (
+        id INTEGER PRIMARY KEY,
+        completed_at_ms INTEGER NOT NULL,
+        duration_ms INTEGER NOT NULL,
+        count INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS sadhana_mantras (
+        sadhana_id INTEGER NOT NULL REFERENCES sadhanas (id),
+        mantra_index INTEGER NOT NULL,
+        name TEXT,
+        repetitions INTEGER NOT NULL,
+        syllables INTEGER NOT NULL,
+        PRIMARY KEY (sadhana_id, mantra_index)
+    );
+    CREATE TABLE IF NOT EXISTS days (
+        date TEXT PRIMARY KEY,
+        sadhanas INTEGER NOT NULL,
+        mantras INTEGER NOT NULL
+    );
+";
+
+/// Converts the given error returned by SQLite into an IO error.
+fn io_error(error: rusqlite::Error) -> io::Error {
+    io::Error::other(error)
+}
+
+/// A count store that saves the counts to a SQLite database, and records every completed
+/// recitation of the sadhana in it. The database has four tables:
+///
+/// - `counts`, with a single row holding the `sadhanas`, `syllables`, and `interrupted_sadhanas`
+///   totals, and the `checkpoint` of the last run in TOML, if it stopped in the middle of a
+///   sadhana.
+/// - `sadhanas`, with a row for each completed recitation of the sadhana, holding the time at
+///   which it completed in milliseconds since the Unix epoch (`completed_at_ms`), the time it took
+///   in milliseconds (`duration_ms`), and the count of the miner including it (`count`).
+/// - `sadhana_mantras`, with a row for each mantra of each recitation, holding the `sadhana_id`,
+///   the `mantra_index` and `name` of the mantra, and the `repetitions` completed and `syllables`
+///   recited during the recitation.
+/// - `days`, with a row for each calendar day on which the miner recited, holding its `date` in the
+///   ISO 8601 format and the `sadhanas` and mantra repetitions (`mantras`) completed during it.
+pub struct SqliteStore {
+    /// The connection to the database.
+    connection: Connection,
+}
+
+impl SqliteStore {
+    /// Opens the database at the given path, creating it and its tables if needed.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<SqliteStore> {
+        Self::with_connection(Connection::open(path).map_err(io_error)?)
+    }
+
+    /// Returns a store that uses the given connection, creating the tables if needed. This allows
+    /// the history to be kept in a database opened by the host program, or in memory.
+    pub fn with_connection(connection: Connection) -> io::Result<SqliteStore> {
+        connection.execute_batch(SCHEMA).map_err(io_error)?;
+        Ok(SqliteStore { connection })
+    }
+
+    /// Returns the connection to the database, to query the history of the recitations.
+    pub fn connection(&self) -> &Connection {
+        &self.connection
+    }
+}
+
+impl CountStore for SqliteStore {
+    fn load(&mut self) -> io::Result<StoredCounts> {
+        let row = self
+            .connection
+            .query_row(
+                "SELECT sadhanas, syllables, interrupted_sadhanas, checkpoint
+                 FROM counts WHERE id = 1",
+                [],
+                |row| {
+                    Ok((
+                        row.get(0)?,
+                        row.get(1)?,
+                        row.get(2)?,
+                        row.get::<_, Option<String>>(3)?,
+                    ))
+                },
+            )
+            .optional()
+            .map_err(io_error)?;
+        let Some((sadhanas, syllables, interrupted_sadhanas, checkpoint)) = row else {
+            return Ok(StoredCounts::default());
+        };
+        let checkpoint = checkpoint
+            .map(|checkpoint| toml::from_str(&checkpoint))
+            .transpose()
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+        let mut statement = self
+            .connection
+            .prepare("SELECT date, sadhanas, mantras FROM days ORDER BY date")
+            .map_err(io_error)?;
+        let rows = statement
+            .query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, row.get(1)?, row.get(2)?))
+            })
+            .map_err(io_error)?;
+        let mut days = Vec::new();
+        for row in rows {
+            let (date, sadhanas, mantras) = row.map_err(io_error)?;
+            let date = Date::parse(&date).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid date {:?}", date),
+                )
+            })?;
+            days.push(DayStats {
+                date,
+                sadhanas,
+                mantras,
+            });
+        }
+        Ok(StoredCounts {
+            sadhanas,
+            syllables,
+            interrupted_sadhanas,
+            checkpoint,
+            days,
+        })
+    }
+
+    fn save(&mut self, counts: &StoredCounts) -> io::Result<()> {
+        let checkpoint = counts
+            .checkpoint
+            .map(|checkpoint| toml::to_string(&checkpoint))
+            .transpose()
+            .map_err(io::Error::other)?;
+        let transaction = self.connection.transaction().map_err(io_error)?;
+        transaction
+            .execute(
+                "INSERT INTO counts (id, sadhanas, syllables, interrupted_sadhanas, checkpoint)
+                 VALUES (1, ?1, ?2, ?3, ?4)
+                 ON CONFLICT (id) DO UPDATE SET
+                 sadhanas = ?1, syllables = ?2, interrupted_sadhanas = ?3, checkpoint = ?4",
+                params![
+                    counts.sadhanas,
+                    counts.syllables,
+                    counts.interrupted_sadhanas,
+                    checkpoint
+                ],
+            )
+            .map_err(io_error)?;
+        transaction
+            .execute("DELETE FROM days", [])
+            .map_err(io_error)?;
+        for day in &counts.days {
+            transaction
+                .execute(
+                    "INSERT INTO days (date, sadhanas, mantras) VALUES (?1, ?2, ?3)",
+                    params![day.date.to_string(), day.sadhanas, day.mantras],
+                )
+                .map_err(io_error)?;
+        }
+        transaction.commit().map_err(io_error)
+    }
+
+    fn record_sadhana(&mut self, record: &SadhanaRecord) -> io::Result<()> {
+        let completed_at = record
+            .completed_at
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or(Duration::ZERO);
+        let transaction = self.connection.transaction().map_err(io_error)?;
+        transaction
+            .execute(
+                "INSERT INTO sadhanas (completed_at_ms, duration_ms, count) VALUES (?1, ?2, ?3)",
+                params![
+                    completed_at.as_millis() as i64,
+                    record.duration.as_millis() as i64,
+                    record.count
+                ],
+            )
+            .map_err(io_error)?;
+        let sadhana_id = transaction.last_insert_rowid();
+        for (index, mantra) in record.mantras.iter().enumerate() {
+            transaction
+                .execute(
+                    "INSERT INTO sadhana_mantras
+                     (sadhana_id, mantra_index, name, repetitions, syllables)
+                     VALUES (?1, ?2, ?3, ?4, ?5)",
+                    params![
+                        sadhana_id,
+                        index,
+                        mantra.name,
+                        mantra.completed,
+                        mantra.syllables
+                    ],
+                )
+                .map_err(io_error)?;
+        }
+        transaction.commit().map_err(io_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use rusqlite::Connection;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use crate::{
+        Checkpoint, CountStore, Date, DayStats, MantraStats, SadhanaRecord, SqliteStore,
+        StoredCounts,
+    };
+
+    #[test]
+    fn counts() -> Result<()> {
+        let mut store = SqliteStore::with_connection(Connection::open_in_memory()?)?;
+        assert_eq!(store.load()?, StoredCounts::default());
+
+        let counts = StoredCounts {
+            sadhanas: 2,
+            syllables: 12,
+            interrupted_sadhanas: 1,
+            checkpoint: Some(Checkpoint {
+                section_index: 1,
+                repeat: 1,
+                unit: 3,
+                selected_mantra: Some(0),
+                cycle: 0,
+            }),
+            days: vec![DayStats {
+                date: Date {
+                    year: 2024,
+                    month: 10,
+                    day: 4,
+                },
+                sadhanas: 2,
+                mantras: 6,
+            }],
+        };
+        store.save(&counts)?;
+        store.save(&counts)?;
+        assert_eq!(store.load()?, counts);
+        Ok(())
+    }
+
+    #[test]
+    fn record_sadhana() -> Result<()> {
+        let mut store = SqliteStore::with_connection(Connection::open_in_memory()?)?;
+        store.record_sadhana(&SadhanaRecord {
+            completed_at: UNIX_EPOCH + Duration::from_secs(60),
+            duration: Duration::from_millis(250),
+            count: 1,
+            mantras: vec![MantraStats {
+                name: Some("Mani".to_string()),
+                completed: 3,
+                syllables: 18,
+            }],
+        })?;
+
+        let sadhana: (i64, i64, i64) = store.connection().query_row(
+            "SELECT completed_at_ms, duration_ms, count FROM sadhanas",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )?;
+        assert_eq!(sadhana, (60_000, 250, 1));
+        let mantra: (String, i64, i64) = store.connection().query_row(
+            "SELECT name, repetitions, syllables FROM sadhana_mantras",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )?;
+        assert_eq!(mantra, ("Mani".to_string(), 3, 18));
+        Ok(())
+    }
+}
@@ -0,0 +1,504 @@
+//! Contains the count stores of the mantra miner, which save the lifetime totals of the miner so
+//! that they survive restarts of the host program, the default store backed by a file, and a store
+//! backed by a write-ahead log for those who cannot afford to lose a count. The `sqlite` feature
+//! adds a store that also keeps a history of every recitation of the sadhana.
+
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    ffi::OsStr,
+    fs::{self, File, OpenOptions},
+    io::{self, ErrorKind, Write},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use crate::{Checkpoint, Date, DayStats, MantraStats};
+
+/// The totals saved by a count store.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(default)]
+pub struct StoredCounts {
+    /// The number of completed recitations of the entire sadhana.
+    pub sadhanas: u64,
+
+    /// The number of syllables and characters recited.
+    pub syllables: u64,
+
+    /// The number of recitations of the sadhana that were interrupted before their completion.
+    pub interrupted_sadhanas: u64,
+
+    /// The position from which the next run resumes, if the last run stopped in the middle of a
+    /// sadhana.
+    pub checkpoint: Option<Checkpoint>,
+
+    /// The recitations completed on each calendar day in the local time zone, from the oldest to
+    /// the most recent, from which the daily statistics are computed.
+    pub days: Vec<DayStats>,
+}
+
+/// A completed recitation of the sadhana, passed to `CountStore::record_sadhana`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SadhanaRecord {
+    /// The time at which the recitation was completed, as shown by the system clock.
+    pub completed_at: SystemTime,
+
+    /// The time taken by the recitation.
+    pub duration: Duration,
+
+    /// The count of the mantra miner including this recitation.
+    pub count: u64,
+
+    /// The repetitions completed and syllables recited of each mantra during the recitation, in
+    /// the same order as in the options.
+    pub mantras: Vec<MantraStats>,
+}
+
+/// Saves the counts of the mantra miner so that they survive restarts. The miner loads the counts
+/// every time it's started and saves them after every completed recitation of the sadhana.
+pub trait CountStore: Send {
+    /// Returns the counts saved last, or the default counts if none have been saved yet.
+    fn load(&mut self) -> io::Result<StoredCounts>;
+
+    /// Saves the given counts, replacing those saved before.
+    fn save(&mut self, counts: &StoredCounts) -> io::Result<()>;
+
+    /// Records the given completed recitation of the sadhana, before the updated counts are
+    /// saved. Does nothing by default, since most stores only keep the totals.
+    fn record_sadhana(&mut self, _record: &SadhanaRecord) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A count store that saves the counts to a small TOML file. The file is replaced atomically by
+/// writing the counts to a temporary file next to it and renaming it, so a crash while saving
+/// leaves the previous counts intact. Both the temporary file and the rename are synced to the
+/// disk before a save returns.
+#[derive(Clone, Debug)]
+pub struct FileStore {
+    /// The path of the file.
+    path: PathBuf,
+}
+
+impl FileStore {
+    /// Returns a store that saves the counts to the file at the given path. The file and its
+    /// directory are not required to exist until the counts are first saved.
+    pub fn new<P: AsRef<Path>>(path: P) -> FileStore {
+        FileStore {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+
+    /// Returns the path of the temporary file written before replacing the file.
+    fn temp_path(&self) -> PathBuf {
+        with_suffix(&self.path, ".tmp")
+    }
+}
+
+/// Returns the given path with the given suffix appended to its file name.
+fn with_suffix(path: &Path, suffix: impl AsRef<OsStr>) -> PathBuf {
+    let mut path = path.to_path_buf().into_os_string();
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+/// Syncs the directory that contains the file at the given path to the disk, so that the file
+/// having been created or renamed survives a crash.
+#[cfg(unix)]
+fn sync_parent(path: &Path) -> io::Result<()> {
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    File::open(parent)?.sync_all()
+}
+
+/// Does nothing, since only Unix can open a directory to sync it.
+#[cfg(not(unix))]
+fn sync_parent(_: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+/// Returns the given days with the given increments of their totals added to them.
+fn add_days(days: &[DayStats], increments: &[DayStats]) -> Vec<DayStats> {
+    let mut totals = BTreeMap::new();
+    for day in days.iter().chain(increments) {
+        let (sadhanas, mantras) = totals.entry(day.date).or_insert((0, 0));
+        *sadhanas += day.sadhanas;
+        *mantras += day.mantras;
+    }
+    totals
+        .into_iter()
+        .map(|(date, (sadhanas, mantras))| DayStats {
+            date,
+            sadhanas,
+            mantras,
+        })
+        .collect()
+}
+
+/// Returns the increments that turn the totals of the saved days into those of the given days, or
+/// `None` if a day is missing or has lower totals than saved.
+fn day_increments(saved: &[DayStats], days: &[DayStats]) -> Option<Vec<DayStats>> {
+    let saved: BTreeMap<Date, &DayStats> = saved.iter().map(|day| (day.date, day)).collect();
+    let mut found = 0;
+    let mut increments = Vec::new();
+    for day in days {
+        let (sadhanas, mantras) = match saved.get(&day.date) {
+            Some(saved) => {
+                found += 1;
+                (saved.sadhanas, saved.mantras)
+            }
+            None => (0, 0),
+        };
+        let increment = DayStats {
+            date: day.date,
+            sadhanas: day.sadhanas.checked_sub(sadhanas)?,
+            mantras: day.mantras.checked_sub(mantras)?,
+        };
+        if increment.sadhanas > 0 || increment.mantras > 0 {
+            increments.push(increment);
+        }
+    }
+    (found == saved.len()).then_some(increments)
+}
+
+impl CountStore for FileStore {
+    fn load(&mut self) -> io::Result<StoredCounts> {
+        match fs::read_to_string(&self.path) {
+            Ok(contents) => toml::from_str(&contents)
+                .map_err(|error| io::Error::new(ErrorKind::InvalidData, error)),
+            Err(error) if error.kind() == ErrorKind::NotFound => Ok(StoredCounts::default()),
+            Err(error) => Err(error),
+        }
+    }
+
+    fn save(&mut self, counts: &StoredCounts) -> io::Result<()> {
+        let contents = toml::to_string(counts).map_err(io::Error::other)?;
+        let temp_path = self.temp_path();
+        let mut file = File::create(&temp_path)?;
+        file.write_all(contents.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&temp_path, &self.path)?;
+        sync_parent(&self.path)
+    }
+}
+
+/// A count store for those who never want to lose a count, which appends the increments of the
+/// counts to a write-ahead log and syncs it to the disk on every save, so that even a hard crash
+/// loses at most the sadhana being recited. The log is compacted into a snapshot, saved as by
+/// `FileStore`, once it holds the given number of increments and every time the counts are
+/// loaded. The log is stored next to the snapshot, with the suffix `.wal`.
+pub struct WalStore {
+    /// The store of the snapshot into which the log is compacted.
+    snapshot: FileStore,
+
+    /// The path of the log.
+    log_path: PathBuf,
+
+    /// The log, opened for appending once the first increment is written.
+    log: Option<File>,
+
+    /// The counts last loaded or saved.
+    saved: StoredCounts,
+
+    /// The number of increments in the log.
+    entries: usize,
+
+    /// The number of increments after which the log is compacted.
+    compact_every: usize,
+}
+
+impl WalStore {
+    /// Returns a store that saves the snapshot of the counts to the file at the given path, and
+    /// compacts the log into it once it holds `compact_every` increments.
+    pub fn new<P: AsRef<Path>>(path: P, compact_every: usize) -> WalStore {
+        let path = path.as_ref();
+        WalStore {
+            snapshot: FileStore::new(path),
+            log_path: with_suffix(path, ".wal"),
+            log: None,
+            saved: StoredCounts::default(),
+            entries: 0,
+            compact_every: compact_every.max(1),
+        }
+    }
+
+    /// Saves the counts to the snapshot and empties the log. The log is only emptied once the
+    /// snapshot has been synced to the disk, so the increments in it are never lost, even if the
+    /// snapshot cannot be saved.
+    fn compact(&mut self) -> io::Result<()> {
+        self.snapshot.save(&self.saved)?;
+        self.log = None;
+        File::create(&self.log_path)?.sync_all()?;
+        self.entries = 0;
+        Ok(())
+    }
+
+    /// Returns the increments in the log, added up. A line that cannot be read, which a crash
+    /// while appending to the log can leave at its end, ends the log.
+    fn read_log(&self) -> io::Result<StoredCounts> {
+        let contents = match fs::read_to_string(&self.log_path) {
+            Ok(contents) => contents,
+            Err(error) if error.kind() == ErrorKind::NotFound => String::new(),
+            Err(error) => return Err(error),
+        };
+        let mut total = StoredCounts::default();
+        for line in contents.split_inclusive('\n') {
+            let Some(entry) = line.strip_suffix('\n').and_then(parse_entry) else {
+                break;
+            };
+            total.sadhanas += entry.sadhanas;
+            total.syllables += entry.syllables;
+            total.days = add_days(&total.days, &entry.days);
+        }
+        Ok(total)
+    }
+}
+
+/// Returns the increments written in the given line of the log: those of the sadhanas and
+/// syllables, followed by the date and the increments of the sadhanas and mantras of each day whose
+/// totals changed, all separated by spaces. Returns `None` if the line cannot be read.
+fn parse_entry(line: &str) -> Option<StoredCounts> {
+    let mut fields = line.split(' ');
+    let mut entry = StoredCounts {
+        sadhanas: fields.next()?.parse().ok()?,
+        syllables: fields.next()?.parse().ok()?,
+        ..StoredCounts::default()
+    };
+    while let Some(date) = fields.next() {
+        entry.days.push(DayStats {
+            date: Date::parse(date)?,
+            sadhanas: fields.next()?.parse().ok()?,
+            mantras: fields.next()?.parse().ok()?,
+        });
+    }
+    Some(entry)
+}
+
+impl CountStore for WalStore {
+    fn load(&mut self) -> io::Result<StoredCounts> {
+        let snapshot = self.snapshot.load()?;
+        let log = self.read_log()?;
+        self.saved = StoredCounts {
+            sadhanas: snapshot.sadhanas + log.sadhanas,
+            syllables: snapshot.syllables + log.syllables,
+            interrupted_sadhanas: snapshot.interrupted_sadhanas,
+            checkpoint: snapshot.checkpoint,
+            days: add_days(&snapshot.days, &log.days),
+        };
+        self.compact()?;
+        Ok(self.saved.clone())
+    }
+
+    fn save(&mut self, counts: &StoredCounts) -> io::Result<()> {
+        // Counts lower than those saved cannot be written as increments, and the interruptions and
+        // checkpoint are only kept in the snapshot, so they replace the snapshot instead.
+        let days = day_increments(&self.saved.days, &counts.days);
+        let Some(days) = days.filter(|_| {
+            counts.sadhanas >= self.saved.sadhanas
+                && counts.syllables >= self.saved.syllables
+                && counts.interrupted_sadhanas == self.saved.interrupted_sadhanas
+                && counts.checkpoint == self.saved.checkpoint
+        }) else {
+            self.saved = counts.clone();
+            return self.compact();
+        };
+        let log = match &mut self.log {
+            Some(log) => log,
+            None => self.log.insert(
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&self.log_path)?,
+            ),
+        };
+        let mut entry = format!(
+            "{} {}",
+            counts.sadhanas - self.saved.sadhanas,
+            counts.syllables - self.saved.syllables
+        );
+        for day in days {
+            entry.push_str(&format!(" {} {} {}", day.date, day.sadhanas, day.mantras));
+        }
+        writeln!(log, "{entry}")?;
+        log.sync_data()?;
+        self.saved = counts.clone();
+        self.entries += 1;
+        if self.entries >= self.compact_every {
+            self.compact()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use std::fs;
+
+    use crate::{
+        store::{CountStore, FileStore, StoredCounts, WalStore},
+        Date, DayStats,
+    };
+
+    /// Returns the totals of the 4th of October 2024.
+    fn day(sadhanas: u64, mantras: u64) -> DayStats {
+        DayStats {
+            date: Date {
+                year: 2024,
+                month: 10,
+                day: 4,
+            },
+            sadhanas,
+            mantras,
+        }
+    }
+
+    #[test]
+    fn missing_file() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let mut store = FileStore::new(dir.path().join("counts.toml"));
+        assert_eq!(store.load()?, StoredCounts::default());
+        Ok(())
+    }
+
+    #[test]
+    fn save_and_load() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("counts.toml");
+        let counts = StoredCounts {
+            sadhanas: 3,
+            syllables: 42,
+            interrupted_sadhanas: 1,
+            checkpoint: None,
+            days: vec![day(3, 9)],
+        };
+        FileStore::new(&path).save(&counts)?;
+        assert_eq!(FileStore::new(&path).load()?, counts);
+
+        // The temporary file is renamed over the file.
+        assert!(!dir.path().join("counts.toml.tmp").exists());
+
+        // The files saved before the days were stored have none.
+        fs::write(&path, "sadhanas = 3\nsyllables = 42\n")?;
+        assert_eq!(FileStore::new(&path).load()?.days, []);
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_file() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("counts.toml");
+        fs::write(&path, "sadhanas = \"many\"")?;
+        assert!(FileStore::new(&path).load().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn wal_store() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("counts.toml");
+        let mut store = WalStore::new(&path, 10);
+        assert_eq!(store.load()?, StoredCounts::default());
+        for sadhanas in 1..=3 {
+            store.save(&StoredCounts {
+                sadhanas,
+                syllables: sadhanas * 6,
+                interrupted_sadhanas: 0,
+                checkpoint: None,
+                days: vec![day(sadhanas, sadhanas * 3)],
+            })?;
+        }
+
+        // The increments are in the log until it's compacted.
+        assert_eq!(
+            fs::read_to_string(dir.path().join("counts.toml.wal"))?,
+            "1 6 2024-10-04 1 3\n".repeat(3)
+        );
+        let counts = StoredCounts {
+            sadhanas: 3,
+            syllables: 18,
+            interrupted_sadhanas: 0,
+            checkpoint: None,
+            days: vec![day(3, 9)],
+        };
+        assert_eq!(WalStore::new(&path, 10).load()?, counts);
+        assert_eq!(FileStore::new(&path).load()?, counts);
+        assert_eq!(fs::read_to_string(dir.path().join("counts.toml.wal"))?, "");
+
+        // Days with lower totals cannot be written as increments, so they replace the snapshot.
+        let mut store = WalStore::new(&path, 10);
+        store.load()?;
+        let counts = StoredCounts {
+            days: vec![day(1, 3)],
+            ..counts
+        };
+        store.save(&counts)?;
+        assert_eq!(FileStore::new(&path).load()?, counts);
+        assert_eq!(fs::read_to_string(dir.path().join("counts.toml.wal"))?, "");
+        Ok(())
+    }
+
+    #[test]
+    fn wal_store_compacts() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("counts.toml");
+        let mut store = WalStore::new(&path, 2);
+        store.load()?;
+        for sadhanas in 1..=3 {
+            store.save(&StoredCounts {
+                sadhanas,
+                syllables: 0,
+                interrupted_sadhanas: 0,
+                checkpoint: None,
+                days: Vec::new(),
+            })?;
+        }
+        assert_eq!(FileStore::new(&path).load()?.sadhanas, 2);
+        assert_eq!(
+            fs::read_to_string(dir.path().join("counts.toml.wal"))?,
+            "1 0\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn wal_store_failed_compaction() -> Result<()> {
+        // The log is kept if the snapshot cannot be saved, such as when the temporary file cannot
+        // be created.
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("counts.toml");
+        let log_path = dir.path().join("counts.toml.wal");
+        fs::write(&log_path, "1 6\n")?;
+        fs::create_dir(dir.path().join("counts.toml.tmp"))?;
+        assert!(WalStore::new(&path, 10).load().is_err());
+        assert_eq!(fs::read_to_string(&log_path)?, "1 6\n");
+
+        fs::remove_dir(dir.path().join("counts.toml.tmp"))?;
+        assert_eq!(WalStore::new(&path, 10).load()?.sadhanas, 1);
+        assert_eq!(fs::read_to_string(&log_path)?, "");
+        Ok(())
+    }
+
+    #[test]
+    fn wal_store_torn_entry() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("counts.toml");
+        fs::write(
+            dir.path().join("counts.toml.wal"),
+            "1 6\n1 6 2024-10-04 1 3\n1 6 2024-10",
+        )?;
+        assert_eq!(
+            WalStore::new(&path, 10).load()?,
+            StoredCounts {
+                sadhanas: 2,
+                syllables: 12,
+                interrupted_sadhanas: 0,
+                checkpoint: None,
+                days: vec![day(1, 3)],
+            }
+        );
+        Ok(())
+    }
+}
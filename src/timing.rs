@@ -0,0 +1,258 @@
+//! Contains the timing of the runs of the mantra miner, used to report how long the miner has been
+//! reciting and how long each recitation of the sadhana takes, and the CPU time of the current
+//! thread, used to keep the miner under its CPU budget.
+
+use std::time::{Duration, Instant, SystemTime};
+
+/// The timing of the current or last run of the mantra miner.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct Timing {
+    /// The time at which the run started, as shown by the system clock.
+    pub(crate) started_at: Option<SystemTime>,
+
+    /// The instant at which the run started, which also identifies the run.
+    pub(crate) started: Option<Instant>,
+
+    /// The instant at which the run ended, either because it was stopped or because it finished.
+    ended: Option<Instant>,
+
+    /// The instant at which the sadhana being recited started.
+    sadhana_started: Option<Instant>,
+
+    /// The number of recitations of the sadhana completed during the run.
+    sadhanas: u32,
+
+    /// The total time taken by the recitations of the sadhana completed during the run.
+    sadhana_time: Duration,
+}
+
+impl Timing {
+    /// Resets the timing for a new run starting now.
+    pub(crate) fn start(&mut self) {
+        *self = Timing {
+            started_at: Some(SystemTime::now()),
+            started: Some(Instant::now()),
+            ..Default::default()
+        };
+    }
+
+    /// Records that the run that started at the given instant has ended, unless another run has
+    /// started since or the run already ended.
+    pub(crate) fn end(&mut self, started: Option<Instant>) {
+        if self.started.is_some() && self.started == started && self.ended.is_none() {
+            self.ended = Some(Instant::now());
+        }
+    }
+
+    /// Records that a recitation of the sadhana has started.
+    pub(crate) fn start_sadhana(&mut self) {
+        self.sadhana_started = Some(Instant::now());
+    }
+
+    /// Records that the recitation of the sadhana that was started last has been completed, and
+    /// returns the time it took, or `None` if no recitation was started.
+    pub(crate) fn complete_sadhana(&mut self) -> Option<Duration> {
+        let duration = self.sadhana_started.take()?.elapsed();
+        self.sadhanas += 1;
+        self.sadhana_time += duration;
+        Some(duration)
+    }
+
+    /// Returns the time since the run started, or the duration of the run if it has ended. Returns
+    /// zero if there was no run.
+    pub(crate) fn elapsed(&self) -> Duration {
+        match (self.started, self.ended) {
+            (Some(started), Some(ended)) => ended.saturating_duration_since(started),
+            (Some(started), None) => started.elapsed(),
+            _ => Duration::ZERO,
+        }
+    }
+
+    /// Returns the average time taken by a recitation of the sadhana during the run, or `None` if
+    /// none has been completed.
+    pub(crate) fn average_sadhana_duration(&self) -> Option<Duration> {
+        (self.sadhanas > 0).then(|| self.sadhana_time / self.sadhanas)
+    }
+
+    /// Returns the estimated time until the run has recited the sadhana `repeats` times. Each
+    /// remaining recitation is expected to take the average observed during the run, or the given
+    /// duration computed from the options if none has been completed yet. The time already spent
+    /// on the sadhana being recited is subtracted.
+    pub(crate) fn estimated_remaining(
+        &self,
+        repeats: usize,
+        sadhana_duration: Duration,
+    ) -> Duration {
+        let per_sadhana = self
+            .average_sadhana_duration()
+            .unwrap_or(sadhana_duration)
+            .as_nanos();
+        let remaining = repeats.saturating_sub(self.sadhanas as usize) as u128;
+        let in_progress = self
+            .sadhana_started
+            .map_or(Duration::ZERO, |started| started.elapsed())
+            .as_nanos();
+        let nanos = (per_sadhana * remaining).saturating_sub(in_progress);
+        Duration::from_nanos(nanos.try_into().unwrap_or(u64::MAX))
+    }
+
+    /// Returns the estimated time until the run has completed `target` mantra repetitions, of which
+    /// `completed` are done. Each remaining repetition is expected to take the average observed
+    /// during the run, or the given duration computed from the options if none has been completed
+    /// yet. Returns `None` if there is neither.
+    pub(crate) fn estimated_remaining_mantras(
+        &self,
+        target: u64,
+        completed: u64,
+        mantra_duration: Option<Duration>,
+    ) -> Option<Duration> {
+        let per_mantra = match completed {
+            0 => mantra_duration?.as_secs_f64(),
+            _ => self.elapsed().as_secs_f64() / completed as f64,
+        };
+        let remaining = target.saturating_sub(completed) as f64;
+        Some(Duration::try_from_secs_f64(per_mantra * remaining).unwrap_or(Duration::MAX))
+    }
+}
+
+/// Returns the CPU time used so far by the current thread, or `None` if it's not available on this
+/// platform.
+#[cfg(unix)]
+pub(crate) fn thread_cpu_time() -> Option<Duration> {
+    let mut time = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    // SAFETY: The call only writes to the given `timespec`, which outlives it.
+    if unsafe { libc::clock_gettime(libc::CLOCK_THREAD_CPUTIME_ID, &mut time) } != 0 {
+        return None;
+    }
+    Some(Duration::new(time.tv_sec as u64, time.tv_nsec as u32))
+}
+
+/// Returns the CPU time used so far by the current thread, or `None` if it's not available on this
+/// platform.
+#[cfg(windows)]
+pub(crate) fn thread_cpu_time() -> Option<Duration> {
+    use windows_sys::Win32::{
+        Foundation::FILETIME,
+        System::Threading::{GetCurrentThread, GetThreadTimes},
+    };
+
+    let mut creation = FILETIME::default();
+    let mut exit = FILETIME::default();
+    let mut kernel = FILETIME::default();
+    let mut user = FILETIME::default();
+    // SAFETY: The pseudo handle returned by `GetCurrentThread` is always valid for the current
+    // thread, and the call only writes to the given structures, which outlive it.
+    let succeeded = unsafe {
+        GetThreadTimes(
+            GetCurrentThread(),
+            &mut creation,
+            &mut exit,
+            &mut kernel,
+            &mut user,
+        )
+    };
+    if succeeded == 0 {
+        return None;
+    }
+
+    // The times are counted in intervals of 100 nanoseconds.
+    let intervals =
+        |time: FILETIME| u64::from(time.dwHighDateTime) << 32 | u64::from(time.dwLowDateTime);
+    Some(Duration::from_nanos(
+        (intervals(kernel) + intervals(user)).saturating_mul(100),
+    ))
+}
+
+/// Returns the CPU time used so far by the current thread, or `None` if it's not available on this
+/// platform.
+#[cfg(not(any(unix, windows)))]
+pub(crate) fn thread_cpu_time() -> Option<Duration> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{thread, time::Duration};
+
+    use crate::timing::Timing;
+
+    #[test]
+    fn timing() {
+        let mut timing = Timing::default();
+        assert_eq!(timing.elapsed(), Duration::ZERO);
+        assert_eq!(timing.average_sadhana_duration(), None);
+
+        timing.start();
+        let started = timing.started;
+        assert!(timing.started_at.is_some());
+        for _ in 0..2 {
+            timing.start_sadhana();
+            thread::sleep(Duration::from_millis(5));
+            timing.complete_sadhana();
+        }
+        let average = timing.average_sadhana_duration().unwrap();
+        assert!(average >= Duration::from_millis(5));
+
+        // Once the run ends, the elapsed time stops growing.
+        timing.end(started);
+        let elapsed = timing.elapsed();
+        assert!(elapsed >= 2 * average);
+        thread::sleep(Duration::from_millis(5));
+        assert_eq!(timing.elapsed(), elapsed);
+
+        // A run that ends after a new one started does not end the new one.
+        timing.start();
+        timing.end(started);
+        thread::sleep(Duration::from_millis(5));
+        assert!(timing.elapsed() >= Duration::from_millis(5));
+        assert_eq!(timing.average_sadhana_duration(), None);
+    }
+
+    #[test]
+    fn estimated_remaining() {
+        let mut timing = Timing::default();
+        timing.start();
+        let configured = Duration::from_secs(10);
+        assert_eq!(timing.estimated_remaining(3, configured), 3 * configured);
+
+        // The time spent on the current sadhana is subtracted.
+        timing.start_sadhana();
+        thread::sleep(Duration::from_millis(5));
+        let remaining = timing.estimated_remaining(3, configured);
+        assert!(remaining <= 3 * configured - Duration::from_millis(5));
+        assert!(remaining > 2 * configured);
+
+        // Once a sadhana is completed, its observed duration replaces the configured one.
+        timing.complete_sadhana();
+        let average = timing.average_sadhana_duration().unwrap();
+        assert_eq!(timing.estimated_remaining(3, configured), 2 * average);
+        assert_eq!(timing.estimated_remaining(1, configured), Duration::ZERO);
+    }
+
+    #[test]
+    fn estimated_remaining_mantras() {
+        let mut timing = Timing::default();
+        timing.start();
+        let configured = Some(Duration::from_secs(1));
+        assert_eq!(
+            timing.estimated_remaining_mantras(3, 0, configured),
+            Some(Duration::from_secs(3))
+        );
+        assert_eq!(timing.estimated_remaining_mantras(3, 0, None), None);
+
+        // Once a repetition is completed, the observed average replaces the configured duration.
+        thread::sleep(Duration::from_millis(10));
+        let remaining = timing
+            .estimated_remaining_mantras(3, 1, configured)
+            .unwrap();
+        assert!(remaining >= Duration::from_millis(20));
+        assert!(remaining < Duration::from_secs(1));
+        assert_eq!(
+            timing.estimated_remaining_mantras(3, 3, configured),
+            Some(Duration::ZERO)
+        );
+    }
+}
@@ -0,0 +1,294 @@
+//! Contains the transcript writer, an output for the mantra miner that writes the recitation to a
+//! file and rotates it once it grows too large or too old, so that a miner left running for months
+//! does not fill the disk.
+
+use std::{
+    ffi::OsString,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+/// Configures when the transcript is rotated and what happens to the rotated files.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Rotation {
+    /// The size in bytes after which the transcript is rotated. If it's `None`, the size is not
+    /// limited.
+    pub max_bytes: Option<u64>,
+
+    /// The time after which the transcript is rotated, measured from when the writer opened it.
+    /// If it's `None`, the age is not limited.
+    pub max_age: Option<Duration>,
+
+    /// The number of rotated files to keep. Older files are deleted. Defaults to 5.
+    pub keep: usize,
+
+    /// Whether to compress the rotated files with gzip. It requires the `gzip` feature.
+    pub compress: bool,
+}
+
+impl Default for Rotation {
+    fn default() -> Self {
+        Rotation {
+            max_bytes: None,
+            max_age: None,
+            keep: 5,
+            compress: false,
+        }
+    }
+}
+
+/// An output that appends the recitation to a transcript file and rotates it as configured. The
+/// rotated files are named after the transcript, with the suffix `.1` for the most recent one,
+/// `.2` for the one before, and so on, followed by `.gz` if they are compressed. The transcript
+/// is only rotated between writes, so the mantra miner, which writes a whole sadhana at a time
+/// unless the sadhana is very long, does not split a sadhana across files.
+pub struct TranscriptWriter {
+    /// The path of the transcript.
+    path: PathBuf,
+
+    /// When to rotate the transcript.
+    rotation: Rotation,
+
+    /// The open transcript.
+    file: File,
+
+    /// The size of the transcript.
+    bytes: u64,
+
+    /// The instant at which the transcript was opened.
+    opened: Instant,
+}
+
+impl TranscriptWriter {
+    /// Opens the transcript at the given path, appending to it if it already exists. Returns an
+    /// error if the file cannot be opened, or if compression is requested without the `gzip`
+    /// feature.
+    pub fn create(path: impl AsRef<Path>, rotation: Rotation) -> io::Result<TranscriptWriter> {
+        if rotation.compress && !cfg!(feature = "gzip") {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "compressing the transcript requires the gzip feature",
+            ));
+        }
+        let path = path.as_ref().to_path_buf();
+        let file = Self::open(&path)?;
+        Ok(TranscriptWriter {
+            bytes: file.metadata()?.len(),
+            path,
+            rotation,
+            file,
+            opened: Instant::now(),
+        })
+    }
+
+    /// Opens the file at the given path for appending, creating it if needed.
+    fn open(path: &Path) -> io::Result<File> {
+        OpenOptions::new().create(true).append(true).open(path)
+    }
+
+    /// Returns the path of the rotated file with the given index.
+    fn rotated_path(&self, index: usize, compressed: bool) -> PathBuf {
+        let mut name = OsString::from(self.path.as_os_str());
+        name.push(format!(".{index}"));
+        if compressed {
+            name.push(".gz");
+        }
+        PathBuf::from(name)
+    }
+
+    /// Returns whether the transcript should be rotated before writing more to it.
+    fn should_rotate(&self) -> bool {
+        self.bytes > 0
+            && (self.rotation.max_bytes.is_some_and(|max| self.bytes >= max)
+                || self
+                    .rotation
+                    .max_age
+                    .is_some_and(|max| self.opened.elapsed() >= max))
+    }
+
+    /// Moves the transcript to the first rotated file, shifting the older ones and deleting those
+    /// beyond the number to keep, and opens a new transcript.
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        for index in (1..=self.rotation.keep).rev() {
+            for compressed in [false, true] {
+                let path = self.rotated_path(index, compressed);
+                if !path.exists() {
+                    continue;
+                }
+                if index == self.rotation.keep {
+                    fs::remove_file(&path)?;
+                } else {
+                    fs::rename(&path, self.rotated_path(index + 1, compressed))?;
+                }
+            }
+        }
+
+        if self.rotation.keep == 0 {
+            fs::remove_file(&self.path)?;
+        } else {
+            let rotated = self.rotated_path(1, false);
+            fs::rename(&self.path, &rotated)?;
+            if self.rotation.compress {
+                compress(&rotated, &self.rotated_path(1, true))?;
+            }
+        }
+        self.file = Self::open(&self.path)?;
+        self.bytes = 0;
+        self.opened = Instant::now();
+        Ok(())
+    }
+}
+
+/// Compresses the file at the given path with gzip into the given destination, and removes the
+/// original.
+#[cfg(feature = "gzip")]
+fn compress(path: &Path, destination: &Path) -> io::Result<()> {
+    use flate2::{write::GzEncoder, Compression};
+
+    let mut encoder = GzEncoder::new(File::create(destination)?, Compression::default());
+    io::copy(&mut File::open(path)?, &mut encoder)?;
+    encoder.finish()?.sync_all()?;
+    fs::remove_file(path)
+}
+
+/// Leaves the file uncompressed, since compression was rejected when the writer was created.
+#[cfg(not(feature = "gzip"))]
+fn compress(_path: &Path, _destination: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+impl Write for TranscriptWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.should_rotate() {
+            self.rotate()?;
+        }
+        let written = self.file.write(buf)?;
+        self.bytes += written as u64;
+        Ok(written)
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        if self.should_rotate() {
+            self.rotate()?;
+        }
+        self.file.write_all(buf)?;
+        self.bytes += buf.len() as u64;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use std::{fs, io::Write, thread, time::Duration};
+
+    use crate::transcript::{Rotation, TranscriptWriter};
+
+    #[test]
+    fn rotate_by_size() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("transcript.txt");
+        let rotation = Rotation {
+            max_bytes: Some(3),
+            keep: 2,
+            ..Default::default()
+        };
+        let mut writer = TranscriptWriter::create(&path, rotation.clone())?;
+        for text in ["om\n", "ah\n", "hum\n", "hri\n"] {
+            writer.write_all(text.as_bytes())?;
+        }
+        writer.flush()?;
+
+        // Each write fills the transcript, and only the two most recent rotated files are kept.
+        assert_eq!(fs::read_to_string(&path)?, "hri\n");
+        assert_eq!(
+            fs::read_to_string(dir.path().join("transcript.txt.1"))?,
+            "hum\n"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.path().join("transcript.txt.2"))?,
+            "ah\n"
+        );
+        assert!(!dir.path().join("transcript.txt.3").exists());
+
+        // An existing transcript is appended to.
+        drop(writer);
+        let mut writer = TranscriptWriter::create(&path, rotation)?;
+        writer.write_all(b"om\n")?;
+        assert_eq!(fs::read_to_string(&path)?, "om\n");
+        assert_eq!(
+            fs::read_to_string(dir.path().join("transcript.txt.1"))?,
+            "hri\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn rotate_by_age() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("transcript.txt");
+        let mut writer = TranscriptWriter::create(
+            &path,
+            Rotation {
+                max_age: Some(Duration::from_millis(20)),
+                ..Default::default()
+            },
+        )?;
+        writer.write_all(b"om\n")?;
+        writer.write_all(b"ah\n")?;
+        thread::sleep(Duration::from_millis(30));
+        writer.write_all(b"hum\n")?;
+        assert_eq!(fs::read_to_string(&path)?, "hum\n");
+        assert_eq!(
+            fs::read_to_string(dir.path().join("transcript.txt.1"))?,
+            "om\nah\n"
+        );
+        Ok(())
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn compress_rotated() -> Result<()> {
+        use flate2::read::GzDecoder;
+        use std::io::Read;
+
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("transcript.txt");
+        let mut writer = TranscriptWriter::create(
+            &path,
+            Rotation {
+                max_bytes: Some(1),
+                compress: true,
+                ..Default::default()
+            },
+        )?;
+        writer.write_all(b"om\n")?;
+        writer.write_all(b"ah\n")?;
+        assert!(!dir.path().join("transcript.txt.1").exists());
+        let mut text = String::new();
+        GzDecoder::new(fs::File::open(dir.path().join("transcript.txt.1.gz"))?)
+            .read_to_string(&mut text)?;
+        assert_eq!(text, "om\n");
+        Ok(())
+    }
+
+    #[cfg(not(feature = "gzip"))]
+    #[test]
+    fn compress_unsupported() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let rotation = Rotation {
+            compress: true,
+            ..Default::default()
+        };
+        let result = TranscriptWriter::create(dir.path().join("transcript.txt"), rotation);
+        assert!(result.is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,186 @@
+//! Contains a live view of the recitation for terminal applications built with `ratatui`. The view
+//! shows the syllable being recited next to a turning prayer wheel, the progress of the sadhana,
+//! and the running counts, and is drawn from a snapshot of the miner taken on each frame.
+
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Paragraph, Widget},
+};
+
+use crate::{MantraMiner, MinerHandle, MinerStats};
+
+/// The frames of the prayer wheel, which turns clockwise by one frame for each recited syllable.
+const WHEEL: [&str; 4] = ["(|)", "(/)", "(-)", "(\\)"];
+
+/// A widget showing the recitation of a mantra miner as it happens.
+///
+/// ```ignore
+/// terminal.draw(|frame| frame.render_widget(LiveView::of(&miner), frame.area()))?;
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct LiveView<'a> {
+    /// The syllable being recited, if any.
+    syllable: Option<String>,
+
+    /// The fraction of the current sadhana that has been recited, if one is in progress.
+    progress: Option<f64>,
+
+    /// The number of syllables recited so far, which turns the prayer wheel.
+    syllables: u64,
+
+    /// The statistics of the miner.
+    stats: MinerStats,
+
+    /// The block around the view, or a bordered block titled `mantra miner` if it's `None`.
+    block: Option<Block<'a>>,
+}
+
+impl<'a> LiveView<'a> {
+    /// Returns a view of the given statistics, with no syllable being recited.
+    pub fn new(stats: MinerStats) -> LiveView<'a> {
+        LiveView {
+            stats,
+            ..Default::default()
+        }
+    }
+
+    /// Returns a view of the current state of the miner. The last syllable recited is only shown if
+    /// the options keep recent output.
+    pub fn of(miner: &MantraMiner) -> LiveView<'a> {
+        LiveView::new(miner.stats())
+            .syllable(miner.recent_output(1).pop())
+            .progress(miner.sadhana_progress())
+            .syllables(miner.syllable_count())
+    }
+
+    /// Returns a view of the current state of the miner controlled by the handle, which does not
+    /// show the syllable being recited.
+    pub fn of_handle(handle: &MinerHandle) -> LiveView<'a> {
+        LiveView::new(handle.stats())
+            .progress(handle.sadhana_progress())
+            .syllables(handle.syllable_count())
+    }
+
+    /// Sets the syllable being recited.
+    pub fn syllable(mut self, syllable: Option<String>) -> LiveView<'a> {
+        self.syllable = syllable;
+        self
+    }
+
+    /// Sets the fraction of the current sadhana that has been recited.
+    pub fn progress(mut self, progress: Option<f64>) -> LiveView<'a> {
+        self.progress = progress;
+        self
+    }
+
+    /// Sets the number of syllables recited so far, which decides the frame of the prayer wheel.
+    pub fn syllables(mut self, syllables: u64) -> LiveView<'a> {
+        self.syllables = syllables;
+        self
+    }
+
+    /// Sets the block drawn around the view.
+    pub fn block(mut self, block: Block<'a>) -> LiveView<'a> {
+        self.block = Some(block);
+        self
+    }
+
+    /// Returns the lines of text shown in the view.
+    fn lines(&self) -> Vec<Line<'_>> {
+        let mut lines = vec![Line::from(vec![
+            Span::raw(WHEEL[(self.syllables % WHEEL.len() as u64) as usize]),
+            Span::raw(" "),
+            Span::styled(
+                self.syllable.as_deref().unwrap_or_default(),
+                Style::default().add_modifier(Modifier::BOLD),
+            ),
+        ])];
+        let progress = self
+            .progress
+            .map(|progress| format!(" ({:.0}%)", progress * 100.0))
+            .unwrap_or_default();
+        lines.push(Line::raw(format!(
+            "sadhanas: {}{}",
+            self.stats.sadhanas, progress
+        )));
+        for (index, mantra) in self.stats.mantras.iter().enumerate() {
+            lines.push(Line::raw(format!(
+                "{}: {}",
+                mantra.label(index),
+                mantra.completed
+            )));
+        }
+        lines
+    }
+}
+
+impl Widget for LiveView<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let block = self
+            .block
+            .clone()
+            .unwrap_or_else(|| Block::bordered().title(" mantra miner "));
+        Paragraph::new(self.lines()).block(block).render(area, buf);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ratatui::{buffer::Buffer, layout::Rect, widgets::Widget};
+
+    use crate::{tui::LiveView, MantraStats, MinerStats};
+
+    /// Renders the view and returns its rows.
+    fn render(view: LiveView) -> Vec<String> {
+        let area = Rect::new(0, 0, 24, 6);
+        let mut buf = Buffer::empty(area);
+        view.render(area, &mut buf);
+        (0..area.height)
+            .map(|y| {
+                (0..area.width)
+                    .map(|x| buf[(x, y)].symbol())
+                    .collect::<String>()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn live_view() {
+        let stats = MinerStats {
+            sadhanas: 3,
+            mantras: vec![
+                MantraStats {
+                    name: Some("Mani".to_string()),
+                    completed: 324,
+                    syllables: 1944,
+                },
+                MantraStats {
+                    name: None,
+                    completed: 3,
+                    syllables: 3,
+                },
+            ],
+            ..Default::default()
+        };
+        let rows = render(
+            LiveView::new(stats)
+                .syllable(Some("hum".to_string()))
+                .progress(Some(0.5))
+                .syllables(1947),
+        );
+        assert_eq!(
+            rows,
+            [
+                "┌ mantra miner ────────┐",
+                "│(\\) hum               │",
+                "│sadhanas: 3 (50%)     │",
+                "│Mani: 324             │",
+                "│#1: 3                 │",
+                "└──────────────────────┘",
+            ]
+        );
+    }
+}
@@ -0,0 +1,190 @@
+//! Contains the `Voice` trait, which decides how the recitation of the mantra miner is voiced. The
+//! default voice writes the syllables to the output of the miner, and host programs can provide
+//! their own to recite through a speaker, a GPIO pin, or a network connection instead.
+
+use std::{
+    io::{self, Write},
+    ops::Range,
+    time::{Instant, SystemTime},
+};
+
+use crate::{Options, Timestamp};
+
+/// Voices the syllables and characters recited by the mantra miner. The errors returned are
+/// handled according to the error policy in the options, as if writing to the output failed.
+pub trait Voice: Send {
+    /// Recites a syllable of a mantra.
+    fn recite_syllable(&mut self, syllable: &str) -> io::Result<()>;
+
+    /// Recites a character of the preparation or conclusion.
+    fn recite_character(&mut self, c: char) -> io::Result<()>;
+
+    /// Recites a grapheme cluster of the preparation or conclusion made of more than one
+    /// character, which is only produced with the `graphemes` feature. Recites each of its
+    /// characters by default.
+    fn recite_grapheme(&mut self, grapheme: &str) -> io::Result<()> {
+        grapheme.chars().try_for_each(|c| self.recite_character(c))
+    }
+
+    /// Called after each recitation of the preparation or conclusion. Does nothing by default.
+    fn end_section(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Called after each recitation of the sadhana and once the recitation finishes, so that the
+    /// voice can release anything it buffered. Does nothing by default.
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The default voice, which writes the recitation to the output of the mantra miner with the
+/// formatting and separator set in the options.
+pub(crate) struct WriterVoice<'a, W: Write> {
+    /// The output to which the recitation is written.
+    pub(crate) output: &'a mut W,
+
+    /// The options of the recitation.
+    pub(crate) options: &'a Options,
+
+    /// The instant from which monotonic timestamps are measured.
+    pub(crate) started: Instant,
+}
+
+/// The bytes written by the default voice for each syllable of the mantras, prepared once for each
+/// set of options so that reciting a syllable is a single write that allocates nothing.
+#[derive(Default)]
+pub(crate) struct PreparedSyllables {
+    /// The formatted syllables of every mantra, followed by the separator, one after the other.
+    bytes: Vec<u8>,
+
+    /// The range of `bytes` taken by each syllable of each mantra.
+    syllables: Vec<Vec<Range<usize>>>,
+}
+
+impl PreparedSyllables {
+    /// Prepares the syllables of the mantras in the options. Nothing is prepared if the syllables
+    /// are timestamped, since their text changes every time they are recited.
+    pub(crate) fn new(options: &Options) -> PreparedSyllables {
+        let format = &options.format;
+        if format.timestamp.is_some() {
+            return PreparedSyllables::default();
+        }
+        let mut bytes = Vec::new();
+        let syllables = options
+            .mantras
+            .iter()
+            .map(|mantra| {
+                mantra
+                    .syllables
+                    .iter()
+                    .map(|syllable| {
+                        let start = bytes.len();
+                        for text in [
+                            format.syllable_prefix.as_str(),
+                            syllable,
+                            &format.syllable_suffix,
+                            options.separator(),
+                        ] {
+                            bytes.extend_from_slice(text.as_bytes());
+                        }
+                        start..bytes.len()
+                    })
+                    .collect()
+            })
+            .collect();
+        PreparedSyllables { bytes, syllables }
+    }
+
+    /// Returns the bytes to write for the given syllable of the given mantra, or `None` if they
+    /// were not prepared.
+    pub(crate) fn get(&self, mantra_index: usize, syllable_index: usize) -> Option<&[u8]> {
+        let range = self.syllables.get(mantra_index)?.get(syllable_index)?;
+        Some(&self.bytes[range.clone()])
+    }
+}
+
+impl<W: Write> WriterVoice<'_, W> {
+    /// Returns the given kind of timestamp for the current time, followed by a space.
+    fn timestamp(&self, timestamp: Timestamp) -> String {
+        match timestamp {
+            Timestamp::WallClock => {
+                format!("{} ", humantime::format_rfc3339_millis(SystemTime::now()))
+            }
+            Timestamp::Monotonic => format!("+{:.3}s ", self.started.elapsed().as_secs_f64()),
+        }
+    }
+}
+
+impl<W: Write + Send> Voice for WriterVoice<'_, W> {
+    fn recite_syllable(&mut self, syllable: &str) -> io::Result<()> {
+        let format = &self.options.format;
+        let timestamp = format
+            .timestamp
+            .map(|timestamp| self.timestamp(timestamp))
+            .unwrap_or_default();
+        for text in [
+            timestamp.as_str(),
+            &format.syllable_prefix,
+            syllable,
+            &format.syllable_suffix,
+            self.options.separator(),
+        ] {
+            self.output.write_all(text.as_bytes())?;
+        }
+        Ok(())
+    }
+
+    fn recite_character(&mut self, c: char) -> io::Result<()> {
+        let mut b = [0; 4];
+        self.output.write_all(c.encode_utf8(&mut b).as_bytes())
+    }
+
+    fn recite_grapheme(&mut self, grapheme: &str) -> io::Result<()> {
+        self.output.write_all(grapheme.as_bytes())
+    }
+
+    fn end_section(&mut self) -> io::Result<()> {
+        if self.options.format.section_newline {
+            self.output.write_all("\n".as_bytes())?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.output.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{voice::PreparedSyllables, Mantra, Options, OutputFormat, Timestamp};
+
+    #[test]
+    fn prepared_syllables() {
+        let options = Options {
+            mantras: vec![Mantra::from_text("om ah"), Mantra::from_text("hri")],
+            separator: Some(" ".to_string()),
+            format: OutputFormat {
+                syllable_prefix: "[".to_string(),
+                syllable_suffix: "]".to_string(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let prepared = PreparedSyllables::new(&options);
+        assert_eq!(prepared.get(0, 1), Some("[ah] ".as_bytes()));
+        assert_eq!(prepared.get(1, 0), Some("[hri] ".as_bytes()));
+        assert_eq!(prepared.get(1, 1), None);
+
+        // Timestamped syllables are formatted each time they are recited.
+        let options = Options {
+            format: OutputFormat {
+                timestamp: Some(Timestamp::Monotonic),
+                ..options.format
+            },
+            ..options
+        };
+        assert_eq!(PreparedSyllables::new(&options).get(0, 0), None);
+    }
+}
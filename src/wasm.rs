@@ -0,0 +1,28 @@
+//! Defines the spawner used to run the mantra miner in the browser, where threads and blocking
+//! sleeps are not available.
+
+use gloo_timers::future::sleep;
+
+use crate::{RecitationTask, Spawner};
+
+/// A spawner that runs each task as a future on the JavaScript event loop, waiting between
+/// syllables with `setTimeout`. Used to run the mantra miner on `wasm32-unknown-unknown`.
+///
+/// The task runs on the current thread, so the mantra miner cannot block waiting for it to finish.
+/// Dropping or stopping the miner takes effect the next time the timer fires.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct WasmSpawner;
+
+impl Spawner for WasmSpawner {
+    fn spawn(&self, mut task: RecitationTask) {
+        wasm_bindgen_futures::spawn_local(async move {
+            while let Some(delay) = task.step() {
+                sleep(delay).await;
+            }
+        });
+    }
+
+    fn can_block(&self) -> bool {
+        false
+    }
+}
@@ -0,0 +1,312 @@
+//! Contains the notifier that posts the milestones and the completion of a session of the mantra
+//! miner to a webhook, so that they can be piped into a chat such as Slack or Discord or into a
+//! home automation system. The notifications are sent as JSON from a tokio task, and failed
+//! deliveries are retried with an exponential backoff.
+
+use serde::Serialize;
+use std::{
+    sync::mpsc::{Receiver, TryRecvError},
+    time::Duration,
+};
+
+use crate::{Error, MinerEvent, Options, Result};
+
+/// How often the notifier checks for new events when none are pending.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The kind of a notification sent to the webhook.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationKind {
+    /// The number of mantra repetitions reached a multiple of the milestone.
+    Milestone,
+
+    /// The number of recitations of the sadhana set in the options was completed.
+    SessionCompleted,
+}
+
+/// The JSON payload posted to the webhook.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct Notification {
+    /// The kind of the notification.
+    pub event: NotificationKind,
+
+    /// A message describing the notification, which chats such as Slack display as is.
+    pub text: String,
+
+    /// The number of mantra repetitions credited since the notifier was created.
+    pub mantras: u64,
+
+    /// The count of the mantra miner, which is the number of completed recitations of the sadhana.
+    pub count: u64,
+}
+
+/// Posts notifications to a webhook from the events of a mantra miner. A notification is sent each
+/// time the repetitions of the mantras reach a multiple of the milestone, if any, and once the
+/// sadhana has been recited the number of times set in the options.
+pub struct WebhookNotifier {
+    /// The client used to post the notifications.
+    client: reqwest::Client,
+
+    /// The URL to which the notifications are posted.
+    url: String,
+
+    /// The number of mantra repetitions between milestones, if any.
+    milestone: Option<u64>,
+
+    /// The number of times a failed delivery is retried.
+    retries: u32,
+
+    /// The delay before the first retry, which doubles with each retry.
+    backoff: Duration,
+
+    /// The number of recitations of the sadhana after which the session is completed, if any.
+    repeats: Option<usize>,
+
+    /// The number of mantra repetitions credited since the notifier was created.
+    mantras: u64,
+
+    /// The last count of the mantra miner received.
+    count: u64,
+}
+
+impl WebhookNotifier {
+    /// Returns a notifier that posts to the given URL for a miner with the given options. By
+    /// default, there are no milestones and failed deliveries are retried three times, starting
+    /// after one second.
+    pub fn new(url: impl Into<String>, options: &Options) -> WebhookNotifier {
+        WebhookNotifier {
+            client: reqwest::Client::new(),
+            url: url.into(),
+            milestone: None,
+            retries: 3,
+            backoff: Duration::from_secs(1),
+            repeats: options.repeats,
+            mantras: 0,
+            count: 0,
+        }
+    }
+
+    /// Sets the number of mantra repetitions between milestones, such as 1,000.
+    pub fn milestone(mut self, milestone: u64) -> WebhookNotifier {
+        self.milestone = Some(milestone).filter(|&milestone| milestone > 0);
+        self
+    }
+
+    /// Sets the number of times a failed delivery is retried, and the delay before the first
+    /// retry, which doubles with each retry.
+    pub fn retries(mut self, retries: u32, backoff: Duration) -> WebhookNotifier {
+        self.retries = retries;
+        self.backoff = backoff;
+        self
+    }
+
+    /// Sets the client used to post the notifications, for example to configure a proxy or a
+    /// timeout.
+    pub fn client(mut self, client: reqwest::Client) -> WebhookNotifier {
+        self.client = client;
+        self
+    }
+
+    /// Posts the notification to the webhook, retrying failed deliveries. Returns
+    /// `Error::WebhookFailed` if the last attempt fails or the webhook responds with an error
+    /// status.
+    pub async fn notify(&self, notification: &Notification) -> Result<()> {
+        let mut backoff = self.backoff;
+        let mut attempt = 0;
+        loop {
+            let response = self
+                .client
+                .post(&self.url)
+                .json(notification)
+                .send()
+                .await
+                .and_then(|response| response.error_for_status());
+            match response {
+                Ok(_) => return Ok(()),
+                Err(_) if attempt < self.retries => {
+                    tokio::time::sleep(backoff).await;
+                    backoff = backoff.saturating_mul(2);
+                    attempt += 1;
+                }
+                Err(err) => return Err(Error::WebhookFailed(err)),
+            }
+        }
+    }
+
+    /// Returns the notification triggered by the given event, if any.
+    fn notification(&mut self, event: &MinerEvent) -> Option<Notification> {
+        match event {
+            MinerEvent::MantraCompleted { credited, .. } => {
+                let before = self.mantras;
+                self.mantras += *credited as u64;
+                let milestone = self.milestone?;
+                let reached = self.mantras / milestone * milestone;
+                (before < reached).then(|| Notification {
+                    event: NotificationKind::Milestone,
+                    text: format!("Reached {reached} mantras"),
+                    mantras: reached,
+                    count: self.count,
+                })
+            }
+            MinerEvent::SadhanaCompleted { run, count } => {
+                self.count = *count;
+                (self.repeats == Some(run + 1)).then(|| Notification {
+                    event: NotificationKind::SessionCompleted,
+                    text: format!(
+                        "Completed the session with {} mantras in {} sadhanas",
+                        self.mantras,
+                        run + 1
+                    ),
+                    mantras: self.mantras,
+                    count: *count,
+                })
+            }
+            MinerEvent::SadhanaStarted { .. } | MinerEvent::SyllableRecited { .. } => None,
+        }
+    }
+
+    /// Posts the notifications triggered by the given events until the session is completed or
+    /// the miner that sent them is dropped. Spawn it as a tokio task with the receiver returned by
+    /// the `subscribe` method of the miner before starting it. A failed notification does not stop
+    /// the later ones, and the error of the first one is returned once the notifier stops.
+    pub async fn run(mut self, events: Receiver<MinerEvent>) -> Result<()> {
+        let mut result = Ok(());
+        loop {
+            let event = match events.try_recv() {
+                Ok(event) => event,
+                Err(TryRecvError::Empty) => {
+                    tokio::time::sleep(POLL_INTERVAL).await;
+                    continue;
+                }
+                Err(TryRecvError::Disconnected) => return result,
+            };
+            if let Some(notification) = self.notification(&event) {
+                let sent = self.notify(&notification).await;
+                if result.is_ok() {
+                    result = sent;
+                }
+                if notification.event == NotificationKind::SessionCompleted {
+                    return result;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use parking_lot::Mutex;
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpListener,
+        sync::Arc,
+        thread,
+        time::Duration,
+    };
+
+    use crate::{
+        Error, Mantra, MantraMiner, Notification, NotificationKind, Options, WebhookNotifier,
+    };
+
+    /// Starts a server that answers each request with the next of the given statuses and records
+    /// the bodies of the requests. Returns its URL and the recorded bodies.
+    fn server(statuses: Vec<u16>) -> Result<(String, Arc<Mutex<Vec<String>>>)> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let url = format!("http://{}/hook", listener.local_addr()?);
+        let bodies = Arc::new(Mutex::new(Vec::new()));
+        let recorded = bodies.clone();
+        thread::spawn(move || {
+            for (stream, status) in listener.incoming().zip(statuses) {
+                let Ok(mut stream) = stream else { return };
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(':') {
+                        if name.eq_ignore_ascii_case("content-length") {
+                            length = value.trim().parse().unwrap();
+                        }
+                    }
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                recorded.lock().push(String::from_utf8(body).unwrap());
+                write!(
+                    stream,
+                    "HTTP/1.1 {status} Status\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                )
+                .unwrap();
+            }
+        });
+        Ok((url, bodies))
+    }
+
+    /// Returns a client that connects to the test server directly.
+    fn client() -> reqwest::Client {
+        reqwest::Client::builder().no_proxy().build().unwrap()
+    }
+
+    #[tokio::test]
+    async fn notifications() -> Result<()> {
+        // The first delivery of the milestone fails and is retried.
+        let (url, bodies) = server(vec![500, 200, 200])?;
+        let options = Options {
+            mantras: vec![Mantra::from_text("om ah hum")],
+            rate: Duration::from_micros(1),
+            repeats: Some(3),
+            ..Default::default()
+        };
+        let miner = MantraMiner::new(options.clone());
+        let notifier = WebhookNotifier::new(url, &options)
+            .milestone(2)
+            .retries(1, Duration::from_millis(1))
+            .client(client());
+        let task = tokio::spawn(notifier.run(miner.subscribe()));
+        miner.start()?;
+        miner.wait()?;
+        task.await??;
+
+        let bodies = bodies.lock();
+        assert_eq!(bodies.len(), 3);
+        assert_eq!(bodies[0], bodies[1]);
+        assert_eq!(
+            bodies[1],
+            r#"{"event":"milestone","text":"Reached 2 mantras","mantras":2,"count":1}"#
+        );
+        assert_eq!(
+            bodies[2],
+            concat!(
+                r#"{"event":"session_completed","text":"Completed the session with 3 mantras "#,
+                r#"in 3 sadhanas","mantras":3,"count":3}"#
+            )
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn failed_delivery() -> Result<()> {
+        let (url, bodies) = server(vec![500, 503])?;
+        let options = Options::default();
+        let notifier = WebhookNotifier::new(url, &options)
+            .retries(1, Duration::from_millis(1))
+            .client(client());
+        let notification = Notification {
+            event: NotificationKind::Milestone,
+            text: "Reached 1 mantras".to_string(),
+            mantras: 1,
+            count: 0,
+        };
+        assert!(matches!(
+            notifier.notify(&notification).await,
+            Err(Error::WebhookFailed(_))
+        ));
+        assert_eq!(bodies.lock().len(), 2);
+        Ok(())
+    }
+}
@@ -0,0 +1,183 @@
+//! Contains the prayer wheel, which holds many copies of a mantra and credits all of them each time
+//! it's turned. Unlike the mantra miner, nothing is recited over time: the recitations are driven
+//! by the rotations requested by the host application, such as a tap, a swipe, or the turn of a
+//! crank.
+
+use std::io::Write;
+
+use crate::{Error, Mantra, MantraStats, Output, Result};
+
+/// A prayer wheel containing a number of copies of a mantra, like the scrolls rolled up inside a
+/// mani wheel. Each call to [`PrayerWheel::rotate`] credits one recitation of every copy, and
+/// writes a single line recording the rotation to the output, if one is set, instead of writing
+/// every syllable.
+pub struct PrayerWheel {
+    /// The mantra contained in the wheel.
+    mantra: Mantra,
+
+    /// The number of copies of the mantra contained in the wheel.
+    copies: u64,
+
+    /// The number of times the wheel has been turned.
+    rotations: u64,
+
+    /// The output to which each rotation is recorded, if any.
+    output: Option<Output>,
+}
+
+impl PrayerWheel {
+    /// Returns a new wheel containing the given number of copies of the mantra. Returns
+    /// `Error::InvalidOptions` if the mantra has no syllables or the wheel contains no copies.
+    pub fn new(mantra: Mantra, copies: u64) -> Result<PrayerWheel> {
+        if mantra.syllables.is_empty() {
+            return Err(Error::InvalidOptions(
+                "the mantra of the prayer wheel has no syllables".to_string(),
+            ));
+        }
+        if copies == 0 {
+            return Err(Error::InvalidOptions(
+                "the prayer wheel must contain at least one copy of the mantra".to_string(),
+            ));
+        }
+        Ok(PrayerWheel {
+            mantra,
+            copies,
+            rotations: 0,
+            output: None,
+        })
+    }
+
+    /// Sets the output to which a line is written after each rotation, made of the syllables of the
+    /// mantra followed by the number of copies credited, such as `om mani padme hum x108`.
+    pub fn with_output(mut self, output: Output) -> PrayerWheel {
+        self.output = Some(output);
+        self
+    }
+
+    /// Sets the number of times the wheel has already been turned, so that the rotations saved by
+    /// the host application are kept when the wheel is created again.
+    pub fn with_rotations(mut self, rotations: u64) -> PrayerWheel {
+        self.rotations = rotations;
+        self
+    }
+
+    /// Returns the mantra contained in the wheel.
+    pub fn mantra(&self) -> &Mantra {
+        &self.mantra
+    }
+
+    /// Returns the number of copies of the mantra contained in the wheel.
+    pub fn copies(&self) -> u64 {
+        self.copies
+    }
+
+    /// Returns the number of times the wheel has been turned.
+    pub fn rotations(&self) -> u64 {
+        self.rotations
+    }
+
+    /// Returns the number of recitations of the mantra credited by all the rotations so far.
+    pub fn recitations(&self) -> u64 {
+        self.rotations.saturating_mul(self.copies)
+    }
+
+    /// Returns the statistics of the mantra, with every credited recitation counted as a completed
+    /// repetition of all its syllables.
+    pub fn stats(&self) -> MantraStats {
+        let completed = usize::try_from(self.recitations()).unwrap_or(usize::MAX);
+        MantraStats {
+            name: self.mantra.name.clone(),
+            completed,
+            syllables: completed.saturating_mul(self.mantra.syllables.len()),
+        }
+    }
+
+    /// Turns the wheel once, crediting a recitation of each copy of the mantra, and records the
+    /// rotation to the output, if any. Returns the number of recitations credited so far. The
+    /// rotation is credited even if writing it to the output fails, in which case
+    /// `Error::WriteFailed` is returned.
+    pub fn rotate(&mut self) -> Result<u64> {
+        self.rotations = self.rotations.saturating_add(1);
+        if let Some(output) = &mut self.output {
+            writeln!(
+                output,
+                "{} x{}",
+                self.mantra.syllables.join(" "),
+                self.copies
+            )?;
+            output.flush()?;
+        }
+        Ok(self.recitations())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use parking_lot::Mutex;
+    use std::{
+        io::{self, Write},
+        sync::Arc,
+    };
+
+    use crate::{wheel::PrayerWheel, Error, Mantra, MantraStats};
+
+    /// An output that stores everything written to it.
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn rotate() -> Result<()> {
+        let buffer = SharedBuffer::default();
+        let mantra = Mantra {
+            name: Some("Mani".to_string()),
+            ..Mantra::from_text("om ma-ni pad-me hum")
+        };
+        let mut wheel = PrayerWheel::new(mantra, 108)?.with_output(Box::new(buffer.clone()));
+        assert_eq!(wheel.rotate()?, 108);
+        assert_eq!(wheel.rotate()?, 216);
+
+        // Each rotation is recorded as a single line.
+        assert_eq!(
+            String::from_utf8(buffer.0.lock().clone())?,
+            "om ma ni pad me hum x108\nom ma ni pad me hum x108\n"
+        );
+        assert_eq!(wheel.rotations(), 2);
+        assert_eq!(
+            wheel.stats(),
+            MantraStats {
+                name: Some("Mani".to_string()),
+                completed: 216,
+                syllables: 216 * 6,
+            }
+        );
+
+        // The saved rotations are kept when the wheel is created again.
+        let wheel = PrayerWheel::new(wheel.mantra().clone(), 108)?.with_rotations(2);
+        assert_eq!(wheel.recitations(), 216);
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_wheel() {
+        assert!(matches!(
+            PrayerWheel::new(Mantra::from_text("om"), 0),
+            Err(Error::InvalidOptions(_))
+        ));
+        assert!(matches!(
+            PrayerWheel::new(Mantra::default(), 108),
+            Err(Error::InvalidOptions(_))
+        ));
+    }
+}